@@ -423,10 +423,44 @@ fn benchmark_slot_allocation_analysis(c: &mut Criterion) {
     group.finish();
 }
 
+fn benchmark_mempool_sequential_scan(c: &mut Criterion) {
+    let total_items = 10_000u64;
+    let mut sparse = framepool::MemPool::<u64>::new();
+    let mut dense = framepool::DenseMemPool::<u64>::new();
+    FramePool::<u64>::resize(&mut sparse, total_items).unwrap();
+    FramePool::<u64>::resize(&mut dense, total_items).unwrap();
+    for i in 0..total_items {
+        sparse.put_frame(i, Arc::new(i)).unwrap();
+        dense.put_frame(i, Arc::new(i)).unwrap();
+    }
+
+    let mut group = c.benchmark_group("mempool_sequential_scan");
+    group.bench_function("MemPool", |b| {
+        b.iter(|| {
+            let mut sum = 0;
+            for i in 0..total_items {
+                sum += *sparse.get_frame_ref(i).unwrap();
+            }
+            black_box(sum)
+        })
+    });
+    group.bench_function("DenseMemPool", |b| {
+        b.iter(|| {
+            let mut sum = 0;
+            for i in 0..total_items {
+                sum += *dense.get_frame_ref(i).unwrap();
+            }
+            black_box(sum)
+        })
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_eviction_strategies,
-    benchmark_slot_allocation_analysis
+    benchmark_slot_allocation_analysis,
+    benchmark_mempool_sequential_scan
 );
 criterion_main!(benches);
 
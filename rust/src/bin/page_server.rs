@@ -0,0 +1,27 @@
+use bufferpool::framepool::{DiskPool, PageServer};
+use serde_json::Value;
+use std::process;
+
+/// Serves the pages of a DiskPool directory over HTTP, for RemotePools on other machines.
+///
+/// Usage: page_server <directory> [address] [threads]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let Some(dir) = args.get(1) else {
+        eprintln!("usage: {} <directory> [address] [threads]", args[0]);
+        process::exit(2);
+    };
+    let addr = args.get(2).map_or("127.0.0.1:7070", String::as_str);
+    let threads = args.get(3).and_then(|t| t.parse().ok()).unwrap_or(4);
+
+    let pool = DiskPool::new::<Value>(dir);
+    let server = match PageServer::start(addr, pool, threads) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("cannot serve {} at {}: {}", dir, addr, e);
+            process::exit(1);
+        }
+    };
+    println!("serving {} at http://{}", dir, addr);
+    server.wait();
+}
@@ -0,0 +1,254 @@
+use std::ops::Range;
+
+use super::{BufferPool, BufferPoolErrors, BufferPoolId, FrameKey, FramePoolId};
+
+// Beyond this many disjoint Sequential ranges, advising another drops the oldest.
+const MAX_SEQUENTIAL_RANGES: usize = 64;
+
+/// Access hints for `BufferPool::advise`, after `madvise`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// No special treatment; clears any `Sequential` hint on the range.
+    Normal,
+    /// The frames will be needed soon: load them now, up to the pool's capacity.
+    WillNeed,
+    /// The frames won't be needed again soon: evict their unpinned pages now.
+    DontNeed,
+    /// The frames will be read once, in order: their pages enter the LRU at the cold end, so a
+    /// scan over them evicts its own pages before anyone else's. The hint lasts until the last
+    /// frame of the range is accessed, and overlapping or adjacent hints merge into one.
+    Sequential,
+}
+
+impl<'a, T> BufferPool<'a, T>
+where
+    T: Clone,
+{
+    /// Applies an access hint to a range of frames. Returns how many frames the hint acted on:
+    /// the number loaded for `WillNeed`, the number evicted for `DontNeed`, and 0 otherwise.
+    pub fn advise(
+        &mut self,
+        frames: Range<FramePoolId>,
+        advice: Advice,
+    ) -> Result<usize, BufferPoolErrors> {
+        match advice {
+            Advice::Normal => {
+                self.sequential
+                    .retain(|r| r.end <= frames.start || frames.end <= r.start);
+                Ok(0)
+            }
+            Advice::Sequential => {
+                if !frames.is_empty() {
+                    let mut merged = frames;
+                    self.sequential.retain(|r| {
+                        let touching = r.start <= merged.end && merged.start <= r.end;
+                        if touching {
+                            merged = merged.start.min(r.start)..merged.end.max(r.end);
+                        }
+                        !touching
+                    });
+                    if self.sequential.len() == MAX_SEQUENTIAL_RANGES {
+                        self.sequential.remove(0);
+                    }
+                    self.sequential.push(merged);
+                }
+                Ok(0)
+            }
+            Advice::WillNeed => {
+                let end = frames.end.min(self.frame_pool.size());
+                let wanted: Vec<FramePoolId> = (frames.start..end)
+                    .filter(|f| !self.frame2buf.contains_key(f))
+                    .take(self.size)
+                    .collect();
+                let loaded = self.get_many(&wanted);
+                Ok(wanted
+                    .iter()
+                    .zip(loaded)
+                    .filter(|(f, data)| data.is_some() && self.frame2buf.contains_key(f))
+                    .count())
+            }
+            Advice::DontNeed => {
+                let mut victims: Vec<(FramePoolId, BufferPoolId)> = self
+                    .frame2buf
+                    .iter()
+                    .filter(|(f, _)| frames.contains(f))
+                    .map(|(f, b)| (*f, *b))
+                    .collect();
+                victims.sort_unstable();
+                let mut evicted = 0;
+                for (_, buf_idx) in victims {
+                    let pinned = self.pages[buf_idx as usize]
+                        .as_ref()
+                        .is_some_and(|page| page.is_pinned());
+                    if !pinned {
+                        self.evict_slot(buf_idx)?;
+                        evicted += 1;
+                    }
+                }
+                Ok(evicted)
+            }
+        }
+    }
+}
+
+impl<'a, T, K> BufferPool<'a, T, K>
+where
+    T: Clone,
+    K: FrameKey,
+{
+    // Records an access to a cached page, on its frame and in the LRU, honouring Sequential
+    // hints. Accesses deferred by get_cached happened first, so they are applied first.
+    pub(super) fn touch(&mut self, frame_idx: &K, buffer_id: BufferPoolId) {
+        if let Some(page) = self.pages[buffer_id as usize].as_ref() {
+            page.mark_accessed();
+        }
+        self.apply_deferred_touches();
+        self.touch_now(frame_idx, buffer_id);
+    }
+
+    // Like touch, for an access that hands out the page's data rather than the page: the
+    // frame counts it here, as no read through the frame follows.
+    pub(super) fn touch_read(&mut self, frame_idx: &K, buffer_id: BufferPoolId) {
+        self.touch(frame_idx, buffer_id);
+        if let Some(page) = self.pages[buffer_id as usize].as_ref() {
+            page.touch();
+        }
+    }
+
+    pub(super) fn apply_deferred_touches(&mut self) {
+        let deferred = std::mem::take(self.deferred_touches.get_mut().unwrap());
+        for (frame_idx, buffer_id) in deferred {
+            // Skip pages evicted since the access
+            if self.frame2buf.get(&frame_idx) == Some(&buffer_id) {
+                self.touch_now(&frame_idx, buffer_id);
+            }
+        }
+    }
+
+    // An access to the last frame of a Sequential range ends the scan, and with it the hint.
+    fn touch_now(&mut self, frame_idx: &K, buffer_id: BufferPoolId) {
+        let slot = frame_idx.slot();
+        let scanned = slot.and_then(|s| self.sequential.iter().position(|r| r.contains(&s)));
+        match scanned {
+            Some(i) => {
+                if slot == Some(self.sequential[i].end - 1) {
+                    self.sequential.remove(i);
+                }
+                self.lru.push_bottom(buffer_id);
+            }
+            None => {
+                self.lru.push(buffer_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{bottom_evictor, filled_mem_pool};
+
+    #[test]
+    fn test_each_access_counts_once() {
+        let mut mem_pool = filled_mem_pool(4, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+        let count = |bp: &mut BufferPool<u64>| bp.get_page(0).unwrap().access_count();
+        assert_eq!(bp.get_page(0).unwrap().data(), 0);
+        assert_eq!(count(&mut bp), 1);
+
+        bp.get_page_arc(0).unwrap();
+        bp.get_cached(&0).unwrap();
+        bp.get_many(&[0, 1]);
+        bp.put_page(0, 10).unwrap();
+        assert_eq!(count(&mut bp), 5);
+        assert_eq!(bp.get_page(1).unwrap().access_count(), 1);
+    }
+
+    #[test]
+    fn test_advise_will_need() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+        bp.get_page(2).unwrap();
+
+        assert_eq!(bp.advise(1..4, Advice::WillNeed).unwrap(), 2);
+        for i in 1..4 {
+            assert!(bp.frame2buf.contains_key(&i));
+        }
+
+        // Capped at the pool size and the end of the frame pool
+        assert_eq!(bp.advise(5..100, Advice::WillNeed).unwrap(), 4);
+        assert_eq!(bp.frame2buf.len(), 4);
+    }
+
+    #[test]
+    fn test_advise_dont_need() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+        for i in 0..4 {
+            bp.get_page(i).unwrap();
+        }
+        bp.get_page(1).unwrap().pin();
+        bp.put_page(2, 20).unwrap();
+
+        assert_eq!(bp.advise(1..3, Advice::DontNeed).unwrap(), 1);
+        assert!(bp.frame2buf.contains_key(&1));
+        assert!(!bp.frame2buf.contains_key(&2));
+        assert!(bp.frame2buf.contains_key(&0));
+        assert!(bp.validate().is_valid());
+
+        // The dirty page was written back on its way out
+        assert_eq!(bp.get_page(2).unwrap().data(), 20);
+    }
+
+    #[test]
+    fn test_advise_sequential_is_scan_resistant() {
+        let mut mem_pool = filled_mem_pool(50, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+        for i in 0..3 {
+            bp.get_page(i).unwrap();
+        }
+
+        bp.advise(10..50, Advice::Sequential).unwrap();
+        for i in 10..50 {
+            assert_eq!(bp.get_page(i).unwrap().data(), i);
+        }
+        for i in 0..3 {
+            assert!(bp.frame2buf.contains_key(&i));
+        }
+
+        // Without the hint the scan flushes out the working set
+        bp.advise(0..100, Advice::Normal).unwrap();
+        for i in 10..20 {
+            bp.get_page(i).unwrap();
+        }
+        for i in 0..3 {
+            assert!(!bp.frame2buf.contains_key(&i));
+        }
+    }
+
+    #[test]
+    fn test_advise_sequential_ranges_stay_bounded() {
+        let mut mem_pool = filled_mem_pool(1000, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+
+        // Overlapping and adjacent hints merge
+        bp.advise(10..20, Advice::Sequential).unwrap();
+        bp.advise(15..30, Advice::Sequential).unwrap();
+        bp.advise(30..40, Advice::Sequential).unwrap();
+        bp.advise(5..12, Advice::Sequential).unwrap();
+        assert_eq!(bp.sequential, vec![5..40]);
+
+        // Reading the last frame of the range ends the hint
+        bp.get_page(20).unwrap();
+        assert_eq!(bp.sequential.len(), 1);
+        bp.get_page(39).unwrap();
+        assert!(bp.sequential.is_empty());
+
+        // Disjoint hints are capped, dropping the oldest
+        for i in 0..100 {
+            bp.advise(i * 10..i * 10 + 5, Advice::Sequential).unwrap();
+        }
+        assert_eq!(bp.sequential.len(), MAX_SEQUENTIAL_RANGES);
+        assert_eq!(bp.sequential[0], 360..365);
+    }
+}
@@ -0,0 +1,543 @@
+use futures::future::join_all;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::{BufferPool, BufferPoolErrors, EvictorFn, FramePoolId, PoolState};
+use crate::framepool::{AsyncFramePool, FramePool, FramePoolError};
+
+/// A buffer pool over an `AsyncFramePool`, for use from async tasks. Every method takes `&self`.
+///
+/// Caching and eviction are those of `BufferPool`, run under a short internal lock that is
+/// never held across an await: frames are read before the lock is taken, and pages written
+/// back by eviction are written after it is released. Until such a write lands, the page is
+/// still served from memory, so readers never see the older copy in the frame pool.
+pub struct AsyncBufferPool<T, P>
+where
+    T: Clone,
+{
+    frames: P,
+    state: Mutex<AsyncState<T>>,
+    // how many pages a stream reads ahead of the one it is yielding
+    read_ahead: usize,
+}
+
+// Read-ahead of new pools' streams.
+const DEFAULT_READ_AHEAD: usize = 4;
+
+struct AsyncState<T>
+where
+    T: Clone,
+{
+    // None only while a BufferPool is borrowing it
+    cache: Option<PoolState<T>>,
+    io: StagedFrames<T>,
+    // frames queued by prefetch, for the maintenance task to load
+    prefetch: VecDeque<FramePoolId>,
+}
+
+// Stands in for the async frame pool while the cache is in use under the lock. Reads are served
+// from frames fetched beforehand, and writes are queued to be issued once the lock is released.
+struct StagedFrames<T> {
+    size: u64,
+    // frames read ahead of the install that needs them
+    fetched: HashMap<FramePoolId, Arc<T>>,
+    // writes made under the lock, to be issued after it is released
+    queued: Vec<(FramePoolId, Arc<T>)>,
+    // the latest data written for each frame whose write has not landed; kept after a failed
+    // write until flush retries it
+    writing: HashMap<FramePoolId, Arc<T>>,
+    // frames with a write being issued right now; later writes to them wait their turn
+    in_flight: HashSet<FramePoolId>,
+    // bumped for every queued write
+    write_seq: u64,
+    // the write_seq of the last write of each frame, kept while reads that started before it
+    // are in flight
+    written_at: HashMap<FramePoolId, u64>,
+    // the write_seq at which each in-flight read of the frame pool started, with counts
+    reads_since: BTreeMap<u64, usize>,
+}
+
+impl<T> FramePool<T> for StagedFrames<T>
+where
+    T: Clone,
+{
+    fn get_frame_ref(&mut self, idx: FramePoolId) -> Result<Arc<T>, FramePoolError> {
+        self.writing
+            .get(&idx)
+            .or_else(|| self.fetched.get(&idx))
+            .cloned()
+            .ok_or_else(|| FramePoolError::NotFound(format!("frame {} was not fetched", idx)))
+    }
+
+    fn put_frame(&mut self, idx: FramePoolId, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.write_seq += 1;
+        self.written_at.insert(idx, self.write_seq);
+        self.writing.insert(idx, Arc::clone(&data));
+        self.queued.push((idx, data));
+        Ok(())
+    }
+
+    fn resize(&mut self, _count: u64) -> Result<(), FramePoolError> {
+        Err(FramePoolError::Unsupported(
+            "resize the frame pool of an AsyncBufferPool directly".to_string(),
+        ))
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        Ok(self.size)
+    }
+}
+
+impl<T> StagedFrames<T> {
+    fn begin_read(&mut self) -> u64 {
+        *self.reads_since.entry(self.write_seq).or_insert(0) += 1;
+        self.write_seq
+    }
+
+    // Ends a read begun by begin_read, and returns whether a write of the frame was queued
+    // since, in which case the data read may be out of date.
+    fn end_read(&mut self, since: u64, idx: FramePoolId) -> bool {
+        if let Some(count) = self.reads_since.get_mut(&since) {
+            *count -= 1;
+            if *count == 0 {
+                self.reads_since.remove(&since);
+            }
+        }
+        let stale = self.written_at.get(&idx).is_some_and(|&seq| seq > since);
+        match self.reads_since.keys().next() {
+            Some(&oldest) => self.written_at.retain(|_, seq| *seq > oldest),
+            None => self.written_at.clear(),
+        }
+        stale
+    }
+}
+
+impl<T> AsyncState<T>
+where
+    T: Clone,
+{
+    fn is_resident(&self, idx: FramePoolId) -> bool {
+        self.io.writing.contains_key(&idx)
+            || self
+                .cache
+                .as_ref()
+                .is_some_and(|cache| cache.frame2buf.contains_key(&idx))
+    }
+
+    // Runs f against the cache, returning its result and the writes it queued. If f panics,
+    // the cache is put back before the panic carries on, and the writes it queued are left
+    // for flush, which writes whatever is still waiting in io.writing.
+    fn run<R>(
+        &mut self,
+        f: impl FnOnce(&mut BufferPool<'_, T>) -> R,
+    ) -> (R, Vec<(FramePoolId, Arc<T>)>) {
+        let cache = self
+            .cache
+            .take()
+            .expect("async buffer pool cache is only taken out during run");
+        let mut pool = BufferPool::from_state(cache, &mut self.io);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(&mut pool)));
+        self.cache = Some(pool.into_state());
+        self.io.fetched.clear();
+        let writes = std::mem::take(&mut self.io.queued);
+        match result {
+            Ok(result) => (result, writes),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<T, P> AsyncBufferPool<T, P>
+where
+    T: Clone + Send + Sync,
+    P: AsyncFramePool<T>,
+{
+    /// Creates a pool of `size` slots over `frames`, which it takes ownership of.
+    pub fn new(size: usize, frames: P, evictor: EvictorFn<T>) -> Self {
+        let mut io = StagedFrames {
+            size: frames.size(),
+            fetched: HashMap::new(),
+            queued: Vec::new(),
+            writing: HashMap::new(),
+            in_flight: HashSet::new(),
+            write_seq: 0,
+            written_at: HashMap::new(),
+            reads_since: BTreeMap::new(),
+        };
+        let cache = BufferPool::new(size, &mut io, evictor).into_state();
+        AsyncBufferPool {
+            frames,
+            state: Mutex::new(AsyncState {
+                cache: Some(cache),
+                io,
+                prefetch: VecDeque::new(),
+            }),
+            read_ahead: DEFAULT_READ_AHEAD.min(size.max(1)),
+        }
+    }
+
+    /// Sets how many pages `stream_range` and `stream_all` read ahead of the page they are
+    /// yielding. Clamped to between one and the pool size, so read-ahead never evicts pages
+    /// the stream has yet to yield.
+    pub fn set_read_ahead(&mut self, pages: usize) {
+        let size = self
+            .state
+            .lock()
+            .unwrap()
+            .cache
+            .as_ref()
+            .map_or(1, |c| c.size);
+        self.read_ahead = pages.clamp(1, size.max(1));
+    }
+
+    pub fn read_ahead(&self) -> usize {
+        self.read_ahead
+    }
+
+    /// Streams the frames in `frames`, in order, as `(frame, data)` pairs. Up to `read_ahead`
+    /// pages are fetched concurrently; a frame that cannot be read yields its error and the
+    /// stream carries on.
+    pub fn stream_range(
+        &self,
+        frames: Range<FramePoolId>,
+    ) -> impl Stream<Item = Result<(FramePoolId, Arc<T>), BufferPoolErrors>> + '_ {
+        stream::iter(frames)
+            .map(move |frame_idx| async move {
+                self.get(frame_idx).await.map(|data| (frame_idx, data))
+            })
+            .buffered(self.read_ahead)
+    }
+
+    /// Streams every frame of the frame pool; see `stream_range`.
+    pub fn stream_all(
+        &self,
+    ) -> impl Stream<Item = Result<(FramePoolId, Arc<T>), BufferPoolErrors>> + '_ {
+        self.stream_range(0..self.frames.size())
+    }
+
+    pub fn frames(&self) -> &P {
+        &self.frames
+    }
+
+    // The lock on the pool's state. A panic under it, in a caller's closure, leaves the state
+    // whole (see AsyncState::run), so the lock is taken even if poisoned.
+    fn lock_state(&self) -> MutexGuard<'_, AsyncState<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queues frames for the maintenance task (see `start_maintenance`) to load ahead of use.
+    /// Frames already queued are not queued twice.
+    pub fn prefetch(&self, frame_idxs: &[FramePoolId]) {
+        let mut state = self.lock_state();
+        for &frame_idx in frame_idxs {
+            if !state.prefetch.contains(&frame_idx) {
+                state.prefetch.push_back(frame_idx);
+            }
+        }
+    }
+
+    // Takes up to count frames off the prefetch queue, skipping those already cached.
+    pub(super) fn take_prefetch(&self, count: usize) -> Vec<FramePoolId> {
+        let mut state = self.lock_state();
+        let mut taken = Vec::new();
+        while taken.len() < count {
+            let Some(frame_idx) = state.prefetch.pop_front() else {
+                break;
+            };
+            if !state.is_resident(frame_idx) {
+                taken.push(frame_idx);
+            }
+        }
+        taken
+    }
+
+    /// Returns a shared handle to the data at the given index if it is cached, without waiting.
+    pub fn get_cached(&self, frame_idx: FramePoolId) -> Option<Arc<T>> {
+        let state = self.lock_state();
+        state.cache.as_ref()?.get_cached(&frame_idx)
+    }
+
+    /// Returns a shared handle to the data at the given index, reading it from the frame pool
+    /// if it is not cached.
+    pub async fn get(&self, frame_idx: FramePoolId) -> Result<Arc<T>, BufferPoolErrors> {
+        loop {
+            let since = {
+                let mut state = self.lock_state();
+                if let Some(data) = state.cache.as_ref().and_then(|c| c.get_cached(&frame_idx)) {
+                    return Ok(data);
+                }
+                state.io.size = self.frames.size();
+                if frame_idx >= state.io.size {
+                    return Err(BufferPoolErrors::IndexOutOfBounds(frame_idx));
+                }
+                // A frame whose write hasn't landed is reloaded from memory, not read back
+                if state.io.writing.contains_key(&frame_idx) {
+                    None
+                } else {
+                    Some(state.io.begin_read())
+                }
+            };
+
+            let read = match since {
+                Some(_) => Some(self.frames.get_frame_ref(frame_idx).await),
+                None => None,
+            };
+
+            let (result, writes) = {
+                let mut state = self.lock_state();
+                if since.is_none() && !state.io.writing.contains_key(&frame_idx) {
+                    // The write landed in the meantime; read it back after all
+                    continue;
+                }
+                if let (Some(since), Some(read)) = (since, read) {
+                    if state.io.end_read(since, frame_idx) {
+                        // Written while we were reading; what we read may be older
+                        continue;
+                    }
+                    let data = read.map_err(BufferPoolErrors::ReadFailed)?;
+                    state.io.fetched.insert(frame_idx, data);
+                }
+                state.run(|pool| pool.get_page_arc_or_err(frame_idx))
+            };
+            // A failed write-back keeps its page in memory until flush retries it
+            let _ = self.write_back(writes).await;
+            return result;
+        }
+    }
+
+    /// Writes data to the page at the given index.
+    pub async fn put(&self, frame_idx: FramePoolId, data: T) -> Result<(), BufferPoolErrors> {
+        self.with_page(frame_idx, |pool| pool.put_page(frame_idx, data))
+            .await
+    }
+
+    /// Modifies the page at the given index in place, marking it dirty.
+    pub async fn modify<F, R>(&self, frame_idx: FramePoolId, f: F) -> Result<R, BufferPoolErrors>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.with_page(frame_idx, |pool| pool.modify_page(frame_idx, f))
+            .await
+    }
+
+    /// Writes every dirty page back to the frame pool, all at once, and returns how many were
+    /// written. Also retries writes left over from evictions that failed.
+    pub async fn flush(&self) -> Result<usize, BufferPoolErrors> {
+        let writes = {
+            let mut state = self.lock_state();
+            let (flushed, mut writes) = state.run(|pool| {
+                pool.check_writable()?;
+                pool.flush_dirty().map_err(BufferPoolErrors::FlushFailed)
+            });
+            flushed?;
+            let io = &state.io;
+            writes.extend(
+                io.writing
+                    .iter()
+                    .filter(|(idx, _)| !io.in_flight.contains(idx))
+                    .filter(|(idx, _)| !writes.iter().any(|(queued, _)| queued == *idx))
+                    .map(|(idx, data)| (*idx, Arc::clone(data)))
+                    .collect::<Vec<_>>(),
+            );
+            writes
+        };
+        let count = writes.len();
+        self.write_back(writes).await?;
+        Ok(count)
+    }
+
+    /// Flushes every dirty page, then asks the frame pool to make its state durable.
+    pub async fn sync(&self) -> Result<(), BufferPoolErrors> {
+        self.flush().await?;
+        self.frames
+            .sync()
+            .await
+            .map_err(BufferPoolErrors::SyncFailed)
+    }
+
+    // Makes the page resident and runs f against the cache while it still is.
+    async fn with_page<R>(
+        &self,
+        frame_idx: FramePoolId,
+        f: impl FnOnce(&mut BufferPool<'_, T>) -> Result<R, BufferPoolErrors>,
+    ) -> Result<R, BufferPoolErrors> {
+        let mut f = Some(f);
+        loop {
+            self.get(frame_idx).await?;
+            let (result, writes) = {
+                let mut state = self.lock_state();
+                if !state.is_resident(frame_idx) {
+                    // Evicted again before we got the lock back
+                    continue;
+                }
+                state.run(f.take().expect("page operation ran twice"))
+            };
+            let _ = self.write_back(writes).await;
+            return result;
+        }
+    }
+
+    // Issues queued writes concurrently. A write to a frame that already has one in flight is
+    // left to that write's issuer, which writes the latest data once its own write lands, so
+    // writes to one frame never overtake each other.
+    async fn write_back(&self, writes: Vec<(FramePoolId, Arc<T>)>) -> Result<(), BufferPoolErrors> {
+        let mut pending: Vec<(FramePoolId, Arc<T>)> = {
+            let mut state = self.lock_state();
+            writes
+                .into_iter()
+                .filter(|(idx, _)| state.io.in_flight.insert(*idx))
+                .collect()
+        };
+        let mut first_error = None;
+        while !pending.is_empty() {
+            let results = join_all(
+                pending
+                    .iter()
+                    .map(|(idx, data)| self.frames.put_frame(*idx, Arc::clone(data))),
+            )
+            .await;
+
+            let mut state = self.lock_state();
+            let mut next = Vec::new();
+            for ((idx, data), result) in pending.into_iter().zip(results) {
+                let latest = state.io.writing.get(&idx).cloned();
+                match (result, latest) {
+                    (_, Some(latest)) if !Arc::ptr_eq(&latest, &data) => next.push((idx, latest)),
+                    (Ok(()), _) => {
+                        state.io.writing.remove(&idx);
+                        state.io.in_flight.remove(&idx);
+                    }
+                    (Err(e), _) => {
+                        state.io.in_flight.remove(&idx);
+                        first_error.get_or_insert(BufferPoolErrors::FlushFailed(e));
+                    }
+                }
+            }
+            pending = next;
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::bottom_evictor;
+    use crate::framepool::AsyncDiskPool;
+
+    async fn setup_pool(dir: &str, count: u64) -> AsyncDiskPool<u64> {
+        let _ = std::fs::remove_dir_all(dir);
+        let frames = AsyncDiskPool::<u64>::open(dir).await.unwrap();
+        frames.resize(count).await.unwrap();
+        frames
+            .put_many((0..count).map(|i| (i, Arc::new(i))).collect())
+            .await
+            .unwrap();
+        frames
+    }
+
+    #[tokio::test]
+    async fn test_async_pool_survives_panicking_closure() {
+        use futures::FutureExt;
+
+        let dir = "/tmp/test_async_pool_survives_panicking_closure";
+        let pool = AsyncBufferPool::new(3, setup_pool(dir, 4).await, bottom_evictor);
+        pool.put(0, 10).await.unwrap();
+
+        let modified = AssertUnwindSafe(pool.modify(1, |_| panic!("modification failed")));
+        assert!(modified.catch_unwind().await.is_err());
+        assert_eq!(*pool.get(0).await.unwrap(), 10);
+        pool.put(1, 11).await.unwrap();
+        assert_eq!(pool.flush().await.unwrap(), 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_async_pool_get_put_flush() {
+        let dir = "/tmp/test_async_pool_get_put_flush";
+        let pool = AsyncBufferPool::new(3, setup_pool(dir, 10).await, bottom_evictor);
+
+        assert_eq!(*pool.get(4).await.unwrap(), 4);
+        assert_eq!(pool.get_cached(4).as_deref(), Some(&4));
+        assert!(pool.get_cached(5).is_none());
+        assert!(matches!(
+            pool.get(50).await,
+            Err(BufferPoolErrors::IndexOutOfBounds(50))
+        ));
+        assert!(matches!(
+            pool.get(10).await,
+            Err(BufferPoolErrors::IndexOutOfBounds(10))
+        ));
+
+        pool.put(1, 100).await.unwrap();
+        pool.modify(2, |v| *v += 200).await.unwrap();
+        assert_eq!(pool.flush().await.unwrap(), 2);
+        assert_eq!(pool.flush().await.unwrap(), 0);
+        assert_eq!(*pool.frames().get_frame_ref(1).await.unwrap(), 100);
+        assert_eq!(*pool.frames().get_frame_ref(2).await.unwrap(), 202);
+        pool.sync().await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_async_pool_concurrent_updates_survive_eviction() {
+        let dir = "/tmp/test_async_pool_concurrent_updates";
+        let pool = AsyncBufferPool::new(2, setup_pool(dir, 8).await, bottom_evictor);
+
+        // Eight tasks each add one to every frame, forcing evictions of dirty pages throughout
+        let tasks = (0..8).map(|t| {
+            let pool = &pool;
+            async move {
+                for i in 0..8 {
+                    pool.modify((i + t) % 8, |v| *v += 1).await.unwrap();
+                }
+            }
+        });
+        join_all(tasks).await;
+
+        pool.flush().await.unwrap();
+        for i in 0..8 {
+            assert_eq!(*pool.get(i).await.unwrap(), i + 8);
+            assert_eq!(*pool.frames().get_frame_ref(i).await.unwrap(), i + 8);
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_async_pool_streams() {
+        let dir = "/tmp/test_async_pool_streams";
+        let mut pool = AsyncBufferPool::new(3, setup_pool(dir, 10).await, bottom_evictor);
+        assert_eq!(pool.read_ahead(), 3);
+        pool.set_read_ahead(100);
+        assert_eq!(pool.read_ahead(), 3);
+        pool.set_read_ahead(2);
+
+        let all: Vec<(FramePoolId, u64)> = pool
+            .stream_all()
+            .map(|item| item.map(|(idx, data)| (idx, *data)).unwrap())
+            .collect()
+            .await;
+        assert_eq!(all, (0..10).map(|i| (i, i)).collect::<Vec<_>>());
+
+        pool.put(5, 55).await.unwrap();
+        let some: Vec<u64> = pool
+            .stream_range(4..7)
+            .map(|item| *item.unwrap().1)
+            .collect()
+            .await;
+        assert_eq!(some, vec![4, 55, 6]);
+
+        // Errors are yielded in place
+        let past_end: Vec<_> = pool.stream_range(9..12).collect().await;
+        assert!(past_end[0].is_ok());
+        assert!(past_end[2].is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,341 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use super::{BufferPool, BufferPoolErrors, EvictorFn, PoolState};
+use crate::framepool::{FramePool, FramePoolError, FrameState, KeyIter, StorageBackend};
+
+/// A read-through cache over a `StorageBackend`, for callers who address data by string key
+/// rather than by frame index.
+///
+/// Holds up to `capacity` values, choosing which to drop with the same evictors as a
+/// BufferPool (`bottom_evictor` gives LRU). Writes go straight through to the backend and
+/// replace the cached value, so nothing is ever dirty and dropping the cache loses nothing.
+/// The wrapper is itself a `StorageBackend`.
+pub struct CachedBackend<T, B>
+where
+    T: Clone,
+{
+    backend: B,
+    // None only while a call is running
+    cache: Option<PoolState<T, String>>,
+    hits: u64,
+    misses: u64,
+}
+
+// Presents a StorageBackend to a BufferPool as a frame pool keyed by string.
+struct BackendFrames<'b, B> {
+    backend: &'b mut B,
+}
+
+impl<T, B> FramePool<T, String> for BackendFrames<'_, B>
+where
+    T: Clone,
+    B: StorageBackend<T>,
+{
+    fn get_frame_ref(&mut self, key: String) -> Result<Arc<T>, FramePoolError> {
+        self.backend.read(&key)
+    }
+
+    fn put_frame(&mut self, key: String, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.backend.write(&key, data)
+    }
+
+    // Keys need no allocating.
+    fn resize(&mut self, _count: u64) -> Result<(), FramePoolError> {
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        0
+    }
+
+    fn frame_state(&self, key: &String) -> FrameState {
+        match self.backend.exists(key) {
+            true => FrameState::Populated,
+            false => FrameState::Absent,
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<String>, FramePoolError> {
+        self.backend.list_keys()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        Ok(self.backend.list_keys()?.len() as u64)
+    }
+}
+
+impl<T, B> CachedBackend<T, B>
+where
+    T: Clone,
+    B: StorageBackend<T>,
+{
+    /// Creates a cache of `capacity` values over `backend`, which it takes ownership of.
+    pub fn new(backend: B, capacity: usize, evictor: EvictorFn<T>) -> Self {
+        let mut cached = CachedBackend {
+            backend,
+            cache: None,
+            hits: 0,
+            misses: 0,
+        };
+        let mut frames = BackendFrames {
+            backend: &mut cached.backend,
+        };
+        cached.cache = Some(BufferPool::new_keyed(capacity, &mut frames, evictor).into_state());
+        cached
+    }
+
+    /// The number of reads served from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of reads that went to the backend.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Whether the value under `key` is cached. Unlike a read, this doesn't count as a use.
+    pub fn is_cached(&self, key: &str) -> bool {
+        self.cache().frame2buf.contains_key(key)
+    }
+
+    /// The wrapped backend.
+    pub fn inner(&self) -> &B {
+        &self.backend
+    }
+
+    /// Drops the cache and returns the wrapped backend.
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+
+    fn cache(&self) -> &PoolState<T, String> {
+        self.cache
+            .as_ref()
+            .expect("cached backend cache is only taken out during with")
+    }
+
+    // Runs f on a BufferPool made of the cache and the backend. If f, or the backend under it,
+    // panics, the cache is put back before the panic carries on.
+    fn with<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut BufferPool<'_, T, String>) -> R,
+    {
+        let state = self
+            .cache
+            .take()
+            .expect("cached backend cache is only taken out during with");
+        let mut frames = BackendFrames {
+            backend: &mut self.backend,
+        };
+        let mut pool = BufferPool::from_state(state, &mut frames);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(&mut pool)));
+        self.cache = Some(pool.into_state());
+        match result {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl<T, B> StorageBackend<T> for CachedBackend<T, B>
+where
+    T: Clone,
+    B: StorageBackend<T>,
+{
+    // A value that can't be cached, because every cached value is pinned, is still returned.
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        if let Some(data) = self.cache().get_cached(&key.to_string()) {
+            self.hits += 1;
+            return Ok(data);
+        }
+        self.misses += 1;
+        match self.with(|pool| pool.try_get_page(key.to_string()).map(|p| p.read_arc())) {
+            Ok(data) => Ok(data),
+            Err(BufferPoolErrors::ReadFailed(e)) => Err(e),
+            Err(_) => self.backend.read(key),
+        }
+    }
+
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.backend.write(key, Arc::clone(&data))?;
+        self.with(|pool| pool.cache_clean(key.to_string(), data));
+        Ok(())
+    }
+
+    fn write_if_absent(&mut self, key: &str, data: Arc<T>) -> Result<bool, FramePoolError> {
+        let written = self.backend.write_if_absent(key, Arc::clone(&data))?;
+        if written {
+            self.with(|pool| pool.cache_clean(key.to_string(), data));
+        }
+        Ok(written)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.is_cached(key) || self.backend.exists(key)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        self.backend.delete(key)?;
+        self.with(|pool| pool.forget(&key.to_string()));
+        Ok(())
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        self.backend.list_keys()
+    }
+
+    fn iter_keys(&self) -> Result<KeyIter<'_>, FramePoolError> {
+        self.backend.iter_keys()
+    }
+
+    fn list_keys_with_prefix(
+        &self,
+        prefix: &str,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Vec<String>, FramePoolError> {
+        self.backend.list_keys_with_prefix(prefix, limit, cursor)
+    }
+}
+
+impl<T, K> BufferPool<'_, T, K>
+where
+    T: Clone,
+    K: super::FrameKey,
+{
+    // Caches data the frame pool already holds as a clean page, replacing any cached copy. If
+    // no room can be made, the data is just not cached.
+    fn cache_clean(&mut self, frame_idx: K, data: Arc<T>) {
+        self.forget(&frame_idx);
+        if self.make_room_for(&frame_idx).is_ok()
+            && let Ok(buffer_id) = self.install(frame_idx.clone(), data)
+        {
+            self.touch(&frame_idx, buffer_id);
+        }
+    }
+
+    // Drops the cached page of frame_idx, if any, without writing it back.
+    fn forget(&mut self, frame_idx: &K) {
+        if let Some(&buffer_id) = self.frame2buf.get(frame_idx) {
+            self.discard_slot(buffer_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::bottom_evictor;
+    use crate::framepool::{FaultInjector, FaultyBackend, FileBackend};
+    use std::collections::HashMap;
+
+    // Panics on reading any key starting with "panic".
+    struct PanickingBackend(HashMap<String, Arc<u64>>);
+
+    impl StorageBackend<u64> for PanickingBackend {
+        fn read(&mut self, key: &str) -> Result<Arc<u64>, FramePoolError> {
+            assert!(!key.starts_with("panic"), "read of {}", key);
+            self.0
+                .get(key)
+                .cloned()
+                .ok_or_else(|| FramePoolError::NotFound(key.to_string()))
+        }
+
+        fn write(&mut self, key: &str, data: Arc<u64>) -> Result<(), FramePoolError> {
+            self.0.insert(key.to_string(), data);
+            Ok(())
+        }
+
+        fn exists(&self, key: &str) -> bool {
+            self.0.contains_key(key)
+        }
+
+        fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+            self.0.remove(key);
+            Ok(())
+        }
+
+        fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+            Ok(self.0.keys().cloned().collect())
+        }
+    }
+
+    #[test]
+    fn test_cached_backend_hits_and_lru() {
+        let test_dir = "/tmp/test_cached_backend_lru";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut backend = FileBackend::new(test_dir);
+        for i in 0..4u64 {
+            backend.write_data(&format!("k{}", i), Arc::new(i)).unwrap();
+        }
+        let mut cached = CachedBackend::<u64, _>::new(backend, 2, bottom_evictor);
+        assert_eq!(*cached.read("k0").unwrap(), 0);
+        assert_eq!(*cached.read("k1").unwrap(), 1);
+        assert_eq!(*cached.read("k0").unwrap(), 0);
+        assert_eq!((cached.hits(), cached.misses()), (1, 2));
+
+        // k1 is least recently used, so loading k2 drops it
+        cached.read("k2").unwrap();
+        assert!(cached.is_cached("k0"));
+        assert!(!cached.is_cached("k1"));
+        assert!(cached.is_cached("k2"));
+
+        // Writes go through and are cached
+        cached.write("k3", Arc::new(30)).unwrap();
+        assert!(cached.is_cached("k3"));
+        assert!(!cached.is_cached("k0"));
+        assert_eq!(*cached.read("k3").unwrap(), 30);
+        assert_eq!(cached.misses(), 3);
+
+        cached.delete("k3").unwrap();
+        assert!(!cached.exists("k3"));
+        assert!(cached.read("k3").is_err());
+        assert_eq!(StorageBackend::<u64>::list_keys(&cached).unwrap().len(), 3);
+        let mut backend = cached.into_inner();
+        assert_eq!(*backend.read_data::<u64>("k2").unwrap(), 2);
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_cached_backend_serves_hits_when_backend_fails() {
+        let test_dir = "/tmp/test_cached_backend_faults";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let faults = FaultInjector::new(0);
+        let backend = FaultyBackend::new(FileBackend::new(test_dir), faults.clone());
+        let mut cached = CachedBackend::<String, _>::new(backend, 4, bottom_evictor);
+        cached.write("a", Arc::new("alpha".to_string())).unwrap();
+
+        faults.set_read_failure_rate(1.0);
+        assert_eq!(*cached.read("a").unwrap(), "alpha");
+        assert!(matches!(cached.read("b"), Err(FramePoolError::Io(_))));
+        assert!(!cached.is_cached("b"));
+
+        // A failed write leaves the cached value alone
+        faults.set_write_failure_rate(1.0);
+        assert!(cached.write("a", Arc::new("beta".to_string())).is_err());
+        assert_eq!(*cached.read("a").unwrap(), "alpha");
+        assert_eq!(faults.injected(), 2);
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_cached_backend_survives_panicking_backend() {
+        let backend = PanickingBackend(HashMap::new());
+        let mut cached = CachedBackend::<u64, _>::new(backend, 4, bottom_evictor);
+        cached.write("a", Arc::new(1)).unwrap();
+
+        let panicked =
+            std::panic::catch_unwind(AssertUnwindSafe(|| cached.read("panic").map(|_| ())));
+        assert!(panicked.is_err());
+        // The cache is still there, and so is what it held
+        assert!(cached.is_cached("a"));
+        assert_eq!(*cached.read("a").unwrap(), 1);
+        assert_eq!(cached.hits(), 1);
+        cached.write("b", Arc::new(2)).unwrap();
+        assert!(cached.is_cached("b"));
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::fmt::Write;
+
+use super::{BufferPool, BufferPoolId, FramePoolId};
+
+// Bookkeeping for one occupied slot; never includes the page data.
+#[derive(Debug)]
+struct SlotState {
+    frame: FramePoolId,
+    pins: u32,
+    dirty: bool,
+    version: u64,
+    // 0 is the least recently used slot
+    lru_position: Option<usize>,
+}
+
+impl<'a, T> BufferPool<'a, T>
+where
+    T: Clone,
+{
+    fn slot_states(&self) -> Vec<Option<SlotState>> {
+        let lru_order = self.lru.order();
+        self.pages
+            .iter()
+            .enumerate()
+            .map(|(buf_idx, page)| {
+                let buf_idx = buf_idx as BufferPoolId;
+                let page = page.as_ref()?;
+                Some(SlotState {
+                    frame: *self.buf2frame.get(&buf_idx)?,
+                    pins: page.pin_count(),
+                    dirty: page.is_dirty(),
+                    version: page.version(),
+                    lru_position: lru_order.iter().position(|b| *b == buf_idx),
+                })
+            })
+            .collect()
+    }
+
+    /// Renders the state of every slot, one per line: the frame it holds, its pin count, dirty
+    /// bit and LRU position (0 is evicted first by `bottom_evictor`). Page data is not printed.
+    pub fn dump(&self) -> String {
+        let slots = self.slot_states();
+        let occupied = slots.iter().filter(|s| s.is_some()).count();
+        let pinned = slots.iter().flatten().filter(|s| s.pins > 0).count();
+        let mut out = format!(
+            "BufferPool: {}/{} slots occupied, {} pinned, {} frames in backing pool{}\n",
+            occupied,
+            self.size,
+            pinned,
+            self.frame_pool.size(),
+            if self.read_only { ", read-only" } else { "" }
+        );
+        for (buf_idx, slot) in slots.iter().enumerate() {
+            match slot {
+                None => writeln!(out, "  slot {}: empty", buf_idx),
+                Some(state) => writeln!(
+                    out,
+                    "  slot {}: frame {}, pins {}, {}, version {}, lru {}",
+                    buf_idx,
+                    state.frame,
+                    state.pins,
+                    if state.dirty { "dirty" } else { "clean" },
+                    state.version,
+                    match state.lru_position {
+                        Some(position) => position.to_string(),
+                        None => "-".to_string(),
+                    }
+                ),
+            }
+            .unwrap();
+        }
+        out
+    }
+}
+
+impl<'a, T> fmt::Debug for BufferPool<'a, T>
+where
+    T: Clone,
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let partitions: Vec<&str> = self.partitions.iter().map(|p| p.name.as_str()).collect();
+        fmt.debug_struct("BufferPool")
+            .field("size", &self.size)
+            .field("frame_pool_size", &self.frame_pool.size())
+            .field("read_only", &self.read_only)
+            .field("checkpoint_seq", &self.checkpoint_seq)
+            .field("partitions", &partitions)
+            .field("slots", &self.slot_states())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::bottom_evictor;
+    use crate::framepool::{FramePool, MemPool};
+    use std::sync::Arc;
+
+    // A type without Debug, to show the dump never needs the data.
+    #[derive(Clone)]
+    struct Opaque(#[allow(dead_code)] u32);
+
+    #[test]
+    fn test_dump() {
+        let mut mem_pool = MemPool::<Opaque>::new();
+        mem_pool.resize(5).unwrap();
+        for i in 0..5 {
+            mem_pool.put_frame(i, Arc::new(Opaque(i as u32))).unwrap();
+        }
+        let mut bp = BufferPool::<Opaque>::new(3, &mut mem_pool, bottom_evictor);
+        bp.get_page(4).unwrap();
+        bp.get_page(2).unwrap().pin();
+        bp.put_page(4, Opaque(40)).unwrap();
+
+        let dump = bp.dump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[0],
+            "BufferPool: 2/3 slots occupied, 1 pinned, 5 frames in backing pool"
+        );
+        assert_eq!(
+            lines[1],
+            "  slot 0: frame 4, pins 0, dirty, version 2, lru 1"
+        );
+        assert_eq!(
+            lines[2],
+            "  slot 1: frame 2, pins 1, clean, version 1, lru 0"
+        );
+        assert_eq!(lines[3], "  slot 2: empty");
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut mem_pool = MemPool::<Opaque>::new();
+        mem_pool.resize(2).unwrap();
+        mem_pool.put_frame(0, Arc::new(Opaque(0))).unwrap();
+        let mut bp = BufferPool::<Opaque>::new(2, &mut mem_pool, bottom_evictor);
+        bp.get_page(0).unwrap();
+
+        let debug = format!("{:?}", bp);
+        assert!(debug.starts_with("BufferPool { size: 2, frame_pool_size: 2, read_only: false"));
+        assert!(debug.contains(
+            "slots: [Some(SlotState { frame: 0, pins: 0, dirty: false, version: 1, lru_position: Some(0) }), None]"
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::overlay::Overlay;
+use super::{BufferPool, BufferPoolErrors, FramePoolId};
+
+/// A copy-on-write view of a BufferPool, created by `BufferPool::fork`.
+///
+/// Writes land in a private overlay; reads of pages the fork has not written fall through
+/// to the parent pool. Nothing the fork does is ever visible through the parent, which makes
+/// it suitable for what-if computations whose results are thrown away.
+pub struct PoolFork<'b, 'a, T>
+where
+    T: Clone,
+{
+    parent: &'b mut BufferPool<'a, T>,
+    // pages written by this fork; shared with the parent until first modified
+    overlay: Overlay<T>,
+}
+
+impl<'b, 'a, T> PoolFork<'b, 'a, T>
+where
+    T: Clone,
+{
+    pub(super) fn new(parent: &'b mut BufferPool<'a, T>) -> Self {
+        PoolFork {
+            parent,
+            overlay: Overlay::new(),
+        }
+    }
+
+    /// Reads a page as the fork sees it.
+    pub fn read(&mut self, frame_idx: FramePoolId) -> Result<Arc<T>, BufferPoolErrors> {
+        self.overlay.read(self.parent, frame_idx)
+    }
+
+    /// Replaces a page in the fork.
+    pub fn write(&mut self, frame_idx: FramePoolId, data: T) {
+        self.overlay.write(frame_idx, data);
+    }
+
+    /// Modifies a page in the fork. The page is copied on its first modification.
+    pub fn modify<F, R>(&mut self, frame_idx: FramePoolId, f: F) -> Result<R, BufferPoolErrors>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.overlay.modify(self.parent, frame_idx, f)
+    }
+
+    /// Whether the fork has its own copy of the page.
+    pub fn is_modified(&self, frame_idx: FramePoolId) -> bool {
+        self.overlay.contains(frame_idx)
+    }
+
+    /// Frame ids the fork has written, in ascending order.
+    pub fn modified_frames(&self) -> Vec<FramePoolId> {
+        self.overlay.frames()
+    }
+
+    /// Ends the fork, returning the pages it wrote.
+    pub fn into_overlay(self) -> HashMap<FramePoolId, Arc<T>> {
+        self.overlay.into_pages().into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{bottom_evictor, filled_mem_pool};
+    use crate::framepool::FramePool;
+
+    #[test]
+    fn test_fork_isolates_writes() {
+        let mut mem_pool = filled_mem_pool(3, |i| vec![i as u32]);
+        let mut bp = BufferPool::<Vec<u32>>::new(2, &mut mem_pool, bottom_evictor);
+
+        let mut fork = bp.fork();
+        fork.modify(0, |v| v.push(10)).unwrap();
+        fork.write(2, vec![99]);
+
+        assert_eq!(*fork.read(0).unwrap(), vec![0, 10]);
+        assert_eq!(*fork.read(1).unwrap(), vec![1]);
+        assert_eq!(*fork.read(2).unwrap(), vec![99]);
+        assert_eq!(fork.modified_frames(), vec![0, 2]);
+        assert!(!fork.is_modified(1));
+        drop(fork);
+
+        assert_eq!(bp.get_page(0).unwrap().data(), vec![0]);
+        assert_eq!(bp.get_page(2).unwrap().data(), vec![2]);
+        assert!(!bp.get_page(0).unwrap().is_dirty());
+        drop(bp);
+        assert_eq!(*mem_pool.get_frame_ref(0).unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn test_fork_into_overlay() {
+        let mut mem_pool = filled_mem_pool(3, |i| vec![i as u32]);
+        let mut bp = BufferPool::<Vec<u32>>::new(1, &mut mem_pool, bottom_evictor);
+
+        let mut fork = bp.fork();
+        fork.modify(1, |v| v[0] = 7).unwrap();
+        // Reading other pages through a small parent cache does not lose the overlay
+        fork.read(0).unwrap();
+        fork.read(2).unwrap();
+        assert_eq!(*fork.read(1).unwrap(), vec![7]);
+
+        let overlay = fork.into_overlay();
+        assert_eq!(overlay.len(), 1);
+        assert_eq!(*overlay[&1], vec![7]);
+        assert_eq!(bp.get_page(1).unwrap().data(), vec![1]);
+    }
+
+    #[test]
+    fn test_fork_out_of_bounds() {
+        let mut mem_pool = filled_mem_pool(3, |i| vec![i as u32]);
+        let mut bp = BufferPool::<Vec<u32>>::new(2, &mut mem_pool, bottom_evictor);
+        let mut fork = bp.fork();
+        assert!(fork.read(10).is_err());
+        assert!(fork.modify(10, |v| v.clear()).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use futures::future::join_all;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use super::{AsyncBufferPool, BufferPoolErrors};
+use crate::framepool::AsyncFramePool;
+
+/// Settings for `AsyncBufferPool::start_maintenance`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceConfig {
+    /// Time between maintenance runs.
+    pub interval: Duration,
+    /// Most frames loaded from the prefetch queue per run.
+    pub prefetch_batch: usize,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        MaintenanceConfig {
+            interval: Duration::from_secs(1),
+            prefetch_batch: 16,
+        }
+    }
+}
+
+/// What a maintenance task has done so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaintenanceStats {
+    pub runs: u64,
+    pub pages_flushed: u64,
+    pub pages_prefetched: u64,
+    pub errors: u64,
+    pub last_error: Option<String>,
+}
+
+impl MaintenanceStats {
+    fn record(&mut self, error: BufferPoolErrors) {
+        self.errors += 1;
+        self.last_error = Some(error.to_string());
+    }
+}
+
+/// A running maintenance task. Dropping the handle does not stop the task: it runs until
+/// `stop` is called or the runtime shuts down.
+pub struct MaintenanceHandle {
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+    stats: Arc<Mutex<MaintenanceStats>>,
+}
+
+impl MaintenanceHandle {
+    pub fn stats(&self) -> MaintenanceStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stops the task after its current run, and waits for a final flush. Returns the stats
+    /// of the whole run.
+    pub async fn stop(self) -> MaintenanceStats {
+        let _ = self.stop.send(true);
+        let _ = self.task.await;
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+impl<T, P> AsyncBufferPool<T, P>
+where
+    T: Clone + Send + Sync + 'static,
+    P: AsyncFramePool<T> + 'static,
+{
+    /// Spawns a task on the current tokio runtime that, every `config.interval`, flushes the
+    /// pool's dirty pages and loads frames queued with `prefetch`. It goes through the same
+    /// paths as any other caller, so it never writes a page back while an eviction is writing
+    /// it, and a page it loads is cached like any other.
+    ///
+    /// # Panics
+    /// Panics if called outside a tokio runtime.
+    pub fn start_maintenance(self: &Arc<Self>, config: MaintenanceConfig) -> MaintenanceHandle {
+        let (stop, mut stopped) = watch::channel(false);
+        let stats = Arc::new(Mutex::new(MaintenanceStats::default()));
+        let pool = Arc::clone(self);
+        let task_stats = Arc::clone(&stats);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(config.interval);
+            // The first tick completes immediately
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = stopped.changed() => break,
+                }
+                pool.maintain(config.prefetch_batch, &task_stats).await;
+            }
+            pool.maintain(0, &task_stats).await;
+        });
+        MaintenanceHandle { stop, task, stats }
+    }
+
+    async fn maintain(&self, prefetch_batch: usize, stats: &Mutex<MaintenanceStats>) {
+        let flushed = self.flush().await;
+        let wanted = self.take_prefetch(prefetch_batch);
+        let loaded = join_all(wanted.iter().map(|&frame_idx| self.get(frame_idx))).await;
+
+        let mut stats = stats.lock().unwrap();
+        stats.runs += 1;
+        match flushed {
+            Ok(count) => stats.pages_flushed += count as u64,
+            // Nothing to flush in a read-only pool
+            Err(BufferPoolErrors::ReadOnly) => {}
+            Err(e) => stats.record(e),
+        }
+        for result in loaded {
+            match result {
+                Ok(_) => stats.pages_prefetched += 1,
+                Err(e) => stats.record(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::bottom_evictor;
+    use crate::framepool::AsyncDiskPool;
+
+    #[tokio::test]
+    async fn test_maintenance_flushes_and_prefetches() {
+        let dir = "/tmp/test_maintenance_flushes_and_prefetches";
+        let _ = std::fs::remove_dir_all(dir);
+        let frames = AsyncDiskPool::<u64>::open(dir).await.unwrap();
+        frames.resize(10).await.unwrap();
+        frames
+            .put_many((0..10).map(|i| (i, Arc::new(i))).collect())
+            .await
+            .unwrap();
+        let pool = Arc::new(AsyncBufferPool::new(4, frames, bottom_evictor));
+
+        let handle = pool.start_maintenance(MaintenanceConfig {
+            interval: Duration::from_millis(5),
+            prefetch_batch: 2,
+        });
+        assert!(handle.is_running());
+        pool.put(1, 100).await.unwrap();
+        pool.prefetch(&[7, 8, 9]);
+
+        for _ in 0..200 {
+            let stats = handle.stats();
+            if stats.pages_flushed >= 1 && stats.pages_prefetched >= 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(*pool.frames().get_frame_ref(1).await.unwrap(), 100);
+        for i in 7..10 {
+            assert!(pool.get_cached(i).is_some());
+        }
+
+        // Stopping flushes whatever was left dirty
+        pool.put(2, 200).await.unwrap();
+        let stats = handle.stop().await;
+        assert_eq!(stats.errors, 0, "{:?}", stats.last_error);
+        assert_eq!(stats.pages_prefetched, 3);
+        assert_eq!(*pool.frames().get_frame_ref(2).await.unwrap(), 200);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
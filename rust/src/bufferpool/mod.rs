@@ -1,11 +1,43 @@
 use rand;
 use rand::{Rng, thread_rng};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::iter::FusedIterator;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
 
 // Re-export modules for integration tests
 pub use crate::framepool;
 pub use crate::unique_stack;
+use framepool::{FrameKey, FramePoolError};
+
+mod advice;
+#[cfg(feature = "async")]
+mod async_pool;
+mod cached_backend;
+mod dump;
+mod fork;
+#[cfg(feature = "async")]
+mod maintenance;
+mod overlay;
+mod partition;
+mod pin;
+mod sharded;
+mod shared;
+mod transaction;
+mod validate;
+pub use advice::Advice;
+#[cfg(feature = "async")]
+pub use async_pool::AsyncBufferPool;
+pub use cached_backend::CachedBackend;
+pub use fork::PoolFork;
+#[cfg(feature = "async")]
+pub use maintenance::{MaintenanceConfig, MaintenanceHandle, MaintenanceStats};
+pub use partition::Partition;
+pub use pin::{PinGuard, PinWarningFn};
+pub use sharded::{ShardStats, ShardedBufferPool};
+pub use shared::SharedBufferPool;
+pub use transaction::Transaction;
+pub use validate::ValidationReport;
 
 type BufferPoolId = u64;
 type FramePoolId = u64;
@@ -19,18 +51,88 @@ type EvictorFn<T> = fn(
 pub enum BufferPoolErrors {
     NoEvictablePage,
     NoPageAvailable,
+    // the requested frame id is beyond the size of the backing frame pool
+    IndexOutOfBounds(FramePoolId),
+    // the backing frame pool failed to produce the requested frame
+    ReadFailed(FramePoolError),
+    // a dirty victim page could not be written back during eviction
+    FlushFailed(FramePoolError),
+    // the frame pool could not make its state durable
+    SyncFailed(FramePoolError),
+    // a transaction could not write `frame` to the frame pool and was rolled back; `rollback`
+    // lists the frames whose prior contents could not be restored
+    CommitFailed {
+        frame: FramePoolId,
+        error: FramePoolError,
+        rollback: Vec<(FramePoolId, FramePoolError)>,
+    },
+    // a conditional write found the page at a different version than expected
+    VersionConflict {
+        expected: u64,
+        actual: u64,
+    },
+    // a write or flush was attempted on a read-only pool
+    ReadOnly,
+    // a partition could not be created, or a frame was requested through the wrong one
+    InvalidPartition(String),
+    // more pages were requested at once than there are unpinned slots
+    InsufficientCapacity {
+        requested: usize,
+        available: usize,
+    },
 }
 
 impl std::fmt::Display for BufferPoolErrors {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        fmt.write_str(match self {
-            Self::NoEvictablePage => "no evictable pages",
-            Self::NoPageAvailable => "no available pages",
-        })
+        match self {
+            Self::NoEvictablePage => fmt.write_str("no evictable pages"),
+            Self::NoPageAvailable => fmt.write_str("no available pages"),
+            Self::IndexOutOfBounds(idx) => write!(fmt, "frame {} is out of bounds", idx),
+            Self::ReadFailed(e) => write!(fmt, "backing store read failed: {}", e),
+            Self::FlushFailed(e) => write!(fmt, "dirty page flush failed: {}", e),
+            Self::SyncFailed(e) => write!(fmt, "frame pool sync failed: {}", e),
+            Self::CommitFailed {
+                frame,
+                error,
+                rollback,
+            } => {
+                write!(fmt, "transaction commit failed: frame {}: {}", frame, error)?;
+                if !rollback.is_empty() {
+                    fmt.write_str("; rollback incomplete")?;
+                    for (frame, error) in rollback {
+                        write!(fmt, ": frame {}: {}", frame, error)?;
+                    }
+                }
+                Ok(())
+            }
+            Self::VersionConflict { expected, actual } => write!(
+                fmt,
+                "version conflict: expected {}, found {}",
+                expected, actual
+            ),
+            Self::ReadOnly => fmt.write_str("buffer pool is read-only"),
+            Self::InvalidPartition(e) => write!(fmt, "invalid partition: {}", e),
+            Self::InsufficientCapacity {
+                requested,
+                available,
+            } => write!(
+                fmt,
+                "{} pages requested but only {} slots available",
+                requested, available
+            ),
+        }
     }
 }
 
-impl std::error::Error for BufferPoolErrors {}
+impl std::error::Error for BufferPoolErrors {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ReadFailed(e) | Self::FlushFailed(e) | Self::SyncFailed(e) => Some(e),
+            Self::CommitFailed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
 
 pub fn random_evictor<T>(
     pages: &[Option<framepool::PageFrame<T>>],
@@ -80,7 +182,59 @@ where
     Err(BufferPoolErrors::NoEvictablePage)
 }
 
-pub struct BufferPool<'a, T>
+/// Token returned by `BufferPool::checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    // increases by one with every checkpoint taken on a pool
+    pub sequence: u64,
+    // number of dirty pages written back as part of the checkpoint
+    pub pages_flushed: usize,
+}
+
+/// A read guard over one page's data, returned by `BufferPool::at`.
+/// Dereferences to `T`. The guard holds a shared snapshot of the data: it stays valid if the
+/// page is evicted, and later modifications to the page are not reflected in it.
+pub struct PageGuard<T> {
+    frame_idx: FramePoolId,
+    data: Arc<T>,
+}
+
+impl<T> PageGuard<T> {
+    /// The frame id this guard was read from.
+    pub fn frame_idx(&self) -> FramePoolId {
+        self.frame_idx
+    }
+
+    /// Releases the guard, keeping the shared handle to the data.
+    pub fn into_arc(self) -> Arc<T> {
+        self.data
+    }
+}
+
+impl<T> std::ops::Deref for PageGuard<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T> AsRef<T> for PageGuard<T> {
+    fn as_ref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for PageGuard<T> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.debug_struct("PageGuard")
+            .field("frame_idx", &self.frame_idx)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+pub struct BufferPool<'a, T, K = FramePoolId>
 where
     T: Clone,
 {
@@ -92,996 +246,3089 @@ where
     pages: Vec<Option<framepool::PageFrame<T>>>,
 
     // maps bufferpool ids to framepool ids
-    buf2frame: HashMap<BufferPoolId, FramePoolId>,
+    buf2frame: HashMap<BufferPoolId, K>,
     // maps framepool ids to bufferpool ids
-    frame2buf: HashMap<FramePoolId, BufferPoolId>,
+    frame2buf: HashMap<K, BufferPoolId>,
     // for removing the least used page
     lru: unique_stack::UniqueStack<BufferPoolId>,
 
     evictor: EvictorFn<T>,
+    // sequence number of the last checkpoint taken
+    checkpoint_seq: u64,
+    // highest page version seen on any evicted page; reloaded pages start above it so a
+    // frame's version never goes backwards across eviction
+    version_floor: u64,
+    // reject writes and flushes, and never track or flush dirty pages
+    read_only: bool,
+    // weighs the data of each page loaded; None leaves pages at the shallow size of T
+    weigher: Option<framepool::Weigher<T>>,
+    // named frame ranges with their own slot quotas
+    partitions: Vec<Partition>,
+    // frame ranges advised as Sequential; their pages enter the LRU at the cold end
+    sequential: Vec<Range<FramePoolId>>,
+    // called for each pin held longer than the given duration, checked when pages are loaded
+    pin_warning: Option<(std::time::Duration, PinWarningFn<K>)>,
+    // accesses made by get_cached through &self, applied to the LRU on the next access
+    // through &mut self
+    deferred_touches: Mutex<Vec<(K, BufferPoolId)>>,
     // the framepool that this bufferpool uses
     // FramePoolIds index into this.
-    frame_pool: &'a mut dyn framepool::FramePool<T>,
+    frame_pool: &'a mut dyn framepool::FramePool<T, K>,
+}
+
+// Everything a BufferPool holds apart from its frame pool, so that a cache can be kept between
+// borrows of a frame pool it owns (see SharedBufferPool).
+pub(crate) struct PoolState<T, K = FramePoolId> {
+    size: usize,
+    pages: Vec<Option<framepool::PageFrame<T>>>,
+    buf2frame: HashMap<BufferPoolId, K>,
+    frame2buf: HashMap<K, BufferPoolId>,
+    lru: unique_stack::UniqueStack<BufferPoolId>,
+    evictor: EvictorFn<T>,
+    checkpoint_seq: u64,
+    version_floor: u64,
+    read_only: bool,
+    weigher: Option<framepool::Weigher<T>>,
+    partitions: Vec<Partition>,
+    sequential: Vec<Range<FramePoolId>>,
+    pin_warning: Option<(std::time::Duration, PinWarningFn<K>)>,
+    deferred_touches: Mutex<Vec<(K, BufferPoolId)>>,
+}
+
+impl<T, K> PoolState<T, K>
+where
+    K: FrameKey,
+{
+    // See BufferPool::get_cached.
+    pub(crate) fn get_cached(&self, frame_idx: &K) -> Option<Arc<T>> {
+        cached_lookup(
+            &self.frame2buf,
+            &self.pages,
+            &self.deferred_touches,
+            frame_idx,
+        )
+    }
+}
+
+// Beyond this many unapplied accesses, get_cached stops recording them until the next access
+// through &mut self; recency is then only approximate.
+const MAX_DEFERRED_TOUCHES: usize = 4096;
+
+fn cached_lookup<T, K: FrameKey>(
+    frame2buf: &HashMap<K, BufferPoolId>,
+    pages: &[Option<framepool::PageFrame<T>>],
+    deferred_touches: &Mutex<Vec<(K, BufferPoolId)>>,
+    frame_idx: &K,
+) -> Option<Arc<T>> {
+    let buffer_id = *frame2buf.get(frame_idx)?;
+    let page = pages[buffer_id as usize].as_ref()?;
+    let data = page.read_arc();
+    let mut touches = deferred_touches.lock().unwrap();
+    if touches.len() < MAX_DEFERRED_TOUCHES {
+        touches.push((frame_idx.clone(), buffer_id));
+    }
+    Some(data)
 }
 
 // Iterator for BufferPool that yields the data T from each frame
-pub struct BufferPoolIterator<'a, T>
+pub struct BufferPoolIterator<'b, 'a, T>
 where
     T: Clone,
 {
-    buffer_pool: &'a mut BufferPool<'a, T>,
+    buffer_pool: &'b mut BufferPool<'a, T>,
     current_index: FramePoolId,
-    total_size: u64,
+    // one past the last frame to visit
+    end_index: FramePoolId,
 }
 
-impl<'a, T> Iterator for BufferPoolIterator<'a, T>
+impl<'b, 'a, T> BufferPoolIterator<'b, 'a, T>
 where
     T: Clone,
 {
-    type Item = T;
+    /// Converts this iterator into one that yields `Arc<T>` handles rather than cloned values.
+    pub fn arcs(self) -> BufferPoolArcIterator<'b, 'a, T> {
+        BufferPoolArcIterator { inner: self }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.current_index >= self.total_size {
-            return None;
+    /// Converts this iterator into one that yields a result for every frame left in the
+    /// range, so that frames which fail to load are reported rather than skipped. Its length
+    /// is exact.
+    pub fn results(self) -> BufferPoolResultIterator<'b, 'a, T> {
+        BufferPoolResultIterator { inner: self }
+    }
+
+    fn next_arc(&mut self) -> Option<Arc<T>> {
+        loop {
+            if let (_, Ok(data)) = self.next_entry()? {
+                return Some(data);
+            }
         }
+    }
 
-        // Use BufferPool's get_page method to transparently handle caching
-        let result = self
-            .buffer_pool
-            .get_page(self.current_index)
-            .map(|page| page.data());
+    fn next_back_arc(&mut self) -> Option<Arc<T>> {
+        loop {
+            if let (_, Ok(data)) = self.next_back_entry()? {
+                return Some(data);
+            }
+        }
+    }
 
+    // Advances one frame, returning its id and its data or why it couldn't be loaded.
+    // Returns None only once the range is exhausted.
+    fn next_entry(&mut self) -> Option<(FramePoolId, Result<Arc<T>, BufferPoolErrors>)> {
+        if self.current_index >= self.end_index {
+            return None;
+        }
+        let frame_idx = self.current_index;
         self.current_index += 1;
-        result
+        Some((frame_idx, self.load(frame_idx)))
+    }
+
+    fn next_back_entry(&mut self) -> Option<(FramePoolId, Result<Arc<T>, BufferPoolErrors>)> {
+        if self.current_index >= self.end_index {
+            return None;
+        }
+        self.end_index -= 1;
+        let frame_idx = self.end_index;
+        Some((frame_idx, self.load(frame_idx)))
+    }
+
+    // Loads through the cache, like get_page_arc, but keeps the reason a frame couldn't be
+    // loaded.
+    fn load(&mut self, frame_idx: FramePoolId) -> Result<Arc<T>, BufferPoolErrors> {
+        self.buffer_pool
+            .try_get_page(frame_idx)
+            .map(|page| page.read_arc())
+    }
+
+    fn remaining(&self) -> usize {
+        self.end_index.saturating_sub(self.current_index) as usize
     }
 }
 
-impl<'a, T> IntoIterator for &'a mut BufferPool<'a, T>
+impl<'b, 'a, T> Iterator for BufferPoolIterator<'b, 'a, T>
 where
     T: Clone,
 {
     type Item = T;
-    type IntoIter = BufferPoolIterator<'a, T>;
 
-    fn into_iter(self) -> Self::IntoIter {
-        let total_size = self.frame_pool.size();
-        BufferPoolIterator {
-            buffer_pool: self,
-            current_index: 0,
-            total_size,
-        }
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_arc().map(|data| (*data).clone())
+    }
+
+    // Frames that can't be loaded are skipped, so the range only bounds the number of items;
+    // see results for an exact length.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining()))
     }
 }
 
-impl<'a, T> BufferPool<'a, T>
+// Reverse scans walk the range from its end, loading through the cache and skipping frames
+// that can't be loaded in the same way, so they visit the same frames as forward scans.
+impl<'b, 'a, T> DoubleEndedIterator for BufferPoolIterator<'b, 'a, T>
 where
     T: Clone,
 {
-    /// Creates a new BufferPool with the specified size, backing storage, and eviction policy.
-    ///
-    /// # Arguments
-    /// * `size` - Maximum number of pages to cache in memory
-    /// * `pool` - The backing storage (MemPool or DiskPool)
-    /// * `evictor` - Function to select which page to evict when cache is full
-    pub fn new(
-        size: usize,
-        pool: &'a mut dyn framepool::FramePool<T>,
-        evictor: EvictorFn<T>,
-    ) -> Self {
-        let mut alloced_pages = Vec::new();
-        for _i in 0..size {
-            alloced_pages.push(None);
-        }
-        BufferPool {
-            size,
-            pages: alloced_pages,
-            buf2frame: HashMap::new(),
-            frame2buf: HashMap::new(),
-            lru: unique_stack::UniqueStack::new(),
-            evictor,
-            frame_pool: pool,
-        }
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.next_back_arc().map(|data| (*data).clone())
     }
+}
 
-    /// Ensures that the backing storage has allocated space up to the given index.
-    pub fn ensure_allocation(&mut self, count: FramePoolId) -> Result<(), String> {
-        self.frame_pool.resize(count)
-    }
+impl<'b, 'a, T> FusedIterator for BufferPoolIterator<'b, 'a, T> where T: Clone {}
 
-    /// Writes a dirty page back to the backing storage if it's in the buffer pool.
-    pub fn sync_index(&mut self, frame_idx: FramePoolId) -> Result<(), String> {
-        if !self.frame2buf.contains_key(&frame_idx) {
-            return Ok(());
-        }
-        let buf_idx = self.frame2buf[&frame_idx];
-        let page = self.pages[buf_idx as usize]
-            .as_ref()
-            .ok_or("unable to access index".to_string())?;
-        if page.is_dirty() {
-            let data_arc = page.get_data_arc();
-            self.frame_pool.put_frame(frame_idx, data_arc)?
-        }
-        Ok(())
-    }
+// Iterator for BufferPool that yields shared handles to each frame's data, without cloning it
+pub struct BufferPoolArcIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    inner: BufferPoolIterator<'b, 'a, T>,
+}
 
-    /// Writes data to the page at the given index.
-    pub fn put_page(&mut self, frame_idx: FramePoolId, data: T) -> Result<(), BufferPoolErrors> {
-        let page = self
-            .get_page(frame_idx)
-            .ok_or(BufferPoolErrors::NoPageAvailable)?;
-        page.with_data(|d: &mut T| *d = data);
-        Ok(())
+impl<'b, 'a, T> Iterator for BufferPoolArcIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    type Item = Arc<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_arc()
     }
 
-    /// Flushes all dirty pages back to the backing storage.
-    pub fn flush_all(&mut self) -> Result<(), String> {
-        for (buf_idx, frame_idx) in self.buf2frame.clone() {
-            if let Some(page) = &self.pages[buf_idx as usize]
-                && page.is_dirty()
-            {
-                let data_arc = page.get_data_arc();
-                self.frame_pool.put_frame(frame_idx, data_arc)?;
-                page.set_dirty(false);
-            }
-        }
-        Ok(())
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
+}
 
-    /// Returns a reference to the page at the given index, loading it if necessary.
-    /// Updates the LRU tracking for the page.
-    pub fn get_page(&mut self, frame_idx: FramePoolId) -> Option<&framepool::PageFrame<T>> {
-        // If this is beyond the size of the backing frame, then we can't get the page.
-        if frame_idx > self.frame_pool.size() {
-            return None;
-        }
+impl<'b, 'a, T> DoubleEndedIterator for BufferPoolArcIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back_arc()
+    }
+}
 
-        if !self.frame2buf.contains_key(&frame_idx) {
-            // Then we don't have the page loaded.
-            if self.frame2buf.len() == self.size {
-                // Precondition of this block: the BufferPool is full.
+impl<'b, 'a, T> FusedIterator for BufferPoolArcIterator<'b, 'a, T> where T: Clone {}
 
-                // Then we are full and must evict the least recently used page.
-                let victim_idx = (self.evictor)(&self.pages, &self.lru).ok()?; // Select a bufferID to remove.
+// Iterator for BufferPool that yields each frame's data, or why it couldn't be loaded
+pub struct BufferPoolResultIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    inner: BufferPoolIterator<'b, 'a, T>,
+}
 
-                let victim_page = self.pages[victim_idx as usize].as_ref().unwrap();
-                // Get the frame_id that was mapped to this buffer slot
-                let victim_frame_id = self.buf2frame[&victim_idx];
+impl<'b, 'a, T> Iterator for BufferPoolResultIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    type Item = Result<Arc<T>, BufferPoolErrors>;
 
-                if victim_page.is_dirty() {
-                    // Flush the page to the pool
-                    let d = self.pages[victim_idx as usize].as_ref()?;
-                    let data_arc = d.get_data_arc();
-                    self.frame_pool.put_frame(victim_frame_id, data_arc).ok()?;
-                }
-                // Precondition: the page is not dirty, or we have flushed it.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_entry().map(|(_, result)| result)
+    }
 
-                self.pages[victim_idx as usize] = None;
-                self.buf2frame.remove(&victim_idx);
-                self.frame2buf.remove(&victim_frame_id);
-                self.lru.delete(victim_idx);
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.inner.remaining(), Some(self.inner.remaining()))
+    }
+}
 
-                // Postcondition of this block: the block is not full, we have 1 slot open.
-            }
+impl<'b, 'a, T> DoubleEndedIterator for BufferPoolResultIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back_entry().map(|(_, result)| result)
+    }
+}
 
-            // Precondition: We are not full, which is a None element in the self.pages vec.
+impl<'b, 'a, T> ExactSizeIterator for BufferPoolResultIterator<'b, 'a, T> where T: Clone {}
 
-            let target_idx = self.pages.iter().position(|x| x.is_none())? as BufferPoolId;
+impl<'b, 'a, T> FusedIterator for BufferPoolResultIterator<'b, 'a, T> where T: Clone {}
 
-            let frame_data = self.frame_pool.get_frame_ref(frame_idx).ok()?;
-            let new_frame = framepool::PageFrame::new_with_arc(frame_data);
+// Iterator for BufferPool that yields (frame id, data) pairs, skipping frames that can't be loaded
+pub struct BufferPoolEntryIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    inner: BufferPoolIterator<'b, 'a, T>,
+}
 
-            self.pages[target_idx as usize] = Some(new_frame);
-            self.buf2frame.insert(target_idx, frame_idx);
-            self.frame2buf.insert(frame_idx, target_idx);
-        }
+impl<'b, 'a, T> Iterator for BufferPoolEntryIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    type Item = (FramePoolId, T);
 
-        match self.frame2buf.get(&frame_idx) {
-            None => None, // this should be an assert tbh.
-            Some(buffer_id) => {
-                let b: u64 = *buffer_id;
-                self.lru.push(b);
-                self.pages[b as usize].as_ref()
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (frame_idx, data) = self.inner.next_entry()?;
+            if let Ok(data) = data {
+                return Some((frame_idx, (*data).clone()));
             }
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.inner.remaining()))
+    }
 }
 
-pub struct SlabMapper<'a, T>
+// Iterator for BufferPool that yields batches of data, each loaded with one grouped lookup
+pub struct BufferPoolChunkIterator<'b, 'a, T>
 where
     T: Clone,
 {
-    slab: BufferPool<'a, T>,
-    stride: usize,
+    inner: BufferPoolIterator<'b, 'a, T>,
+    chunk_size: u64,
 }
 
-impl<'a, T> SlabMapper<'a, T>
+impl<'b, 'a, T> Iterator for BufferPoolChunkIterator<'b, 'a, T>
 where
     T: Clone,
 {
-    pub fn new(size: usize, pool: &'a mut dyn framepool::FramePool<T>, stride: usize) -> Self {
-        SlabMapper {
-            slab: BufferPool::new(size, pool, bottom_evictor),
-            stride,
+    type Item = Vec<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.inner.current_index;
+        if start >= self.inner.end_index {
+            return None;
         }
+        let end = (start + self.chunk_size).min(self.inner.end_index);
+        self.inner.current_index = end;
+
+        let frame_idxs: Vec<FramePoolId> = (start..end).collect();
+        let chunk = self
+            .inner
+            .buffer_pool
+            .get_many(&frame_idxs)
+            .into_iter()
+            .flatten()
+            .map(|data| (*data).clone())
+            .collect();
+        Some(chunk)
     }
 
-    pub fn load(&mut self) -> Result<(), String> {
-        self.slab.ensure_allocation(0)?;
-        Ok(())
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let chunks = (self.inner.remaining() as u64).div_ceil(self.chunk_size) as usize;
+        (chunks, Some(chunks))
     }
+}
 
-    pub fn flush(&mut self, seq: Vec<T>) -> Result<(), String> {
-        let required_allocation = seq.len().div_ceil(self.stride);
-        self.slab
-            .ensure_allocation(required_allocation as FramePoolId)?;
+// Iterator for BufferPool that loads one chunk of frames at a time and hands each chunk out
+// as a rayon parallel iterator.
+#[cfg(feature = "rayon")]
+pub struct BufferPoolParChunkIterator<'b, 'a, T>
+where
+    T: Clone,
+{
+    inner: BufferPoolIterator<'b, 'a, T>,
+    chunk_size: u64,
+}
 
-        // Phase 1: Write to backing store (external, out of our control)
-        // If this fails, nothing has been modified yet, so we can safely return error
-        for i in 0..required_allocation {
-            let bottom = i * self.stride;
-            if bottom < seq.len() {
-                let data_arc = Arc::new(seq[bottom].clone());
-                self.slab
-                    .frame_pool
-                    .put_frame(i as FramePoolId, data_arc)
-                    .map_err(|e| {
-                        format!("Failed to write to backing store at frame {}: {}", i, e)
-                    })?;
-            }
+#[cfg(feature = "rayon")]
+impl<'b, 'a, T> Iterator for BufferPoolParChunkIterator<'b, 'a, T>
+where
+    T: Clone + Send + Sync,
+{
+    type Item = rayon::vec::IntoIter<(FramePoolId, Arc<T>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        use rayon::iter::IntoParallelIterator;
+
+        let start = self.inner.current_index;
+        if start >= self.inner.end_index {
+            return None;
         }
+        let end = (start + self.chunk_size).min(self.inner.end_index);
+        self.inner.current_index = end;
+
+        let frame_idxs: Vec<FramePoolId> = (start..end).collect();
+        let chunk: Vec<(FramePoolId, Arc<T>)> = frame_idxs
+            .iter()
+            .zip(self.inner.buffer_pool.get_many(&frame_idxs))
+            .filter_map(|(&frame_idx, data)| data.map(|data| (frame_idx, data)))
+            .collect();
+        Some(chunk.into_par_iter())
+    }
+}
 
-        // Phase 2: Update BufferPool (under our control)
-        // If this fails after backing store writes succeeded, we have inconsistent state
-        // But per your requirement, both must succeed, so we continue trying all updates
-        let mut buffer_errors = Vec::new();
+// Iterator over the pages currently held in the pool, yielding (frame id, page) pairs.
+// Reads only: nothing is loaded and LRU state is untouched.
+pub struct CachedPageIterator<'b, T> {
+    pages: std::iter::Enumerate<std::slice::Iter<'b, Option<framepool::PageFrame<T>>>>,
+    buf2frame: &'b HashMap<BufferPoolId, FramePoolId>,
+}
 
-        for i in 0..required_allocation {
-            let bottom = i * self.stride;
-            if bottom < seq.len()
-                && let Err(e) = self.slab.put_page(i as FramePoolId, seq[bottom].clone())
+impl<'b, T> Iterator for CachedPageIterator<'b, T> {
+    type Item = (FramePoolId, &'b framepool::PageFrame<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (buf_idx, page) = self.pages.next()?;
+            if let Some(page) = page
+                && let Some(&frame_idx) = self.buf2frame.get(&(buf_idx as BufferPoolId))
             {
-                buffer_errors.push((i, e));
+                return Some((frame_idx, page));
             }
         }
-
-        // If any buffer updates failed, report all failures
-        if !buffer_errors.is_empty() {
-            let error_msgs: Vec<String> = buffer_errors
-                .into_iter()
-                .map(|(frame, err)| format!("Frame {}: {}", frame, err))
-                .collect();
-            return Err(format!(
-                "BufferPool updates failed: {}",
-                error_msgs.join("; ")
-            ));
-        }
-
-        Ok(())
     }
+}
 
-    pub fn get(&mut self, idx: usize) -> Option<T> {
-        let page_idx = (idx / self.stride) as FramePoolId;
-        self.slab.get_page(page_idx).map(|page| page.data())
+impl<'b, 'a, T> IntoIterator for &'b mut BufferPool<'a, T>
+where
+    T: Clone,
+{
+    type Item = T;
+    type IntoIter = BufferPoolIterator<'b, 'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let total_size = self.frame_pool.size();
+        self.range(0..total_size)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::framepool;
-    use crate::framepool::{FramePool, MemPool};
-    use crate::unique_stack;
+impl<'a, T> BufferPool<'a, T>
+where
+    T: Clone,
+{
+    /// Creates a new BufferPool with the specified size, backing storage, and eviction policy.
+    ///
+    /// # Arguments
+    /// * `size` - Maximum number of pages to cache in memory
+    /// * `pool` - The backing storage (MemPool or DiskPool)
+    /// * `evictor` - Function to select which page to evict when cache is full
+    pub fn new(
+        size: usize,
+        pool: &'a mut dyn framepool::FramePool<T>,
+        evictor: EvictorFn<T>,
+    ) -> Self {
+        Self::new_keyed(size, pool, evictor)
+    }
+
+    /// Returns an iterator over the pages currently cached, as `(frame id, page)` pairs in
+    /// slot order. Nothing is loaded and recency tracking is not updated, so this is safe
+    /// to use for metrics and debugging from a shared reference.
+    pub fn cached_iter(&self) -> CachedPageIterator<'_, T> {
+        CachedPageIterator {
+            pages: self.pages.iter().enumerate(),
+            buf2frame: &self.buf2frame,
+        }
+    }
+
+    /// Returns an iterator over the data of every frame in the backing pool. The iterator
+    /// only borrows the pool for its own lifetime, so the pool can be used again afterwards.
+    pub fn iter_mut(&mut self) -> BufferPoolIterator<'_, 'a, T> {
+        let total_size = self.frame_pool.size();
+        self.range(0..total_size)
+    }
+
+    /// Returns an iterator over the data of the frames in `range`, with the same transparent
+    /// caching and eviction as iterating the whole pool. The end of the range is clamped to
+    /// the size of the backing frame pool.
+    pub fn range(&mut self, range: Range<FramePoolId>) -> BufferPoolIterator<'_, 'a, T> {
+        let end_index = range.end.min(self.frame_pool.size());
+        BufferPoolIterator {
+            buffer_pool: self,
+            current_index: range.start,
+            end_index,
+        }
+    }
+
+    /// Returns an iterator over `(frame id, data)` pairs for the whole frame pool.
+    /// Frames that are missing or fail to load are skipped rather than ending the iteration,
+    /// so sparse pools can be walked meaningfully.
+    pub fn iter_entries(&mut self) -> BufferPoolEntryIterator<'_, 'a, T> {
+        let total_size = self.frame_pool.size();
+        BufferPoolEntryIterator {
+            inner: self.range(0..total_size),
+        }
+    }
+
+    /// Returns an iterator over the whole frame pool in batches of up to `chunk_size` values.
+    /// Each batch is resolved with a single `get_many` call; frames that can't be loaded are
+    /// left out of their batch.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0.
+    pub fn iter_chunks(&mut self, chunk_size: usize) -> BufferPoolChunkIterator<'_, 'a, T> {
+        assert!(chunk_size != 0, "chunk_size must be non-zero");
+        let total_size = self.frame_pool.size();
+        BufferPoolChunkIterator {
+            inner: self.range(0..total_size),
+            chunk_size: chunk_size as u64,
+        }
+    }
+
+    /// Visits every frame through the cache, handing `f` a reference to each value rather
+    /// than a clone. Frames that can't be loaded are skipped.
+    pub fn for_each_ref<F>(&mut self, mut f: F)
+    where
+        F: FnMut(FramePoolId, &T),
+    {
+        let total_size = self.frame_pool.size();
+        for frame_idx in 0..total_size {
+            if let Some(page) = self.get_page(frame_idx) {
+                page.read_data(|data| f(frame_idx, data));
+            }
+        }
+    }
+
+    /// Returns an iterator of rayon parallel iterators, one per chunk of up to `chunk_size`
+    /// frames. Loading goes through the cache on the calling thread, one `get_many` per chunk;
+    /// the `(frame id, data)` pairs of each chunk can then be processed across threads.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0.
+    #[cfg(feature = "rayon")]
+    pub fn par_iter_chunks(&mut self, chunk_size: usize) -> BufferPoolParChunkIterator<'_, 'a, T> {
+        assert!(chunk_size != 0, "chunk_size must be non-zero");
+        let total_size = self.frame_pool.size();
+        BufferPoolParChunkIterator {
+            inner: self.range(0..total_size),
+            chunk_size: chunk_size as u64,
+        }
+    }
+
+    /// Runs `f` over every frame on rayon's thread pool. Frames are loaded on the calling
+    /// thread, a chunk the size of this pool at a time, and only `f` runs in parallel, so at
+    /// most one pool's worth of values is held outside the cache at a time. Frames that can't
+    /// be loaded are skipped. To load frames in parallel too, use `ShardedBufferPool::par_iter`.
+    #[cfg(feature = "rayon")]
+    pub fn par_for_each<F>(&mut self, f: F)
+    where
+        F: Fn(FramePoolId, &T) + Send + Sync,
+        T: Send + Sync,
+    {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let total_size = self.frame_pool.size();
+        let chunk_size = self.size.max(1) as u64;
+        let mut start = 0;
+        while start < total_size {
+            let end = (start + chunk_size).min(total_size);
+            let frame_idxs: Vec<FramePoolId> = (start..end).collect();
+            let chunk: Vec<(FramePoolId, Arc<T>)> = frame_idxs
+                .iter()
+                .zip(self.get_many(&frame_idxs))
+                .filter_map(|(&frame_idx, data)| data.map(|data| (frame_idx, data)))
+                .collect();
+            chunk
+                .into_par_iter()
+                .for_each(|(frame_idx, data)| f(frame_idx, &data));
+            start = end;
+        }
+    }
+
+    /// Starts a transaction over this pool. Modifications are staged in the transaction and
+    /// only reach the cache and backing store when it is committed.
+    pub fn begin(&mut self) -> Transaction<'_, 'a, T> {
+        Transaction::new(self)
+    }
+
+    /// Creates a copy-on-write fork of this pool. The fork's writes are kept in a private
+    /// overlay and never reach this pool; reads of unwritten pages go through this pool.
+    pub fn fork(&mut self) -> PoolFork<'_, 'a, T> {
+        PoolFork::new(self)
+    }
+
+    /// Appends values to the end of the backing frame pool, allocating frames as needed.
+    /// Values are written in batches the size of this pool: each batch costs one `resize`
+    /// followed by one `put_frames`. Returns the number of frames appended.
+    ///
+    /// If a write fails, the frame pool is truncated back to end with the last value written
+    /// before it and the error is returned, so the pool's size still tells how many values
+    /// were appended. Frame pools that can't truncate are left with the frames allocated.
+    pub fn bulk_append<I>(&mut self, values: I) -> Result<u64, FramePoolError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.check_writable()
+            .map_err(|_| FramePoolError::ReadOnly)?;
+        let batch_size = self.size.max(1);
+        let mut appended = 0;
+        let mut values = values.into_iter().peekable();
+        while values.peek().is_some() {
+            let first_idx = self.frame_pool.size();
+            let batch: Vec<(FramePoolId, Arc<T>)> = values
+                .by_ref()
+                .take(batch_size)
+                .enumerate()
+                .map(|(offset, value)| (first_idx + offset as FramePoolId, Arc::new(value)))
+                .collect();
+            self.frame_pool.resize(batch.len() as u64)?;
+            let results = self.frame_pool.put_frames(batch.clone());
+            for ((frame_idx, data_arc), result) in batch.into_iter().zip(results) {
+                if let Err(e) = result {
+                    let _ = self.frame_pool.truncate(frame_idx);
+                    return Err(e);
+                }
+                // Keep any stale cached copy of this frame in step with the write.
+                if let Some(&buf_idx) = self.frame2buf.get(&frame_idx)
+                    && let Some(page) = &self.pages[buf_idx as usize]
+                {
+                    page.put_arc(data_arc);
+                    page.set_dirty(false);
+                }
+                appended += 1;
+            }
+        }
+        Ok(appended)
+    }
+
+    /// Returns a guard that dereferences to the data at the given index, loading it if
+    /// necessary: `*pool.at(5)?` instead of `pool.get_page(5).unwrap().data()`.
+    pub fn at(&mut self, frame_idx: FramePoolId) -> Result<PageGuard<T>, BufferPoolErrors> {
+        let data = self.try_get_page(frame_idx)?.read_arc();
+        Ok(PageGuard { frame_idx, data })
+    }
+
+    /// Fills open slots with the given frames, treating earlier ids as hotter. Nothing is
+    /// evicted: loading stops once the pool is full, and frames whose partition is at its
+    /// quota or that fail to read are skipped. Returns the number of frames loaded.
+    ///
+    /// Passing the result of `hot_frames` from a previous run restores that working set.
+    pub fn warm(&mut self, frame_idxs: &[FramePoolId]) -> usize {
+        let mut loaded = Vec::new();
+        let mut remaining = frame_idxs;
+        // Read as many frames as there are free slots in one batch, and go round again for
+        // slots left open by failed reads or full partitions.
+        'batches: while !remaining.is_empty() && self.frame2buf.len() < self.size {
+            let free = self.size - self.frame2buf.len();
+            let mut batch: Vec<FramePoolId> = Vec::with_capacity(free);
+            let mut batched: HashSet<FramePoolId> = HashSet::with_capacity(free);
+            let mut taken = 0;
+            for &frame_idx in remaining {
+                taken += 1;
+                if self.frame2buf.contains_key(&frame_idx)
+                    || frame_idx >= self.frame_pool.size()
+                    || !self.has_quota_for(&frame_idx)
+                    || !batched.insert(frame_idx)
+                {
+                    continue;
+                }
+                batch.push(frame_idx);
+                if batch.len() == free {
+                    break;
+                }
+            }
+            remaining = &remaining[taken..];
+
+            let reads = self.frame_pool.get_frames(&batch);
+            for (frame_idx, read) in batch.into_iter().zip(reads) {
+                // Earlier frames of the batch may have used up the partition's quota
+                let Ok(frame_data) = read else {
+                    continue;
+                };
+                if !self.has_quota_for(&frame_idx) {
+                    continue;
+                }
+                match self.install(frame_idx, frame_data) {
+                    Ok(buffer_id) => loaded.push(buffer_id),
+                    Err(_) => break 'batches,
+                }
+            }
+        }
+        // Coldest first, so the first requested frame ends up most recently used.
+        for &buffer_id in loaded.iter().rev() {
+            self.lru.push(buffer_id);
+        }
+        loaded.len()
+    }
+
+    /// Fills open slots with frames 0, 1, 2, ... up to `count` frames, as `warm` does.
+    pub fn warm_sequential(&mut self, count: u64) -> usize {
+        let end = count.min(self.frame_pool.size());
+        let frame_idxs: Vec<FramePoolId> = (0..end).collect();
+        self.warm(&frame_idxs)
+    }
+
+    /// The frame ids currently cached, most recently used first. Save this before shutting
+    /// down and pass it to `warm` after a restart to restore the working set.
+    pub fn hot_frames(&self) -> Vec<FramePoolId> {
+        self.lru
+            .order()
+            .into_iter()
+            .rev()
+            .filter_map(|buf_idx| self.buf2frame.get(&buf_idx).copied())
+            .collect()
+    }
+}
+
+impl<'a, T, K> BufferPool<'a, T, K>
+where
+    T: Clone,
+    K: FrameKey,
+{
+    /// Creates a BufferPool over a frame pool addressed by keys of type `K`, such as
+    /// `(table_id, page_no)` pairs or strings. Otherwise the same as `new`.
+    pub fn new_keyed(
+        size: usize,
+        pool: &'a mut dyn framepool::FramePool<T, K>,
+        evictor: EvictorFn<T>,
+    ) -> Self {
+        let mut alloced_pages = Vec::new();
+        for _i in 0..size {
+            alloced_pages.push(None);
+        }
+        BufferPool {
+            size,
+            pages: alloced_pages,
+            buf2frame: HashMap::new(),
+            frame2buf: HashMap::new(),
+            lru: unique_stack::UniqueStack::new(),
+            evictor,
+            checkpoint_seq: 0,
+            version_floor: 0,
+            read_only: pool.is_read_only(),
+            weigher: None,
+            partitions: Vec::new(),
+            sequential: Vec::new(),
+            pin_warning: None,
+            deferred_touches: Mutex::new(Vec::new()),
+            frame_pool: pool,
+        }
+    }
+
+    // Detaches the cache from the frame pool.
+    pub(crate) fn into_state(self) -> PoolState<T, K> {
+        PoolState {
+            size: self.size,
+            pages: self.pages,
+            buf2frame: self.buf2frame,
+            frame2buf: self.frame2buf,
+            lru: self.lru,
+            evictor: self.evictor,
+            checkpoint_seq: self.checkpoint_seq,
+            version_floor: self.version_floor,
+            read_only: self.read_only,
+            weigher: self.weigher,
+            partitions: self.partitions,
+            sequential: self.sequential,
+            pin_warning: self.pin_warning,
+            deferred_touches: self.deferred_touches,
+        }
+    }
+
+    // Reattaches a cache detached by into_state to the frame pool it was built over.
+    pub(crate) fn from_state(
+        state: PoolState<T, K>,
+        pool: &'a mut dyn framepool::FramePool<T, K>,
+    ) -> Self {
+        BufferPool {
+            size: state.size,
+            pages: state.pages,
+            buf2frame: state.buf2frame,
+            frame2buf: state.frame2buf,
+            lru: state.lru,
+            evictor: state.evictor,
+            checkpoint_seq: state.checkpoint_seq,
+            version_floor: state.version_floor,
+            read_only: state.read_only,
+            weigher: state.weigher,
+            partitions: state.partitions,
+            sequential: state.sequential,
+            pin_warning: state.pin_warning,
+            deferred_touches: state.deferred_touches,
+            frame_pool: pool,
+        }
+    }
+
+    /// Whether this pool rejects writes. Pools over a read-only frame pool start read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Switches the pool into or out of read-only mode. Dirty pages are flushed before the
+    /// pool becomes read-only. A pool over a read-only frame pool cannot be made writable.
+    pub fn set_read_only(&mut self, read_only: bool) -> Result<(), BufferPoolErrors> {
+        if !read_only && self.frame_pool.is_read_only() {
+            return Err(BufferPoolErrors::ReadOnly);
+        }
+        if read_only && !self.read_only {
+            self.flush_dirty().map_err(BufferPoolErrors::FlushFailed)?;
+        }
+        self.read_only = read_only;
+        for page in self.pages.iter().flatten() {
+            page.set_read_only(read_only);
+        }
+        Ok(())
+    }
+
+    /// Weighs each page's data with `weigher`, for instance to count memory `T` owns on the
+    /// heap, so that `PageFrame::byte_size` reports it. Pages already cached are weighed again.
+    pub fn set_weigher<F>(&mut self, weigher: F)
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        let weigher: framepool::Weigher<T> = Arc::new(weigher);
+        for slot in self.pages.iter_mut() {
+            *slot = slot
+                .take()
+                .map(|page| page.with_weigher(Arc::clone(&weigher)));
+        }
+        self.weigher = Some(weigher);
+    }
+
+    fn check_writable(&self) -> Result<(), BufferPoolErrors> {
+        if self.read_only {
+            return Err(BufferPoolErrors::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Ensures that the backing storage has allocated space up to the given index.
+    pub fn ensure_allocation(&mut self, count: FramePoolId) -> Result<(), FramePoolError> {
+        self.frame_pool.resize(count)
+    }
+
+    /// Grows the backing storage to hold `count` frames. Unlike `ensure_allocation`, `count` is
+    /// the size wanted, not the number of frames to add.
+    pub fn grow_to(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.check_writable()
+            .map_err(|_| FramePoolError::ReadOnly)?;
+        self.frame_pool.grow_to(count)
+    }
+
+    /// Shrinks the backing storage to `count` frames, freeing every frame past the new end.
+    /// Cached pages of the freed frames are dropped, dirty or not. If the frame pool fails to
+    /// truncate, the cache is left as it was.
+    pub fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.check_writable()
+            .map_err(|_| FramePoolError::ReadOnly)?;
+        self.frame_pool.truncate(count)?;
+        let doomed: Vec<BufferPoolId> = self
+            .buf2frame
+            .iter()
+            .filter(|(_, frame_idx)| frame_idx.slot().is_some_and(|slot| slot >= count))
+            .map(|(buf_idx, _)| *buf_idx)
+            .collect();
+        for buf_idx in doomed {
+            self.discard_slot(buf_idx);
+        }
+        Ok(())
+    }
+
+    /// Writes a dirty page back to the backing storage if it's in the buffer pool.
+    pub fn sync_index(&mut self, frame_idx: K) -> Result<(), FramePoolError> {
+        self.check_writable()
+            .map_err(|_| FramePoolError::ReadOnly)?;
+        if !self.frame2buf.contains_key(&frame_idx) {
+            return Ok(());
+        }
+        let buf_idx = self.frame2buf[&frame_idx];
+        let page = self.pages[buf_idx as usize]
+            .as_ref()
+            .ok_or_else(|| FramePoolError::Corruption("unable to access index".to_string()))?;
+        if page.is_dirty() {
+            let data_arc = page.data_to_flush()?;
+            self.frame_pool.put_frame(frame_idx, data_arc)?
+        }
+        Ok(())
+    }
+
+    /// Writes data to the page at the given index.
+    pub fn put_page(&mut self, frame_idx: K, data: T) -> Result<(), BufferPoolErrors> {
+        self.check_writable()?;
+        let page = self
+            .get_page(frame_idx)
+            .ok_or(BufferPoolErrors::NoPageAvailable)?;
+        // replaced whole, so a change a panic cut short is gone
+        page.put(data);
+        page.set_dirty(true);
+        Ok(())
+    }
+
+    /// Writes data to the page at the given index only if the page is still at version
+    /// `expected` (as read from `PageFrame::version`). Returns the page's new version.
+    pub fn put_page_if_version(
+        &mut self,
+        frame_idx: K,
+        expected: u64,
+        data: T,
+    ) -> Result<u64, BufferPoolErrors> {
+        self.check_writable()?;
+        self.try_get_page(frame_idx)?
+            .put_if_version(expected, data)
+            .map_err(|actual| BufferPoolErrors::VersionConflict { expected, actual })
+    }
+
+    /// Modifies the page at the given index in place, marking it dirty.
+    pub fn modify_page<F, R>(&mut self, frame_idx: K, f: F) -> Result<R, BufferPoolErrors>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.check_writable()?;
+        Ok(self.try_get_page(frame_idx)?.with_data(f))
+    }
+
+    /// Flushes all dirty pages back to the backing storage.
+    pub fn flush_all(&mut self) -> Result<(), FramePoolError> {
+        self.check_writable()
+            .map_err(|_| FramePoolError::ReadOnly)?;
+        self.flush_dirty().map(|_| ())
+    }
+
+    // Writes every dirty page back to the frame pool in one batch, returning how many were
+    // written. Pages whose write failed, or that a panic left half modified, stay dirty.
+    fn flush_dirty(&mut self) -> Result<usize, FramePoolError> {
+        let mut dirty = Vec::new();
+        let mut writes = Vec::new();
+        let mut first_error = None;
+        for (buf_idx, frame_idx) in self.buf2frame.iter() {
+            if let Some(page) = &self.pages[*buf_idx as usize]
+                && page.is_dirty()
+            {
+                match page.data_to_flush() {
+                    Ok(data) => {
+                        dirty.push(*buf_idx);
+                        writes.push((frame_idx.clone(), data));
+                    }
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        let mut flushed = 0;
+        for (buf_idx, result) in dirty.into_iter().zip(self.frame_pool.put_frames(writes)) {
+            match result {
+                Ok(()) => {
+                    if let Some(page) = &self.pages[buf_idx as usize] {
+                        page.set_dirty(false);
+                    }
+                    flushed += 1;
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(flushed), Err)
+    }
+
+    /// Flushes all dirty pages, asks the frame pool to make its state durable (fsync for
+    /// DiskPool), and returns a token identifying this checkpoint. Sequence numbers start at 1
+    /// and increase with every successful checkpoint of this pool.
+    pub fn checkpoint(&mut self) -> Result<Checkpoint, BufferPoolErrors> {
+        self.check_writable()?;
+        let pages_flushed = self.flush_dirty().map_err(BufferPoolErrors::FlushFailed)?;
+        self.frame_pool
+            .sync()
+            .map_err(BufferPoolErrors::SyncFailed)?;
+        self.checkpoint_seq += 1;
+        Ok(Checkpoint {
+            sequence: self.checkpoint_seq,
+            pages_flushed,
+        })
+    }
+
+    /// Returns a reference to the page at the given index, loading it if necessary.
+    /// Updates the LRU tracking for the page.
+    pub fn get_page(&mut self, frame_idx: K) -> Option<&framepool::PageFrame<T>> {
+        self.try_get_page(frame_idx).ok()
+    }
+
+    /// Returns a shared handle to the data at the given index if it is already cached, without
+    /// loading anything. Takes `&self`, so any number of readers can share the pool; the access
+    /// still counts towards the page's recency, from the next call that takes `&mut self`.
+    pub fn get_cached(&self, frame_idx: &K) -> Option<Arc<T>> {
+        cached_lookup(
+            &self.frame2buf,
+            &self.pages,
+            &self.deferred_touches,
+            frame_idx,
+        )
+    }
+
+    /// Drops the cached copy of a frame that changed in the frame pool behind the pool's back,
+    /// as a `ChangeWatch` reports, so that the next read loads it again. Returns whether a copy
+    /// was dropped: a dirty or pinned page is kept, as dropping it would lose its changes or
+    /// take it from under its holder.
+    pub fn invalidate(&mut self, frame_idx: &K) -> bool {
+        let Some(&buffer_id) = self.frame2buf.get(frame_idx) else {
+            return false;
+        };
+        match &self.pages[buffer_id as usize] {
+            Some(page) if page.is_dirty() || page.is_pinned() => false,
+            _ => {
+                self.discard_slot(buffer_id);
+                true
+            }
+        }
+    }
+
+    // Like get_page_arc, but keeps the reason a page could not be loaded.
+    #[cfg(feature = "async")]
+    fn get_page_arc_or_err(&mut self, frame_idx: K) -> Result<Arc<T>, BufferPoolErrors> {
+        self.try_get_page(frame_idx).map(|page| page.read_arc())
+    }
+
+    /// Returns a shared handle to the data at the given index, loading it if necessary.
+    /// Unlike `get_page(idx).map(|page| page.data())`, this does not clone the value.
+    pub fn get_page_arc(&mut self, frame_idx: K) -> Option<Arc<T>> {
+        self.get_page(frame_idx).map(|page| page.read_arc())
+    }
+
+    /// Returns a reference to the page at the given index, loading it if necessary.
+    /// Unlike `get_page`, reports why the page could not be made available.
+    pub fn try_get_page(
+        &mut self,
+        frame_idx: K,
+    ) -> Result<&framepool::PageFrame<T>, BufferPoolErrors> {
+        // If this is beyond the size of the backing frame, then we can't get the page.
+        if let Some(slot) = frame_idx.slot()
+            && slot >= self.frame_pool.size()
+        {
+            return Err(BufferPoolErrors::IndexOutOfBounds(slot));
+        }
+
+        if !self.frame2buf.contains_key(&frame_idx) {
+            // Then we don't have the page loaded.
+            // Read before evicting, so a failed read doesn't cost us a cached page.
+            let frame_data = self
+                .frame_pool
+                .get_frame_ref(frame_idx.clone())
+                .map_err(BufferPoolErrors::ReadFailed)?;
+
+            // Evict if we are full, or if the frame's partition is at its quota.
+            self.make_room_for(&frame_idx)?;
+
+            // Precondition: We are not full, which is a None element in the self.pages vec.
+            self.install(frame_idx.clone(), frame_data)?;
+        }
+
+        let buffer_id = self.frame2buf[&frame_idx];
+        self.touch(&frame_idx, buffer_id);
+        self.pages[buffer_id as usize]
+            .as_ref()
+            .ok_or(BufferPoolErrors::NoPageAvailable)
+    }
+
+    /// Returns the page at the given index, first filling it with `f()` if the frame pool holds
+    /// no data for it. Frames that are allocated but were never written count as holding no
+    /// data, so a placeholder some pools' `resize` leaves is never read as a real value. The
+    /// new page is dirty and reaches the frame pool on eviction or flush.
+    pub fn get_or_insert_with<F>(
+        &mut self,
+        frame_idx: K,
+        f: F,
+    ) -> Result<&framepool::PageFrame<T>, BufferPoolErrors>
+    where
+        F: FnOnce() -> T,
+    {
+        if !self.frame2buf.contains_key(&frame_idx) && !self.frame_pool.exists(&frame_idx) {
+            if let Some(slot) = frame_idx.slot()
+                && slot >= self.frame_pool.size()
+            {
+                return Err(BufferPoolErrors::IndexOutOfBounds(slot));
+            }
+            self.check_writable()?;
+            self.make_room_for(&frame_idx)?;
+            let buf_idx = self.install(frame_idx.clone(), Arc::new(f()))?;
+            if let Some(page) = &self.pages[buf_idx as usize] {
+                page.set_dirty(true);
+            }
+        }
+        self.try_get_page(frame_idx)
+    }
+
+    /// Resolves a batch of frames in one call, returning a shared handle for each requested
+    /// index (`None` where the frame could not be loaded).
+    /// Cached frames are served first; the misses are then read from the backing store and
+    /// room is made for them with a single round of evictions. Misses beyond the capacity of
+    /// the pool are returned without being cached.
+    pub fn get_many(&mut self, frame_idxs: &[K]) -> Vec<Option<Arc<T>>> {
+        let mut resolved: HashMap<K, Option<Arc<T>>> = HashMap::new();
+        let mut misses = Vec::new();
+        for frame_idx in frame_idxs {
+            if resolved.contains_key(frame_idx) {
+                continue;
+            }
+            match self.frame2buf.get(frame_idx) {
+                Some(&buffer_id) => {
+                    self.touch_read(frame_idx, buffer_id);
+                    let data = self.pages[buffer_id as usize]
+                        .as_ref()
+                        .map(|page| page.get_data_arc());
+                    resolved.insert(frame_idx.clone(), data);
+                }
+                None => {
+                    resolved.insert(frame_idx.clone(), None);
+                    misses.push(frame_idx.clone());
+                }
+            }
+        }
+
+        // Read every miss before evicting anything, so failed reads don't cost cached pages.
+        let size = self.frame_pool.size();
+        let readable: Vec<K> = misses
+            .into_iter()
+            .filter(|frame_idx| frame_idx.slot().is_none_or(|slot| slot < size))
+            .collect();
+        let reads = self.frame_pool.get_frames(&readable);
+        let mut loaded = Vec::new();
+        for (frame_idx, read) in readable.into_iter().zip(reads) {
+            if let Ok(frame_data) = read {
+                resolved.insert(frame_idx.clone(), Some(Arc::clone(&frame_data)));
+                loaded.push((frame_idx, frame_data));
+            }
+        }
+
+        if self.partitions.is_empty() {
+            self.check_pins();
+            self.apply_deferred_touches();
+            let open_slots = self.size - self.frame2buf.len();
+            let wanted = loaded.len().min(self.size);
+            for _ in open_slots..wanted {
+                if self.evict().is_err() {
+                    break;
+                }
+            }
+            for (frame_idx, frame_data) in loaded {
+                match self.install(frame_idx.clone(), frame_data) {
+                    Ok(buffer_id) => self.touch_read(&frame_idx, buffer_id),
+                    Err(_) => break,
+                }
+            }
+        } else {
+            // Quotas depend on which partition each frame lands in, so make room frame by frame.
+            for (frame_idx, frame_data) in loaded.into_iter().take(self.size) {
+                if self.make_room_for(&frame_idx).is_err() {
+                    continue;
+                }
+                if let Ok(buffer_id) = self.install(frame_idx.clone(), frame_data) {
+                    self.touch_read(&frame_idx, buffer_id);
+                }
+            }
+        }
+
+        frame_idxs
+            .iter()
+            .map(|frame_idx| resolved[frame_idx].clone())
+            .collect()
+    }
+
+    // Places freshly read frame data into an open slot and records the mapping.
+    fn install(
+        &mut self,
+        frame_idx: K,
+        frame_data: Arc<T>,
+    ) -> Result<BufferPoolId, BufferPoolErrors> {
+        let target_idx = self
+            .pages
+            .iter()
+            .position(|x| x.is_none())
+            .ok_or(BufferPoolErrors::NoPageAvailable)? as BufferPoolId;
+
+        let mut new_frame = framepool::PageFrame::new_with_arc(frame_data);
+        if let Some(weigher) = &self.weigher {
+            new_frame = new_frame.with_weigher(Arc::clone(weigher));
+        }
+        new_frame.set_version(self.version_floor + 1);
+        new_frame.set_read_only(self.read_only);
+
+        self.pages[target_idx as usize] = Some(new_frame);
+        self.buf2frame.insert(target_idx, frame_idx.clone());
+        self.frame2buf.insert(frame_idx, target_idx);
+        Ok(target_idx)
+    }
+
+    // Evicts the page chosen by the evictor, flushing it to the frame pool first if dirty.
+    // Postcondition: one slot in self.pages is open.
+    fn evict(&mut self) -> Result<(), BufferPoolErrors> {
+        let victim_idx = (self.evictor)(&self.pages, &self.lru)?;
+        self.evict_slot(victim_idx)
+    }
+
+    // Evicts the page in the given slot, flushing it to the frame pool first if dirty.
+    fn evict_slot(&mut self, victim_idx: BufferPoolId) -> Result<(), BufferPoolErrors> {
+        // Get the frame_id that was mapped to this buffer slot
+        let victim_frame_id = self.buf2frame[&victim_idx].clone();
+        let victim_page = self.pages[victim_idx as usize]
+            .as_ref()
+            .ok_or(BufferPoolErrors::NoPageAvailable)?;
+
+        if !self.read_only && victim_page.is_dirty() {
+            // Flush the page to the pool
+            let data_arc = victim_page
+                .data_to_flush()
+                .map_err(BufferPoolErrors::FlushFailed)?;
+            self.frame_pool
+                .put_frame(victim_frame_id, data_arc)
+                .map_err(BufferPoolErrors::FlushFailed)?;
+        }
+        // Precondition: the page is not dirty, or we have flushed it.
+
+        self.discard_slot(victim_idx);
+        Ok(())
+    }
+
+    // Empties a buffer slot without writing its page back.
+    fn discard_slot(&mut self, buf_idx: BufferPoolId) {
+        if let Some(page) = self.pages[buf_idx as usize].take() {
+            self.version_floor = self.version_floor.max(page.version());
+        }
+        if let Some(frame_idx) = self.buf2frame.remove(&buf_idx) {
+            self.frame2buf.remove(&frame_idx);
+        }
+        self.lru.delete(buf_idx);
+    }
+}
+
+pub struct SlabMapper<'a, T>
+where
+    T: Clone,
+{
+    slab: BufferPool<'a, T>,
+    stride: usize,
+}
+
+impl<'a, T> SlabMapper<'a, T>
+where
+    T: Clone,
+{
+    pub fn new(size: usize, pool: &'a mut dyn framepool::FramePool<T>, stride: usize) -> Self {
+        SlabMapper {
+            slab: BufferPool::new(size, pool, bottom_evictor),
+            stride,
+        }
+    }
+
+    pub fn load(&mut self) -> Result<(), FramePoolError> {
+        self.slab.ensure_allocation(0)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self, seq: Vec<T>) -> Result<(), FramePoolError> {
+        let required_allocation = seq.len().div_ceil(self.stride);
+        self.slab
+            .ensure_allocation(required_allocation as FramePoolId)?;
+
+        // Phase 1: Write to backing store (external, out of our control)
+        // If this fails, nothing has been modified yet, so we can safely return error
+        for i in 0..required_allocation {
+            let bottom = i * self.stride;
+            if bottom < seq.len() {
+                let data_arc = Arc::new(seq[bottom].clone());
+                self.slab.frame_pool.put_frame(i as FramePoolId, data_arc)?;
+            }
+        }
+
+        // Phase 2: Update BufferPool (under our control)
+        // If this fails after backing store writes succeeded, we have inconsistent state
+        // But per your requirement, both must succeed, so we continue trying all updates
+        let mut buffer_errors = Vec::new();
+
+        for i in 0..required_allocation {
+            let bottom = i * self.stride;
+            if bottom < seq.len()
+                && let Err(e) = self.slab.put_page(i as FramePoolId, seq[bottom].clone())
+            {
+                buffer_errors.push((i, e));
+            }
+        }
+
+        // If any buffer updates failed, report all failures
+        if !buffer_errors.is_empty() {
+            let error_msgs: Vec<String> = buffer_errors
+                .into_iter()
+                .map(|(frame, err)| format!("Frame {}: {}", frame, err))
+                .collect();
+            return Err(FramePoolError::Corruption(format!(
+                "BufferPool updates failed: {}",
+                error_msgs.join("; ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&mut self, idx: usize) -> Option<T> {
+        let page_idx = (idx / self.stride) as FramePoolId;
+        self.slab.get_page(page_idx).map(|page| page.data())
+    }
+
+    /// Appends a sequence after the pages already in the slab. As with `flush`, the first
+    /// element of each stride is the one stored for its page. A failed write leaves the slab
+    /// ending with the last page written, as for `BufferPool::bulk_append`.
+    pub fn bulk_append<I>(&mut self, seq: I) -> Result<u64, FramePoolError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.slab.bulk_append(seq.into_iter().step_by(self.stride))
+    }
+}
+
+/// Appends through `bulk_append`.
+///
+/// # Panics
+/// Panics if the backing frame pool rejects an allocation or write.
+impl<'a, T> Extend<T> for BufferPool<'a, T>
+where
+    T: Clone,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.bulk_append(iter)
+            .expect("failed to append to the backing frame pool");
+    }
+}
+
+/// Appends through `SlabMapper::bulk_append`.
+///
+/// # Panics
+/// Panics if the backing frame pool rejects an allocation or write.
+impl<'a, T> Extend<T> for SlabMapper<'a, T>
+where
+    T: Clone,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.bulk_append(iter)
+            .expect("failed to append to the backing frame pool");
+    }
+}
+
+// A MemPool of count frames, frame i holding value(i), for tests to put a BufferPool over.
+#[cfg(test)]
+pub(crate) fn filled_mem_pool<T: Clone>(
+    count: u64,
+    value: impl Fn(u64) -> T,
+) -> framepool::MemPool<T> {
+    use framepool::FramePool;
+    let mut mem_pool = framepool::MemPool::new();
+    mem_pool.resize(count).unwrap();
+    for i in 0..count {
+        mem_pool.put_frame(i, Arc::new(value(i))).unwrap();
+    }
+    mem_pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool;
+    use crate::framepool::{DiskPool, FramePool, MemPool};
+    use crate::unique_stack;
+
+    #[test]
+    fn test_new() {
+        let mut pool = MemPool::<u8>::new();
+        let bp = BufferPool::<u8>::new(10, &mut pool, bottom_evictor);
+        assert_eq!(bp.size, 10);
+        assert_eq!(bp.pages.len(), 10);
+        assert_eq!(bp.buf2frame.len(), 0);
+        assert_eq!(bp.frame2buf.len(), 0);
+        assert_eq!(bp.lru.len(), 0);
+    }
+
+    #[test]
+    fn test_get_page_loads_from_pool() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(1).unwrap();
+
+        let data_arc = Arc::new(42u8);
+        mem_pool.put_frame(0, data_arc).unwrap();
+
+        let mut bp = BufferPool::<u8>::new(10, &mut mem_pool, bottom_evictor);
+
+        // First access should load from pool
+        let page = bp.get_page(0).unwrap();
+        assert_eq!(page.data(), 42u8);
+        assert_eq!(bp.frame2buf.len(), 1);
+        assert_eq!(bp.buf2frame.len(), 1);
+    }
+
+    #[test]
+    fn test_put_page() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(10).unwrap();
+
+        // Initialize with dummy data
+        for i in 0..10 {
+            let data_arc = Arc::new(i as u8);
+            mem_pool.put_frame(i, data_arc).unwrap();
+        }
+
+        let mut bp = BufferPool::<u8>::new(5, &mut mem_pool, bottom_evictor);
+
+        bp.put_page(0, 100).unwrap();
+        let page = bp.get_page(0).unwrap();
+        assert_eq!(page.data(), 100);
+    }
+
+    #[test]
+    fn test_eviction_when_full() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(10).unwrap();
+
+        // Initialize with data
+        for i in 0..10 {
+            let data_arc = Arc::new(i as u8);
+            mem_pool.put_frame(i, data_arc).unwrap();
+        }
+
+        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+
+        // Load 3 pages (fills buffer)
+        bp.get_page(0);
+        bp.get_page(1);
+        bp.get_page(2);
+        assert_eq!(bp.frame2buf.len(), 3);
+
+        // Load 4th page should trigger eviction
+        bp.get_page(3);
+        assert_eq!(bp.frame2buf.len(), 3); // Still 3, one was evicted
+        assert!(bp.frame2buf.contains_key(&3)); // New page is loaded
+    }
+
+    #[test]
+    fn test_lru_tracking() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(5).unwrap();
+
+        for i in 0..5 {
+            let data_arc = Arc::new(i as u8);
+            mem_pool.put_frame(i, data_arc).unwrap();
+        }
+
+        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+
+        bp.get_page(0);
+        bp.get_page(1);
+        bp.get_page(2);
+
+        // Access page 0 again, should move to top of LRU
+        bp.get_page(0);
+
+        // Load new page, should evict page 1 (least recently used)
+        bp.get_page(3);
+        assert!(!bp.frame2buf.contains_key(&1));
+        assert!(bp.frame2buf.contains_key(&0));
+        assert!(bp.frame2buf.contains_key(&2));
+        assert!(bp.frame2buf.contains_key(&3));
+    }
+
+    #[test]
+    fn test_dirty_page_flush() {
+        let mut mem_pool = MemPool::<Vec<u8>>::new();
+        mem_pool.resize(2).unwrap();
+
+        let data_arc = Arc::new(vec![1, 2, 3]);
+        mem_pool.put_frame(0, data_arc).unwrap();
+
+        let data_arc2 = Arc::new(vec![5, 6]);
+        mem_pool.put_frame(1, data_arc2).unwrap();
+
+        let mut bp = BufferPool::<Vec<u8>>::new(1, &mut mem_pool, bottom_evictor);
+
+        // Load and modify page
+        {
+            let page = bp.get_page(0).unwrap();
+            page.with_data(|v| v.push(4));
+            assert!(page.is_dirty());
+        }
+
+        // Load another page, should flush dirty page
+        bp.get_page(1);
+
+        // Reload page 0 to verify it was flushed
+        let page = bp.get_page(0).unwrap();
+        assert_eq!(page.data(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_sync_index() {
+        let mut mem_pool = MemPool::<String>::new();
+        mem_pool.resize(1).unwrap();
+
+        let data_arc = Arc::new("initial".to_string());
+        mem_pool.put_frame(0, data_arc).unwrap();
+
+        let mut bp = BufferPool::<String>::new(2, &mut mem_pool, bottom_evictor);
+
+        // Load and modify
+        let page = bp.get_page(0).unwrap();
+        page.put("modified".to_string());
+        page.set_dirty(true);
+
+        // Sync the page
+        bp.sync_index(0).unwrap();
+
+        // Verify it was written to backing storage
+        let frame_arc = mem_pool.get_frame_ref(0).unwrap();
+        assert_eq!(*frame_arc, "modified");
+    }
+
+    #[test]
+    fn test_sync_index_not_loaded() {
+        let mut mem_pool = MemPool::<u8>::new();
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+
+        // Syncing a page that's not loaded should be OK
+        let result = bp.sync_index(0);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_flush_all() {
+        let mut mem_pool = MemPool::<i32>::new();
+        mem_pool.resize(3).unwrap();
+
+        for i in 0..3 {
+            let data_arc = Arc::new((i * 10) as i32);
+            mem_pool.put_frame(i, data_arc).unwrap();
+        }
+
+        let mut bp = BufferPool::<i32>::new(3, &mut mem_pool, bottom_evictor);
+
+        // Load and modify all pages
+        for i in 0..3 {
+            let page = bp.get_page(i).unwrap();
+            page.put((i * 10 + 1) as i32);
+            page.set_dirty(true);
+        }
+
+        // Flush all
+        bp.flush_all().unwrap();
+
+        // Verify all were written
+        for i in 0..3 {
+            let frame_arc = mem_pool.get_frame_ref(i).unwrap();
+            assert_eq!(*frame_arc, (i * 10 + 1) as i32);
+        }
+    }
+
+    #[test]
+    fn test_ensure_allocation() {
+        let mut mem_pool = MemPool::<u8>::new();
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+
+        bp.ensure_allocation(5).unwrap();
+
+        // Check size through the buffer pool's frame_pool reference
+        assert_eq!(bp.frame_pool.size(), 5);
+    }
+
+    #[test]
+    fn test_get_page_beyond_size() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(5).unwrap();
+
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+
+        let page = bp.get_page(10);
+        assert!(page.is_none());
+    }
+
+    #[test]
+    fn test_pinned_page_not_evicted() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(5).unwrap();
+
+        for i in 0..5 {
+            let data_arc = Arc::new(i as u8);
+            mem_pool.put_frame(i, data_arc).unwrap();
+        }
+
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+
+        // Load and pin page 0
+        {
+            let page0 = bp.get_page(0).unwrap();
+            page0.pin();
+        }
+
+        // Load page 1
+        bp.get_page(1);
+
+        // Try to load page 2 - should evict page 1, not pinned page 0
+        bp.get_page(2);
+
+        assert!(bp.frame2buf.contains_key(&0)); // Pinned page still there
+        assert!(!bp.frame2buf.contains_key(&1)); // Page 1 was evicted
+        assert!(bp.frame2buf.contains_key(&2)); // New page loaded
+
+        // Unpin page 0
+        if let Some(page0) = bp.pages[0].as_ref() {
+            page0.unpin();
+        }
+    }
+
+    #[test]
+    fn test_bottom_evictor() {
+        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
+        for _ in 0..5 {
+            pages.push(None);
+        }
+        pages[0] = Some(framepool::PageFrame::new(0));
+        pages[2] = Some(framepool::PageFrame::new(2));
+        pages[4] = Some(framepool::PageFrame::new(4));
+
+        let mut lru = unique_stack::UniqueStack::new();
+        lru.push(2); // Least recently used
+        lru.push(0);
+        lru.push(4); // Most recently used
+
+        let evicted = bottom_evictor::<u8>(&pages, &lru).unwrap();
+        assert_eq!(evicted, 2); // Should evict least recently used
+    }
+
+    #[test]
+    fn test_bottom_evictor_all_pinned() {
+        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
+        for _ in 0..3 {
+            pages.push(None);
+        }
+
+        for (i, page) in pages.iter_mut().enumerate().take(3) {
+            let frame = framepool::PageFrame::new(i as u8);
+            frame.pin();
+            *page = Some(frame);
+        }
+
+        let mut lru = unique_stack::UniqueStack::new();
+        lru.push(0);
+        lru.push(1);
+        lru.push(2);
+
+        let result = bottom_evictor::<u8>(&pages, &lru);
+        assert!(result.is_err());
+
+        // Unpin to cleanup
+        for p in pages.iter().flatten() {
+            p.unpin();
+        }
+    }
+
+    #[test]
+    fn test_random_evictor() {
+        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
+        for _ in 0..10 {
+            pages.push(None);
+        }
+
+        // Fill some slots
+        for i in [1, 3, 5, 7, 9] {
+            pages[i] = Some(framepool::PageFrame::new(i as u8));
+        }
+
+        let lru = unique_stack::UniqueStack::new();
+
+        let evicted = random_evictor::<u8>(&pages, &lru).unwrap();
+        assert!([1, 3, 5, 7, 9].contains(&(evicted as usize)));
+    }
+
+    #[test]
+    fn test_random_evictor_all_pinned() {
+        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
+        for _ in 0..3 {
+            pages.push(None);
+        }
+
+        for (i, page) in pages.iter_mut().enumerate().take(3) {
+            let frame = framepool::PageFrame::new(i as u8);
+            frame.pin();
+            *page = Some(frame);
+        }
+
+        let lru = unique_stack::UniqueStack::new();
+        let result = random_evictor::<u8>(&pages, &lru);
+        assert!(result.is_err());
+
+        // Unpin to cleanup
+        for p in pages.iter().flatten() {
+            p.unpin();
+        }
+    }
 
     #[test]
-    fn test_new() {
-        let mut pool = MemPool::<u8>::new();
-        let bp = BufferPool::<u8>::new(10, &mut pool, bottom_evictor);
-        assert_eq!(bp.size, 10);
-        assert_eq!(bp.pages.len(), 10);
-        assert_eq!(bp.buf2frame.len(), 0);
-        assert_eq!(bp.frame2buf.len(), 0);
-        assert_eq!(bp.lru.len(), 0);
+    fn test_error_display() {
+        let err = BufferPoolErrors::NoEvictablePage;
+        assert_eq!(format!("{}", err), "no evictable pages");
+
+        let err = BufferPoolErrors::NoPageAvailable;
+        assert_eq!(format!("{}", err), "no available pages");
+    }
+
+    #[test]
+    fn test_with_diskpool() {
+        let test_dir = "/tmp/test_bufferpool_disk";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut disk_pool = framepool::DiskPool::new::<String>(test_dir);
+        <framepool::DiskPool as framepool::FramePool<String>>::resize(&mut disk_pool, 3).unwrap();
+
+        // Write initial data
+        for i in 0..3 {
+            let data_arc = Arc::new(format!("page_{}", i));
+            <framepool::DiskPool as framepool::FramePool<String>>::put_frame(
+                &mut disk_pool,
+                i,
+                data_arc,
+            )
+            .unwrap();
+        }
+
+        let mut bp = BufferPool::<String>::new(2, &mut disk_pool, bottom_evictor);
+
+        // Test operations
+        let page = bp.get_page(0).unwrap();
+        assert_eq!(page.data(), "page_0");
+
+        bp.put_page(1, "modified_1".to_string()).unwrap();
+        bp.flush_all().unwrap();
+
+        // Verify persistence
+        let frame_arc =
+            <framepool::DiskPool as framepool::FramePool<String>>::get_frame_ref(&mut disk_pool, 1)
+                .unwrap();
+        assert_eq!(*frame_arc, "modified_1");
+
+        // Clean up
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_slab_mapper_new() {
+        let mut mem_pool = MemPool::<i32>::new();
+        let mapper = SlabMapper::new(5, &mut mem_pool, 10);
+        assert_eq!(mapper.stride, 10);
+    }
+
+    #[test]
+    fn test_slab_mapper_load() {
+        let mut mem_pool = MemPool::<i32>::new();
+        let mut mapper = SlabMapper::new(5, &mut mem_pool, 10);
+
+        let result = mapper.load();
+        assert!(result.is_ok());
+        assert_eq!(mem_pool.size(), 0); // ensure_allocation(0) doesn't resize
+    }
+
+    #[test]
+    fn test_slab_mapper_flush() {
+        let mut mem_pool = MemPool::<i32>::new();
+        let mut mapper = SlabMapper::new(5, &mut mem_pool, 3);
+
+        let data = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        mapper.flush(data).unwrap();
+
+        // Should have allocated 4 pages (10 items / 3 stride = 4 pages)
+        assert_eq!(mem_pool.size(), 4);
+    }
+
+    #[test]
+    fn test_slab_mapper_get() {
+        let mut mem_pool = MemPool::<i32>::new();
+        let mut mapper = SlabMapper::new(5, &mut mem_pool, 3);
+
+        let data = vec![10, 20, 30, 40, 50];
+        mapper.flush(data).unwrap();
+
+        // Get items from different pages
+        let val = mapper.get(0);
+        assert_eq!(val, Some(10));
+
+        let val = mapper.get(3);
+        assert_eq!(val, Some(40));
+    }
+
+    #[test]
+    fn test_slab_mapper_with_diskpool() {
+        let test_dir = "/tmp/test_slabmapper_disk";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut disk_pool = framepool::DiskPool::new::<String>(test_dir);
+        let mut mapper = SlabMapper::new(3, &mut disk_pool, 2);
+
+        let data = vec![
+            "a".to_string(),
+            "b".to_string(),
+            "c".to_string(),
+            "d".to_string(),
+            "e".to_string(),
+        ];
+
+        mapper.flush(data).unwrap();
+
+        let val = mapper.get(0);
+        assert_eq!(val, Some("a".to_string()));
+
+        let val = mapper.get(2);
+        assert_eq!(val, Some("c".to_string()));
+
+        // Clean up
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_put_page_no_page_available() {
+        let mut mem_pool = MemPool::<u8>::new();
+        // Don't resize the pool, so there are no pages
+
+        let mut bp = BufferPool::<u8>::new(5, &mut mem_pool, bottom_evictor);
+
+        let result = bp.put_page(0, 42);
+        assert!(result.is_err());
+        match result {
+            Err(BufferPoolErrors::NoPageAvailable) => (),
+            _ => panic!("Expected NoPageAvailable error"),
+        }
+    }
+
+    #[test]
+    fn test_eviction_correctness_stress() {
+        // Stress test to ensure eviction logic maintains consistent state
+        let mut mem_pool = MemPool::<String>::new();
+        mem_pool.resize(20).unwrap();
+
+        // Initialize backing storage with data
+        for i in 0..20 {
+            let data_arc = Arc::new(format!("page_{}", i));
+            mem_pool.put_frame(i, data_arc).unwrap();
+        }
+
+        let mut bp = BufferPool::<String>::new(3, &mut mem_pool, bottom_evictor);
+
+        // Perform many operations to stress test the eviction logic
+        for round in 0..10 {
+            for i in 0..20 {
+                let page = bp.get_page(i);
+                assert!(page.is_some(), "Should be able to load page {}", i);
+
+                // Verify mapping consistency after each operation
+                assert_eq!(
+                    bp.frame2buf.len(),
+                    bp.buf2frame.len(),
+                    "Mapping lengths should be equal in round {}, access {}",
+                    round,
+                    i
+                );
+                assert!(
+                    bp.frame2buf.len() <= 3,
+                    "Should never exceed buffer pool size"
+                );
+
+                // Verify bidirectional mapping consistency
+                for (frame_id, buf_id) in &bp.frame2buf {
+                    assert_eq!(
+                        bp.buf2frame[buf_id], *frame_id,
+                        "Bidirectional mapping should be consistent"
+                    );
+                }
+
+                for (buf_id, frame_id) in &bp.buf2frame {
+                    assert_eq!(
+                        bp.frame2buf[frame_id], *buf_id,
+                        "Reverse mapping should be consistent"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_slab_mapper_transaction_safety() {
+        // Test that SlabMapper operations are atomic - either both succeed or both fail
+        let mut mem_pool = MemPool::<i32>::new();
+        let mut mapper = SlabMapper::new(3, &mut mem_pool, 2);
+
+        // Test successful case
+        let data = vec![10, 20, 30, 40, 50];
+        let result = mapper.flush(data.clone());
+        assert!(result.is_ok(), "Flush should succeed");
+
+        // Verify data was written correctly
+        for (i, expected) in data.iter().enumerate() {
+            if i % 2 == 0 {
+                // Only first element of each stride is accessible with current impl
+                let val = mapper.get(i);
+                assert_eq!(
+                    val,
+                    Some(*expected),
+                    "Should retrieve correct value at index {}",
+                    i
+                );
+            }
+        }
+
+        // Test with empty data - should handle gracefully
+        let empty_data = vec![];
+        let result = mapper.flush(empty_data);
+        assert!(result.is_ok(), "Empty flush should succeed");
+    }
+
+    #[test]
+    fn test_bufferpool_errors_display() {
+        // Test all error variants display correctly
+        let no_evict_err = BufferPoolErrors::NoEvictablePage;
+        let no_page_err = BufferPoolErrors::NoPageAvailable;
+
+        assert_eq!(format!("{}", no_evict_err), "no evictable pages");
+        assert_eq!(format!("{}", no_page_err), "no available pages");
+    }
+
+    #[test]
+    fn test_slab_mapper_get_out_of_bounds() {
+        let mut mem_pool = framepool::MemPool::new();
+        let mut mapper = SlabMapper::new(2, &mut mem_pool, 2);
+        mapper.load().unwrap();
+
+        // Add some data
+        let data = vec![10, 20, 30];
+        mapper.flush(data).unwrap();
+
+        // Try to get out of bounds index
+        assert_eq!(mapper.get(100), None);
+        assert_eq!(mapper.get(4), None);
     }
 
     #[test]
-    fn test_get_page_loads_from_pool() {
-        let mut mem_pool = MemPool::<u8>::new();
+    fn test_sync_index_with_clean_page() {
+        let mut mem_pool = framepool::MemPool::new();
         mem_pool.resize(1).unwrap();
-
         let data_arc = Arc::new(42u8);
         mem_pool.put_frame(0, data_arc).unwrap();
 
-        let mut bp = BufferPool::<u8>::new(10, &mut mem_pool, bottom_evictor);
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
 
-        // First access should load from pool
-        let page = bp.get_page(0).unwrap();
-        assert_eq!(page.data(), 42u8);
-        assert_eq!(bp.frame2buf.len(), 1);
-        assert_eq!(bp.buf2frame.len(), 1);
+        // Get a page
+        let _page = bp.get_page(0).unwrap();
+        // Don't mark it dirty
+
+        // Syncing a clean page should be a no-op
+        let result = bp.sync_index(0);
+        assert!(result.is_ok());
     }
 
     #[test]
-    fn test_put_page() {
-        let mut mem_pool = MemPool::<u8>::new();
-        mem_pool.resize(10).unwrap();
+    fn test_flush_all_empty_pool() {
+        let mut mem_pool = framepool::MemPool::new();
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
 
-        // Initialize with dummy data
-        for i in 0..10 {
-            let data_arc = Arc::new(i as u8);
+        // Flush empty pool should succeed
+        let result = bp.flush_all();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_get_page_beyond_available() {
+        let mut mem_pool = framepool::MemPool::new();
+        let mut bp = BufferPool::<u8>::new(1, &mut mem_pool, bottom_evictor);
+
+        // Try to get a page beyond what's available
+        let result = bp.get_page(100);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_bufferpool_iterator_basic() {
+        let mut mem_pool = MemPool::<String>::new();
+        mem_pool.resize(3).unwrap();
+
+        // Initialize with test data
+        for i in 0..3 {
+            let data_arc = Arc::new(format!("data_{}", i));
             mem_pool.put_frame(i, data_arc).unwrap();
         }
 
-        let mut bp = BufferPool::<u8>::new(5, &mut mem_pool, bottom_evictor);
+        let mut bp = BufferPool::<String>::new(2, &mut mem_pool, bottom_evictor);
 
-        bp.put_page(0, 100).unwrap();
-        let page = bp.get_page(0).unwrap();
-        assert_eq!(page.data(), 100);
+        // Collect all data using the iterator
+        let collected: Vec<String> = (&mut bp).into_iter().collect();
+
+        assert_eq!(collected.len(), 3);
+        assert_eq!(collected[0], "data_0");
+        assert_eq!(collected[1], "data_1");
+        assert_eq!(collected[2], "data_2");
     }
 
     #[test]
-    fn test_eviction_when_full() {
-        let mut mem_pool = MemPool::<u8>::new();
-        mem_pool.resize(10).unwrap();
+    fn test_bufferpool_iterator_with_caching() {
+        let mut mem_pool = MemPool::<i32>::new();
+        mem_pool.resize(5).unwrap();
 
-        // Initialize with data
-        for i in 0..10 {
-            let data_arc = Arc::new(i as u8);
+        // Initialize with test data
+        for i in 0..5 {
+            let data_arc = Arc::new((i * 10) as i32);
             mem_pool.put_frame(i, data_arc).unwrap();
         }
 
-        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+        // Small buffer pool to force evictions
+        let mut bp = BufferPool::<i32>::new(2, &mut mem_pool, bottom_evictor);
 
-        // Load 3 pages (fills buffer)
-        bp.get_page(0);
-        bp.get_page(1);
-        bp.get_page(2);
-        assert_eq!(bp.frame2buf.len(), 3);
+        // Iterate and verify caching is transparent
+        let collected: Vec<i32> = (&mut bp).into_iter().collect();
+        let sum: i32 = collected.iter().sum();
 
-        // Load 4th page should trigger eviction
-        bp.get_page(3);
-        assert_eq!(bp.frame2buf.len(), 3); // Still 3, one was evicted
-        assert!(bp.frame2buf.contains_key(&3)); // New page is loaded
+        assert_eq!(sum, 10 + 20 + 30 + 40); // 100
+        assert_eq!(collected.len(), 5);
+
+        // The pool stays usable once the iterator is dropped
+        assert_eq!(bp.frame2buf.len(), 2);
+        assert_eq!(bp.get_page(0).unwrap().data(), 0);
     }
 
     #[test]
-    fn test_lru_tracking() {
+    fn test_bufferpool_iterator_empty() {
         let mut mem_pool = MemPool::<u8>::new();
-        mem_pool.resize(5).unwrap();
+        let mut bp = BufferPool::<u8>::new(5, &mut mem_pool, bottom_evictor);
 
-        for i in 0..5 {
-            let data_arc = Arc::new(i as u8);
+        let collected: Vec<u8> = (&mut bp).into_iter().collect();
+        assert_eq!(collected.len(), 0);
+    }
+
+    #[test]
+    fn test_bufferpool_iterator_skips_missing_frame() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(4).unwrap();
+        for i in [0, 1, 3] {
+            mem_pool.put_frame(i, Arc::new(i as u8)).unwrap();
+        }
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+
+        // Both directions skip frame 2
+        let iter = bp.iter_mut();
+        assert_eq!(iter.size_hint(), (0, Some(4)));
+        assert_eq!(iter.collect::<Vec<u8>>(), vec![0, 1, 3]);
+        assert_eq!(bp.iter_mut().rev().collect::<Vec<u8>>(), vec![3, 1, 0]);
+        assert_eq!(bp.iter_mut().arcs().size_hint(), (0, Some(4)));
+
+        // results reports it, with an exact length
+        let mut results = bp.iter_mut().results();
+        assert_eq!(results.len(), 4);
+        assert_eq!(*results.next_back().unwrap().unwrap(), 3);
+        assert!(results.next_back().unwrap().is_err());
+        assert_eq!(results.len(), 2);
+        assert_eq!(*results.next().unwrap().unwrap(), 0);
+        assert_eq!(*results.next().unwrap().unwrap(), 1);
+        assert_eq!(results.len(), 0);
+        assert!(results.next().is_none());
+        assert!(results.next_back().is_none());
+    }
+
+    #[test]
+    fn test_bufferpool_iterator_partial_data() {
+        let mut mem_pool = MemPool::<Option<String>>::new();
+        mem_pool.resize(3).unwrap();
+
+        // Only populate some frames
+        let data1 = Arc::new(Some("first".to_string()));
+        let data2 = Arc::new(None);
+        let data3 = Arc::new(Some("third".to_string()));
+
+        mem_pool.put_frame(0, data1).unwrap();
+        mem_pool.put_frame(1, data2).unwrap();
+        mem_pool.put_frame(2, data3).unwrap();
+
+        let mut bp = BufferPool::<Option<String>>::new(2, &mut mem_pool, bottom_evictor);
+
+        let collected: Vec<Option<String>> = (&mut bp).into_iter().collect();
+
+        assert_eq!(collected.len(), 3);
+        assert_eq!(collected[0], Some("first".to_string()));
+        assert_eq!(collected[1], None);
+        assert_eq!(collected[2], Some("third".to_string()));
+    }
+
+    #[test]
+    fn test_bufferpool_iterator_stress() {
+        let mut mem_pool = MemPool::<usize>::new();
+        mem_pool.resize(100).unwrap();
+
+        // Initialize with index values
+        for i in 0..100 {
+            let data_arc = Arc::new(i as usize);
             mem_pool.put_frame(i, data_arc).unwrap();
         }
 
-        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+        // Very small buffer to force lots of evictions
+        let mut bp = BufferPool::<usize>::new(3, &mut mem_pool, bottom_evictor);
 
-        bp.get_page(0);
-        bp.get_page(1);
-        bp.get_page(2);
+        let collected: Vec<usize> = (&mut bp).into_iter().collect();
 
-        // Access page 0 again, should move to top of LRU
-        bp.get_page(0);
+        assert_eq!(collected.len(), 100);
+        for (i, &value) in collected.iter().enumerate() {
+            assert_eq!(value, i, "Value at index {} should be {}", i, i);
+        }
+    }
 
-        // Load new page, should evict page 1 (least recently used)
-        bp.get_page(3);
-        assert!(!bp.frame2buf.contains_key(&1));
-        assert!(bp.frame2buf.contains_key(&0));
-        assert!(bp.frame2buf.contains_key(&2));
-        assert!(bp.frame2buf.contains_key(&3));
+    // A MemPool whose writes always fail, for exercising the eviction flush path.
+    struct ReadOnlyMemPool<T> {
+        inner: MemPool<T>,
+    }
+
+    impl<T: Clone> framepool::FramePool<T> for ReadOnlyMemPool<T> {
+        fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+            self.inner.get_frame_ref(idx)
+        }
+        fn put_frame(&mut self, _idx: u64, _data: Arc<T>) -> Result<(), FramePoolError> {
+            Err(FramePoolError::ReadOnly)
+        }
+        fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+            self.inner.resize(count)
+        }
+        fn size(&self) -> u64 {
+            self.inner.size()
+        }
+        fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+            self.inner.assess_size()
+        }
     }
 
     #[test]
-    fn test_dirty_page_flush() {
-        let mut mem_pool = MemPool::<Vec<u8>>::new();
+    fn test_try_get_page_out_of_bounds() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(5).unwrap();
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+
+        match bp.try_get_page(10) {
+            Err(BufferPoolErrors::IndexOutOfBounds(10)) => (),
+            _ => panic!("Expected IndexOutOfBounds error"),
+        }
+        // The first frame past the end is out of bounds too
+        assert!(matches!(
+            bp.try_get_page(5),
+            Err(BufferPoolErrors::IndexOutOfBounds(5))
+        ));
+    }
+
+    #[test]
+    fn test_try_get_page_read_failed() {
+        let mut mem_pool = MemPool::<u8>::new();
         mem_pool.resize(2).unwrap();
+        mem_pool.put_frame(0, Arc::new(0)).unwrap();
+        let mut bp = BufferPool::<u8>::new(1, &mut mem_pool, bottom_evictor);
+        bp.get_page(0).unwrap();
 
-        let data_arc = Arc::new(vec![1, 2, 3]);
-        mem_pool.put_frame(0, data_arc).unwrap();
+        // Frame 1 is allocated but empty, so the read fails
+        match bp.try_get_page(1) {
+            Err(BufferPoolErrors::ReadFailed(FramePoolError::NotFound(_))) => (),
+            _ => panic!("Expected ReadFailed error"),
+        }
+        // The failed read must not have evicted the cached page
+        assert!(bp.frame2buf.contains_key(&0));
+    }
 
-        let data_arc2 = Arc::new(vec![5, 6]);
-        mem_pool.put_frame(1, data_arc2).unwrap();
+    #[test]
+    fn test_try_get_page_all_pinned() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(2).unwrap();
+        for i in 0..2 {
+            mem_pool.put_frame(i, Arc::new(i as u8)).unwrap();
+        }
+        let mut bp = BufferPool::<u8>::new(1, &mut mem_pool, bottom_evictor);
+        bp.try_get_page(0).unwrap().pin();
 
-        let mut bp = BufferPool::<Vec<u8>>::new(1, &mut mem_pool, bottom_evictor);
+        match bp.try_get_page(1) {
+            Err(BufferPoolErrors::NoEvictablePage) => (),
+            _ => panic!("Expected NoEvictablePage error"),
+        }
+    }
 
-        // Load and modify page
-        {
-            let page = bp.get_page(0).unwrap();
-            page.with_data(|v| v.push(4));
-            assert!(page.is_dirty());
+    #[test]
+    fn test_try_get_page_flush_failed() {
+        let mut pool = ReadOnlyMemPool {
+            inner: MemPool::<u8>::new(),
+        };
+        pool.inner.resize(2).unwrap();
+        for i in 0..2 {
+            pool.inner.put_frame(i, Arc::new(i as u8)).unwrap();
         }
+        let mut bp = BufferPool::<u8>::new(1, &mut pool, bottom_evictor);
+        bp.put_page(0, 42).unwrap();
 
-        // Load another page, should flush dirty page
-        bp.get_page(1);
+        match bp.try_get_page(1) {
+            Err(BufferPoolErrors::FlushFailed(FramePoolError::ReadOnly)) => (),
+            _ => panic!("Expected FlushFailed error"),
+        }
+        // The dirty page stays cached rather than being dropped
+        assert_eq!(bp.get_page(0).unwrap().data(), 42);
+    }
 
-        // Reload page 0 to verify it was flushed
-        let page = bp.get_page(0).unwrap();
-        assert_eq!(page.data(), vec![1, 2, 3, 4]);
+    #[test]
+    fn test_typed_error_display() {
+        assert_eq!(
+            format!("{}", BufferPoolErrors::IndexOutOfBounds(7)),
+            "frame 7 is out of bounds"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                BufferPoolErrors::ReadFailed(FramePoolError::OutOfBounds(7))
+            ),
+            "backing store read failed: Frame 7 is out of bounds"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                BufferPoolErrors::FlushFailed(FramePoolError::ReadOnly)
+            ),
+            "dirty page flush failed: Pool is read-only"
+        );
+
+        let err = BufferPoolErrors::SyncFailed(std::io::Error::other("disk gone").into());
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "I/O error: disk gone");
+        assert!(std::error::Error::source(source).is_some());
     }
 
     #[test]
-    fn test_sync_index() {
-        let mut mem_pool = MemPool::<String>::new();
+    fn test_get_page_arc_shares_data() {
+        let mut mem_pool = MemPool::<Vec<u8>>::new();
         mem_pool.resize(1).unwrap();
+        mem_pool.put_frame(0, Arc::new(vec![1, 2, 3])).unwrap();
 
-        let data_arc = Arc::new("initial".to_string());
-        mem_pool.put_frame(0, data_arc).unwrap();
+        let mut bp = BufferPool::<Vec<u8>>::new(2, &mut mem_pool, bottom_evictor);
 
-        let mut bp = BufferPool::<String>::new(2, &mut mem_pool, bottom_evictor);
+        let arc = bp.get_page_arc(0).unwrap();
+        assert_eq!(*arc, vec![1, 2, 3]);
+        // Same allocation as the cached page, not a deep copy
+        assert!(Arc::ptr_eq(&arc, &bp.get_page(0).unwrap().get_data_arc()));
 
-        // Load and modify
-        let page = bp.get_page(0).unwrap();
-        page.put("modified".to_string());
-        page.set_dirty(true);
+        // Later modification of the page leaves the handle's snapshot untouched
+        bp.put_page(0, vec![9]).unwrap();
+        assert_eq!(*arc, vec![1, 2, 3]);
+        assert!(bp.get_page_arc(5).is_none());
+    }
 
-        // Sync the page
-        bp.sync_index(0).unwrap();
+    #[test]
+    fn test_get_cached_through_shared_ref() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(4).unwrap();
+        for i in 0..4 {
+            mem_pool.put_frame(i, Arc::new(i as u32 * 10)).unwrap();
+        }
+        let mut bp = BufferPool::<u32>::new(2, &mut mem_pool, bottom_evictor);
+        bp.get_page(0).unwrap();
+        bp.get_page(1).unwrap();
+
+        let readers = (&bp, &bp);
+        assert_eq!(readers.0.get_cached(&0).as_deref(), Some(&0));
+        assert_eq!(readers.1.get_cached(&0).as_deref(), Some(&0));
+        // Nothing is loaded through a shared reference
+        assert!(bp.get_cached(&2).is_none());
+        assert!(!bp.frame2buf.contains_key(&2));
+
+        // The reads of frame 0 made frame 1 the least recently used
+        bp.get_page(2).unwrap();
+        assert!(bp.frame2buf.contains_key(&0));
+        assert!(!bp.frame2buf.contains_key(&1));
+        assert!(bp.validate().is_valid());
+    }
 
-        // Verify it was written to backing storage
-        let frame_arc = mem_pool.get_frame_ref(0).unwrap();
-        assert_eq!(*frame_arc, "modified");
+    #[test]
+    fn test_panicking_modification() {
+        let mut mem_pool = MemPool::<Vec<u32>>::new();
+        mem_pool.resize(2).unwrap();
+        for i in 0..2 {
+            mem_pool.put_frame(i, Arc::new(vec![i as u32])).unwrap();
+        }
+        let mut bp = BufferPool::<Vec<u32>>::new(2, &mut mem_pool, bottom_evictor);
+        bp.get_page(1).unwrap().with_data(|v| v.push(10));
+        let page = bp.get_page(0).unwrap();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            page.with_data(|v| {
+                v.push(1);
+                panic!("boom");
+            })
+        }));
+        assert!(panicked.is_err());
+
+        // Readers carry on, but the half-made change is never written back
+        assert_eq!(bp.get_page(0).unwrap().data(), vec![0, 1]);
+        assert!(matches!(
+            bp.flush_all(),
+            Err(FramePoolError::Poisoned(message)) if message == "boom"
+        ));
+        assert!(bp.get_page(0).unwrap().is_dirty());
+        assert!(!bp.get_page(1).unwrap().is_dirty());
+        assert!(matches!(bp.sync_index(0), Err(FramePoolError::Poisoned(_))));
+
+        bp.put_page(0, vec![2]).unwrap();
+        bp.flush_all().unwrap();
+        drop(bp);
+        assert_eq!(*mem_pool.get_frame_ref(0).unwrap(), vec![2]);
     }
 
     #[test]
-    fn test_sync_index_not_loaded() {
-        let mut mem_pool = MemPool::<u8>::new();
-        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+    fn test_page_access_times() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(2).unwrap();
+        for i in 0..2 {
+            mem_pool.put_frame(i, Arc::new(i as u32)).unwrap();
+        }
+        let mut bp = BufferPool::<u32>::new(2, &mut mem_pool, bottom_evictor);
+        bp.get_page(0).unwrap();
+        bp.get_page(1).unwrap();
+        // looked up without going through the pool, which would count as an access
+        let access = |bp: &BufferPool<u32>, idx| {
+            let buffer_id = bp.frame2buf[&idx] as usize;
+            bp.pages[buffer_id].as_ref().unwrap().last_access()
+        };
+        let loaded = access(&bp, 0);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        bp.get_page(0).unwrap();
+        let got = access(&bp, 0);
+        assert!(got > loaded);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        bp.get_cached(&1);
+        bp.flush_all().unwrap();
+        assert_eq!(access(&bp, 0), got);
+        assert!(access(&bp, 1) > got);
+    }
 
-        // Syncing a page that's not loaded should be OK
-        let result = bp.sync_index(0);
-        assert!(result.is_ok());
+    #[test]
+    fn test_bufferpool_iterator_arcs() {
+        let mut mem_pool = MemPool::<String>::new();
+        mem_pool.resize(4).unwrap();
+        for i in 0..4 {
+            mem_pool
+                .put_frame(i, Arc::new(format!("data_{}", i)))
+                .unwrap();
+        }
+
+        let mut bp = BufferPool::<String>::new(2, &mut mem_pool, bottom_evictor);
+
+        let collected: Vec<Arc<String>> = (&mut bp).into_iter().arcs().collect();
+        assert_eq!(collected.len(), 4);
+        for (i, data) in collected.iter().enumerate() {
+            assert_eq!(**data, format!("data_{}", i));
+        }
     }
 
     #[test]
-    fn test_flush_all() {
-        let mut mem_pool = MemPool::<i32>::new();
-        mem_pool.resize(3).unwrap();
-
-        for i in 0..3 {
-            let data_arc = Arc::new((i * 10) as i32);
-            mem_pool.put_frame(i, data_arc).unwrap();
+    fn test_get_many() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(6).unwrap();
+        for i in 0..5 {
+            mem_pool.put_frame(i, Arc::new(i as u8 * 10)).unwrap();
         }
 
-        let mut bp = BufferPool::<i32>::new(3, &mut mem_pool, bottom_evictor);
-
-        // Load and modify all pages
-        for i in 0..3 {
-            let page = bp.get_page(i).unwrap();
-            page.put((i * 10 + 1) as i32);
-            page.set_dirty(true);
-        }
+        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+        bp.get_page(0);
 
-        // Flush all
-        bp.flush_all().unwrap();
+        // Mix of a cached frame, misses, a duplicate, an empty frame and an out-of-range id
+        let results = bp.get_many(&[0, 1, 2, 1, 5, 42]);
+        let values: Vec<Option<u8>> = results.iter().map(|r| r.as_deref().copied()).collect();
+        assert_eq!(
+            values,
+            vec![Some(0), Some(10), Some(20), Some(10), None, None]
+        );
 
-        // Verify all were written
-        for i in 0..3 {
-            let frame_arc = mem_pool.get_frame_ref(i).unwrap();
-            assert_eq!(*frame_arc, (i * 10 + 1) as i32);
-        }
+        assert_eq!(bp.frame2buf.len(), 3);
+        assert!(bp.frame2buf.contains_key(&0));
+        assert!(bp.frame2buf.contains_key(&1));
+        assert!(bp.frame2buf.contains_key(&2));
     }
 
     #[test]
-    fn test_ensure_allocation() {
+    fn test_get_many_past_the_end() {
+        // Frames 0 and 2 only, so the pool's size is 2 yet frame 2 could be read
         let mut mem_pool = MemPool::<u8>::new();
-        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+        mem_pool.put_frame(0, Arc::new(0)).unwrap();
+        mem_pool.put_frame(2, Arc::new(2)).unwrap();
 
-        bp.ensure_allocation(5).unwrap();
-
-        // Check size through the buffer pool's frame_pool reference
-        assert_eq!(bp.frame_pool.size(), 5);
+        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+        assert_eq!(bp.get_many(&[2]), vec![None]);
+        assert!(bp.frame2buf.is_empty());
     }
 
     #[test]
-    fn test_get_page_beyond_size() {
+    fn test_get_many_larger_than_pool() {
         let mut mem_pool = MemPool::<u8>::new();
         mem_pool.resize(5).unwrap();
+        for i in 0..5 {
+            mem_pool.put_frame(i, Arc::new(i as u8)).unwrap();
+        }
 
         let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
 
-        let page = bp.get_page(10);
-        assert!(page.is_none());
+        let results = bp.get_many(&[0, 1, 2, 3, 4]);
+        assert!(results.iter().all(|r| r.is_some()));
+        assert_eq!(bp.frame2buf.len(), 2);
+        assert_eq!(bp.frame2buf.len(), bp.buf2frame.len());
     }
 
     #[test]
-    fn test_pinned_page_not_evicted() {
+    fn test_bufferpool_range() {
+        let mut mem_pool = MemPool::<usize>::new();
+        mem_pool.resize(100).unwrap();
+        for i in 0..100 {
+            mem_pool.put_frame(i, Arc::new(i as usize)).unwrap();
+        }
+
+        let mut bp = BufferPool::<usize>::new(3, &mut mem_pool, bottom_evictor);
+
+        let collected: Vec<usize> = bp.range(40..45).collect();
+        assert_eq!(collected, vec![40, 41, 42, 43, 44]);
+    }
+
+    #[test]
+    fn test_bufferpool_range_clamped() {
         let mut mem_pool = MemPool::<u8>::new();
         mem_pool.resize(5).unwrap();
-
         for i in 0..5 {
-            let data_arc = Arc::new(i as u8);
-            mem_pool.put_frame(i, data_arc).unwrap();
+            mem_pool.put_frame(i, Arc::new(i as u8)).unwrap();
         }
 
         let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
 
-        // Load and pin page 0
-        {
-            let page0 = bp.get_page(0).unwrap();
-            page0.pin();
-        }
-
-        // Load page 1
-        bp.get_page(1);
+        let collected: Vec<u8> = bp.range(3..50).collect();
+        assert_eq!(collected, vec![3, 4]);
+    }
 
-        // Try to load page 2 - should evict page 1, not pinned page 0
-        bp.get_page(2);
+    #[test]
+    fn test_bufferpool_iterator_rev() {
+        let mut mem_pool = MemPool::<usize>::new();
+        mem_pool.resize(10).unwrap();
+        for i in 0..10 {
+            mem_pool.put_frame(i, Arc::new(i as usize)).unwrap();
+        }
 
-        assert!(bp.frame2buf.contains_key(&0)); // Pinned page still there
-        assert!(!bp.frame2buf.contains_key(&1)); // Page 1 was evicted
-        assert!(bp.frame2buf.contains_key(&2)); // New page loaded
+        let mut bp = BufferPool::<usize>::new(3, &mut mem_pool, bottom_evictor);
 
-        // Unpin page 0
-        if let Some(page0) = bp.pages[0].as_ref() {
-            page0.unpin();
-        }
+        let collected: Vec<usize> = (&mut bp).into_iter().rev().collect();
+        assert_eq!(collected, (0..10).rev().collect::<Vec<usize>>());
     }
 
     #[test]
-    fn test_bottom_evictor() {
-        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
-        for _ in 0..5 {
-            pages.push(None);
+    fn test_bufferpool_iterator_size_hint_and_both_ends() {
+        let mut mem_pool = MemPool::<usize>::new();
+        mem_pool.resize(10).unwrap();
+        for i in 0..10 {
+            mem_pool.put_frame(i, Arc::new(i as usize)).unwrap();
         }
-        pages[0] = Some(framepool::PageFrame::new(0));
-        pages[2] = Some(framepool::PageFrame::new(2));
-        pages[4] = Some(framepool::PageFrame::new(4));
 
-        let mut lru = unique_stack::UniqueStack::new();
-        lru.push(2); // Least recently used
-        lru.push(0);
-        lru.push(4); // Most recently used
+        let mut bp = BufferPool::<usize>::new(3, &mut mem_pool, bottom_evictor);
 
-        let evicted = bottom_evictor::<u8>(&pages, &lru).unwrap();
-        assert_eq!(evicted, 2); // Should evict least recently used
+        let mut iter = bp.range(2..8);
+        assert_eq!(iter.size_hint(), (0, Some(6)));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next_back(), Some(7));
+        assert_eq!(iter.size_hint(), (0, Some(4)));
+
+        let mut arcs = iter.arcs();
+        assert_eq!(arcs.next_back().as_deref(), Some(&6));
+        assert_eq!(arcs.next().as_deref(), Some(&3));
+        assert_eq!(arcs.size_hint(), (0, Some(2)));
+        assert_eq!(arcs.count(), 2);
     }
 
     #[test]
-    fn test_bottom_evictor_all_pinned() {
-        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
-        for _ in 0..3 {
-            pages.push(None);
+    fn test_bufferpool_iter_entries_skips_missing() {
+        let mut mem_pool = MemPool::<String>::new();
+        mem_pool.resize(5).unwrap();
+        for i in [0, 2, 4] {
+            mem_pool
+                .put_frame(i, Arc::new(format!("data_{}", i)))
+                .unwrap();
         }
 
-        for (i, page) in pages.iter_mut().enumerate().take(3) {
-            let frame = framepool::PageFrame::new(i as u8);
-            frame.pin();
-            *page = Some(frame);
+        let mut bp = BufferPool::<String>::new(2, &mut mem_pool, bottom_evictor);
+
+        let entries: Vec<(u64, String)> = bp.iter_entries().collect();
+        assert_eq!(
+            entries,
+            vec![
+                (0, "data_0".to_string()),
+                (2, "data_2".to_string()),
+                (4, "data_4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bufferpool_iter_chunks() {
+        let mut mem_pool = MemPool::<usize>::new();
+        mem_pool.resize(10).unwrap();
+        for i in 0..10 {
+            mem_pool.put_frame(i, Arc::new(i as usize)).unwrap();
         }
 
-        let mut lru = unique_stack::UniqueStack::new();
-        lru.push(0);
-        lru.push(1);
-        lru.push(2);
+        let mut bp = BufferPool::<usize>::new(3, &mut mem_pool, bottom_evictor);
 
-        let result = bottom_evictor::<u8>(&pages, &lru);
-        assert!(result.is_err());
+        let iter = bp.iter_chunks(4);
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        let chunks: Vec<Vec<usize>> = iter.collect();
+        assert_eq!(chunks, vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+    }
 
-        // Unpin to cleanup
-        for p in pages.iter().flatten() {
-            p.unpin();
-        }
+    #[test]
+    #[should_panic(expected = "chunk_size must be non-zero")]
+    fn test_bufferpool_iter_chunks_zero() {
+        let mut mem_pool = MemPool::<u8>::new();
+        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+        let _ = bp.iter_chunks(0);
     }
 
     #[test]
-    fn test_random_evictor() {
-        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
-        for _ in 0..10 {
-            pages.push(None);
+    fn test_for_each_ref() {
+        let mut mem_pool = MemPool::<Vec<u64>>::new();
+        mem_pool.resize(6).unwrap();
+        for i in 0..5 {
+            mem_pool.put_frame(i, Arc::new(vec![i; 4])).unwrap();
         }
 
-        // Fill some slots
-        for i in [1, 3, 5, 7, 9] {
-            pages[i] = Some(framepool::PageFrame::new(i as u8));
-        }
+        let mut bp = BufferPool::<Vec<u64>>::new(2, &mut mem_pool, bottom_evictor);
 
-        let lru = unique_stack::UniqueStack::new();
+        let mut visited = Vec::new();
+        let mut total = 0;
+        bp.for_each_ref(|idx, data| {
+            visited.push(idx);
+            total += data.iter().sum::<u64>();
+        });
+        assert_eq!(visited, vec![0, 1, 2, 3, 4]);
+        assert_eq!(total, 4 * (1 + 2 + 3 + 4));
 
-        let evicted = random_evictor::<u8>(&pages, &lru).unwrap();
-        assert!([1, 3, 5, 7, 9].contains(&(evicted as usize)));
+        // The pool is still usable afterwards, and nothing was marked dirty
+        assert!(!bp.get_page(4).unwrap().is_dirty());
     }
 
+    #[cfg(feature = "rayon")]
     #[test]
-    fn test_random_evictor_all_pinned() {
-        let mut pages: Vec<Option<framepool::PageFrame<u8>>> = Vec::new();
-        for _ in 0..3 {
-            pages.push(None);
-        }
+    fn test_par_iter_chunks() {
+        use rayon::iter::ParallelIterator;
 
-        for (i, page) in pages.iter_mut().enumerate().take(3) {
-            let frame = framepool::PageFrame::new(i as u8);
-            frame.pin();
-            *page = Some(frame);
+        let mut mem_pool = MemPool::<u64>::new();
+        mem_pool.resize(100).unwrap();
+        for i in 0..100 {
+            mem_pool.put_frame(i, Arc::new(i)).unwrap();
         }
 
-        let lru = unique_stack::UniqueStack::new();
-        let result = random_evictor::<u8>(&pages, &lru);
-        assert!(result.is_err());
+        let mut bp = BufferPool::<u64>::new(8, &mut mem_pool, bottom_evictor);
 
-        // Unpin to cleanup
-        for p in pages.iter().flatten() {
-            p.unpin();
+        let mut chunks = 0;
+        let mut total = 0;
+        for chunk in bp.par_iter_chunks(16) {
+            chunks += 1;
+            total += chunk.map(|(idx, data)| idx + *data).sum::<u64>();
         }
+        assert_eq!(chunks, 7);
+        assert_eq!(total, 2 * (0..100).sum::<u64>());
     }
 
+    #[cfg(feature = "rayon")]
     #[test]
-    fn test_error_display() {
-        let err = BufferPoolErrors::NoEvictablePage;
-        assert_eq!(format!("{}", err), "no evictable pages");
+    fn test_par_for_each() {
+        use std::sync::atomic::{AtomicU64, Ordering};
 
-        let err = BufferPoolErrors::NoPageAvailable;
-        assert_eq!(format!("{}", err), "no available pages");
+        let mut mem_pool = MemPool::<u64>::new();
+        mem_pool.resize(50).unwrap();
+        for i in 0..50 {
+            mem_pool.put_frame(i, Arc::new(i * 2)).unwrap();
+        }
+
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+
+        let total = AtomicU64::new(0);
+        let visits = AtomicU64::new(0);
+        bp.par_for_each(|_, data| {
+            total.fetch_add(*data, Ordering::Relaxed);
+            visits.fetch_add(1, Ordering::Relaxed);
+        });
+        assert_eq!(visits.load(Ordering::Relaxed), 50);
+        assert_eq!(total.load(Ordering::Relaxed), 2 * (0..50).sum::<u64>());
+        assert!(bp.frame2buf.len() <= 4);
     }
 
     #[test]
-    fn test_with_diskpool() {
-        let test_dir = "/tmp/test_bufferpool_disk";
-        let _ = std::fs::remove_dir_all(test_dir);
+    fn test_iter_mut_then_reuse_pool() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(4).unwrap();
+        for i in 0..4 {
+            mem_pool.put_frame(i, Arc::new(i as u8)).unwrap();
+        }
 
-        let mut disk_pool = framepool::DiskPool::new::<String>(test_dir);
-        <framepool::DiskPool as framepool::FramePool<String>>::resize(&mut disk_pool, 3).unwrap();
+        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
 
-        // Write initial data
-        for i in 0..3 {
-            let data_arc = Arc::new(format!("page_{}", i));
-            <framepool::DiskPool as framepool::FramePool<String>>::put_frame(
-                &mut disk_pool,
-                i,
-                data_arc,
-            )
-            .unwrap();
+        let first: Vec<u8> = bp.iter_mut().collect();
+        assert_eq!(first, vec![0, 1, 2, 3]);
+
+        bp.put_page(1, 10).unwrap();
+        let second: Vec<u8> = bp.iter_mut().collect();
+        assert_eq!(second, vec![0, 10, 2, 3]);
+
+        for data in &mut bp {
+            assert!(data <= 10);
         }
+        assert!(bp.get_page(3).is_some());
+    }
 
-        let mut bp = BufferPool::<String>::new(2, &mut disk_pool, bottom_evictor);
+    #[test]
+    fn test_cached_iter() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(5).unwrap();
+        for i in 0..5 {
+            mem_pool.put_frame(i, Arc::new(i as u8 * 10)).unwrap();
+        }
 
-        // Test operations
-        let page = bp.get_page(0).unwrap();
-        assert_eq!(page.data(), "page_0");
+        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+        assert_eq!(bp.cached_iter().count(), 0);
 
-        bp.put_page(1, "modified_1".to_string()).unwrap();
-        bp.flush_all().unwrap();
+        bp.get_page(4);
+        bp.get_page(1);
+        bp.put_page(3, 99).unwrap();
+        let lru_before = bp.lru.order();
+
+        let mut cached: Vec<(u64, u8, bool)> = bp
+            .cached_iter()
+            .map(|(idx, page)| (idx, page.data(), page.is_dirty()))
+            .collect();
+        cached.sort();
+        assert_eq!(cached, vec![(1, 10, false), (3, 99, true), (4, 40, false)]);
+
+        // Read-only: recency and residency are unchanged
+        assert_eq!(bp.lru.order(), lru_before);
+        assert_eq!(bp.frame2buf.len(), 3);
+    }
 
-        // Verify persistence
-        let frame_arc =
-            <framepool::DiskPool as framepool::FramePool<String>>::get_frame_ref(&mut disk_pool, 1)
-                .unwrap();
-        assert_eq!(*frame_arc, "modified_1");
+    #[test]
+    fn test_bulk_append() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(2).unwrap();
+        mem_pool.put_frame(0, Arc::new(100)).unwrap();
+        mem_pool.put_frame(1, Arc::new(101)).unwrap();
 
-        // Clean up
-        let _ = std::fs::remove_dir_all(test_dir);
+        let mut bp = BufferPool::<u32>::new(3, &mut mem_pool, bottom_evictor);
+
+        let appended = bp.bulk_append(0..7).unwrap();
+        assert_eq!(appended, 7);
+        assert_eq!(bp.frame_pool.size(), 9);
+
+        bp.extend(vec![7, 8]);
+        let collected: Vec<u32> = bp.iter_mut().collect();
+        assert_eq!(collected, vec![100, 101, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
     }
 
     #[test]
-    fn test_slab_mapper_new() {
-        let mut mem_pool = MemPool::<i32>::new();
-        let mapper = SlabMapper::new(5, &mut mem_pool, 10);
-        assert_eq!(mapper.stride, 10);
+    fn test_bulk_append_failure_truncates() {
+        let faults = framepool::FaultInjector::new(0);
+        let mut pool = framepool::FaultyPool::new(MemPool::<u32>::new(), faults.clone());
+        let mut bp = BufferPool::<u32>::new(3, &mut pool, bottom_evictor);
+
+        // The sixth write fails, partway through the second batch
+        faults.fail_after(Some(5));
+        assert!(bp.bulk_append(0..8).is_err());
+        assert_eq!(bp.frame_pool.size(), 5);
+        faults.fail_after(None);
+        let appended: Vec<u32> = bp.iter_mut().collect();
+        assert_eq!(appended, vec![0, 1, 2, 3, 4]);
     }
 
     #[test]
-    fn test_slab_mapper_load() {
+    fn test_bulk_append_writes_in_batches() {
+        let mut pool = framepool::InstrumentedPool::new(MemPool::<u32>::new());
+        let metrics = pool.metrics();
+        let mut bp = BufferPool::<u32>::new(3, &mut pool, bottom_evictor);
+
+        assert_eq!(bp.bulk_append(0..7).unwrap(), 7);
+        let writes = metrics.snapshot().writes;
+        assert_eq!(writes.count, 7);
+        // one put_frames per batch of three
+        assert_eq!(writes.latency.samples, 3);
+        assert_eq!(*bp.get_page_arc(6).unwrap(), 6);
+    }
+
+    #[test]
+    fn test_slab_mapper_extend() {
         let mut mem_pool = MemPool::<i32>::new();
-        let mut mapper = SlabMapper::new(5, &mut mem_pool, 10);
+        let mut mapper = SlabMapper::new(2, &mut mem_pool, 2);
 
-        let result = mapper.load();
-        assert!(result.is_ok());
-        assert_eq!(mem_pool.size(), 0); // ensure_allocation(0) doesn't resize
+        mapper.flush(vec![10, 20, 30, 40]).unwrap();
+        mapper.extend(vec![50, 60, 70]);
+
+        assert_eq!(mapper.get(0), Some(10));
+        assert_eq!(mapper.get(2), Some(30));
+        assert_eq!(mapper.get(4), Some(50));
+        assert_eq!(mapper.get(6), Some(70));
+        assert_eq!(mapper.get(8), None);
     }
 
     #[test]
-    fn test_slab_mapper_flush() {
-        let mut mem_pool = MemPool::<i32>::new();
-        let mut mapper = SlabMapper::new(5, &mut mem_pool, 3);
+    fn test_at_guard() {
+        let mut mem_pool = MemPool::<String>::new();
+        mem_pool.resize(3).unwrap();
+        for i in 0..3 {
+            mem_pool
+                .put_frame(i, Arc::new(format!("page_{}", i)))
+                .unwrap();
+        }
 
-        let data = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
-        mapper.flush(data).unwrap();
+        let mut bp = BufferPool::<String>::new(1, &mut mem_pool, bottom_evictor);
 
-        // Should have allocated 4 pages (10 items / 3 stride = 4 pages)
-        assert_eq!(mem_pool.size(), 4);
+        let guard = bp.at(1).unwrap();
+        assert_eq!(guard.frame_idx(), 1);
+        assert_eq!(*guard, "page_1");
+        assert_eq!(guard.len(), 6);
+
+        // The guard outlives the page's residency in the pool
+        assert_eq!(bp.at(2).unwrap().as_str(), "page_2");
+        assert_eq!(guard.as_ref(), "page_1");
+        assert_eq!(*guard.into_arc(), "page_1");
+
+        match bp.at(10) {
+            Err(BufferPoolErrors::IndexOutOfBounds(10)) => (),
+            _ => panic!("Expected IndexOutOfBounds error"),
+        }
     }
 
     #[test]
-    fn test_slab_mapper_get() {
-        let mut mem_pool = MemPool::<i32>::new();
-        let mut mapper = SlabMapper::new(5, &mut mem_pool, 3);
+    fn test_checkpoint() {
+        let mut mem_pool = MemPool::<u8>::new();
+        mem_pool.resize(3).unwrap();
+        for i in 0..3 {
+            mem_pool.put_frame(i, Arc::new(i as u8)).unwrap();
+        }
 
-        let data = vec![10, 20, 30, 40, 50];
-        mapper.flush(data).unwrap();
+        let mut bp = BufferPool::<u8>::new(3, &mut mem_pool, bottom_evictor);
+        bp.put_page(0, 10).unwrap();
+        bp.put_page(2, 12).unwrap();
 
-        // Get items from different pages
-        let val = mapper.get(0);
-        assert_eq!(val, Some(10));
+        let first = bp.checkpoint().unwrap();
+        assert_eq!(first.sequence, 1);
+        assert_eq!(first.pages_flushed, 2);
 
-        let val = mapper.get(3);
-        assert_eq!(val, Some(40));
+        let second = bp.checkpoint().unwrap();
+        assert_eq!(second.sequence, 2);
+        assert_eq!(second.pages_flushed, 0);
+
+        assert_eq!(*mem_pool.get_frame_ref(0).unwrap(), 10);
+        assert_eq!(*mem_pool.get_frame_ref(2).unwrap(), 12);
     }
 
     #[test]
-    fn test_slab_mapper_with_diskpool() {
-        let test_dir = "/tmp/test_slabmapper_disk";
+    fn test_checkpoint_with_diskpool() {
+        let test_dir = "/tmp/test_bufferpool_checkpoint";
         let _ = std::fs::remove_dir_all(test_dir);
 
         let mut disk_pool = framepool::DiskPool::new::<String>(test_dir);
-        let mut mapper = SlabMapper::new(3, &mut disk_pool, 2);
-
-        let data = vec![
-            "a".to_string(),
-            "b".to_string(),
-            "c".to_string(),
-            "d".to_string(),
-            "e".to_string(),
-        ];
-
-        mapper.flush(data).unwrap();
+        <framepool::DiskPool as framepool::FramePool<String>>::resize(&mut disk_pool, 2).unwrap();
+        let mut bp = BufferPool::<String>::new(2, &mut disk_pool, bottom_evictor);
+        bp.bulk_append(vec!["a".to_string()]).unwrap();
+        bp.put_page(2, "b".to_string()).unwrap();
 
-        let val = mapper.get(0);
-        assert_eq!(val, Some("a".to_string()));
+        let checkpoint = bp.checkpoint().unwrap();
+        assert_eq!(checkpoint.sequence, 1);
+        assert_eq!(checkpoint.pages_flushed, 1);
 
-        let val = mapper.get(2);
-        assert_eq!(val, Some("c".to_string()));
+        let frame_arc =
+            <framepool::DiskPool as framepool::FramePool<String>>::get_frame_ref(&mut disk_pool, 2)
+                .unwrap();
+        assert_eq!(*frame_arc, "b");
 
         // Clean up
         let _ = std::fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_put_page_no_page_available() {
-        let mut mem_pool = MemPool::<u8>::new();
-        // Don't resize the pool, so there are no pages
-
-        let mut bp = BufferPool::<u8>::new(5, &mut mem_pool, bottom_evictor);
-
-        let result = bp.put_page(0, 42);
-        assert!(result.is_err());
-        match result {
-            Err(BufferPoolErrors::NoPageAvailable) => (),
-            _ => panic!("Expected NoPageAvailable error"),
+    fn test_checkpoint_flush_failed() {
+        let mut pool = ReadOnlyMemPool {
+            inner: MemPool::<u8>::new(),
+        };
+        pool.inner.resize(1).unwrap();
+        pool.inner.put_frame(0, Arc::new(0)).unwrap();
+        let mut bp = BufferPool::<u8>::new(1, &mut pool, bottom_evictor);
+        bp.put_page(0, 1).unwrap();
+
+        match bp.checkpoint() {
+            Err(BufferPoolErrors::FlushFailed(_)) => (),
+            _ => panic!("Expected FlushFailed error"),
         }
     }
 
     #[test]
-    fn test_eviction_correctness_stress() {
-        // Stress test to ensure eviction logic maintains consistent state
-        let mut mem_pool = MemPool::<String>::new();
-        mem_pool.resize(20).unwrap();
-
-        // Initialize backing storage with data
-        for i in 0..20 {
-            let data_arc = Arc::new(format!("page_{}", i));
-            mem_pool.put_frame(i, data_arc).unwrap();
+    fn test_put_page_if_version() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(3).unwrap();
+        for i in 0..3 {
+            mem_pool.put_frame(i, Arc::new(i as u32)).unwrap();
         }
 
-        let mut bp = BufferPool::<String>::new(3, &mut mem_pool, bottom_evictor);
-
-        // Perform many operations to stress test the eviction logic
-        for round in 0..10 {
-            for i in 0..20 {
-                let page = bp.get_page(i);
-                assert!(page.is_some(), "Should be able to load page {}", i);
-
-                // Verify mapping consistency after each operation
-                assert_eq!(
-                    bp.frame2buf.len(),
-                    bp.buf2frame.len(),
-                    "Mapping lengths should be equal in round {}, access {}",
-                    round,
-                    i
-                );
-                assert!(
-                    bp.frame2buf.len() <= 3,
-                    "Should never exceed buffer pool size"
-                );
+        let mut bp = BufferPool::<u32>::new(1, &mut mem_pool, bottom_evictor);
 
-                // Verify bidirectional mapping consistency
-                for (frame_id, buf_id) in &bp.frame2buf {
-                    assert_eq!(
-                        bp.buf2frame[buf_id], *frame_id,
-                        "Bidirectional mapping should be consistent"
-                    );
-                }
+        let seen = bp.get_page(0).unwrap().version();
+        let computed = bp.get_page(0).unwrap().data() + 100;
+        let new_version = bp.put_page_if_version(0, seen, computed).unwrap();
+        assert!(new_version > seen);
 
-                for (buf_id, frame_id) in &bp.buf2frame {
-                    assert_eq!(
-                        bp.frame2buf[frame_id], *buf_id,
-                        "Reverse mapping should be consistent"
-                    );
-                }
+        match bp.put_page_if_version(0, seen, 7) {
+            Err(BufferPoolErrors::VersionConflict { expected, actual }) => {
+                assert_eq!(expected, seen);
+                assert_eq!(actual, new_version);
             }
+            _ => panic!("Expected VersionConflict error"),
         }
+        assert_eq!(bp.get_page(0).unwrap().data(), 100);
     }
 
     #[test]
-    fn test_slab_mapper_transaction_safety() {
-        // Test that SlabMapper operations are atomic - either both succeed or both fail
-        let mut mem_pool = MemPool::<i32>::new();
-        let mut mapper = SlabMapper::new(3, &mut mem_pool, 2);
+    fn test_page_version_survives_eviction() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(2).unwrap();
+        for i in 0..2 {
+            mem_pool.put_frame(i, Arc::new(i as u32)).unwrap();
+        }
 
-        // Test successful case
-        let data = vec![10, 20, 30, 40, 50];
-        let result = mapper.flush(data.clone());
-        assert!(result.is_ok(), "Flush should succeed");
+        let mut bp = BufferPool::<u32>::new(1, &mut mem_pool, bottom_evictor);
 
-        // Verify data was written correctly
-        for (i, expected) in data.iter().enumerate() {
-            if i % 2 == 0 {
-                // Only first element of each stride is accessible with current impl
-                let val = mapper.get(i);
-                assert_eq!(
-                    val,
-                    Some(*expected),
-                    "Should retrieve correct value at index {}",
-                    i
-                );
-            }
+        bp.put_page(0, 5).unwrap();
+        bp.put_page(0, 6).unwrap();
+        let before = bp.get_page(0).unwrap().version();
+
+        // Evict frame 0 and bring it back
+        bp.get_page(1);
+        let after = bp.get_page(0).unwrap().version();
+        assert!(after > before);
+
+        // A writer holding the pre-eviction version must not succeed
+        assert!(bp.put_page_if_version(0, before, 9).is_err());
+    }
+
+    #[test]
+    fn test_bufferpool_weigher() {
+        let mut mem_pool = MemPool::<Vec<u8>>::new();
+        mem_pool.resize(3).unwrap();
+        for i in 0..3 {
+            mem_pool
+                .put_frame(i, Arc::new(vec![0; i as usize]))
+                .unwrap();
         }
 
-        // Test with empty data - should handle gracefully
-        let empty_data = vec![];
-        let result = mapper.flush(empty_data);
-        assert!(result.is_ok(), "Empty flush should succeed");
+        let mut bp = BufferPool::<Vec<u8>>::new(2, &mut mem_pool, bottom_evictor);
+        let shallow = std::mem::size_of::<Vec<u8>>() as u64;
+        assert_eq!(bp.get_page(1).unwrap().byte_size(), shallow);
+
+        // Cached pages are weighed again, and pages loaded later weighed as they come in
+        bp.set_weigher(|data: &Vec<u8>| data.len() as u64);
+        assert_eq!(bp.get_page(1).unwrap().byte_size(), 1);
+        assert_eq!(bp.get_page(2).unwrap().byte_size(), 2);
+        bp.put_page(2, vec![0; 7]).unwrap();
+        assert_eq!(bp.get_page(2).unwrap().byte_size(), 7);
     }
 
     #[test]
-    fn test_bufferpool_errors_display() {
-        // Test all error variants display correctly
-        let no_evict_err = BufferPoolErrors::NoEvictablePage;
-        let no_page_err = BufferPoolErrors::NoPageAvailable;
+    fn test_read_only_pool() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(3).unwrap();
+        for i in 0..3 {
+            mem_pool.put_frame(i, Arc::new(i as u32)).unwrap();
+        }
 
-        assert_eq!(format!("{}", no_evict_err), "no evictable pages");
-        assert_eq!(format!("{}", no_page_err), "no available pages");
+        let mut bp = BufferPool::<u32>::new(2, &mut mem_pool, bottom_evictor);
+        assert!(!bp.is_read_only());
+        bp.put_page(0, 10).unwrap();
+
+        // Going read-only writes back pending changes first
+        bp.set_read_only(true).unwrap();
+        assert!(bp.is_read_only());
+        assert!(!bp.get_page(0).unwrap().is_dirty());
+        assert!(bp.get_page(0).unwrap().is_read_only());
+
+        assert!(matches!(bp.put_page(1, 5), Err(BufferPoolErrors::ReadOnly)));
+        assert!(matches!(
+            bp.modify_page(1, |v| *v += 1),
+            Err(BufferPoolErrors::ReadOnly)
+        ));
+        assert!(matches!(bp.checkpoint(), Err(BufferPoolErrors::ReadOnly)));
+        assert!(bp.flush_all().is_err());
+        assert!(bp.sync_index(0).is_err());
+        assert!(bp.bulk_append(vec![1]).is_err());
+        let mut txn = bp.begin();
+        txn.write(1, 5);
+        assert!(matches!(txn.commit(), Err(BufferPoolErrors::ReadOnly)));
+
+        // Reads still work, including pages loaded after the switch
+        let values: Vec<u32> = bp.iter_mut().collect();
+        assert_eq!(values, vec![10, 1, 2]);
+
+        bp.set_read_only(false).unwrap();
+        bp.modify_page(1, |v| *v += 1).unwrap();
+        bp.flush_all().unwrap();
+        drop(bp);
+        assert_eq!(*mem_pool.get_frame_ref(0).unwrap(), 10);
+        assert_eq!(*mem_pool.get_frame_ref(1).unwrap(), 2);
     }
 
     #[test]
-    fn test_slab_mapper_get_out_of_bounds() {
-        let mut mem_pool = framepool::MemPool::new();
-        let mut mapper = SlabMapper::new(2, &mut mem_pool, 2);
-        mapper.load().unwrap();
+    fn test_read_only_diskpool_bufferpool() {
+        let test_dir = "/tmp/test_read_only_diskpool_bufferpool";
+        let _ = std::fs::remove_dir_all(test_dir);
 
-        // Add some data
-        let data = vec![10, 20, 30];
-        mapper.flush(data).unwrap();
+        let mut writer = DiskPool::new::<String>(test_dir);
+        <DiskPool as FramePool<String>>::resize(&mut writer, 2).unwrap();
+        writer.put_frame(0, Arc::new("a".to_string())).unwrap();
+        writer.put_frame(1, Arc::new("b".to_string())).unwrap();
+        drop(writer);
+
+        let mut reader = DiskPool::open_read_only::<String>(test_dir).unwrap();
+        let mut bp = BufferPool::<String>::new(1, &mut reader, bottom_evictor);
+        assert!(bp.is_read_only());
+        assert_eq!(bp.get_page(0).unwrap().data(), "a");
+        assert_eq!(bp.get_page(1).unwrap().data(), "b");
+        assert!(matches!(
+            bp.put_page(0, "z".to_string()),
+            Err(BufferPoolErrors::ReadOnly)
+        ));
+        assert!(matches!(
+            bp.set_read_only(false),
+            Err(BufferPoolErrors::ReadOnly)
+        ));
 
-        // Try to get out of bounds index
-        assert_eq!(mapper.get(100), None);
-        assert_eq!(mapper.get(4), None);
+        let _ = std::fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_sync_index_with_clean_page() {
-        let mut mem_pool = framepool::MemPool::new();
-        mem_pool.resize(1).unwrap();
-        let data_arc = Arc::new(42u8);
-        mem_pool.put_frame(0, data_arc).unwrap();
+    fn test_warm_respects_capacity() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(10).unwrap();
+        for i in 0..10 {
+            mem_pool.put_frame(i, Arc::new(i as u32)).unwrap();
+        }
 
-        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
+        let mut bp = BufferPool::<u32>::new(3, &mut mem_pool, bottom_evictor);
+        bp.get_page(9).unwrap();
 
-        // Get a page
-        let _page = bp.get_page(0).unwrap();
-        // Don't mark it dirty
+        // Already-cached, out-of-range and excess frames are skipped
+        assert_eq!(bp.warm(&[9, 4, 42, 5, 6, 7]), 2);
+        assert_eq!(bp.frame2buf.len(), 3);
+        assert!(bp.frame2buf.contains_key(&9));
+        assert!(!bp.frame2buf.contains_key(&6));
+        assert_eq!(bp.hot_frames(), vec![4, 5, 9]);
 
-        // Syncing a clean page should be a no-op
-        let result = bp.sync_index(0);
-        assert!(result.is_ok());
+        // A full pool loads nothing
+        assert_eq!(bp.warm_sequential(10), 0);
     }
 
     #[test]
-    fn test_flush_all_empty_pool() {
-        let mut mem_pool = framepool::MemPool::new();
-        let mut bp = BufferPool::<u8>::new(2, &mut mem_pool, bottom_evictor);
-
-        // Flush empty pool should succeed
-        let result = bp.flush_all();
-        assert!(result.is_ok());
+    fn test_warm_skips_repeats() {
+        let mut mem_pool = filled_mem_pool(2000, |i| i);
+        let mut bp = BufferPool::<u64>::new(1000, &mut mem_pool, bottom_evictor);
+
+        // Each frame is read once however often it is asked for
+        let wanted: Vec<FramePoolId> = (0..4000).map(|i| i % 1000).collect();
+        assert_eq!(bp.warm(&wanted), 1000);
+        assert_eq!(bp.frame2buf.len(), 1000);
+        assert!(bp.validate().is_valid());
     }
 
     #[test]
-    fn test_get_page_beyond_available() {
-        let mut mem_pool = framepool::MemPool::new();
-        let mut bp = BufferPool::<u8>::new(1, &mut mem_pool, bottom_evictor);
+    fn test_warm_sequential() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(2).unwrap();
+        for i in 0..2 {
+            mem_pool.put_frame(i, Arc::new(i as u32)).unwrap();
+        }
 
-        // Try to get a page beyond what's available
-        let result = bp.get_page(100);
-        assert!(result.is_none());
+        let mut bp = BufferPool::<u32>::new(4, &mut mem_pool, bottom_evictor);
+        assert_eq!(bp.warm_sequential(10), 2);
+        assert_eq!(bp.hot_frames(), vec![0, 1]);
     }
 
     #[test]
-    fn test_bufferpool_iterator_basic() {
-        let mut mem_pool = MemPool::<String>::new();
-        mem_pool.resize(3).unwrap();
-
-        // Initialize with test data
-        for i in 0..3 {
-            let data_arc = Arc::new(format!("data_{}", i));
-            mem_pool.put_frame(i, data_arc).unwrap();
+    fn test_hot_frames_round_trip() {
+        let mut mem_pool = MemPool::<u32>::new();
+        mem_pool.resize(10).unwrap();
+        for i in 0..10 {
+            mem_pool.put_frame(i, Arc::new(i as u32)).unwrap();
         }
 
-        let mut bp = BufferPool::<String>::new(2, &mut mem_pool, bottom_evictor);
+        let saved = {
+            let mut bp = BufferPool::<u32>::new(3, &mut mem_pool, bottom_evictor);
+            for i in [1, 8, 3, 8, 6] {
+                bp.get_page(i).unwrap();
+            }
+            bp.hot_frames()
+        };
+        assert_eq!(saved, vec![6, 8, 3]);
 
-        // Collect all data using the iterator
-        let collected: Vec<String> = (&mut bp).into_iter().collect();
+        let mut bp = BufferPool::<u32>::new(3, &mut mem_pool, bottom_evictor);
+        assert_eq!(bp.warm(&saved), 3);
+        assert_eq!(bp.hot_frames(), saved);
 
-        assert_eq!(collected.len(), 3);
-        assert_eq!(collected[0], "data_0");
-        assert_eq!(collected[1], "data_1");
-        assert_eq!(collected[2], "data_2");
+        // The restored recency decides what goes first
+        bp.get_page(0).unwrap();
+        assert!(!bp.frame2buf.contains_key(&3));
     }
 
     #[test]
-    fn test_bufferpool_iterator_with_caching() {
-        let mut mem_pool = MemPool::<i32>::new();
-        mem_pool.resize(5).unwrap();
-
-        // Initialize with test data
-        for i in 0..5 {
-            let data_arc = Arc::new((i * 10) as i32);
-            mem_pool.put_frame(i, data_arc).unwrap();
+    fn test_composite_keys() {
+        let mut mem_pool = MemPool::<String, (u64, u64)>::new_keyed();
+        for table in 0..3u64 {
+            for page in 0..4u64 {
+                mem_pool
+                    .put_frame((table, page), Arc::new(format!("t{}p{}", table, page)))
+                    .unwrap();
+            }
         }
 
-        // Small buffer pool to force evictions
-        let mut bp = BufferPool::<i32>::new(2, &mut mem_pool, bottom_evictor);
-
-        // Iterate and verify caching is transparent
-        let collected: Vec<i32> = (&mut bp).into_iter().collect();
-        let sum: i32 = collected.iter().sum();
+        let mut bp = BufferPool::new_keyed(2, &mut mem_pool, bottom_evictor);
+        assert_eq!(bp.get_page((1, 2)).unwrap().data(), "t1p2");
+        assert_eq!(bp.get_page((2, 3)).unwrap().data(), "t2p3");
+        assert!(bp.try_get_page((7, 0)).is_err());
 
-        assert_eq!(sum, 0 + 10 + 20 + 30 + 40); // 100
-        assert_eq!(collected.len(), 5);
+        bp.put_page((0, 0), "changed".to_string()).unwrap();
+        // Forces (0, 0) out, writing it back
+        bp.get_page((1, 1)).unwrap();
+        bp.get_page((1, 3)).unwrap();
+        assert!(!bp.frame2buf.contains_key(&(0, 0)));
 
-        // Note: Can't check internal state after consuming the iterator
-        // because it requires accessing bp after it's been mutably borrowed
+        let values = bp.get_many(&[(0, 0), (2, 1)]);
+        assert_eq!(*values[0].as_ref().unwrap().as_ref(), "changed");
+        assert_eq!(*values[1].as_ref().unwrap().as_ref(), "t2p1");
     }
 
     #[test]
-    fn test_bufferpool_iterator_empty() {
-        let mut mem_pool = MemPool::<u8>::new();
-        let mut bp = BufferPool::<u8>::new(5, &mut mem_pool, bottom_evictor);
+    fn test_string_keys() {
+        let mut mem_pool = MemPool::<u32, String>::new_keyed();
+        mem_pool
+            .put_frame("alpha".to_string(), Arc::new(1))
+            .unwrap();
+        mem_pool.put_frame("beta".to_string(), Arc::new(2)).unwrap();
+        assert_eq!(FramePool::<u32, String>::size(&mem_pool), 2);
 
-        let collected: Vec<u8> = (&mut bp).into_iter().collect();
-        assert_eq!(collected.len(), 0);
+        {
+            let mut bp = BufferPool::new_keyed(1, &mut mem_pool, bottom_evictor);
+            bp.modify_page("alpha".to_string(), |v| *v += 10).unwrap();
+            assert_eq!(bp.get_page("beta".to_string()).unwrap().data(), 2);
+            bp.flush_all().unwrap();
+        }
+        assert_eq!(*mem_pool.get_frame_ref("alpha".to_string()).unwrap(), 11);
     }
 
     #[test]
-    fn test_bufferpool_iterator_partial_data() {
-        let mut mem_pool = MemPool::<Option<String>>::new();
-        mem_pool.resize(3).unwrap();
-
-        // Only populate some frames
-        let data1 = Arc::new(Some("first".to_string()));
-        let data2 = Arc::new(None);
-        let data3 = Arc::new(Some("third".to_string()));
-
-        mem_pool.put_frame(0, data1).unwrap();
-        mem_pool.put_frame(1, data2).unwrap();
-        mem_pool.put_frame(2, data3).unwrap();
-
-        let mut bp = BufferPool::<Option<String>>::new(2, &mut mem_pool, bottom_evictor);
+    fn test_truncate_invalidates_cache() {
+        let mut mem_pool = MemPool::<u64>::new();
+        mem_pool.resize(10).unwrap();
+        for i in 0..10 {
+            mem_pool.put_frame(i, Arc::new(i)).unwrap();
+        }
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+        bp.get_page(2).unwrap();
+        bp.get_page(7).unwrap();
+        bp.put_page(8, 80).unwrap();
 
-        let collected: Vec<Option<String>> = (&mut bp).into_iter().collect();
+        bp.truncate(5).unwrap();
+        assert!(bp.frame2buf.contains_key(&2));
+        assert!(!bp.frame2buf.contains_key(&7));
+        assert!(!bp.frame2buf.contains_key(&8));
+        assert!(bp.validate().is_valid());
+        assert!(bp.get_page(7).is_none());
 
-        assert_eq!(collected.len(), 3);
-        assert_eq!(collected[0], Some("first".to_string()));
-        assert_eq!(collected[1], None);
-        assert_eq!(collected[2], Some("third".to_string()));
+        // The dropped dirty page is not written back
+        bp.flush_all().unwrap();
+        bp.grow_to(9).unwrap();
+        bp.grow_to(3).unwrap();
+        assert_eq!(bp.frame_pool.size(), 9);
+        assert!(bp.try_get_page(8).is_err());
+        assert_eq!(bp.get_page(2).unwrap().data(), 2);
     }
 
     #[test]
-    fn test_bufferpool_iterator_stress() {
-        let mut mem_pool = MemPool::<usize>::new();
-        mem_pool.resize(100).unwrap();
-
-        // Initialize with index values
-        for i in 0..100 {
-            let data_arc = Arc::new(i as usize);
-            mem_pool.put_frame(i, data_arc).unwrap();
-        }
+    fn test_get_or_insert_with() {
+        let mut mem_pool = MemPool::<u64>::new();
+        mem_pool.resize(4).unwrap();
+        mem_pool.put_frame(1, Arc::new(10)).unwrap();
+        let mut bp = BufferPool::<u64>::new(2, &mut mem_pool, bottom_evictor);
+
+        // Populated frames are read, never replaced
+        assert_eq!(bp.get_or_insert_with(1, || 99).unwrap().data(), 10);
+        // Allocated but empty frames are filled
+        let page = bp.get_or_insert_with(2, || 20).unwrap();
+        assert_eq!(page.data(), 20);
+        assert!(page.is_dirty());
+        assert!(matches!(
+            bp.get_or_insert_with(4, || 40),
+            Err(BufferPoolErrors::IndexOutOfBounds(4))
+        ));
 
-        // Very small buffer to force lots of evictions
-        let mut bp = BufferPool::<usize>::new(3, &mut mem_pool, bottom_evictor);
+        bp.flush_all().unwrap();
+        drop(bp);
+        assert!(mem_pool.exists(&2));
+        assert_eq!(*mem_pool.get_frame_ref(2).unwrap(), 20);
+    }
 
-        let collected: Vec<usize> = (&mut bp).into_iter().collect();
+    #[test]
+    fn test_get_or_insert_with_diskpool_placeholder() {
+        let test_dir = "/tmp/test_get_or_insert_placeholder";
+        let _ = std::fs::remove_dir_all(test_dir);
+        let mut disk_pool = framepool::DiskPool::new::<Vec<u64>>(test_dir);
+        <framepool::DiskPool as FramePool<Vec<u64>>>::resize(&mut disk_pool, 2).unwrap();
+        disk_pool.put_frame(0, Arc::new(vec![1u64])).unwrap();
+
+        let mut bp = BufferPool::<Vec<u64>>::new(2, &mut disk_pool, bottom_evictor);
+        assert_eq!(bp.get_or_insert_with(0, Vec::new).unwrap().data(), vec![1]);
+        assert_eq!(
+            bp.get_or_insert_with(1, || vec![2]).unwrap().data(),
+            vec![2]
+        );
+        bp.flush_all().unwrap();
+        drop(bp);
 
-        assert_eq!(collected.len(), 100);
-        for (i, &value) in collected.iter().enumerate() {
-            assert_eq!(value, i, "Value at index {} should be {}", i, i);
-        }
+        assert_eq!(
+            FramePool::<Vec<u64>>::frame_state(&disk_pool, &1),
+            framepool::FrameState::Populated
+        );
+        let _ = std::fs::remove_dir_all(test_dir);
     }
 }
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::{BufferPool, BufferPoolErrors, FramePoolId};
+
+// Pages written over a BufferPool without touching it: copy-on-write copies of its data that
+// reads see in place of the pool's. Transaction stages its changes in one and PoolFork keeps
+// its private writes in one.
+pub(super) struct Overlay<T> {
+    // in frame order, so that whatever is done with them is done in a canonical order
+    pages: BTreeMap<FramePoolId, Arc<T>>,
+}
+
+impl<T> Overlay<T>
+where
+    T: Clone,
+{
+    pub(super) fn new() -> Self {
+        Overlay {
+            pages: BTreeMap::new(),
+        }
+    }
+
+    // The overlay's copy of the page if it has one, otherwise the pool's current data.
+    pub(super) fn read(
+        &self,
+        pool: &mut BufferPool<'_, T>,
+        frame_idx: FramePoolId,
+    ) -> Result<Arc<T>, BufferPoolErrors> {
+        match self.pages.get(&frame_idx) {
+            Some(data) => Ok(Arc::clone(data)),
+            None => Ok(pool.at(frame_idx)?.into_arc()),
+        }
+    }
+
+    pub(super) fn write(&mut self, frame_idx: FramePoolId, data: T) {
+        self.pages.insert(frame_idx, Arc::new(data));
+    }
+
+    // Modifies the page as read, copying it on its first modification; the pool's copy is
+    // left untouched.
+    pub(super) fn modify<F, R>(
+        &mut self,
+        pool: &mut BufferPool<'_, T>,
+        frame_idx: FramePoolId,
+        f: F,
+    ) -> Result<R, BufferPoolErrors>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut data = self.read(pool, frame_idx)?;
+        // Drop our extra handle so make_mut only copies when the pool still shares the data.
+        self.pages.remove(&frame_idx);
+        let result = f(Arc::make_mut(&mut data));
+        self.pages.insert(frame_idx, data);
+        Ok(result)
+    }
+
+    pub(super) fn contains(&self, frame_idx: FramePoolId) -> bool {
+        self.pages.contains_key(&frame_idx)
+    }
+
+    // Frame ids with pages in the overlay, in ascending order.
+    pub(super) fn frames(&self) -> Vec<FramePoolId> {
+        self.pages.keys().copied().collect()
+    }
+
+    pub(super) fn into_pages(self) -> BTreeMap<FramePoolId, Arc<T>> {
+        self.pages
+    }
+}
@@ -0,0 +1,411 @@
+use std::ops::Range;
+
+use super::{BufferPool, BufferPoolErrors, BufferPoolId, FrameKey, FramePoolId, framepool};
+
+/// A named range of frame ids with its own slot quota, created by `BufferPool::add_partition`.
+///
+/// Partitions only apply to keys with a slot number (see `FrameKey::slot`). Frames in a
+/// partition are only ever cached in at most `max_slots` slots, and loading one of them never
+/// evicts a page belonging to a different partition. A scan through one tenant's frames can
+/// therefore only displace that tenant's pages and unpartitioned pages.
+///
+/// A partition may also have a byte quota, `max_bytes`, set with
+/// `BufferPool::set_partition_max_bytes`. Pages are weighed by `PageFrame::byte_size`, so give
+/// the pool a weigher (`BufferPool::set_weigher`) when `T` holds data on the heap. A page is
+/// only loaded into a partition whose pages weigh less than its quota, so the last page loaded
+/// may take it over by up to that page's size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    pub name: String,
+    pub frames: Range<FramePoolId>,
+    pub max_slots: usize,
+    pub max_bytes: Option<u64>,
+}
+
+impl Partition {
+    fn holds<K: FrameKey>(&self, frame_idx: &K) -> bool {
+        frame_idx
+            .slot()
+            .is_some_and(|slot| self.frames.contains(&slot))
+    }
+}
+
+impl<'a, T, K> BufferPool<'a, T, K>
+where
+    T: Clone,
+    K: FrameKey,
+{
+    /// Assigns the frames in `frames` to a new partition named `name`, which may occupy at most
+    /// `max_slots` slots of this pool.
+    ///
+    /// Partitions may not overlap, and their quotas together may not exceed the pool size.
+    /// Pages already cached above the new quota stay cached until the partition next loads a
+    /// page.
+    pub fn add_partition(
+        &mut self,
+        name: &str,
+        frames: Range<FramePoolId>,
+        max_slots: usize,
+    ) -> Result<(), BufferPoolErrors> {
+        if max_slots == 0 {
+            return Err(BufferPoolErrors::InvalidPartition(format!(
+                "{}: quota must be at least one slot",
+                name
+            )));
+        }
+        if frames.is_empty() {
+            return Err(BufferPoolErrors::InvalidPartition(format!(
+                "{}: frame range is empty",
+                name
+            )));
+        }
+        for partition in self.partitions.iter() {
+            if partition.name == name {
+                return Err(BufferPoolErrors::InvalidPartition(format!(
+                    "{}: already exists",
+                    name
+                )));
+            }
+            if partition.frames.start < frames.end && frames.start < partition.frames.end {
+                return Err(BufferPoolErrors::InvalidPartition(format!(
+                    "{}: frames overlap partition {}",
+                    name, partition.name
+                )));
+            }
+        }
+        let reserved: usize = self.partitions.iter().map(|p| p.max_slots).sum();
+        if reserved + max_slots > self.size {
+            return Err(BufferPoolErrors::InvalidPartition(format!(
+                "{}: quotas would total {} slots in a pool of {}",
+                name,
+                reserved + max_slots,
+                self.size
+            )));
+        }
+        self.partitions.push(Partition {
+            name: name.to_string(),
+            frames,
+            max_slots,
+            max_bytes: None,
+        });
+        Ok(())
+    }
+
+    /// Limits the named partition's pages to `max_bytes` bytes as well as its slot quota, or
+    /// lifts the limit with `None`. Pages already cached above the new quota stay cached until
+    /// the partition next loads a page.
+    pub fn set_partition_max_bytes(
+        &mut self,
+        name: &str,
+        max_bytes: Option<u64>,
+    ) -> Result<(), BufferPoolErrors> {
+        if max_bytes == Some(0) {
+            return Err(BufferPoolErrors::InvalidPartition(format!(
+                "{}: byte quota must be at least one byte",
+                name
+            )));
+        }
+        let partition = self
+            .partitions
+            .iter_mut()
+            .find(|p| p.name == name)
+            .ok_or_else(|| {
+                BufferPoolErrors::InvalidPartition(format!("{}: no such partition", name))
+            })?;
+        partition.max_bytes = max_bytes;
+        Ok(())
+    }
+
+    /// Removes a partition; its frames become unpartitioned. Returns false if there was no
+    /// partition with that name.
+    pub fn remove_partition(&mut self, name: &str) -> bool {
+        let before = self.partitions.len();
+        self.partitions.retain(|p| p.name != name);
+        self.partitions.len() != before
+    }
+
+    /// The partitions of this pool, in the order they were added.
+    pub fn partitions(&self) -> &[Partition] {
+        &self.partitions
+    }
+
+    /// The number of slots currently holding pages of the named partition.
+    pub fn partition_usage(&self, name: &str) -> Option<usize> {
+        let partition = self.partitions.iter().find(|p| p.name == name)?;
+        Some(self.resident_in(partition))
+    }
+
+    /// The bytes held by cached pages of the named partition, as weighed by
+    /// `PageFrame::byte_size`.
+    pub fn partition_bytes(&self, name: &str) -> Option<u64> {
+        let partition = self.partitions.iter().find(|p| p.name == name)?;
+        Some(self.bytes_in(partition))
+    }
+
+    /// Like `try_get_page`, but fails unless the frame belongs to the named partition.
+    pub fn get_page_in(
+        &mut self,
+        partition: &str,
+        frame_idx: K,
+    ) -> Result<&framepool::PageFrame<T>, BufferPoolErrors> {
+        let owner = self
+            .partitions
+            .iter()
+            .find(|p| p.name == partition)
+            .ok_or_else(|| {
+                BufferPoolErrors::InvalidPartition(format!("{}: no such partition", partition))
+            })?;
+        if !owner.holds(&frame_idx) {
+            return Err(BufferPoolErrors::InvalidPartition(format!(
+                "{}: frame is outside {:?}",
+                partition, owner.frames
+            )));
+        }
+        self.try_get_page(frame_idx)
+    }
+
+    fn resident_in(&self, partition: &Partition) -> usize {
+        self.frame2buf
+            .keys()
+            .filter(|f| partition.holds(*f))
+            .count()
+    }
+
+    fn bytes_in(&self, partition: &Partition) -> u64 {
+        self.frame2buf
+            .iter()
+            .filter(|(f, _)| partition.holds(*f))
+            .filter_map(|(_, buf_idx)| self.pages[*buf_idx as usize].as_ref())
+            .map(|page| page.byte_size())
+            .sum()
+    }
+
+    // Whether the partition has a free slot and bytes to spare under its quotas.
+    fn has_room(&self, partition: &Partition) -> bool {
+        self.resident_in(partition) < partition.max_slots
+            && partition
+                .max_bytes
+                .is_none_or(|max_bytes| self.bytes_in(partition) < max_bytes)
+    }
+
+    // Whether the frame's partition, if any, has room for it under its quotas.
+    pub(super) fn has_quota_for(&self, frame_idx: &K) -> bool {
+        match self.partitions.iter().find(|p| p.holds(frame_idx)) {
+            Some(partition) => self.has_room(partition),
+            None => true,
+        }
+    }
+
+    // Evicts as needed so that frame_idx can be installed: one page if the pool is full, or
+    // the partition's own pages until it is under its quotas. Victims are never taken from
+    // another partition.
+    pub(super) fn make_room_for(&mut self, frame_idx: &K) -> Result<(), BufferPoolErrors> {
+        self.check_pins();
+        self.apply_deferred_touches();
+        if self.partitions.is_empty() {
+            let full = self.frame2buf.len() == self.size;
+            return if full { self.evict() } else { Ok(()) };
+        }
+
+        let owner = self.partitions.iter().position(|p| p.holds(frame_idx));
+        // Getting under a byte quota can take more than one eviction.
+        loop {
+            let full = self.frame2buf.len() == self.size;
+            let at_quota = owner.is_some_and(|i| !self.has_room(&self.partitions[i]));
+            if !full && !at_quota {
+                return Ok(());
+            }
+            self.evict_for(owner, at_quota)?;
+        }
+    }
+
+    // Evicts one page to make room for a page of the partition at owner, if any.
+    fn evict_for(&mut self, owner: Option<usize>, at_quota: bool) -> Result<(), BufferPoolErrors> {
+        // Candidates are the owning partition's pages, plus unpartitioned pages unless the
+        // partition is at its quota.
+        let partitions = &self.partitions;
+        let eligible = |victim: &K| match partitions.iter().position(|p| p.holds(victim)) {
+            Some(i) => Some(i) == owner,
+            None => !at_quota,
+        };
+        let hidden: Vec<BufferPoolId> = self
+            .buf2frame
+            .iter()
+            .filter(|(_, frame)| !eligible(frame))
+            .map(|(buf, _)| *buf)
+            .collect();
+        if hidden.len() == self.frame2buf.len() {
+            return Err(BufferPoolErrors::NoEvictablePage);
+        }
+
+        // Let the configured evictor choose among the candidates by hiding every other slot.
+        let mut stashed = Vec::with_capacity(hidden.len());
+        for buf_idx in hidden {
+            stashed.push((buf_idx, self.pages[buf_idx as usize].take()));
+        }
+        let victim = (self.evictor)(&self.pages, &self.lru);
+        for (buf_idx, page) in stashed {
+            self.pages[buf_idx as usize] = page;
+        }
+        self.evict_slot(victim?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{bottom_evictor, filled_mem_pool, random_evictor};
+    use crate::framepool::{FramePool, MemPool};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scan_does_not_evict_other_partition() {
+        let mut mem_pool = filled_mem_pool(40, |i| i);
+        let mut bp = BufferPool::<u64>::new(6, &mut mem_pool, bottom_evictor);
+        bp.add_partition("a", 0..20, 3).unwrap();
+        bp.add_partition("b", 20..40, 3).unwrap();
+
+        for i in 20..23 {
+            bp.get_page_in("b", i).unwrap();
+        }
+        // Tenant a scans its whole range
+        for i in 0..20 {
+            assert_eq!(bp.get_page_in("a", i).unwrap().data(), i);
+        }
+
+        assert_eq!(bp.partition_usage("a"), Some(3));
+        assert_eq!(bp.partition_usage("b"), Some(3));
+        for i in 20..23 {
+            assert!(bp.frame2buf.contains_key(&i));
+        }
+    }
+
+    #[test]
+    fn test_partition_quota_with_random_evictor() {
+        let mut mem_pool = filled_mem_pool(20, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, random_evictor);
+        bp.add_partition("scan", 0..10, 2).unwrap();
+
+        for i in 10..12 {
+            bp.get_page(i).unwrap();
+        }
+        for i in 0..10 {
+            bp.get_page(i).unwrap();
+            assert!(bp.partition_usage("scan").unwrap() <= 2);
+        }
+        assert!(bp.frame2buf.contains_key(&10));
+        assert!(bp.frame2buf.contains_key(&11));
+    }
+
+    #[test]
+    fn test_unpartitioned_loads_spare_partitions() {
+        let mut mem_pool = filled_mem_pool(20, |i| i);
+        let mut bp = BufferPool::<u64>::new(3, &mut mem_pool, bottom_evictor);
+        bp.add_partition("hot", 0..2, 2).unwrap();
+
+        bp.get_page(0).unwrap();
+        bp.get_page(1).unwrap();
+        for i in 2..20 {
+            bp.get_page(i).unwrap();
+        }
+        assert!(bp.frame2buf.contains_key(&0));
+        assert!(bp.frame2buf.contains_key(&1));
+
+        // get_many respects the same rule
+        let values = bp.get_many(&[5, 6, 7]);
+        assert_eq!(values.len(), 3);
+        assert!(values.iter().all(|v| v.is_some()));
+        assert!(bp.frame2buf.contains_key(&0));
+        assert!(bp.frame2buf.contains_key(&1));
+    }
+
+    #[test]
+    fn test_add_partition_validation() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+
+        bp.add_partition("a", 0..5, 2).unwrap();
+        assert!(bp.add_partition("a", 5..10, 1).is_err());
+        assert!(bp.add_partition("b", 4..8, 1).is_err());
+        assert!(bp.add_partition("b", 5..10, 3).is_err());
+        assert!(bp.add_partition("b", 5..10, 0).is_err());
+        assert!(bp.add_partition("b", 5..5, 1).is_err());
+        bp.add_partition("b", 5..10, 2).unwrap();
+        assert_eq!(bp.partitions().len(), 2);
+
+        assert!(matches!(
+            bp.get_page_in("a", 7),
+            Err(BufferPoolErrors::InvalidPartition(_))
+        ));
+        assert!(matches!(
+            bp.get_page_in("c", 0),
+            Err(BufferPoolErrors::InvalidPartition(_))
+        ));
+
+        assert!(bp.remove_partition("a"));
+        assert!(!bp.remove_partition("a"));
+        assert_eq!(bp.partition_usage("a"), None);
+    }
+
+    #[test]
+    fn test_full_quota_partitions_reject_unpartitioned() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(2, &mut mem_pool, bottom_evictor);
+        bp.add_partition("a", 0..5, 2).unwrap();
+
+        bp.get_page(0).unwrap();
+        bp.get_page(1).unwrap();
+        assert!(matches!(
+            bp.try_get_page(7),
+            Err(BufferPoolErrors::NoEvictablePage)
+        ));
+        assert_eq!(bp.get_page(2).unwrap().data(), 2);
+    }
+
+    #[test]
+    fn test_partition_byte_quota() {
+        let mut mem_pool = MemPool::<Vec<u8>>::new();
+        mem_pool.resize(20).unwrap();
+        for i in 0..20 {
+            mem_pool.put_frame(i, Arc::new(vec![0; 4])).unwrap();
+        }
+        let mut bp = BufferPool::<Vec<u8>>::new(8, &mut mem_pool, bottom_evictor);
+        bp.set_weigher(|data: &Vec<u8>| data.len() as u64);
+        bp.add_partition("a", 0..10, 6).unwrap();
+        assert!(bp.set_partition_max_bytes("a", Some(0)).is_err());
+        assert!(bp.set_partition_max_bytes("b", Some(10)).is_err());
+        bp.set_partition_max_bytes("a", Some(10)).unwrap();
+
+        bp.get_page(15).unwrap();
+        for i in 0..10 {
+            bp.get_page(i).unwrap();
+            assert!(bp.partition_bytes("a").unwrap() <= 12);
+        }
+        // Loaded while under ten bytes, so the last page takes it to twelve
+        assert_eq!(bp.partition_usage("a"), Some(3));
+        assert_eq!(bp.partition_bytes("a"), Some(12));
+        assert!(bp.frame2buf.contains_key(&15));
+
+        // A page that grows counts against the quota when the partition next loads
+        bp.put_page(9, vec![0; 20]).unwrap();
+        bp.get_page(0).unwrap();
+        assert_eq!(bp.partition_usage("a"), Some(1));
+
+        bp.set_partition_max_bytes("a", None).unwrap();
+        for i in 1..6 {
+            bp.get_page(i).unwrap();
+        }
+        assert_eq!(bp.partition_usage("a"), Some(6));
+    }
+
+    #[test]
+    fn test_warm_respects_quota() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(4, &mut mem_pool, bottom_evictor);
+        bp.add_partition("a", 0..5, 1).unwrap();
+
+        assert_eq!(bp.warm_sequential(10), 4);
+        assert_eq!(bp.partition_usage("a"), Some(1));
+        assert_eq!(bp.hot_frames(), vec![0, 5, 6, 7]);
+    }
+}
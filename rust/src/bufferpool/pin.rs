@@ -0,0 +1,272 @@
+use std::ops::Deref;
+use std::time::Duration;
+
+use super::{BufferPool, BufferPoolErrors, FrameKey, FramePoolId, framepool};
+use crate::framepool::PinInfo;
+
+/// Callback for `BufferPool::set_pin_warning`, given the pinned frame and the pin's details.
+pub type PinWarningFn<K = FramePoolId> = Box<dyn Fn(K, &PinInfo) + Send + Sync>;
+
+/// A pinned page, returned by `BufferPool::pin_many`. The page cannot be evicted while the
+/// guard is alive; dropping the guard unpins it.
+pub struct PinGuard<'b, T> {
+    frame_idx: FramePoolId,
+    page: &'b framepool::PageFrame<T>,
+}
+
+impl<'b, T> PinGuard<'b, T> {
+    pub fn frame_idx(&self) -> FramePoolId {
+        self.frame_idx
+    }
+}
+
+impl<'b, T> Deref for PinGuard<'b, T> {
+    type Target = framepool::PageFrame<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.page
+    }
+}
+
+impl<'b, T> Drop for PinGuard<'b, T> {
+    fn drop(&mut self) {
+        self.page.unpin();
+    }
+}
+
+impl<'a, T> BufferPool<'a, T>
+where
+    T: Clone,
+{
+    /// Loads and pins every requested frame, or none of them.
+    ///
+    /// Frames are pinned in ascending frame order, whatever order they were requested in, so
+    /// callers pinning overlapping sets always acquire pins in the same order. Before anything is
+    /// loaded the request is checked against the slots not already pinned by someone else; if a
+    /// frame still fails to load, every pin taken so far is released and the error is returned.
+    /// Returns one guard per distinct frame, in ascending frame order.
+    #[track_caller]
+    pub fn pin_many(
+        &mut self,
+        frame_idxs: &[FramePoolId],
+    ) -> Result<Vec<PinGuard<'_, T>>, BufferPoolErrors> {
+        let mut wanted = frame_idxs.to_vec();
+        wanted.sort_unstable();
+        wanted.dedup();
+
+        let pinned_elsewhere = self
+            .buf2frame
+            .iter()
+            .filter(|(buf_idx, frame_idx)| {
+                wanted.binary_search(frame_idx).is_err()
+                    && self.pages[**buf_idx as usize]
+                        .as_ref()
+                        .is_some_and(|page| page.is_pinned())
+            })
+            .count();
+        let available = self.size - pinned_elsewhere;
+        if wanted.len() > available {
+            return Err(BufferPoolErrors::InsufficientCapacity {
+                requested: wanted.len(),
+                available,
+            });
+        }
+
+        for (pinned, &frame_idx) in wanted.iter().enumerate() {
+            match self.try_get_page(frame_idx) {
+                Ok(page) => page.pin(),
+                Err(e) => {
+                    for &undo_idx in wanted[..pinned].iter() {
+                        let buf_idx = self.frame2buf[&undo_idx];
+                        if let Some(page) = &self.pages[buf_idx as usize] {
+                            page.unpin();
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(wanted
+            .into_iter()
+            .map(|frame_idx| PinGuard {
+                frame_idx,
+                page: self.pages[self.frame2buf[&frame_idx] as usize]
+                    .as_ref()
+                    .expect("pinned page was evicted"),
+            })
+            .collect())
+    }
+}
+
+impl<'a, T, K> BufferPool<'a, T, K>
+where
+    T: Clone,
+    K: FrameKey,
+{
+    /// Every outstanding pin on a cached page, longest-held first.
+    pub fn pin_report(&self) -> Vec<(K, PinInfo)> {
+        let mut report: Vec<(K, PinInfo)> = self
+            .buf2frame
+            .iter()
+            .filter_map(|(buf_idx, frame_idx)| {
+                let page = self.pages[*buf_idx as usize].as_ref()?;
+                Some(
+                    page.pins()
+                        .into_iter()
+                        .map(move |pin| (frame_idx.clone(), pin)),
+                )
+            })
+            .flatten()
+            .collect();
+        report.sort_by_key(|(_, pin)| pin.since);
+        report
+    }
+
+    /// Calls `hook` once for every pin found to have been held longer than `max`. Pins are
+    /// checked whenever the pool has to load a page, which is when a leaked pin starts to cost
+    /// evictions.
+    pub fn set_pin_warning<F>(&mut self, max: Duration, hook: F)
+    where
+        F: Fn(K, &PinInfo) + Send + Sync + 'static,
+    {
+        self.pin_warning = Some((max, Box::new(hook)));
+    }
+
+    pub fn clear_pin_warning(&mut self) {
+        self.pin_warning = None;
+    }
+
+    pub(super) fn check_pins(&self) {
+        let Some((max, hook)) = &self.pin_warning else {
+            return;
+        };
+        for (buf_idx, frame_idx) in self.buf2frame.iter() {
+            if let Some(page) = &self.pages[*buf_idx as usize] {
+                for pin in page.take_overdue_pins(*max) {
+                    hook(frame_idx.clone(), &pin);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{bottom_evictor, filled_mem_pool, random_evictor};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pin_many() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(3, &mut mem_pool, random_evictor);
+
+        let guards = bp.pin_many(&[7, 2, 7, 5]).unwrap();
+        let pinned: Vec<FramePoolId> = guards.iter().map(|g| g.frame_idx()).collect();
+        assert_eq!(pinned, vec![2, 5, 7]);
+        for guard in guards.iter() {
+            assert!(guard.is_pinned());
+            assert_eq!(guard.data(), guard.frame_idx());
+        }
+        drop(guards);
+
+        for i in [2, 5, 7] {
+            assert!(!bp.get_page(i).unwrap().is_pinned());
+        }
+    }
+
+    #[test]
+    fn test_pin_many_insufficient_capacity() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(3, &mut mem_pool, bottom_evictor);
+        bp.get_page(0).unwrap().pin();
+
+        match bp.pin_many(&[1, 2, 3]) {
+            Err(BufferPoolErrors::InsufficientCapacity {
+                requested,
+                available,
+            }) => {
+                assert_eq!(requested, 3);
+                assert_eq!(available, 2);
+            }
+            _ => panic!("Expected InsufficientCapacity error"),
+        }
+        // Nothing was loaded
+        assert_eq!(bp.frame2buf.len(), 1);
+
+        // A frame the caller already pinned doesn't count against them
+        assert_eq!(bp.pin_many(&[0, 1, 2]).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_pin_many_releases_on_failure() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(3, &mut mem_pool, bottom_evictor);
+
+        assert!(bp.pin_many(&[1, 2, 50]).is_err());
+        for i in [1, 2] {
+            assert!(!bp.get_page(i).unwrap().is_pinned());
+        }
+    }
+
+    #[test]
+    fn test_pin_report() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(3, &mut mem_pool, bottom_evictor);
+        bp.get_page(4).unwrap().pin_with_label("long-lived");
+        std::thread::sleep(Duration::from_millis(5));
+        let guards = bp.pin_many(&[1]).unwrap();
+        let line = line!() - 1;
+        drop(guards);
+        bp.get_page(1).unwrap().pin();
+
+        let report = bp.pin_report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].0, 4);
+        assert_eq!(report[0].1.label.as_deref(), Some("long-lived"));
+        assert!(report[0].1.held_for() >= report[1].1.held_for());
+        assert_eq!(report[1].0, 1);
+        assert_ne!(report[1].1.site.line(), line);
+        assert_eq!(report[1].1.site.file(), file!());
+    }
+
+    #[test]
+    fn test_pin_many_records_caller() {
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(3, &mut mem_pool, bottom_evictor);
+        let guards = bp.pin_many(&[1]).unwrap();
+        let line = line!() - 1;
+        let pins = guards[0].pins();
+        assert_eq!(pins[0].site.file(), file!());
+        assert_eq!(pins[0].site.line(), line);
+    }
+
+    #[test]
+    fn test_pin_warning() {
+        use std::sync::Mutex;
+
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let mut mem_pool = filled_mem_pool(10, |i| i);
+        let mut bp = BufferPool::<u64>::new(2, &mut mem_pool, bottom_evictor);
+        let sink = Arc::clone(&warnings);
+        bp.set_pin_warning(Duration::from_millis(5), move |frame_idx, pin| {
+            sink.lock().unwrap().push((frame_idx, pin.label.clone()));
+        });
+
+        bp.get_page(0).unwrap().pin_with_label("leak");
+        bp.get_page(1).unwrap();
+        bp.get_page(2).unwrap();
+        assert!(warnings.lock().unwrap().is_empty());
+
+        std::thread::sleep(Duration::from_millis(10));
+        bp.get_page(3).unwrap();
+        bp.get_page(4).unwrap();
+        // Reported once, on the first load after the pin went overdue
+        assert_eq!(
+            *warnings.lock().unwrap(),
+            vec![(0, Some("leak".to_string()))]
+        );
+        bp.clear_pin_warning();
+    }
+}
@@ -0,0 +1,413 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use super::{BufferPoolErrors, EvictorFn, FramePoolId, SharedBufferPool};
+use crate::framepool::{FrameMeta, FramePool, FramePoolError, FrameState};
+
+/// Occupancy and hit counts for a `ShardedBufferPool` or one of its shards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShardStats {
+    pub capacity: usize,
+    pub cached: usize,
+    pub dirty: usize,
+    pub pinned: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// A buffer pool split into independent shards, each with its own slots, LRU and lock, so
+/// threads working on frames in different shards never wait for each other.
+///
+/// Frames are assigned to shards by a hash of their id, and every shard gets an equal share of
+/// the slots. The shards share the frame pool, which is locked only while a shard reads or
+/// writes a frame; cache hits touch nothing but their own shard. Handles are cheap to clone.
+pub struct ShardedBufferPool<T>
+where
+    T: Clone,
+{
+    shards: Arc<Vec<Shard<T>>>,
+}
+
+struct Shard<T>
+where
+    T: Clone,
+{
+    pool: SharedBufferPool<T>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+// A FramePool handle that the shards share, locking the real pool for each call.
+struct SharedFrames<T>
+where
+    T: Clone,
+{
+    inner: Arc<Mutex<Box<dyn FramePool<T> + Send>>>,
+}
+
+impl<T> SharedFrames<T>
+where
+    T: Clone,
+{
+    // The real pool, even if a shard panicked using it: a panic inside a frame pool call costs
+    // that call, not every shard after it.
+    fn lock(&self) -> MutexGuard<'_, Box<dyn FramePool<T> + Send>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// Batches and discards are forwarded too, so that shards flush through the pool's own batching.
+impl<T> FramePool<T> for SharedFrames<T>
+where
+    T: Clone,
+{
+    fn get_frame_ref(&mut self, idx: FramePoolId) -> Result<Arc<T>, FramePoolError> {
+        self.lock().get_frame_ref(idx)
+    }
+    fn put_frame(&mut self, idx: FramePoolId, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.lock().put_frame(idx, data)
+    }
+    fn get_frames(&mut self, idxs: &[FramePoolId]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        self.lock().get_frames(idxs)
+    }
+    fn put_frames(
+        &mut self,
+        frames: Vec<(FramePoolId, Arc<T>)>,
+    ) -> Vec<Result<(), FramePoolError>> {
+        self.lock().put_frames(frames)
+    }
+    fn discard_frame(&mut self, idx: &FramePoolId) -> Result<(), FramePoolError> {
+        self.lock().discard_frame(idx)
+    }
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.lock().resize(count)
+    }
+    fn grow_to(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.lock().grow_to(count)
+    }
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.lock().truncate(count)
+    }
+    fn frame_state(&self, idx: &FramePoolId) -> FrameState {
+        self.lock().frame_state(idx)
+    }
+    fn frame_ids(&self) -> Result<Vec<FramePoolId>, FramePoolError> {
+        self.lock().frame_ids()
+    }
+    fn frame_meta(&self, idx: &FramePoolId) -> Result<FrameMeta, FramePoolError> {
+        self.lock().frame_meta(idx)
+    }
+    fn size(&self) -> u64 {
+        self.lock().size()
+    }
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.lock().assess_size()
+    }
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.lock().sync()
+    }
+    fn is_read_only(&self) -> bool {
+        self.lock().is_read_only()
+    }
+}
+
+impl<T> Clone for ShardedBufferPool<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        ShardedBufferPool {
+            shards: Arc::clone(&self.shards),
+        }
+    }
+}
+
+impl<T> ShardedBufferPool<T>
+where
+    T: Clone + 'static,
+{
+    /// Creates a pool of `size` slots spread over `shard_count` shards, over `frame_pool`,
+    /// which it takes ownership of.
+    ///
+    /// # Panics
+    /// Panics if `shard_count` is zero or larger than `size`.
+    pub fn new<P>(size: usize, shard_count: usize, frame_pool: P, evictor: EvictorFn<T>) -> Self
+    where
+        P: FramePool<T> + Send + 'static,
+    {
+        assert!(
+            shard_count > 0 && shard_count <= size,
+            "shard count must be between 1 and the pool size"
+        );
+        let frames: Arc<Mutex<Box<dyn FramePool<T> + Send>>> =
+            Arc::new(Mutex::new(Box::new(frame_pool)));
+        let shards = (0..shard_count)
+            .map(|i| {
+                // Spread the remainder over the first shards
+                let slots = size / shard_count + usize::from(i < size % shard_count);
+                let frames = SharedFrames {
+                    inner: Arc::clone(&frames),
+                };
+                Shard {
+                    pool: SharedBufferPool::new(slots, frames, evictor),
+                    hits: AtomicU64::new(0),
+                    misses: AtomicU64::new(0),
+                }
+            })
+            .collect();
+        ShardedBufferPool {
+            shards: Arc::new(shards),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The shard that caches the given frame.
+    pub fn shard_for(&self, frame_idx: FramePoolId) -> usize {
+        // Fibonacci hashing, so that strided access still spreads over every shard
+        let hash = frame_idx.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32;
+        (hash % self.shards.len() as u64) as usize
+    }
+
+    /// Returns a shared handle to the data at the given index, loading it if necessary.
+    pub fn get(&self, frame_idx: FramePoolId) -> Option<Arc<T>> {
+        let shard = &self.shards[self.shard_for(frame_idx)];
+        if let Some(data) = shard.pool.get_cached(frame_idx) {
+            shard.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(data);
+        }
+        shard.pool.with(|pool| {
+            let counter = if pool.frame2buf.contains_key(&frame_idx) {
+                &shard.hits
+            } else {
+                &shard.misses
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+            pool.get_page_arc(frame_idx)
+        })
+    }
+
+    /// Writes data to the page at the given index.
+    pub fn put(&self, frame_idx: FramePoolId, data: T) -> Result<(), BufferPoolErrors> {
+        self.shards[self.shard_for(frame_idx)]
+            .pool
+            .put(frame_idx, data)
+    }
+
+    /// Modifies the page at the given index in place, marking it dirty.
+    pub fn modify<F, R>(&self, frame_idx: FramePoolId, f: F) -> Result<R, BufferPoolErrors>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.shards[self.shard_for(frame_idx)]
+            .pool
+            .modify(frame_idx, f)
+    }
+
+    /// A rayon parallel iterator over `(frame id, data)` for every frame in the frame pool.
+    /// Rayon splits the frame ids into ranges across its threads, and each thread loads its
+    /// frames itself, through the shards' locks, so loading and processing both run in
+    /// parallel. Frames that can't be loaded are skipped.
+    #[cfg(feature = "rayon")]
+    pub fn par_iter(&self) -> impl rayon::iter::ParallelIterator<Item = (FramePoolId, Arc<T>)> + '_
+    where
+        T: Send + Sync,
+    {
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let size = self.shards[0].pool.with(|pool| pool.frame_pool.size());
+        (0..size)
+            .into_par_iter()
+            .filter_map(move |frame_idx| self.get(frame_idx).map(|data| (frame_idx, data)))
+    }
+
+    /// Flushes the dirty pages of every shard, one shard at a time.
+    pub fn flush(&self) -> Result<(), FramePoolError> {
+        for shard in self.shards.iter() {
+            shard.pool.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Statistics for each shard, in shard order.
+    pub fn shard_stats(&self) -> Vec<ShardStats> {
+        self.shards
+            .iter()
+            .map(|shard| {
+                shard.pool.with(|pool| {
+                    let pages: Vec<_> = pool.pages.iter().flatten().collect();
+                    ShardStats {
+                        capacity: pool.size,
+                        cached: pages.len(),
+                        dirty: pages.iter().filter(|p| p.is_dirty()).count(),
+                        pinned: pages.iter().filter(|p| p.is_pinned()).count(),
+                        hits: shard.hits.load(Ordering::Relaxed),
+                        misses: shard.misses.load(Ordering::Relaxed),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Statistics summed over every shard.
+    pub fn stats(&self) -> ShardStats {
+        self.shard_stats()
+            .into_iter()
+            .fold(ShardStats::default(), |total, s| ShardStats {
+                capacity: total.capacity + s.capacity,
+                cached: total.cached + s.cached,
+                dirty: total.dirty + s.dirty,
+                pinned: total.pinned + s.pinned,
+                hits: total.hits + s.hits,
+                misses: total.misses + s.misses,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{bottom_evictor, filled_mem_pool};
+    use crate::framepool::{InstrumentedPool, MemPool};
+    use std::thread;
+
+    #[test]
+    fn test_sharded_pool_spreads_frames() {
+        let pool = ShardedBufferPool::new(10, 4, filled_mem_pool(100, |i| i), bottom_evictor);
+        assert_eq!(pool.shard_count(), 4);
+
+        let mut per_shard = vec![0; 4];
+        for i in 0..100 {
+            per_shard[pool.shard_for(i)] += 1;
+            assert_eq!(pool.shard_for(i), pool.shard_for(i));
+        }
+        assert!(per_shard.iter().all(|&n| n > 10), "{:?}", per_shard);
+
+        let capacities: Vec<usize> = pool.shard_stats().iter().map(|s| s.capacity).collect();
+        assert_eq!(capacities, vec![3, 3, 2, 2]);
+    }
+
+    #[test]
+    fn test_sharded_pool_across_threads() {
+        let pool = ShardedBufferPool::new(8, 4, filled_mem_pool(32, |i| i), bottom_evictor);
+
+        let workers: Vec<_> = (0..4)
+            .map(|t| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    for _ in 0..25 {
+                        for i in 0..32 {
+                            pool.modify(i, |v| *v += 1).unwrap();
+                            assert!(pool.get((i * 7 + t) % 32).is_some());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        pool.flush().unwrap();
+        for i in 0..32 {
+            assert_eq!(*pool.get(i).unwrap(), i + 100);
+        }
+        let stats = pool.stats();
+        assert_eq!(stats.capacity, 8);
+        assert_eq!(stats.dirty, 0);
+        assert_eq!(stats.hits + stats.misses, 4 * 25 * 32 + 32);
+    }
+
+    #[test]
+    fn test_sharded_pool_stats() {
+        let pool = ShardedBufferPool::new(4, 2, filled_mem_pool(10, |i| i), bottom_evictor);
+        pool.get(3);
+        pool.get(3);
+        pool.put(5, 50).unwrap();
+
+        let stats = pool.stats();
+        assert_eq!(stats.cached, 2);
+        assert_eq!(stats.dirty, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+
+        let shard = pool.shard_for(3);
+        assert_eq!(pool.shard_stats()[shard].hits, 1);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sharded_pool_par_iter() {
+        use rayon::iter::ParallelIterator;
+
+        let pool = ShardedBufferPool::new(8, 4, filled_mem_pool(200, |i| i), bottom_evictor);
+        let matching = pool.par_iter().filter(|(idx, data)| *idx == **data).count();
+        assert_eq!(matching, 200);
+
+        let total: u64 = pool.par_iter().map(|(_, data)| *data).sum();
+        assert_eq!(total, (0..200).sum::<u64>());
+        let stats = pool.stats();
+        assert_eq!(stats.hits + stats.misses, 400);
+        assert!(stats.cached <= 8);
+    }
+
+    #[test]
+    fn test_sharded_pool_flushes_in_batches() {
+        let frames = InstrumentedPool::new(filled_mem_pool(20, |i| i));
+        let metrics = frames.metrics();
+        let pool = ShardedBufferPool::new(20, 2, frames, bottom_evictor);
+        for i in 0..8 {
+            pool.put(i, i * 10).unwrap();
+        }
+        pool.flush().unwrap();
+
+        let writes = metrics.snapshot().writes;
+        assert_eq!(writes.count, 8);
+        // one put_frames per shard
+        assert_eq!(writes.latency.samples, 2);
+    }
+
+    // Panics reading one frame, poisoning the lock the shards share.
+    struct PanickingPool {
+        inner: MemPool<u64>,
+        panic_on: FramePoolId,
+    }
+
+    impl FramePool<u64> for PanickingPool {
+        fn get_frame_ref(&mut self, idx: FramePoolId) -> Result<Arc<u64>, FramePoolError> {
+            assert_ne!(idx, self.panic_on, "frame pool panicked");
+            self.inner.get_frame_ref(idx)
+        }
+        fn put_frame(&mut self, idx: FramePoolId, data: Arc<u64>) -> Result<(), FramePoolError> {
+            self.inner.put_frame(idx, data)
+        }
+        fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+            self.inner.resize(count)
+        }
+        fn size(&self) -> u64 {
+            FramePool::<u64>::size(&self.inner)
+        }
+        fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+            self.inner.assess_size()
+        }
+    }
+
+    #[test]
+    fn test_sharded_pool_survives_panicking_frame_pool() {
+        let frames = PanickingPool {
+            inner: filled_mem_pool(10, |i| i),
+            panic_on: 3,
+        };
+        let pool = ShardedBufferPool::new(4, 2, frames, bottom_evictor);
+        let reader = pool.clone();
+        assert!(thread::spawn(move || reader.get(3)).join().is_err());
+
+        for i in (0..10).filter(|&i| i != 3) {
+            assert_eq!(*pool.get(i).unwrap(), i);
+        }
+        pool.put(4, 40).unwrap();
+        pool.flush().unwrap();
+    }
+}
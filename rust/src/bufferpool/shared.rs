@@ -0,0 +1,236 @@
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use super::{BufferPool, BufferPoolErrors, Checkpoint, EvictorFn, FramePoolId, PoolState};
+use crate::framepool::{FramePool, FramePoolError};
+
+/// A BufferPool that owns its frame pool and can be shared between threads.
+///
+/// Handles are cheap to clone and every method takes `&self`. Reads of cached pages share an
+/// internal read lock and run concurrently (see `BufferPool::get_cached`); everything else,
+/// including loading a page that isn't cached, takes the lock exclusively for the duration of
+/// the call.
+pub struct SharedBufferPool<T>
+where
+    T: Clone,
+{
+    inner: Arc<RwLock<SharedState<T>>>,
+}
+
+struct SharedState<T>
+where
+    T: Clone,
+{
+    // only ever locked through get_mut, under the write lock; the Mutex just makes it Sync
+    frame_pool: Mutex<Box<dyn FramePool<T> + Send>>,
+    // None only while a call is running
+    cache: Option<PoolState<T>>,
+}
+
+impl<T> Clone for SharedBufferPool<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        SharedBufferPool {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> SharedBufferPool<T>
+where
+    T: Clone,
+{
+    /// Creates a shared pool of `size` slots over `frame_pool`, which it takes ownership of.
+    pub fn new<P>(size: usize, mut frame_pool: P, evictor: EvictorFn<T>) -> Self
+    where
+        P: FramePool<T> + Send + 'static,
+    {
+        let cache = BufferPool::new(size, &mut frame_pool, evictor).into_state();
+        SharedBufferPool {
+            inner: Arc::new(RwLock::new(SharedState {
+                frame_pool: Mutex::new(Box::new(frame_pool)),
+                cache: Some(cache),
+            })),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the underlying BufferPool, for operations without a
+    /// `&self` counterpart here. Other threads wait until `f` returns.
+    ///
+    /// If `f` panics, the pool is kept as `f` left it and the panic carries on; other handles
+    /// go on using the pool.
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut BufferPool<'_, T>) -> R,
+    {
+        // A panic in f leaves the state whole, below, so a lock it poisoned is taken anyway
+        let mut guard = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+        let state = &mut *guard;
+        let cache = state
+            .cache
+            .take()
+            .expect("shared buffer pool cache is only taken out during a call");
+        let frame_pool = state
+            .frame_pool
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut pool = BufferPool::from_state(cache, frame_pool.as_mut());
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(&mut pool)));
+        state.cache = Some(pool.into_state());
+        match result {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Returns a shared handle to the data at the given index, loading it if necessary.
+    pub fn get(&self, frame_idx: FramePoolId) -> Option<Arc<T>> {
+        self.get_cached(frame_idx)
+            .or_else(|| self.with(|pool| pool.get_page_arc(frame_idx)))
+    }
+
+    /// Returns a shared handle to the data at the given index if it is cached, under the
+    /// shared read lock.
+    pub fn get_cached(&self, frame_idx: FramePoolId) -> Option<Arc<T>> {
+        let guard = self.inner.read().unwrap_or_else(PoisonError::into_inner);
+        guard.cache.as_ref()?.get_cached(&frame_idx)
+    }
+
+    /// Writes data to the page at the given index.
+    pub fn put(&self, frame_idx: FramePoolId, data: T) -> Result<(), BufferPoolErrors> {
+        self.with(|pool| pool.put_page(frame_idx, data))
+    }
+
+    /// Modifies the page at the given index in place, marking it dirty.
+    pub fn modify<F, R>(&self, frame_idx: FramePoolId, f: F) -> Result<R, BufferPoolErrors>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.with(|pool| pool.modify_page(frame_idx, f))
+    }
+
+    /// Flushes all dirty pages back to the frame pool.
+    pub fn flush(&self) -> Result<(), FramePoolError> {
+        self.with(|pool| pool.flush_all())
+    }
+
+    /// Flushes all dirty pages and syncs the frame pool; see `BufferPool::checkpoint`.
+    pub fn checkpoint(&self) -> Result<Checkpoint, BufferPoolErrors> {
+        self.with(|pool| pool.checkpoint())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::bottom_evictor;
+    use crate::framepool::MemPool;
+    use std::thread;
+
+    fn assert_send_sync<S: Send + Sync>() {}
+
+    #[test]
+    fn test_shared_pool_is_send_sync() {
+        assert_send_sync::<SharedBufferPool<u64>>();
+        assert_send_sync::<SharedBufferPool<Vec<String>>>();
+    }
+
+    #[test]
+    fn test_shared_pool_survives_panicking_call() {
+        let mut mem_pool = MemPool::<u64>::new();
+        mem_pool.resize(4).unwrap();
+        for i in 0..4 {
+            mem_pool.put_frame(i, Arc::new(i)).unwrap();
+        }
+        let pool = SharedBufferPool::new(2, mem_pool, bottom_evictor);
+        pool.put(0, 10).unwrap();
+
+        let other = pool.clone();
+        let panicked = thread::spawn(move || other.with(|_| panic!("call failed"))).join();
+        assert!(panicked.is_err());
+        assert_eq!(*pool.get_cached(0).unwrap(), 10);
+        assert_eq!(*pool.get(3).unwrap(), 3);
+        pool.flush().unwrap();
+    }
+
+    #[test]
+    fn test_shared_pool_across_threads() {
+        let mut mem_pool = MemPool::<u64>::new();
+        mem_pool.resize(16).unwrap();
+        for i in 0..16 {
+            mem_pool.put_frame(i, Arc::new(0)).unwrap();
+        }
+        let pool = SharedBufferPool::new(4, mem_pool, bottom_evictor);
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    for round in 0..50 {
+                        for i in 0..16 {
+                            pool.modify(i, |v| *v += 1).unwrap();
+                            assert!(pool.get((i + round) % 16).is_some());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        pool.flush().unwrap();
+        for i in 0..16 {
+            assert_eq!(*pool.get(i).unwrap(), 200);
+        }
+        assert!(pool.with(|bp| bp.validate().is_valid()));
+    }
+
+    #[test]
+    fn test_shared_pool_put_and_checkpoint() {
+        let mut mem_pool = MemPool::<String>::new();
+        mem_pool.resize(3).unwrap();
+        for i in 0..3 {
+            mem_pool.put_frame(i, Arc::new(String::new())).unwrap();
+        }
+        let pool = SharedBufferPool::new(1, mem_pool, bottom_evictor);
+        let other = pool.clone();
+
+        pool.put(0, "zero".to_string()).unwrap();
+        other.put(2, "two".to_string()).unwrap();
+        assert_eq!(*pool.get(0).unwrap(), "zero");
+
+        let checkpoint = other.checkpoint().unwrap();
+        assert_eq!(checkpoint.sequence, 1);
+        assert_eq!(pool.with(|bp| bp.hot_frames()), vec![0]);
+    }
+
+    #[test]
+    fn test_shared_pool_get_cached() {
+        let mut mem_pool = MemPool::<u64>::new();
+        mem_pool.resize(4).unwrap();
+        for i in 0..4 {
+            mem_pool.put_frame(i, Arc::new(i)).unwrap();
+        }
+        let pool = SharedBufferPool::new(2, mem_pool, bottom_evictor);
+        assert!(pool.get_cached(1).is_none());
+        assert_eq!(*pool.get(1).unwrap(), 1);
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        assert_eq!(*pool.get_cached(1).unwrap(), 1);
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert!(pool.with(|bp| bp.validate().is_valid()));
+    }
+}
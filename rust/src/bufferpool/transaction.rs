@@ -0,0 +1,299 @@
+use std::sync::Arc;
+
+use super::overlay::Overlay;
+use super::{BufferPool, BufferPoolErrors, FramePoolId};
+use crate::framepool::{FramePoolError, FrameState};
+
+/// A set of page modifications staged against a BufferPool, created by `BufferPool::begin`.
+///
+/// Staged pages are copy-on-write copies of the cached data, so nothing is visible through
+/// the pool until `commit`. Dropping the transaction without committing discards it.
+pub struct Transaction<'b, 'a, T>
+where
+    T: Clone,
+{
+    buffer_pool: &'b mut BufferPool<'a, T>,
+    // staged page contents, in frame order so commits write in a canonical order
+    staged: Overlay<T>,
+}
+
+// A frame a commit writes, and what it held before: its data, or None if it held none.
+type Undo<T> = (FramePoolId, Option<Arc<T>>);
+
+impl<'b, 'a, T> Transaction<'b, 'a, T>
+where
+    T: Clone,
+{
+    pub(super) fn new(buffer_pool: &'b mut BufferPool<'a, T>) -> Self {
+        Transaction {
+            buffer_pool,
+            staged: Overlay::new(),
+        }
+    }
+
+    /// Reads a page as this transaction sees it: the staged copy if there is one, otherwise
+    /// the pool's current data.
+    pub fn read(&mut self, frame_idx: FramePoolId) -> Result<Arc<T>, BufferPoolErrors> {
+        self.staged.read(self.buffer_pool, frame_idx)
+    }
+
+    /// Stages a replacement value for a page.
+    pub fn write(&mut self, frame_idx: FramePoolId, data: T) {
+        self.staged.write(frame_idx, data);
+    }
+
+    /// Stages a modification of a page. The first modification of a page copies its data;
+    /// the pool's copy is left untouched.
+    pub fn modify<F, R>(&mut self, frame_idx: FramePoolId, f: F) -> Result<R, BufferPoolErrors>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.staged.modify(self.buffer_pool, frame_idx, f)
+    }
+
+    /// Frame ids with staged modifications, in ascending order.
+    pub fn staged_frames(&self) -> Vec<FramePoolId> {
+        self.staged.frames()
+    }
+
+    /// Applies every staged page to the backing store, in one batch, and then to the cache.
+    ///
+    /// Fails with `ReadOnly` without writing anything if the pool is read-only, and with
+    /// `CommitFailed` without writing anything if a page to be overwritten can't be read. If
+    /// any write to the backing store fails, the pages already written are restored to their
+    /// prior contents, or emptied again if they held none, the cache is left untouched, and
+    /// `CommitFailed` is returned, listing any page that could not be restored.
+    pub fn commit(self) -> Result<(), BufferPoolErrors> {
+        let pool = self.buffer_pool;
+        pool.check_writable()?;
+        let staged = self.staged.into_pages();
+
+        // Phase 1: keep undo images of what we overwrite, then write to the backing store.
+        let mut undo: Vec<Undo<T>> = Vec::with_capacity(staged.len());
+        for &frame_idx in staged.keys() {
+            let prior = match pool.frame_pool.frame_state(&frame_idx) {
+                FrameState::Populated => match pool.frame_pool.get_frame_ref(frame_idx) {
+                    Ok(data) => Some(data),
+                    Err(FramePoolError::NotFound(_)) => None,
+                    Err(error) => {
+                        return Err(BufferPoolErrors::CommitFailed {
+                            frame: frame_idx,
+                            error,
+                            rollback: Vec::new(),
+                        });
+                    }
+                },
+                FrameState::Empty | FrameState::Absent => None,
+            };
+            undo.push((frame_idx, prior));
+        }
+        let batch = staged
+            .iter()
+            .map(|(&frame_idx, data)| (frame_idx, Arc::clone(data)))
+            .collect();
+        let results = pool.frame_pool.put_frames(batch);
+        let mut written = Vec::new();
+        let mut failed = None;
+        for (undo, result) in undo.into_iter().zip(results) {
+            match result {
+                Ok(()) => written.push(undo),
+                Err(error) => {
+                    failed.get_or_insert((undo.0, error));
+                }
+            }
+        }
+        if let Some((frame, error)) = failed {
+            return Err(BufferPoolErrors::CommitFailed {
+                frame,
+                error,
+                rollback: restore(pool, written),
+            });
+        }
+
+        // Phase 2: the backing store holds the new data, so cached copies become clean.
+        for (frame_idx, data) in staged {
+            if let Some(&buf_idx) = pool.frame2buf.get(&frame_idx)
+                && let Some(page) = &pool.pages[buf_idx as usize]
+            {
+                page.put_arc(data);
+                page.set_dirty(false);
+            }
+        }
+        Ok(())
+    }
+
+    /// Discards every staged modification.
+    pub fn rollback(self) {}
+}
+
+// Puts back what a failed commit overwrote, returning the frames that couldn't be restored.
+fn restore<T: Clone>(
+    pool: &mut BufferPool<'_, T>,
+    written: Vec<Undo<T>>,
+) -> Vec<(FramePoolId, FramePoolError)> {
+    let mut failures = Vec::new();
+    let mut priors = Vec::new();
+    for (frame_idx, prior) in written {
+        match prior {
+            Some(data) => priors.push((frame_idx, data)),
+            None => {
+                if let Err(e) = pool.frame_pool.discard_frame(&frame_idx) {
+                    failures.push((frame_idx, e));
+                }
+            }
+        }
+    }
+    let frame_idxs: Vec<FramePoolId> = priors.iter().map(|(frame_idx, _)| *frame_idx).collect();
+    for (frame_idx, result) in frame_idxs
+        .into_iter()
+        .zip(pool.frame_pool.put_frames(priors))
+    {
+        if let Err(e) = result {
+            failures.push((frame_idx, e));
+        }
+    }
+    failures.sort_by_key(|(frame_idx, _)| *frame_idx);
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{bottom_evictor, filled_mem_pool};
+    use crate::framepool::{FramePool, FramePoolError, MemPool};
+
+    // A MemPool that rejects writes to one frame id, and reads of another.
+    struct FailingFramePool {
+        inner: MemPool<u32>,
+        fail_on: FramePoolId,
+        unreadable: Option<FramePoolId>,
+    }
+
+    impl FramePool<u32> for FailingFramePool {
+        fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<u32>, FramePoolError> {
+            if Some(idx) == self.unreadable {
+                return Err(std::io::Error::other("read rejected").into());
+            }
+            self.inner.get_frame_ref(idx)
+        }
+        fn put_frame(&mut self, idx: u64, data: Arc<u32>) -> Result<(), FramePoolError> {
+            if idx == self.fail_on {
+                return Err(std::io::Error::other("write rejected").into());
+            }
+            self.inner.put_frame(idx, data)
+        }
+        fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+            self.inner.resize(count)
+        }
+        fn size(&self) -> u64 {
+            self.inner.size()
+        }
+        fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+            self.inner.assess_size()
+        }
+        fn frame_state(&self, idx: &u64) -> FrameState {
+            self.inner.frame_state(idx)
+        }
+        fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+            self.inner.discard_frame(idx)
+        }
+    }
+
+    #[test]
+    fn test_transaction_commit() {
+        let mut mem_pool = filled_mem_pool(4, |i| i as u32);
+        let mut bp = BufferPool::new(2, &mut mem_pool, bottom_evictor);
+        bp.get_page(0);
+
+        let mut txn = bp.begin();
+        txn.write(0, 100);
+        txn.modify(3, |v| *v += 30).unwrap();
+        assert_eq!(*txn.read(3).unwrap(), 33);
+        assert_eq!(txn.staged_frames(), vec![0, 3]);
+        txn.commit().unwrap();
+
+        assert_eq!(bp.get_page(0).unwrap().data(), 100);
+        assert!(!bp.get_page(0).unwrap().is_dirty());
+        assert_eq!(bp.get_page(3).unwrap().data(), 33);
+        assert_eq!(*mem_pool.get_frame_ref(0).unwrap(), 100);
+        assert_eq!(*mem_pool.get_frame_ref(3).unwrap(), 33);
+    }
+
+    #[test]
+    fn test_transaction_isolated_until_commit() {
+        let mut mem_pool = filled_mem_pool(2, |i| i as u32);
+        let mut bp = BufferPool::new(2, &mut mem_pool, bottom_evictor);
+
+        let mut txn = bp.begin();
+        txn.modify(1, |v| *v = 50).unwrap();
+        txn.rollback();
+
+        assert_eq!(bp.get_page(1).unwrap().data(), 1);
+        assert!(!bp.get_page(1).unwrap().is_dirty());
+
+        {
+            let mut txn = bp.begin();
+            txn.write(1, 60);
+            // dropped without commit
+        }
+        assert_eq!(bp.get_page(1).unwrap().data(), 1);
+    }
+
+    #[test]
+    fn test_transaction_commit_failure_restores_backing_store() {
+        let mut pool = FailingFramePool {
+            inner: filled_mem_pool(4, |i| i as u32),
+            fail_on: 2,
+            unreadable: None,
+        };
+        // Frame 0 is allocated but holds nothing
+        pool.inner.discard_frame(&0).unwrap();
+        let mut bp = BufferPool::new(4, &mut pool, bottom_evictor);
+        bp.get_page(1);
+
+        let mut txn = bp.begin();
+        txn.write(0, 0);
+        txn.write(1, 10);
+        txn.write(2, 20);
+        txn.write(3, 30);
+        match txn.commit() {
+            Err(e @ BufferPoolErrors::CommitFailed { .. }) => assert_eq!(
+                e.to_string(),
+                "transaction commit failed: frame 2: I/O error: write rejected"
+            ),
+            _ => panic!("Expected CommitFailed error"),
+        }
+
+        // The cache never saw the staged data
+        assert_eq!(bp.get_page(1).unwrap().data(), 1);
+        assert!(!bp.get_page(1).unwrap().is_dirty());
+
+        // The frames written were restored, and frame 0 emptied again
+        assert_eq!(*pool.inner.get_frame_ref(1).unwrap(), 1);
+        assert_eq!(*pool.inner.get_frame_ref(3).unwrap(), 3);
+        assert_eq!(pool.inner.frame_state(&0), FrameState::Empty);
+    }
+
+    #[test]
+    fn test_transaction_commit_refuses_without_undo_image() {
+        let mut pool = FailingFramePool {
+            inner: filled_mem_pool(4, |i| i as u32),
+            fail_on: 99,
+            unreadable: Some(3),
+        };
+        let mut bp = BufferPool::new(4, &mut pool, bottom_evictor);
+
+        let mut txn = bp.begin();
+        txn.write(1, 10);
+        txn.write(3, 30);
+        match txn.commit() {
+            Err(BufferPoolErrors::CommitFailed {
+                frame: 3, rollback, ..
+            }) => assert!(rollback.is_empty()),
+            _ => panic!("Expected CommitFailed error"),
+        }
+        // Nothing was written
+        assert_eq!(*pool.inner.get_frame_ref(1).unwrap(), 1);
+        assert_eq!(*pool.inner.get_frame_ref(3).unwrap(), 3);
+    }
+}
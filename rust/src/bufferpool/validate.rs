@@ -0,0 +1,185 @@
+use std::fmt;
+
+use super::{BufferPool, BufferPoolId};
+
+/// The result of `BufferPool::validate`: every broken invariant found, one message each.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if self.issues.is_empty() {
+            return fmt.write_str("buffer pool is consistent");
+        }
+        writeln!(
+            fmt,
+            "{} buffer pool invariant(s) broken:",
+            self.issues.len()
+        )?;
+        for issue in self.issues.iter() {
+            writeln!(fmt, "  - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T> BufferPool<'a, T>
+where
+    T: Clone,
+{
+    /// Checks the pool's internal bookkeeping and reports every inconsistency found:
+    /// - buf2frame and frame2buf are inverses of each other
+    /// - a slot holds a page exactly when it is mapped to a frame
+    /// - the LRU stack holds exactly the occupied slots
+    /// - mapped frames lie within the frame pool
+    /// - a read-only pool has no dirty pages, and its pages are flagged read-only
+    pub fn validate(&self) -> ValidationReport {
+        let mut issues = Vec::new();
+
+        if self.pages.len() != self.size {
+            issues.push(format!(
+                "pool has {} slots but a size of {}",
+                self.pages.len(),
+                self.size
+            ));
+        }
+
+        for (&buf_idx, &frame_idx) in self.buf2frame.iter() {
+            match self.frame2buf.get(&frame_idx) {
+                Some(&back) if back == buf_idx => {}
+                Some(&back) => issues.push(format!(
+                    "slot {} maps to frame {}, but frame {} maps to slot {}",
+                    buf_idx, frame_idx, frame_idx, back
+                )),
+                None => issues.push(format!(
+                    "slot {} maps to frame {}, which has no reverse mapping",
+                    buf_idx, frame_idx
+                )),
+            }
+            if frame_idx >= self.frame_pool.size() {
+                issues.push(format!(
+                    "slot {} maps to frame {}, beyond the frame pool size of {}",
+                    buf_idx,
+                    frame_idx,
+                    self.frame_pool.size()
+                ));
+            }
+        }
+        for (&frame_idx, &buf_idx) in self.frame2buf.iter() {
+            if !self.buf2frame.contains_key(&buf_idx) {
+                issues.push(format!(
+                    "frame {} maps to slot {}, which has no reverse mapping",
+                    frame_idx, buf_idx
+                ));
+            }
+        }
+
+        for (buf_idx, page) in self.pages.iter().enumerate() {
+            let buf_idx = buf_idx as BufferPoolId;
+            let mapped = self.buf2frame.contains_key(&buf_idx);
+            match page {
+                Some(page) => {
+                    if !mapped {
+                        issues.push(format!("slot {} holds a page but is not mapped", buf_idx));
+                    }
+                    if !self.lru.contains(&buf_idx) {
+                        issues.push(format!(
+                            "slot {} holds a page but is not in the LRU",
+                            buf_idx
+                        ));
+                    }
+                    if self.read_only && page.is_dirty() {
+                        issues.push(format!("slot {} is dirty in a read-only pool", buf_idx));
+                    }
+                    if page.is_read_only() != self.read_only {
+                        issues.push(format!(
+                            "slot {} read-only flag is {}, but the pool's is {}",
+                            buf_idx,
+                            page.is_read_only(),
+                            self.read_only
+                        ));
+                    }
+                }
+                None => {
+                    if mapped {
+                        issues.push(format!("slot {} is mapped but holds no page", buf_idx));
+                    }
+                }
+            }
+        }
+
+        for buf_idx in self.lru.order() {
+            if self.pages.get(buf_idx as usize).is_none_or(|p| p.is_none()) {
+                issues.push(format!("LRU holds slot {}, which is empty", buf_idx));
+            }
+        }
+
+        ValidationReport { issues }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{bottom_evictor, filled_mem_pool};
+
+    #[test]
+    fn test_validate_after_normal_use() {
+        let mut mem_pool = filled_mem_pool(10, |i| i as u32);
+        let mut bp = BufferPool::<u32>::new(3, &mut mem_pool, bottom_evictor);
+        assert!(bp.validate().is_valid());
+
+        for i in [0, 5, 2, 7, 5, 9] {
+            bp.get_page(i).unwrap();
+        }
+        bp.put_page(2, 20).unwrap();
+        bp.get_many(&[1, 3, 4]);
+        bp.set_read_only(true).unwrap();
+
+        let report = bp.validate();
+        assert!(report.is_valid(), "{}", report);
+        assert_eq!(report.to_string(), "buffer pool is consistent");
+    }
+
+    #[test]
+    fn test_validate_reports_broken_mappings() {
+        let mut mem_pool = filled_mem_pool(10, |i| i as u32);
+        let mut bp = BufferPool::<u32>::new(3, &mut mem_pool, bottom_evictor);
+        bp.get_page(0).unwrap();
+        bp.get_page(1).unwrap();
+
+        let slot = bp.frame2buf[&1];
+        bp.frame2buf.insert(1, 99);
+        bp.lru.delete(slot);
+        bp.lru.push(2);
+
+        let report = bp.validate();
+        assert!(!report.is_valid());
+        assert_eq!(report.issues.len(), 4, "{}", report);
+        assert!(
+            report
+                .to_string()
+                .starts_with("4 buffer pool invariant(s) broken:")
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_frames_past_the_end() {
+        let mut mem_pool = filled_mem_pool(10, |i| i as u32);
+        let mut bp = BufferPool::<u32>::new(3, &mut mem_pool, bottom_evictor);
+        bp.get_page(9).unwrap();
+        bp.frame_pool.truncate(9).unwrap();
+
+        let report = bp.validate();
+        assert_eq!(report.issues.len(), 1, "{}", report);
+        assert!(report.issues[0].contains("beyond the frame pool size of 9"));
+    }
+}
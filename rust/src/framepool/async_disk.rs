@@ -0,0 +1,198 @@
+use futures::future::{join_all, try_join_all};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::fs;
+
+use super::FramePoolError;
+
+// The async counterpart of FramePool. Methods take &self so that a pool can serve many
+// requests at once; implementations synchronize whatever state they keep.
+pub trait AsyncFramePool<T>: Send + Sync
+where
+    T: Clone + Send + Sync,
+{
+    fn get_frame_ref(
+        &self,
+        idx: u64,
+    ) -> impl Future<Output = Result<Arc<T>, FramePoolError>> + Send;
+    fn put_frame(
+        &self,
+        idx: u64,
+        data: Arc<T>,
+    ) -> impl Future<Output = Result<(), FramePoolError>> + Send;
+    fn resize(&self, count: u64) -> impl Future<Output = Result<(), FramePoolError>> + Send;
+    // internally known size of the pool.
+    fn size(&self) -> u64;
+    // sync makes every completed put_frame durable. Pools without durable state need not override it.
+    fn sync(&self) -> impl Future<Output = Result<(), FramePoolError>> + Send {
+        async { Ok(()) }
+    }
+
+    // Reads several frames concurrently, returning one result per index, in order.
+    fn get_many(
+        &self,
+        idxs: &[u64],
+    ) -> impl Future<Output = Vec<Result<Arc<T>, FramePoolError>>> + Send {
+        join_all(idxs.iter().map(|&idx| self.get_frame_ref(idx)))
+    }
+
+    // Writes several frames concurrently. Stops at the first error, which leaves the other
+    // writes in an unknown state.
+    fn put_many(
+        &self,
+        frames: Vec<(u64, Arc<T>)>,
+    ) -> impl Future<Output = Result<(), FramePoolError>> + Send {
+        let writes: Vec<_> = frames
+            .into_iter()
+            .map(|(idx, data)| self.put_frame(idx, data))
+            .collect();
+        async move { try_join_all(writes).await.map(|_| ()) }
+    }
+}
+
+// A DiskPool driven by tokio::fs. It uses the same directory layout as DiskPool and its
+// default JSON pages, so either can open a directory written by the other.
+pub struct AsyncDiskPool<T> {
+    dirname: PathBuf,
+    size: AtomicU64,
+    // pages written since the last sync
+    unsynced: Mutex<HashSet<u64>>,
+    _data: PhantomData<fn() -> T>,
+}
+
+impl<T> AsyncDiskPool<T> {
+    // Opens the pool directory, creating it if needed, and counts the pages already in it.
+    pub async fn open(dirname: &str) -> Result<Self, FramePoolError> {
+        let dirname = PathBuf::from(dirname);
+        fs::create_dir_all(&dirname).await?;
+        let size = count_pages(&dirname).await?;
+        Ok(AsyncDiskPool {
+            dirname,
+            size: AtomicU64::new(size),
+            unsynced: Mutex::new(HashSet::new()),
+            _data: PhantomData,
+        })
+    }
+
+    fn page_path(&self, pageid: u64) -> PathBuf {
+        self.dirname.join(format!("page_{}", pageid))
+    }
+}
+
+async fn count_pages(dirname: &Path) -> Result<u64, FramePoolError> {
+    let mut entries = fs::read_dir(dirname).await?;
+    let mut count = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with("page_"))
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+impl<T> AsyncFramePool<T> for AsyncDiskPool<T>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone + Send + Sync,
+{
+    async fn get_frame_ref(&self, id: u64) -> Result<Arc<T>, FramePoolError> {
+        let s = fs::read_to_string(self.page_path(id))
+            .await
+            .map_err(|e| FramePoolError::from_io(e, || format!("page {}", id)))?;
+        let result: T = serde_json::from_str(&s)?;
+        Ok(Arc::new(result))
+    }
+
+    async fn put_frame(&self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let s = serde_json::to_string(&*data)?;
+        fs::write(self.page_path(idx), s).await?;
+        self.unsynced.lock().unwrap().insert(idx);
+        Ok(())
+    }
+
+    async fn resize(&self, count: u64) -> Result<(), FramePoolError> {
+        let old_sz = self.size.load(Ordering::SeqCst);
+        for i in 0..count {
+            let path = self.page_path(old_sz + i);
+            if !fs::try_exists(&path).await.unwrap_or(false) {
+                fs::write(path, "{}").await?;
+            }
+        }
+        self.size.store(old_sz + count, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    // fsync every page written since the last sync, concurrently, then the directory.
+    async fn sync(&self) -> Result<(), FramePoolError> {
+        let pending: Vec<u64> = self.unsynced.lock().unwrap().drain().collect();
+        let syncs = pending.iter().map(|idx| async move {
+            let path = self.page_path(*idx);
+            let file = fs::File::open(&path).await;
+            match file {
+                Ok(file) => file.sync_all().await,
+                Err(e) => Err(e),
+            }
+        });
+        if let Err(e) = try_join_all(syncs).await {
+            // Keep the pages for the next attempt
+            self.unsynced.lock().unwrap().extend(pending);
+            return Err(e.into());
+        }
+        fs::File::open(&self.dirname).await?.sync_all().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{DiskPool, FramePool};
+
+    #[tokio::test]
+    async fn test_async_diskpool_roundtrip() {
+        let temp_dir = "/tmp/test_async_diskpool_roundtrip";
+        let _ = std::fs::remove_dir_all(temp_dir);
+
+        let pool = AsyncDiskPool::<Vec<u32>>::open(temp_dir).await.unwrap();
+        assert_eq!(pool.size(), 0);
+        pool.resize(4).await.unwrap();
+        assert_eq!(pool.size(), 4);
+
+        pool.put_many((0..4).map(|i| (i, Arc::new(vec![i as u32; 3]))).collect())
+            .await
+            .unwrap();
+        let read = pool.get_many(&[3, 1, 7]).await;
+        assert_eq!(*read[0].as_ref().unwrap().as_ref(), vec![3, 3, 3]);
+        assert_eq!(*read[1].as_ref().unwrap().as_ref(), vec![1, 1, 1]);
+        assert!(read[2].is_err());
+        pool.sync().await.unwrap();
+
+        // The same directory reads back through the blocking DiskPool
+        let mut disk_pool = DiskPool::new::<Vec<u32>>(temp_dir);
+        assert_eq!(
+            FramePool::<Vec<u32>>::assess_size(&mut disk_pool).unwrap(),
+            4
+        );
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut disk_pool, 2).unwrap(),
+            vec![2, 2, 2]
+        );
+        drop(pool);
+
+        let reopened = AsyncDiskPool::<Vec<u32>>::open(temp_dir).await.unwrap();
+        assert_eq!(reopened.size(), 4);
+        let _ = std::fs::remove_dir_all(temp_dir);
+    }
+}
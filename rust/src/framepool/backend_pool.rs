@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use super::{FramePool, FramePoolError, FrameState, StorageBackend};
+
+// Turns a frame id into a backend key, and a key back into the frame id it names (None for
+// keys that name no frame).
+pub type FormatKeyFn = fn(u64) -> String;
+pub type ParseKeyFn = fn(&str) -> Option<u64>;
+
+// A FramePool over any StorageBackend, storing frame i under a key made from i ("frame_i"
+// unless set otherwise with with_key_format). This puts a BufferPool in front of a
+// FileBackend, or of any other backend.
+//
+// The pool's size starts at one past the highest frame id found in the backend. Keys that
+// don't parse as frame ids are left alone, so the backend can hold other data too.
+pub struct BackendFramePool<B> {
+    backend: B,
+    format_key: FormatKeyFn,
+    parse_key: ParseKeyFn,
+    size: u64,
+}
+
+fn default_format(idx: u64) -> String {
+    format!("frame_{}", idx)
+}
+
+fn default_parse(key: &str) -> Option<u64> {
+    key.strip_prefix("frame_")?.parse().ok()
+}
+
+impl<B> BackendFramePool<B> {
+    pub fn new<T>(backend: B) -> Self
+    where
+        T: Clone,
+        B: StorageBackend<T>,
+    {
+        let mut pool = BackendFramePool {
+            backend,
+            format_key: default_format,
+            parse_key: default_parse,
+            size: 0,
+        };
+        pool.size = pool.stored_size().unwrap_or(0);
+        pool
+    }
+
+    // Stores frames under keys made by format and read back by parse, which must invert it.
+    // The size is worked out again from the keys that parse.
+    pub fn with_key_format<T>(mut self, format: FormatKeyFn, parse: ParseKeyFn) -> Self
+    where
+        T: Clone,
+        B: StorageBackend<T>,
+    {
+        self.format_key = format;
+        self.parse_key = parse;
+        self.size = self.stored_size().unwrap_or(0);
+        self
+    }
+
+    // The key frame idx is stored under.
+    pub fn key(&self, idx: u64) -> String {
+        (self.format_key)(idx)
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn into_inner(self) -> B {
+        self.backend
+    }
+
+    // The ids of the frames stored in the backend, in ascending order.
+    fn stored_ids<T>(&self) -> Result<Vec<u64>, FramePoolError>
+    where
+        T: Clone,
+        B: StorageBackend<T>,
+    {
+        let mut ids: Vec<u64> = self
+            .backend
+            .list_keys()?
+            .iter()
+            .filter_map(|key| (self.parse_key)(key))
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn stored_size<T>(&self) -> Result<u64, FramePoolError>
+    where
+        T: Clone,
+        B: StorageBackend<T>,
+    {
+        Ok(self.stored_ids()?.last().map_or(0, |idx| idx + 1))
+    }
+}
+
+impl<T, B> FramePool<T> for BackendFramePool<B>
+where
+    T: Clone,
+    B: StorageBackend<T>,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        self.backend.read(&self.key(idx))
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let key = self.key(idx);
+        self.backend.write(&key, data)?;
+        self.size = self.size.max(idx + 1);
+        Ok(())
+    }
+
+    // Frames are stored only once written, so resizing only moves the end of the pool.
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.size += count;
+        Ok(())
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        for idx in FramePool::<T>::frame_ids(self)? {
+            if idx >= count && self.backend.exists(&self.key(idx)) {
+                let key = self.key(idx);
+                self.backend.delete(&key)?;
+            }
+        }
+        self.size = self.size.min(count);
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.size = self.size.max(self.stored_size()?);
+        Ok(self.size)
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        if self.backend.exists(&self.key(*idx)) {
+            FrameState::Populated
+        } else if *idx < self.size {
+            FrameState::Empty
+        } else {
+            FrameState::Absent
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids = self.stored_ids()?;
+        ids.extend(0..self.size);
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        let key = self.key(*idx);
+        match self.backend.exists(&key) {
+            true => self.backend.delete(&key),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use crate::framepool::FileBackend;
+
+    #[test]
+    fn test_backend_pool_behind_bufferpool() {
+        let test_dir = "/tmp/test_backend_frame_pool";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut pool = BackendFramePool::new::<u64>(FileBackend::new(test_dir));
+        FramePool::<u64>::resize(&mut pool, 8).unwrap();
+        for i in 0..8 {
+            pool.put_frame(i, Arc::new(i)).unwrap();
+        }
+        {
+            let mut bp = BufferPool::<u64>::new(3, &mut pool, bottom_evictor);
+            for i in 0..8 {
+                bp.modify_page(i, |v| *v *= 2).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        let mut backend = pool.into_inner();
+        assert_eq!(*backend.read_data::<u64>("frame_5").unwrap(), 10);
+
+        // A new pool over the same files finds its size from the keys
+        backend.write_data("notes", Arc::new(0u64)).unwrap();
+        let mut pool = BackendFramePool::new::<u64>(backend);
+        assert_eq!(FramePool::<u64>::size(&pool), 8);
+        FramePool::<u64>::truncate(&mut pool, 6).unwrap();
+        assert_eq!(
+            FramePool::<u64>::frame_ids(&pool).unwrap(),
+            (0..6).collect::<Vec<_>>()
+        );
+        assert!(StorageBackend::<u64>::exists(pool.inner(), "notes"));
+        assert_eq!(*FramePool::<u64>::get_frame_ref(&mut pool, 3).unwrap(), 6);
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_backend_pool_key_format() {
+        let test_dir = "/tmp/test_backend_frame_pool_keys";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut pool = BackendFramePool::new::<String>(FileBackend::new(test_dir))
+            .with_key_format::<String>(
+                |idx| format!("{:08x}", idx),
+                |key| u64::from_str_radix(key, 16).ok(),
+            );
+        pool.put_frame(26, Arc::new("z".to_string())).unwrap();
+        assert_eq!(pool.key(26), "0000001a");
+        assert_eq!(FramePool::<String>::size(&pool), 27);
+        assert_eq!(
+            FramePool::<String>::frame_state(&pool, &26),
+            FrameState::Populated
+        );
+        assert_eq!(
+            FramePool::<String>::frame_state(&pool, &3),
+            FrameState::Empty
+        );
+        assert!(matches!(
+            FramePool::<String>::get_frame_ref(&mut pool, 3),
+            Err(FramePoolError::Io(_) | FramePoolError::NotFound(_))
+        ));
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+}
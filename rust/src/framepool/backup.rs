@@ -0,0 +1,190 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use super::{
+    Codec, DiskPool, Durability, FramePoolError, MANIFEST, Manifest, is_fanout_dir, page_files,
+    write_atomic,
+};
+
+impl<C: Codec> DiskPool<C> {
+    // Writes the pool's manifest and every page to a tar archive at path, returning the number
+    // of pages archived. The archive is written beside path and renamed into place when
+    // complete.
+    //
+    // The archive is consistent as long as nothing writes the pool meanwhile: the pool's lock
+    // keeps other processes out, and &mut self everything in this one but pools opened
+    // unlocked. A BufferPool over the pool must be flushed first, or the archive lacks the
+    // pages it holds dirty.
+    pub fn backup_to(&mut self, path: impl AsRef<Path>) -> Result<u64, FramePoolError> {
+        let path = path.as_ref();
+        self.initialize()?;
+        if self.read_manifest()?.is_none() {
+            // a read-only pool over a directory from before manifests
+            return Err(FramePoolError::NotFound(format!(
+                "{} in {}",
+                MANIFEST,
+                self.dirname.display()
+            )));
+        }
+        let staging = path.with_extension("tar.tmp");
+        let archived = self.write_archive(&staging).and_then(|pages| {
+            fs::rename(&staging, path)?;
+            Ok(pages)
+        });
+        if archived.is_err() {
+            let _ = fs::remove_file(&staging);
+        }
+        archived
+    }
+
+    fn write_archive(&self, path: &Path) -> Result<u64, FramePoolError> {
+        let mut archive = tar::Builder::new(fs::File::create(path)?);
+        archive.append_path_with_name(self.dirname.join(MANIFEST), MANIFEST)?;
+        let pages = page_files(&self.dirname)?;
+        for (_, page) in pages.iter() {
+            archive.append_path_with_name(page, page.strip_prefix(&self.dirname).unwrap())?;
+        }
+        let file = archive.into_inner()?;
+        if self.durability == Durability::Always {
+            file.sync_all()?;
+        }
+        Ok(pages.len() as u64)
+    }
+
+    // Replaces the pool's contents with those of an archive backup_to wrote, returning the
+    // number of pages restored. The archive must hold pages of the pool's type and codec;
+    // it is checked before anything is deleted. Pages are laid out as the pool lays them out,
+    // whatever the layout of the pool archived, and synced before this returns.
+    pub fn restore_from(&mut self, path: impl AsRef<Path>) -> Result<u64, FramePoolError> {
+        let path = path.as_ref();
+        self.check_writable()?;
+        self.initialize()?;
+
+        let mut manifest = None;
+        for entry in tar::Archive::new(fs::File::open(path)?).entries()? {
+            let mut entry = entry?;
+            if archived_page(&entry.path()?)?.is_none() {
+                let mut bytes = Vec::new();
+                entry.read_to_end(&mut bytes)?;
+                manifest = Some(serde_json::from_slice::<Manifest>(&bytes).map_err(|e| {
+                    FramePoolError::Corruption(format!("unreadable archived {}: {}", MANIFEST, e))
+                })?);
+            }
+        }
+        let manifest = manifest.ok_or_else(|| {
+            FramePoolError::Corruption(format!("{} has no {}", path.display(), MANIFEST))
+        })?;
+        self.check_manifest(&manifest)?;
+
+        for (_, page) in page_files(&self.dirname)? {
+            fs::remove_file(page)?;
+        }
+        self.unsynced.clear();
+        let mut restored = 0;
+        for entry in tar::Archive::new(fs::File::open(path)?).entries()? {
+            let mut entry = entry?;
+            let Some(idx) = archived_page(&entry.path()?)? else {
+                continue;
+            };
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            let target = self.page_path(idx);
+            self.make_parent(&target)?;
+            write_atomic(&target, &bytes, false)?;
+            self.mark_unsynced(idx);
+            restored += 1;
+        }
+        self.size = manifest.size;
+        self.write_manifest()?;
+        self.sync()?;
+        Ok(restored)
+    }
+}
+
+// The page id of an archive entry, or None for the manifest. Anything else, and in particular
+// any path that could lead out of the pool's directory, is refused.
+fn archived_page(name: &Path) -> Result<Option<u64>, FramePoolError> {
+    if name == Path::new(MANIFEST) {
+        return Ok(None);
+    }
+    let refuse = || FramePoolError::Corruption(format!("unexpected archive entry {:?}", name));
+    let mut parts: Vec<PathBuf> = Vec::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => parts.push(PathBuf::from(part)),
+            _ => return Err(refuse()),
+        }
+    }
+    let file = parts.pop().ok_or_else(refuse)?;
+    let dirs_ok = parts
+        .iter()
+        .all(|dir| dir.to_str().is_some_and(is_fanout_dir));
+    match file.to_str().and_then(|f| f.strip_prefix("page_")) {
+        Some(id) if dirs_ok => id.parse().map(Some).map_err(|_| refuse()),
+        _ => Err(refuse()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use crate::framepool::FramePool;
+
+    #[test]
+    fn test_diskpool_backup_and_restore() {
+        let test_dir = "/tmp/test_diskpool_backup";
+        let archive = "/tmp/test_diskpool_backup.tar";
+        let _ = fs::remove_dir_all(test_dir);
+        let _ = fs::remove_dir_all(format!("{}_restored", test_dir));
+
+        let mut pool = DiskPool::new::<u64>(test_dir).with_fanout(1);
+        FramePool::<u64>::resize(&mut pool, 30).unwrap();
+        {
+            let mut bp = BufferPool::<u64>::new(5, &mut pool, bottom_evictor);
+            for i in 0..20 {
+                bp.get_or_insert_with(i, || i * 2).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        assert_eq!(pool.backup_to(archive).unwrap(), 20);
+
+        // Restored over later changes, in another layout
+        FramePool::<u64>::truncate(&mut pool, 3).unwrap();
+        pool.put_frame(1, std::sync::Arc::new(99u64)).unwrap();
+        drop(pool);
+        let mut flat = DiskPool::new::<u64>(test_dir);
+        assert_eq!(flat.restore_from(archive).unwrap(), 20);
+        assert_eq!(FramePool::<u64>::size(&flat), 30);
+        assert_eq!(*FramePool::<u64>::get_frame_ref(&mut flat, 1).unwrap(), 2);
+        assert_eq!(*FramePool::<u64>::get_frame_ref(&mut flat, 19).unwrap(), 38);
+        assert!(Path::new(&format!("{}/page_19", test_dir)).exists());
+
+        // And into a new directory, but not into a pool of another type
+        let restored = format!("{}_restored", test_dir);
+        let mut copy = DiskPool::new::<u64>(&restored);
+        copy.restore_from(archive).unwrap();
+        assert_eq!(*FramePool::<u64>::get_frame_ref(&mut copy, 7).unwrap(), 14);
+        let mut wrong = DiskPool::new::<String>(&format!("{}_wrong", test_dir));
+        assert!(matches!(
+            wrong.restore_from(archive),
+            Err(FramePoolError::TypeMismatch { .. })
+        ));
+
+        let _ = fs::remove_dir_all(test_dir);
+        let _ = fs::remove_dir_all(restored);
+        let _ = fs::remove_dir_all(format!("{}_wrong", test_dir));
+        let _ = fs::remove_file(archive);
+    }
+
+    #[test]
+    fn test_archived_page_names() {
+        assert_eq!(archived_page(Path::new("manifest.json")).unwrap(), None);
+        assert_eq!(archived_page(Path::new("page_4")).unwrap(), Some(4));
+        assert_eq!(archived_page(Path::new("ab/0f/page_12")).unwrap(), Some(12));
+        for bad in ["../page_1", "/tmp/page_1", "notes", "xy/page_1", "page_x"] {
+            assert!(archived_page(Path::new(bad)).is_err(), "{}", bad);
+        }
+    }
+}
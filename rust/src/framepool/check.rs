@@ -0,0 +1,168 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+use super::{Codec, DiskPool, FramePoolError, page_files};
+
+// What DiskPool::check found. Ids are in ascending order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    // pages checked
+    pub pages: u64,
+    // frames within the pool without a page file: never written, or lost
+    pub missing: Vec<u64>,
+    // page files at or past the end of the pool
+    pub extra: Vec<u64>,
+    // pages that can't be read: unreadable, failing their checksum or page header, or not
+    // decoding to the pool's page type
+    pub corrupt: Vec<u64>,
+    // where repair moved the extra and corrupt pages
+    pub quarantined: Vec<PathBuf>,
+}
+
+impl CheckReport {
+    // Whether every page file belongs to the pool and reads back.
+    pub fn is_clean(&self) -> bool {
+        self.extra.is_empty() && self.corrupt.is_empty()
+    }
+}
+
+// Where check's repair mode moves pages, under the pool's directory.
+const QUARANTINE: &str = "quarantine";
+
+impl<C: Codec> DiskPool<C> {
+    // Checks every page file in the pool's directory, fsck-style: that it reads, matches its
+    // checksum or page header if the pool writes them, and decodes to T, and that its id is
+    // within the size the manifest records. With repair, extra and corrupt pages are moved
+    // out of the pool into the quarantine directory under it, leaving their frames unwritten.
+    pub fn check<T>(&mut self, repair: bool) -> Result<CheckReport, FramePoolError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        if repair {
+            self.check_writable()?;
+        }
+        self.initialize()?;
+        let mut report = CheckReport::default();
+        let mut next = 0;
+        for (idx, path) in page_files(&self.dirname)? {
+            report.pages += 1;
+            report.missing.extend(next..idx.min(self.size));
+            next = next.max(idx + 1);
+            let bad = if idx >= self.size {
+                report.extra.push(idx);
+                true
+            } else if !self.page_reads::<T>(idx, &path) {
+                report.corrupt.push(idx);
+                true
+            } else {
+                false
+            };
+            if bad && repair {
+                report.quarantined.push(self.quarantine(&path)?);
+                self.unsynced.remove(&idx);
+            }
+        }
+        report.missing.extend(next..self.size);
+        Ok(report)
+    }
+
+    fn page_reads<T>(&self, idx: u64, path: &PathBuf) -> bool
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let Ok(bytes) = fs::read(path) else {
+            return false;
+        };
+        match self.checked_body(idx, &bytes) {
+            // the placeholder resize used to write is an empty frame, not a page
+            Ok(b"{}") if !self.page_headers => true,
+            Ok(body) => self.codec.decode::<T>(body).is_ok() || self.upgrade::<T>(body).is_some(),
+            Err(_) => false,
+        }
+    }
+
+    // Moves path into the quarantine directory, under a name not already taken there.
+    fn quarantine(&self, path: &PathBuf) -> Result<PathBuf, FramePoolError> {
+        let dir = self.dirname.join(QUARANTINE);
+        fs::create_dir_all(&dir)?;
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let mut target = dir.join(&name);
+        let mut n = 1;
+        while target.exists() {
+            target = dir.join(format!("{}.{}", name, n));
+            n += 1;
+        }
+        fs::rename(path, &target)?;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{FramePool, FrameState};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_diskpool_check_and_repair() {
+        let test_dir = "/tmp/test_diskpool_check";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = DiskPool::new::<Vec<u32>>(test_dir).with_checksums();
+        FramePool::<Vec<u32>>::resize(&mut pool, 6).unwrap();
+        for i in [0, 1, 2, 4] {
+            pool.put_frame(i, Arc::new(vec![i as u32])).unwrap();
+        }
+        let report = pool.check::<Vec<u32>>(false).unwrap();
+        assert!(report.is_clean());
+        assert_eq!((report.pages, report.missing), (4, vec![3, 5]));
+
+        // A damaged page, a page of another type, and one past the end
+        let path = format!("{}/page_1", test_dir);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] = b'}';
+        fs::write(&path, bytes).unwrap();
+        pool.write_page(2, &"two".to_string()).unwrap();
+        fs::write(format!("{}/page_9", test_dir), "[9]").unwrap();
+
+        let report = pool.check::<Vec<u32>>(false).unwrap();
+        assert!(!report.is_clean());
+        assert_eq!(report.corrupt, vec![1, 2]);
+        assert_eq!(report.extra, vec![9]);
+        assert_eq!(report.missing, vec![3, 5]);
+        assert!(report.quarantined.is_empty());
+
+        // Repair moves them aside, and the pool checks clean
+        let report = pool.check::<Vec<u32>>(true).unwrap();
+        assert_eq!(report.quarantined.len(), 3);
+        assert!(report.quarantined.iter().all(|path| path.exists()));
+        assert_eq!(
+            FramePool::<Vec<u32>>::frame_state(&pool, &1),
+            FrameState::Empty
+        );
+        let report = pool.check::<Vec<u32>>(false).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.missing, vec![1, 2, 3, 5]);
+
+        // Quarantined pages of the same name don't overwrite one another
+        fs::write(format!("{}/page_9", test_dir), "[9]").unwrap();
+        let report = pool.check::<Vec<u32>>(true).unwrap();
+        assert_eq!(
+            report.quarantined[0],
+            PathBuf::from(format!("{}/quarantine/page_9.1", test_dir))
+        );
+
+        drop(pool);
+        let mut reader = DiskPool::open_read_only::<Vec<u32>>(test_dir)
+            .unwrap()
+            .with_checksums();
+        assert!(reader.check::<Vec<u32>>(false).unwrap().is_clean());
+        assert!(matches!(
+            reader.check::<Vec<u32>>(true),
+            Err(FramePoolError::ReadOnly)
+        ));
+        let _ = fs::remove_dir_all(test_dir);
+    }
+}
@@ -0,0 +1,408 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{Codec, FrameMeta, FramePool, FramePoolError, FrameState, JsonCodec, PagedFile, fnv1a};
+
+// A FramePool of values of any size over a PagedFile, splitting each value's encoding across
+// as many pages as it takes, so a value much larger than a page is written and read back a
+// page at a time rather than as one huge file. The file's page size is the chunk size.
+//
+// Frame idx keeps its first chunk in page idx and the rest in pages allocated from the file;
+// each chunk's header names the frame it belongs to and the page holding the next chunk. A
+// page holding a later chunk of some frame reads as an empty frame, and writing a frame there
+// first moves the chunk to another page. Frames are written in place, the first chunk last;
+// sync makes the writes durable.
+pub struct ChunkedPool<C = JsonCodec> {
+    file: PagedFile,
+    codec: C,
+}
+
+// The header at the start of every chunk: its kind, then from byte 4 the length of its part
+// of the value, the page of the next chunk, the frame's first page and a CRC32 of the part,
+// all little-endian.
+const CHUNK_HEADER_LEN: usize = 32;
+const FIRST: u8 = 1;
+const LATER: u8 = 2;
+// ends a frame's chain of chunks
+const NO_CHUNK: u64 = u64::MAX;
+
+struct ChunkHeader {
+    kind: u8,
+    len: usize,
+    next: u64,
+    head: u64,
+    crc: u32,
+}
+
+impl ChunkHeader {
+    fn parse(page: &[u8]) -> Self {
+        let field = |at: usize| u64::from_le_bytes(page[at..at + 8].try_into().unwrap());
+        let half = |at: usize| u32::from_le_bytes(page[at..at + 4].try_into().unwrap());
+        ChunkHeader {
+            kind: page[0],
+            len: half(4) as usize,
+            next: field(8),
+            head: field(16),
+            crc: half(24),
+        }
+    }
+
+    // The chunk holding part, with this header in front of it.
+    fn wrap(&self, part: &[u8]) -> Vec<u8> {
+        let mut page = Vec::with_capacity(CHUNK_HEADER_LEN + part.len());
+        page.extend_from_slice(&[self.kind, 0, 0, 0]);
+        page.extend_from_slice(&(self.len as u32).to_le_bytes());
+        page.extend_from_slice(&self.next.to_le_bytes());
+        page.extend_from_slice(&self.head.to_le_bytes());
+        page.extend_from_slice(&self.crc.to_le_bytes());
+        page.resize(CHUNK_HEADER_LEN, 0);
+        page.extend_from_slice(part);
+        page
+    }
+}
+
+impl ChunkedPool {
+    // A pool in a new file at path, in chunks of page_size bytes, replacing any file there.
+    pub fn create(path: impl AsRef<Path>, page_size: usize) -> Result<Self, FramePoolError> {
+        Ok(Self::new(PagedFile::create(path, page_size)?))
+    }
+
+    // Opens a pool create made.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FramePoolError> {
+        Ok(Self::new(PagedFile::open(path)?))
+    }
+
+    pub fn new(file: PagedFile) -> Self {
+        ChunkedPool {
+            file,
+            codec: JsonCodec::default(),
+        }
+    }
+}
+
+impl<C: Codec> ChunkedPool<C> {
+    // The same pool, encoding values with codec instead.
+    pub fn with_codec<D: Codec>(self, codec: D) -> ChunkedPool<D> {
+        ChunkedPool {
+            file: self.file,
+            codec,
+        }
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.file.page_size()
+    }
+
+    pub fn into_inner(self) -> PagedFile {
+        self.file
+    }
+
+    // The pages holding frame idx's chunks, first to last.
+    pub fn chunks(&self, idx: u64) -> Result<Vec<u64>, FramePoolError> {
+        let mut pages = Vec::new();
+        self.walk(idx, |id, _| pages.push(id))?;
+        Ok(pages)
+    }
+
+    // Follows frame idx's chain of chunks, checking each, and passes f each one's page and
+    // part of the value.
+    fn walk(&self, idx: u64, mut f: impl FnMut(u64, &[u8])) -> Result<(), FramePoolError> {
+        let (mut id, mut kind) = (idx, FIRST);
+        let mut count = 0;
+        while id != NO_CHUNK {
+            let broken = || FramePoolError::Corruption(format!("frame {}'s chunks", idx));
+            if id >= self.file.page_count() || self.file.is_free(id) {
+                return Err(match id == idx {
+                    true => FramePoolError::NotFound(format!("frame {}", idx)),
+                    false => broken(),
+                });
+            }
+            let page = self.file.read(id)?;
+            let header = ChunkHeader::parse(&page);
+            if header.kind != kind || header.head != idx {
+                return Err(match id == idx {
+                    true => FramePoolError::NotFound(format!("frame {}, never written", idx)),
+                    false => broken(),
+                });
+            }
+            let part = page
+                .get(CHUNK_HEADER_LEN..CHUNK_HEADER_LEN + header.len)
+                .ok_or(FramePoolError::CorruptPage { idx: id })?;
+            if crc32fast::hash(part) != header.crc {
+                return Err(FramePoolError::CorruptPage { idx: id });
+            }
+            count += 1;
+            if count > self.file.page_count() {
+                return Err(broken());
+            }
+            f(id, part);
+            (id, kind) = (header.next, LATER);
+        }
+        Ok(())
+    }
+
+    // The encoded value of frame idx, put back together from its chunks.
+    fn read_value(&self, idx: u64) -> Result<Vec<u8>, FramePoolError> {
+        let mut bytes = Vec::new();
+        self.walk(idx, |_, part| bytes.extend_from_slice(part))?;
+        Ok(bytes)
+    }
+
+    fn write_value(&mut self, idx: u64, bytes: &[u8]) -> Result<(), FramePoolError> {
+        let mut spare = self.claim(idx)?.into_iter();
+        let capacity = self.file.page_size() - CHUNK_HEADER_LEN;
+        let parts: Vec<&[u8]> = match bytes.is_empty() {
+            true => vec![bytes],
+            false => bytes.chunks(capacity).collect(),
+        };
+        let mut pages = vec![idx];
+        for _ in 1..parts.len() {
+            pages.push(match spare.next() {
+                Some(id) => id,
+                None => self.file.allocate()?,
+            });
+        }
+        // the last chunk first, so no chunk links to one not yet written
+        for (n, part) in parts.iter().enumerate().rev() {
+            let header = ChunkHeader {
+                kind: if n == 0 { FIRST } else { LATER },
+                len: part.len(),
+                next: pages.get(n + 1).copied().unwrap_or(NO_CHUNK),
+                head: idx,
+                crc: crc32fast::hash(part),
+            };
+            self.file.write(pages[n], &header.wrap(part))?;
+        }
+        for id in spare {
+            self.file.free(id)?;
+        }
+        Ok(())
+    }
+
+    // Readies page idx for frame idx's first chunk, returning the pages of the frame's later
+    // chunks for the new value to reuse.
+    fn claim(&mut self, idx: u64) -> Result<Vec<u64>, FramePoolError> {
+        if idx >= self.file.page_count() || self.file.is_free(idx) {
+            // claims the page, adding pages up to it if it is past the end
+            FramePool::<Vec<u8>>::put_frame(&mut self.file, idx, Arc::new(Vec::new()))?;
+            return Ok(Vec::new());
+        }
+        let header = ChunkHeader::parse(&self.file.read(idx)?);
+        match header.kind {
+            // a frame whose chunks are damaged leaves its later pages allocated rather than
+            // keep it from being written again
+            FIRST if header.head == idx => Ok(self
+                .chunks(idx)
+                .map(|mut pages| pages.split_off(1))
+                .unwrap_or_default()),
+            LATER => {
+                self.relocate(idx, header.head)?;
+                Ok(Vec::new())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    // Moves the chunk of frame head in page id to a newly allocated page, leaving id to the
+    // caller.
+    fn relocate(&mut self, id: u64, head: u64) -> Result<(), FramePoolError> {
+        // a chunk its frame no longer links to is free to take
+        let Ok(pages) = self.chunks(head) else {
+            return Ok(());
+        };
+        let Some(at) = pages
+            .iter()
+            .position(|page| *page == id)
+            .filter(|at| *at > 0)
+        else {
+            return Ok(());
+        };
+        let chunk = self.file.read(id)?;
+        let to = self.file.allocate()?;
+        self.file.write(to, &chunk)?;
+        let mut previous = self.file.read(pages[at - 1])?;
+        previous[8..16].copy_from_slice(&to.to_le_bytes());
+        self.file.write(pages[at - 1], &previous)
+    }
+
+    // Whether page idx holds the first chunk of frame idx.
+    fn is_written(&self, idx: u64) -> bool {
+        idx < self.file.page_count()
+            && !self.file.is_free(idx)
+            && self.file.read(idx).is_ok_and(|page| {
+                let header = ChunkHeader::parse(&page);
+                header.kind == FIRST && header.head == idx
+            })
+    }
+}
+
+impl<T, C> FramePool<T> for ChunkedPool<C>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Codec,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        let bytes = self.read_value(idx)?;
+        Ok(Arc::new(self.codec.decode(&bytes)?))
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let bytes = self.codec.encode(&*data)?;
+        self.write_value(idx, &bytes)
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        FramePool::<Vec<u8>>::resize(&mut self.file, count)
+    }
+
+    fn size(&self) -> u64 {
+        self.file.page_count()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        Ok(self.file.page_count())
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.file.sync()
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        if *idx >= self.file.page_count() {
+            FrameState::Absent
+        } else if self.is_written(*idx) {
+            FrameState::Populated
+        } else {
+            FrameState::Empty
+        }
+    }
+
+    // The size and checksum are of the encoded value, not of the pages holding it.
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        let bytes = self.read_value(*idx)?;
+        Ok(FrameMeta {
+            size: bytes.len() as u64,
+            modified: None,
+            checksum: Some(fnv1a(&bytes)),
+        })
+    }
+
+    // Frees every page the frame's chunks take.
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        if !self.is_written(*idx) {
+            return Ok(());
+        }
+        let pages = self.chunks(*idx).unwrap_or_else(|_| vec![*idx]);
+        for id in pages {
+            self.file.free(id)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use std::fs;
+
+    #[test]
+    fn test_chunked_pool_large_values() {
+        let dir = "/tmp/test_chunked_pool";
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        let path = format!("{}/pages.db", dir);
+
+        let mut pool = ChunkedPool::create(&path, 128).unwrap();
+        FramePool::<Vec<u64>>::resize(&mut pool, 4).unwrap();
+        let big: Vec<u64> = (0..1000).collect();
+        pool.put_frame(0, Arc::new(big.clone())).unwrap();
+        pool.put_frame(1, Arc::new(vec![7u64])).unwrap();
+        let chunks = pool.chunks(0).unwrap();
+        let encoded = serde_json::to_vec(&big).unwrap().len();
+        assert_eq!(chunks.len(), encoded.div_ceil(128 - CHUNK_HEADER_LEN));
+        assert_eq!(pool.chunks(1).unwrap(), vec![1]);
+        assert_eq!(
+            *FramePool::<Vec<u64>>::get_frame_ref(&mut pool, 0).unwrap(),
+            big
+        );
+
+        // A later chunk's page reads as empty; writing a frame there moves the chunk aside
+        let taken = chunks[1..].iter().copied().find(|id| *id < 4).unwrap();
+        assert_eq!(
+            FramePool::<Vec<u64>>::frame_state(&pool, &taken),
+            FrameState::Empty
+        );
+        pool.put_frame(taken, Arc::new(vec![42u64])).unwrap();
+        assert!(!pool.chunks(0).unwrap().contains(&taken));
+        assert_eq!(
+            *FramePool::<Vec<u64>>::get_frame_ref(&mut pool, 0).unwrap(),
+            big
+        );
+        assert_eq!(
+            *FramePool::<Vec<u64>>::get_frame_ref(&mut pool, taken).unwrap(),
+            vec![42]
+        );
+
+        // Shrinking a value frees the pages it no longer needs
+        let free = pool.file.free_count();
+        pool.put_frame(0, Arc::new(vec![1u64, 2, 3])).unwrap();
+        assert_eq!(pool.chunks(0).unwrap(), vec![0]);
+        assert_eq!(pool.file.free_count(), free + chunks.len() as u64 - 1);
+        pool.put_frame(0, Arc::new(big.clone())).unwrap();
+        drop(pool);
+
+        let mut pool = ChunkedPool::open(&path).unwrap();
+        assert_eq!(
+            *FramePool::<Vec<u64>>::get_frame_ref(&mut pool, 0).unwrap(),
+            big
+        );
+        let meta = FramePool::<Vec<u64>>::frame_meta(&pool, &0).unwrap();
+        assert_eq!(meta.size, encoded as u64);
+
+        // Damage to any chunk is caught
+        let last = *pool.chunks(0).unwrap().last().unwrap();
+        let mut page = pool.file.read(last).unwrap();
+        page[CHUNK_HEADER_LEN] ^= 1;
+        pool.file.write(last, &page).unwrap();
+        assert!(matches!(
+            FramePool::<Vec<u64>>::get_frame_ref(&mut pool, 0),
+            Err(FramePoolError::CorruptPage { idx }) if idx == last
+        ));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_chunked_pool_behind_bufferpool() {
+        let dir = "/tmp/test_chunked_pool_bp";
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+        let path = format!("{}/pages.db", dir);
+
+        let mut pool = ChunkedPool::create(&path, 64).unwrap();
+        FramePool::<String>::resize(&mut pool, 3).unwrap();
+        {
+            let mut bp = BufferPool::<String>::new(3, &mut pool, bottom_evictor);
+            bp.get_or_insert_with(0, || "x".repeat(500)).unwrap();
+            bp.get_or_insert_with(2, String::new).unwrap();
+            bp.flush_all().unwrap();
+        }
+        assert_eq!(
+            *FramePool::<String>::get_frame_ref(&mut pool, 0).unwrap(),
+            "x".repeat(500)
+        );
+        assert_eq!(
+            *FramePool::<String>::get_frame_ref(&mut pool, 2).unwrap(),
+            ""
+        );
+
+        // Discarding frees every chunk
+        let pages = pool.chunks(0).unwrap().len() as u64;
+        let free = pool.file.free_count();
+        FramePool::<String>::discard_frame(&mut pool, &0).unwrap();
+        assert_eq!(pool.file.free_count(), free + pages);
+        assert!(FramePool::<String>::get_frame_ref(&mut pool, 0).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
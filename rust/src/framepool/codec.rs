@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+use super::FramePoolError;
+
+// How DiskPool and FileBackend turn frames into bytes and back. JSON is the default; the
+// binary codecs behind the bincode, cbor and msgpack features are smaller and faster for
+// numeric data, but a directory must always be read with the codec it was written with.
+pub trait Codec {
+    fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError>;
+    fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, FramePoolError>;
+    // file extension FileBackend gives the files it writes, without the dot
+    fn extension(&self) -> &'static str;
+}
+
+// serde_json, compact unless made with JsonCodec::pretty.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec {
+    pretty: bool,
+}
+
+impl JsonCodec {
+    // Indented JSON, easier to read by hand and larger on disk.
+    pub fn pretty() -> Self {
+        JsonCodec { pretty: true }
+    }
+}
+
+impl Codec for JsonCodec {
+    fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError> {
+        if self.pretty {
+            Ok(serde_json::to_vec_pretty(data)?)
+        } else {
+            Ok(serde_json::to_vec(data)?)
+        }
+    }
+
+    fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, FramePoolError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+}
+
+// bincode 1.x with its default options: fixed-width little-endian integers.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl Codec for BincodeCodec {
+    fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError> {
+        bincode::serialize(data).map_err(|e| FramePoolError::Codec(e.to_string()))
+    }
+
+    fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, FramePoolError> {
+        bincode::deserialize(bytes).map_err(|e| FramePoolError::Codec(e.to_string()))
+    }
+
+    fn extension(&self) -> &'static str {
+        "bin"
+    }
+}
+
+// CBOR (RFC 8949), through ciborium.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "cbor")]
+impl Codec for CborCodec {
+    fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(data, &mut bytes)
+            .map_err(|e| FramePoolError::Codec(e.to_string()))?;
+        Ok(bytes)
+    }
+
+    fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, FramePoolError> {
+        ciborium::from_reader(bytes).map_err(|e| FramePoolError::Codec(e.to_string()))
+    }
+
+    fn extension(&self) -> &'static str {
+        "cbor"
+    }
+}
+
+// MessagePack, through rmp-serde. Structs are written as arrays, without field names.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackCodec;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePackCodec {
+    fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError> {
+        rmp_serde::to_vec(data).map_err(|e| FramePoolError::Codec(e.to_string()))
+    }
+
+    fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, FramePoolError> {
+        rmp_serde::from_slice(bytes).map_err(|e| FramePoolError::Codec(e.to_string()))
+    }
+
+    fn extension(&self) -> &'static str {
+        "msgpack"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Page {
+        id: u64,
+        values: Vec<f64>,
+        tags: HashMap<String, i32>,
+    }
+
+    fn sample() -> Page {
+        Page {
+            id: 7,
+            values: vec![1.5, -2.25, 1e10],
+            tags: HashMap::from([("a".to_string(), 1), ("b".to_string(), -1)]),
+        }
+    }
+
+    fn roundtrip<C: Codec>(codec: C) -> usize {
+        let bytes = codec.encode(&sample()).unwrap();
+        assert_eq!(codec.decode::<Page>(&bytes).unwrap(), sample());
+        assert!(codec.decode::<Page>(&bytes[..bytes.len() / 2]).is_err());
+        bytes.len()
+    }
+
+    #[test]
+    fn test_codecs_roundtrip() {
+        let json = roundtrip(JsonCodec::default());
+        assert!(roundtrip(JsonCodec::pretty()) > json);
+        #[cfg(feature = "bincode")]
+        roundtrip(BincodeCodec);
+        #[cfg(feature = "cbor")]
+        assert!(roundtrip(CborCodec) < json);
+        #[cfg(feature = "msgpack")]
+        assert!(roundtrip(MessagePackCodec) < json);
+    }
+}
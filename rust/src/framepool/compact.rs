@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{
+    Codec, DiskPool, FramePoolError, FrameState, page_file_state, page_files, write_atomic,
+};
+
+// How far a compact has got, as reported to its progress callback after each page.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactProgress {
+    // pages dealt with: for a PagedFile copied into the compacted file, for a DiskPool moved
+    pub done: u64,
+    pub total: u64,
+}
+
+// What a compact did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Compaction {
+    // the pages given new ids, as (old id, new id), in ascending order
+    pub moved: Vec<(u64, u64)>,
+    // bytes of disk given back
+    pub reclaimed: u64,
+}
+
+// The pages to move to compact a pool of written pages (in ascending order) into the lowest
+// ids: those past the new end, into the gaps before it, lowest first.
+pub(super) fn plan_moves(written: &[u64]) -> Vec<(u64, u64)> {
+    let live = written.len() as u64;
+    let kept: HashSet<u64> = written.iter().copied().filter(|id| *id < live).collect();
+    let gaps = (0..live).filter(|id| !kept.contains(id));
+    written
+        .iter()
+        .copied()
+        .filter(|id| *id >= live)
+        .zip(gaps)
+        .collect()
+}
+
+// Where a compacted copy of the file at path is built: beside it, under a temporary's name.
+pub(super) fn staging_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.compact.tmp", name))
+}
+
+// The journal DiskPool::compact keeps in the pool's directory while it moves pages.
+const COMPACT_JOURNAL: &str = "compact.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Journal {
+    // the pool's size once compacted
+    size: u64,
+    moved: Vec<(u64, u64)>,
+}
+
+impl<C: Codec> DiskPool<C> {
+    // Renumbers the pool's pages so the written ones take the lowest ids, and shrinks the pool
+    // to them: pages past the new end move into the unwritten frames before it, and are
+    // listed in the Compaction returned, for whatever records their ids. The placeholders
+    // resize used to write go, and so does everything gc deletes. progress is told how many
+    // pages have been moved after each.
+    //
+    // The moves are journaled, and the journal synced, before any is made, whatever the
+    // pool's durability, and the pages are synced before the pool's new size is recorded. A
+    // compaction a crash interrupts is finished when the pool is next opened for writing.
+    pub fn compact(
+        &mut self,
+        mut progress: impl FnMut(&CompactProgress),
+    ) -> Result<Compaction, FramePoolError> {
+        self.check_writable()?;
+        self.initialize()?;
+        let written: Vec<u64> = page_files(&self.dirname)?
+            .into_iter()
+            .filter(|(idx, path)| {
+                *idx < self.size && page_file_state(path) == FrameState::Populated
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        let journal = Journal {
+            size: written.len() as u64,
+            moved: plan_moves(&written),
+        };
+        let path = self.dirname.join(COMPACT_JOURNAL);
+        write_atomic(&path, &serde_json::to_vec(&journal)?, true)?;
+        let moved = self.finish_compaction(journal, &mut progress)?;
+        let dirname = self.dirname.clone();
+        let reclaimed = self.collect_garbage(&dirname)?;
+        Ok(Compaction { moved, reclaimed })
+    }
+
+    // Finishes a compaction a crash interrupted, if the directory holds its journal.
+    pub(super) fn finish_interrupted_compaction(&mut self) -> Result<(), FramePoolError> {
+        let bytes = match fs::read(self.dirname.join(COMPACT_JOURNAL)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        self.finish_compaction(serde_json::from_slice(&bytes)?, &mut |_| {})?;
+        Ok(())
+    }
+
+    // Makes the journal's moves, passing over those made before a crash, records the pool's
+    // new size and drops the journal.
+    fn finish_compaction(
+        &mut self,
+        journal: Journal,
+        progress: &mut dyn FnMut(&CompactProgress),
+    ) -> Result<Vec<(u64, u64)>, FramePoolError> {
+        let mut done = CompactProgress {
+            done: 0,
+            total: journal.moved.len() as u64,
+        };
+        for (from, to) in journal.moved.iter() {
+            let (source, target) = (self.page_path(*from), self.page_path(*to));
+            if source.exists() {
+                self.make_parent(&target)?;
+                fs::rename(&source, &target)?;
+            }
+            self.unsynced.remove(from);
+            self.mark_unsynced(*to);
+            done.done += 1;
+            progress(&done);
+        }
+        self.sync()?;
+        self.size = journal.size;
+        self.store_manifest(true)?;
+        fs::remove_file(self.dirname.join(COMPACT_JOURNAL))?;
+        Ok(journal.moved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::FramePool;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_plan_moves() {
+        assert_eq!(plan_moves(&[0, 2, 5, 7]), vec![(5, 1), (7, 3)]);
+        assert!(plan_moves(&[0, 1, 2]).is_empty());
+        assert_eq!(plan_moves(&[9]), vec![(9, 0)]);
+        assert!(plan_moves(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_diskpool_compact() {
+        let test_dir = "/tmp/test_diskpool_compact";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = DiskPool::new::<u32>(test_dir).with_fanout(1);
+        FramePool::<u32>::resize(&mut pool, 10).unwrap();
+        for i in [0, 1, 2, 5, 7] {
+            pool.put_frame(i, Arc::new(i as u32 * 10)).unwrap();
+        }
+        FramePool::<u32>::discard_frame(&mut pool, &1).unwrap();
+        assert_eq!(FramePool::<u32>::frame_state(&pool, &1), FrameState::Empty);
+
+        let mut reports = Vec::new();
+        let compaction = pool.compact(|p| reports.push(*p)).unwrap();
+        assert_eq!(compaction.moved, vec![(5, 1), (7, 3)]);
+        assert_eq!(
+            reports.last().unwrap(),
+            &CompactProgress { done: 2, total: 2 }
+        );
+        assert_eq!(FramePool::<u32>::size(&pool), 4);
+        let values: Vec<u32> = (0..4)
+            .map(|i| *FramePool::<u32>::get_frame_ref(&mut pool, i).unwrap())
+            .collect();
+        assert_eq!(values, vec![0, 50, 20, 70]);
+        assert!(!Path::new(test_dir).join(COMPACT_JOURNAL).exists());
+        assert_eq!(pool.compact(|_| {}).unwrap(), Compaction::default());
+        drop(pool);
+
+        // A compaction interrupted after its first move is finished on the next open
+        let mut pool = DiskPool::new::<u32>(test_dir).with_fanout(1);
+        FramePool::<u32>::resize(&mut pool, 4).unwrap();
+        pool.put_frame(6, Arc::new(60u32)).unwrap();
+        pool.put_frame(7, Arc::new(61u32)).unwrap();
+        FramePool::<u32>::discard_frame(&mut pool, &2).unwrap();
+        let (from, to) = (pool.page_path(6), pool.page_path(2));
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        fs::rename(from, to).unwrap();
+        let journal = Journal {
+            size: 5,
+            moved: vec![(6, 2), (7, 4)],
+        };
+        let path = Path::new(test_dir).join(COMPACT_JOURNAL);
+        fs::write(&path, serde_json::to_vec(&journal).unwrap()).unwrap();
+        drop(pool);
+
+        let mut pool = DiskPool::new::<u32>(test_dir).with_fanout(1);
+        assert_eq!(*FramePool::<u32>::get_frame_ref(&mut pool, 2).unwrap(), 60);
+        assert_eq!(*FramePool::<u32>::get_frame_ref(&mut pool, 4).unwrap(), 61);
+        assert_eq!(FramePool::<u32>::size(&pool), 5);
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(test_dir);
+    }
+}
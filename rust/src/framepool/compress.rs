@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Codec, FramePoolError, JsonCodec};
+
+// The compression a Compressed codec applies to the pages it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    // pages are stored as the inner codec wrote them, behind the header byte
+    None,
+    // zstd at the given level; 0 means zstd's default
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+// The header byte in front of every page, saying how the rest of it is stored.
+const RAW: u8 = 0;
+const ZSTD: u8 = 1;
+const LZ4: u8 = 2;
+
+// Wraps a codec, compressing what it encodes and decompressing before it decodes. Each page
+// starts with a header byte naming its compression, so pages written with different settings,
+// or left uncompressed because compressing didn't make them smaller, read back alike. Pages
+// must have been written through a Compressed codec; plain pages have no header.
+#[derive(Debug, Clone, Copy)]
+pub struct Compressed<C = JsonCodec> {
+    inner: C,
+    compression: Compression,
+}
+
+impl<C: Codec> Compressed<C> {
+    pub fn new(inner: C, compression: Compression) -> Self {
+        Compressed { inner, compression }
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+}
+
+fn corrupt(e: impl std::fmt::Display) -> FramePoolError {
+    FramePoolError::Corruption(format!("page does not decompress: {}", e))
+}
+
+#[cfg(not(all(feature = "zstd", feature = "lz4")))]
+fn not_built(algorithm: &str) -> FramePoolError {
+    FramePoolError::Unsupported(format!(
+        "page compressed with {}, which this build does not include",
+        algorithm
+    ))
+}
+
+impl<C: Codec> Codec for Compressed<C> {
+    fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError> {
+        let raw = self.inner.encode(data)?;
+        let (flag, packed): (u8, Option<Vec<u8>>) = match self.compression {
+            Compression::None => (RAW, None),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(level) => (ZSTD, Some(zstd::bulk::compress(&raw, level)?)),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => (LZ4, Some(lz4_flex::compress_prepend_size(&raw))),
+        };
+        let (flag, body) = match packed {
+            Some(packed) if packed.len() < raw.len() => (flag, packed),
+            _ => (RAW, raw),
+        };
+        let mut page = Vec::with_capacity(body.len() + 1);
+        page.push(flag);
+        page.extend_from_slice(&body);
+        Ok(page)
+    }
+
+    fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, FramePoolError> {
+        let Some((&flag, body)) = bytes.split_first() else {
+            return Err(FramePoolError::Corruption(
+                "page has no compression header".to_string(),
+            ));
+        };
+        match flag {
+            RAW => self.inner.decode(body),
+            ZSTD => {
+                #[cfg(feature = "zstd")]
+                {
+                    let raw = zstd::stream::decode_all(body).map_err(corrupt)?;
+                    self.inner.decode(&raw)
+                }
+                #[cfg(not(feature = "zstd"))]
+                Err(not_built("zstd"))
+            }
+            LZ4 => {
+                #[cfg(feature = "lz4")]
+                {
+                    let raw = lz4_flex::decompress_size_prepended(body).map_err(corrupt)?;
+                    self.inner.decode(&raw)
+                }
+                #[cfg(not(feature = "lz4"))]
+                Err(not_built("lz4"))
+            }
+            _ => Err(corrupt(format!("unknown compression header {}", flag))),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        self.inner.extension()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{DiskPool, FramePool};
+    use std::sync::Arc;
+
+    fn settings() -> Vec<Compression> {
+        vec![
+            Compression::None,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd(0),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4,
+        ]
+    }
+
+    #[test]
+    fn test_compressed_codec_roundtrip() {
+        let data = vec![7u32; 1000];
+        let plain = JsonCodec::default().encode(&data).unwrap();
+        for compression in settings() {
+            let codec = Compressed::new(JsonCodec::default(), compression);
+            let page = codec.encode(&data).unwrap();
+            if compression == Compression::None {
+                assert_eq!(page[0], RAW);
+                assert_eq!(page[1..], plain[..]);
+            } else {
+                assert!(page.len() < plain.len() / 10);
+            }
+            // Every setting reads every other's pages
+            for other in settings() {
+                let reader = Compressed::new(JsonCodec::default(), other);
+                assert_eq!(reader.decode::<Vec<u32>>(&page).unwrap(), data);
+            }
+        }
+
+        // Incompressible data is stored raw
+        let codec = Compressed::new(JsonCodec::default(), *settings().last().unwrap());
+        assert_eq!(codec.encode(&1u8).unwrap(), vec![RAW, b'1']);
+        assert!(matches!(
+            codec.decode::<u8>(&[]),
+            Err(FramePoolError::Corruption(_))
+        ));
+        assert!(matches!(
+            codec.decode::<u8>(&[9, b'1']),
+            Err(FramePoolError::Corruption(_))
+        ));
+    }
+
+    #[test]
+    fn test_compressed_diskpool() {
+        let test_dir = "/tmp/test_compressed_diskpool";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let compression = *settings().last().unwrap();
+        let mut pool = DiskPool::new::<Vec<u64>>(test_dir)
+            .with_codec(Compressed::new(JsonCodec::default(), compression));
+        FramePool::<Vec<u64>>::resize(&mut pool, 2).unwrap();
+        pool.put_frame(1, Arc::new(vec![42u64; 500])).unwrap();
+        assert_eq!(
+            *FramePool::<Vec<u64>>::get_frame_ref(&mut pool, 1).unwrap(),
+            vec![42; 500]
+        );
+        if compression != Compression::None {
+            let meta = FramePool::<Vec<u64>>::frame_meta(&pool, &1).unwrap();
+            assert!(meta.size < 100);
+        }
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+}
@@ -0,0 +1,127 @@
+use super::{FramePool, FramePoolError, FrameState};
+
+// How far a copy_pool or resume_copy has got, as reported to its progress callback after
+// each batch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CopyProgress {
+    // frames written to the destination
+    pub copied: u64,
+    // frames passed over: never written in the source, or, resuming, already in the destination
+    pub skipped: u64,
+    // frames in the source, written or not
+    pub total: u64,
+}
+
+// Frames read and written, and the destination synced, at a time.
+const COPY_BATCH: usize = 64;
+
+// Copies every frame of src into dst, whatever pools they are (a MemPool into a DiskPool, a
+// JSON DiskPool into a bincode one, ...), growing dst to src's size. Frames are copied in
+// batches of ids in ascending order, dst is synced after each, and progress is told how far
+// the copy has got. Frames src holds no data for are left alone in dst.
+//
+// On failure the frames of the batches before are in dst and durable, and resume_copy
+// picks up from there.
+pub fn copy_pool<T: Clone>(
+    src: &mut dyn FramePool<T>,
+    dst: &mut dyn FramePool<T>,
+    progress: impl FnMut(&CopyProgress),
+) -> Result<CopyProgress, FramePoolError> {
+    copy_frames(src, dst, false, progress)
+}
+
+// copy_pool, skipping the frames dst already holds data for: those an interrupted copy got to.
+// A frame changed in src since it was copied is not copied again.
+pub fn resume_copy<T: Clone>(
+    src: &mut dyn FramePool<T>,
+    dst: &mut dyn FramePool<T>,
+    progress: impl FnMut(&CopyProgress),
+) -> Result<CopyProgress, FramePoolError> {
+    copy_frames(src, dst, true, progress)
+}
+
+fn copy_frames<T: Clone>(
+    src: &mut dyn FramePool<T>,
+    dst: &mut dyn FramePool<T>,
+    resume: bool,
+    mut progress: impl FnMut(&CopyProgress),
+) -> Result<CopyProgress, FramePoolError> {
+    let ids = src.frame_ids()?;
+    let mut done = CopyProgress {
+        total: ids.len() as u64,
+        ..CopyProgress::default()
+    };
+    dst.grow_to(src.size())?;
+    for batch in ids.chunks(COPY_BATCH) {
+        let wanted: Vec<u64> = batch
+            .iter()
+            .copied()
+            .filter(|id| src.frame_state(id) == FrameState::Populated)
+            .filter(|id| !(resume && dst.exists(id)))
+            .collect();
+        done.skipped += (batch.len() - wanted.len()) as u64;
+        let mut frames = Vec::with_capacity(wanted.len());
+        for (id, read) in wanted.iter().zip(src.get_frames(&wanted)) {
+            frames.push((*id, read?));
+        }
+        for written in dst.put_frames(frames) {
+            written?;
+        }
+        dst.sync()?;
+        done.copied += wanted.len() as u64;
+        progress(&done);
+    }
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{DiskPool, FaultInjector, FaultyPool, MemPool};
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_copy_pool_mem_to_disk_and_resume() {
+        let test_dir = "/tmp/test_copy_pool";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut src = MemPool::<u64>::new();
+        src.resize(200).unwrap();
+        for i in (0..200).filter(|i| i % 10 != 0) {
+            src.put_frame(i, Arc::new(i * 7)).unwrap();
+        }
+
+        // The copy fails part way through
+        let faults = FaultInjector::new(1);
+        let mut dst = FaultyPool::new(DiskPool::new::<u64>(test_dir), faults.clone());
+        faults.fail_after(Some(100));
+        let mut reports = Vec::new();
+        assert!(copy_pool(&mut src, &mut dst, |done| reports.push(*done)).is_err());
+        let before = *reports.last().unwrap();
+        assert!(before.copied > 0 && before.copied < 180);
+
+        // and resuming it copies only what's missing, which is less than the batches reported
+        // done missed, as the failed batch got part way too
+        faults.fail_after(None);
+        let done = resume_copy(&mut src, &mut dst, |_| ()).unwrap();
+        assert_eq!(done.total, 200);
+        assert!(done.copied < 180 - before.copied);
+        assert_eq!(done.copied + done.skipped, 200);
+
+        let mut dst = dst.into_inner();
+        assert_eq!(FramePool::<u64>::size(&dst), 200);
+        assert_eq!(
+            *FramePool::<u64>::get_frame_ref(&mut dst, 199).unwrap(),
+            199 * 7
+        );
+        assert_eq!(FramePool::<u64>::frame_state(&dst, &10), FrameState::Empty);
+
+        // A second, full copy copies everything again
+        let mut reports = 0;
+        let done = copy_pool(&mut src, &mut dst, |_| reports += 1).unwrap();
+        assert_eq!((done.copied, done.skipped), (180, 20));
+        assert_eq!(reports, 4);
+        let _ = fs::remove_dir_all(test_dir);
+    }
+}
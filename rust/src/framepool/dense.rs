@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState};
+
+// A memory-only FramePool for contiguous frame ids, stored in a Vec indexed by id. Lookups are
+// a bounds check and an index, and a scan walks memory in order, so it beats MemPool's
+// HashMap whenever ids are dense. Use MemPool when ids are sparse: writing frame n here
+// allocates every id below it.
+pub struct DenseMemPool<T> {
+    frames: Vec<Option<Arc<T>>>,
+}
+
+impl<T> DenseMemPool<T> {
+    pub fn new() -> Self {
+        DenseMemPool { frames: Vec::new() }
+    }
+
+    // A pool with room for capacity frames before it reallocates.
+    pub fn with_capacity(capacity: usize) -> Self {
+        DenseMemPool {
+            frames: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+impl<T> Default for DenseMemPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FramePool<T> for DenseMemPool<T>
+where
+    T: Clone,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        match self.frames.get(idx as usize) {
+            Some(Some(data)) => Ok(Arc::clone(data)),
+            Some(None) => Err(FramePoolError::NotFound(
+                "frame slot exists but is empty".to_string(),
+            )),
+            None => Err(FramePoolError::NotFound("no such frame".to_string())),
+        }
+    }
+
+    // Writing past the end allocates every frame in between.
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let idx = idx as usize;
+        if idx >= self.frames.len() {
+            self.frames.resize(idx + 1, None);
+        }
+        self.frames[idx] = Some(data);
+        Ok(())
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.frames.resize(self.frames.len() + count as usize, None);
+        Ok(())
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.frames.truncate(count as usize);
+        Ok(())
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        match self.frames.get_mut(*idx as usize) {
+            Some(frame) => {
+                *frame = None;
+                Ok(())
+            }
+            None => Err(FramePoolError::NotFound("no such frame".to_string())),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.frames.len() as u64
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        Ok(self.frames.len() as u64)
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        match self.frames.get(*idx as usize) {
+            Some(Some(_)) => FrameState::Populated,
+            Some(None) => FrameState::Empty,
+            None => FrameState::Absent,
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        Ok((0..self.frames.len() as u64).collect())
+    }
+
+    // Sizes are the shallow size of T, as for MemPool. No write times are kept.
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        match self.frames.get(*idx as usize) {
+            Some(frame) => Ok(FrameMeta {
+                size: if frame.is_some() {
+                    std::mem::size_of::<T>() as u64
+                } else {
+                    0
+                },
+                modified: None,
+                checksum: None,
+            }),
+            None => Err(FramePoolError::NotFound("no such frame".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+
+    #[test]
+    fn test_dense_mempool_read_write() {
+        let mut pool = DenseMemPool::<u64>::new();
+        pool.resize(3).unwrap();
+        assert_eq!(pool.size(), 3);
+        pool.put_frame(1, Arc::new(10)).unwrap();
+        assert_eq!(*pool.get_frame_ref(1).unwrap(), 10);
+        assert!(matches!(
+            pool.get_frame_ref(0),
+            Err(FramePoolError::NotFound(_))
+        ));
+        assert!(matches!(
+            pool.get_frame_ref(3),
+            Err(FramePoolError::NotFound(_))
+        ));
+        assert_eq!(pool.frame_state(&0), FrameState::Empty);
+        assert_eq!(pool.frame_state(&1), FrameState::Populated);
+
+        // Writing past the end grows the pool
+        pool.put_frame(5, Arc::new(50)).unwrap();
+        assert_eq!(pool.size(), 6);
+        assert_eq!(pool.frame_state(&4), FrameState::Empty);
+
+        pool.discard_frame(&5).unwrap();
+        assert_eq!(pool.frame_state(&5), FrameState::Empty);
+        assert!(pool.discard_frame(&6).is_err());
+
+        pool.truncate(2).unwrap();
+        assert_eq!(pool.frame_ids().unwrap(), vec![0, 1]);
+        pool.grow_to(4).unwrap();
+        assert_eq!(pool.size(), 4);
+        assert!(!pool.exists(&3));
+    }
+
+    #[test]
+    fn test_dense_mempool_under_bufferpool() {
+        let mut pool = DenseMemPool::with_capacity(10);
+        pool.resize(10).unwrap();
+        for i in 0..10 {
+            pool.put_frame(i, Arc::new(i)).unwrap();
+        }
+        let mut bp = BufferPool::<u64>::new(3, &mut pool, bottom_evictor);
+        let all: Vec<u64> = (&mut bp).into_iter().collect();
+        assert_eq!(all, (0..10).collect::<Vec<_>>());
+        bp.put_page(4, 40).unwrap();
+        bp.flush_all().unwrap();
+        drop(bp);
+        assert_eq!(*pool.get_frame_ref(4).unwrap(), 40);
+    }
+}
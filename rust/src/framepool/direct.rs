@@ -0,0 +1,95 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+
+// The alignment O_DIRECT asks of file offsets, transfer lengths and memory buffers. Devices
+// have logical blocks of 512 or 4096 bytes; this suits either.
+pub const DIRECT_IO_ALIGN: usize = 4096;
+
+// Opens files with O_DIRECT, so their reads and writes go straight to the device rather than
+// through the OS page cache.
+pub(super) fn o_direct(options: &mut OpenOptions) -> &mut OpenOptions {
+    options.custom_flags(libc::O_DIRECT)
+}
+
+// A zeroed buffer of len bytes at an aligned address.
+struct AlignedBuf {
+    bytes: Vec<u8>,
+    start: usize,
+    len: usize,
+}
+
+impl AlignedBuf {
+    fn new(len: usize) -> Self {
+        let bytes = vec![0; len + DIRECT_IO_ALIGN];
+        let start = bytes.as_ptr().align_offset(DIRECT_IO_ALIGN);
+        AlignedBuf { bytes, start, len }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[self.start..self.start + self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes[self.start..self.start + self.len]
+    }
+}
+
+// The start and length of the aligned blocks covering len bytes at offset.
+fn blocks(offset: u64, len: usize) -> (u64, usize) {
+    let align = DIRECT_IO_ALIGN as u64;
+    let start = offset / align * align;
+    let end = (offset + len as u64).div_ceil(align) * align;
+    (start, (end - start) as usize)
+}
+
+// read_at and write_at for a file opened with O_DIRECT, for any offset and length: the
+// blocks covering them are read whole, and a write to part of a block reads it in first.
+// The blocks must lie within the file.
+pub(super) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    let (start, len) = blocks(offset, buf.len());
+    let mut block = AlignedBuf::new(len);
+    file.read_exact_at(block.as_mut_slice(), start)?;
+    let at = (offset - start) as usize;
+    buf.copy_from_slice(&block.as_slice()[at..at + buf.len()]);
+    Ok(())
+}
+
+pub(super) fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    let (start, len) = blocks(offset, buf.len());
+    let mut block = AlignedBuf::new(len);
+    if start != offset || len != buf.len() {
+        file.read_exact_at(block.as_mut_slice(), start)?;
+    }
+    let at = (offset - start) as usize;
+    block.as_mut_slice()[at..at + buf.len()].copy_from_slice(buf);
+    file.write_all_at(block.as_slice(), start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_direct_io_unaligned_access() {
+        let path = "/tmp/test_direct_io.bin";
+        let file = o_direct(OpenOptions::new().read(true).write(true).create(true))
+            .truncate(true)
+            .open(path)
+            .unwrap();
+        file.set_len(3 * DIRECT_IO_ALIGN as u64).unwrap();
+
+        // A write straddling a block boundary leaves the rest of both blocks alone
+        write_at(&file, &[7; 4096], 0).unwrap();
+        write_at(&file, b"straddle", 4092).unwrap();
+        let mut buf = [0; 12];
+        read_at(&file, &mut buf, 4090).unwrap();
+        assert_eq!(&buf, b"\x07\x07straddle\0\0");
+        write_at(&file, &[1; 4096], 8192).unwrap();
+        let mut page = vec![0; 4096];
+        read_at(&file, &mut page, 8192).unwrap();
+        assert_eq!(page, vec![1; 4096]);
+        assert_eq!(blocks(4092, 8), (0, 8192));
+        let _ = std::fs::remove_file(path);
+    }
+}
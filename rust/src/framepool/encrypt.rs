@@ -0,0 +1,299 @@
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use serde::{Deserialize, Serialize};
+
+use super::{Codec, FramePoolError, JsonCodec};
+
+// Supplies the keys an Encrypted codec seals and opens pages with. Every page records the id
+// of the key it was sealed with, so keys can be rotated: new writes use current_key_id, and
+// older pages stay readable as long as their key can still be looked up.
+pub trait KeyProvider {
+    fn current_key_id(&self) -> u32;
+    // the 256-bit key with the given id
+    fn key(&self, key_id: u32) -> Result<[u8; 32], FramePoolError>;
+}
+
+// A single key, with id 0.
+#[derive(Clone)]
+pub struct StaticKey([u8; 32]);
+
+impl StaticKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        StaticKey(key)
+    }
+}
+
+// Never print the key.
+impl std::fmt::Debug for StaticKey {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("StaticKey(..)")
+    }
+}
+
+impl KeyProvider for StaticKey {
+    fn current_key_id(&self) -> u32 {
+        0
+    }
+
+    fn key(&self, key_id: u32) -> Result<[u8; 32], FramePoolError> {
+        match key_id {
+            0 => Ok(self.0),
+            _ => Err(FramePoolError::NotFound(format!("key {}", key_id))),
+        }
+    }
+}
+
+// The AEAD cipher an Encrypted codec seals new pages with. Pages sealed with either cipher
+// read back whichever one is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn tag(self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 1,
+            Cipher::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(Cipher::Aes256Gcm),
+            2 => Some(Cipher::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+}
+
+// Page header: format version, cipher, key id (little-endian) and nonce. The whole header is
+// authenticated along with the page, so none of it can be altered without the page failing
+// to open.
+const VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = 2 + 4 + NONCE_LEN;
+
+// Wraps a codec, sealing what it encodes with an AEAD cipher under a fresh random nonce per
+// page, and opening pages before they are decoded. A page that was altered, truncated or
+// sealed under another key fails with Corruption rather than decoding to garbage.
+//
+// Pages are not bound to their frame id, so someone able to write the directory can swap two
+// pages sealed under the same key. To compress as well, wrap a Compressed codec: encrypted
+// bytes don't compress.
+pub struct Encrypted<K, C = JsonCodec> {
+    inner: C,
+    keys: K,
+    cipher: Cipher,
+}
+
+impl<K: KeyProvider, C: Codec> Encrypted<K, C> {
+    pub fn new(inner: C, keys: K, cipher: Cipher) -> Self {
+        Encrypted {
+            inner,
+            keys,
+            cipher,
+        }
+    }
+
+    pub fn keys(&self) -> &K {
+        &self.keys
+    }
+}
+
+fn seal(cipher: Cipher, key: &[u8; 32], nonce: &[u8], payload: Payload) -> Option<Vec<u8>> {
+    match cipher {
+        Cipher::Aes256Gcm => Aes256Gcm::new(key.into())
+            .encrypt(nonce.into(), payload)
+            .ok(),
+        Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into())
+            .encrypt(nonce.into(), payload)
+            .ok(),
+    }
+}
+
+fn open(cipher: Cipher, key: &[u8; 32], nonce: &[u8], payload: Payload) -> Option<Vec<u8>> {
+    match cipher {
+        Cipher::Aes256Gcm => Aes256Gcm::new(key.into())
+            .decrypt(nonce.into(), payload)
+            .ok(),
+        Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into())
+            .decrypt(nonce.into(), payload)
+            .ok(),
+    }
+}
+
+impl<K: KeyProvider, C: Codec> Codec for Encrypted<K, C> {
+    fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError> {
+        let plain = self.inner.encode(data)?;
+        let key_id = self.keys.current_key_id();
+        let key = self.keys.key(key_id)?;
+
+        let mut page = Vec::with_capacity(HEADER_LEN + plain.len() + 16);
+        page.push(VERSION);
+        page.push(self.cipher.tag());
+        page.extend_from_slice(&key_id.to_le_bytes());
+        let mut nonce = [0; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        page.extend_from_slice(&nonce);
+
+        let payload = Payload {
+            msg: &plain,
+            aad: &page,
+        };
+        let sealed = seal(self.cipher, &key, &nonce, payload)
+            .ok_or_else(|| FramePoolError::Codec("page could not be encrypted".to_string()))?;
+        page.extend_from_slice(&sealed);
+        Ok(page)
+    }
+
+    fn decode<T: for<'de> Deserialize<'de>>(&self, bytes: &[u8]) -> Result<T, FramePoolError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(FramePoolError::Corruption(
+                "page is too short to be encrypted".to_string(),
+            ));
+        }
+        let (header, sealed) = bytes.split_at(HEADER_LEN);
+        if header[0] != VERSION {
+            return Err(FramePoolError::Corruption(format!(
+                "unknown encrypted page version {}",
+                header[0]
+            )));
+        }
+        let cipher = Cipher::from_tag(header[1]).ok_or_else(|| {
+            FramePoolError::Corruption(format!("unknown page cipher {}", header[1]))
+        })?;
+        let key_id = u32::from_le_bytes(header[2..6].try_into().unwrap());
+        let key = self.keys.key(key_id)?;
+
+        let payload = Payload {
+            msg: sealed,
+            aad: header,
+        };
+        let plain = open(cipher, &key, &header[6..], payload)
+            .ok_or_else(|| FramePoolError::Corruption("page failed authentication".to_string()))?;
+        self.inner.decode(&plain)
+    }
+
+    fn extension(&self) -> &'static str {
+        self.inner.extension()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{DiskPool, FileBackend, FramePool};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    // Two keys, rotated from 1 to 2
+    struct Rotating {
+        current: u32,
+        keys: HashMap<u32, [u8; 32]>,
+    }
+
+    impl KeyProvider for Rotating {
+        fn current_key_id(&self) -> u32 {
+            self.current
+        }
+
+        fn key(&self, key_id: u32) -> Result<[u8; 32], FramePoolError> {
+            self.keys
+                .get(&key_id)
+                .copied()
+                .ok_or_else(|| FramePoolError::NotFound(format!("key {}", key_id)))
+        }
+    }
+
+    #[test]
+    fn test_encrypted_codec_roundtrip_and_tamper() {
+        let secret = "user secret".to_string();
+        for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+            let codec = Encrypted::new(JsonCodec::default(), StaticKey::new([7; 32]), cipher);
+            let page = codec.encode(&secret).unwrap();
+            assert!(!page.windows(6).any(|w| w == b"secret"));
+            assert_eq!(codec.decode::<String>(&page).unwrap(), secret);
+            // A fresh nonce each time
+            assert_ne!(codec.encode(&secret).unwrap(), page);
+
+            // Flipping any bit, header or body, is caught
+            for pos in [1, 3, 10, HEADER_LEN, page.len() - 1] {
+                let mut tampered = page.clone();
+                tampered[pos] ^= 0x04;
+                assert!(codec.decode::<String>(&tampered).is_err());
+            }
+            assert!(matches!(
+                codec.decode::<String>(&page[..page.len() - 1]),
+                Err(FramePoolError::Corruption(_))
+            ));
+
+            let wrong = Encrypted::new(JsonCodec::default(), StaticKey::new([8; 32]), cipher);
+            assert!(matches!(
+                wrong.decode::<String>(&page),
+                Err(FramePoolError::Corruption(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_encrypted_diskpool_key_rotation() {
+        let test_dir = "/tmp/test_encrypted_diskpool";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut keys = Rotating {
+            current: 1,
+            keys: HashMap::from([(1, [1; 32])]),
+        };
+        let mut pool = DiskPool::new::<Vec<u32>>(test_dir).with_codec(Encrypted::new(
+            JsonCodec::default(),
+            keys,
+            Cipher::Aes256Gcm,
+        ));
+        pool.put_frame(0, Arc::new(vec![1u32, 2, 3])).unwrap();
+        let plain = std::fs::read(format!("{}/page_0", test_dir)).unwrap();
+        assert!(!plain.windows(5).any(|w| w == b"1,2,3"));
+
+        keys = Rotating {
+            current: 2,
+            keys: HashMap::from([(1, [1; 32]), (2, [2; 32])]),
+        };
+        let mut pool = pool.with_codec(Encrypted::new(
+            JsonCodec::default(),
+            keys,
+            Cipher::ChaCha20Poly1305,
+        ));
+        pool.put_frame(1, Arc::new(vec![4u32])).unwrap();
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 0).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 1).unwrap(),
+            vec![4]
+        );
+
+        // Without the old key, its pages can't be read
+        let mut pool = pool.with_codec(Encrypted::new(
+            JsonCodec::default(),
+            StaticKey::new([2; 32]),
+            Cipher::Aes256Gcm,
+        ));
+        assert!(matches!(
+            FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 0),
+            Err(FramePoolError::NotFound(_))
+        ));
+
+        let mut backend = FileBackend::new(test_dir).with_codec(Encrypted::new(
+            JsonCodec::pretty(),
+            StaticKey::new([3; 32]),
+            Cipher::Aes256Gcm,
+        ));
+        backend.write_data("k", Arc::new(5u8)).unwrap();
+        assert_eq!(*backend.read_data::<u8>("k").unwrap(), 5);
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+}
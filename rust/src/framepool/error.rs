@@ -0,0 +1,158 @@
+use std::fmt;
+use std::io;
+
+// The ways a FramePool or StorageBackend operation can fail.
+#[derive(Debug)]
+pub enum FramePoolError {
+    // there is no frame (or key) by that name; the message says which
+    NotFound(String),
+    // the underlying storage failed
+    Io(io::Error),
+    // a frame could not be serialized or deserialized
+    Serde(serde_json::Error),
+    // a non-JSON codec could not encode or decode a frame
+    Codec(String),
+    // stored data or pool state is unusable
+    Corruption(String),
+    // a stored page does not match its checksum
+    CorruptPage { idx: u64 },
+    // the frame id is beyond the size of the pool
+    OutOfBounds(u64),
+    // a write was attempted on a read-only pool
+    ReadOnly,
+    // the pool does not support the operation
+    Unsupported(String),
+    // a write would take the pool past its byte limit
+    CapacityExceeded { needed: u64, limit: u64 },
+    // another pool has the storage locked; the message says which
+    Locked(String),
+    // the pool holds pages of another type than the one asked for
+    TypeMismatch { stored: String, requested: String },
+    // a page's data was left part way through a modification by a panic; the message is the
+    // panic's
+    Poisoned(String),
+}
+
+impl fmt::Display for FramePoolError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FramePoolError::NotFound(what) => write!(fmt, "Not found: {}", what),
+            FramePoolError::Io(e) => write!(fmt, "I/O error: {}", e),
+            FramePoolError::Serde(e) => write!(fmt, "Serialization error: {}", e),
+            FramePoolError::Codec(msg) => write!(fmt, "Codec error: {}", msg),
+            FramePoolError::Corruption(msg) => write!(fmt, "Corrupt pool: {}", msg),
+            FramePoolError::CorruptPage { idx } => {
+                write!(fmt, "Page {} does not match its checksum", idx)
+            }
+            FramePoolError::OutOfBounds(idx) => write!(fmt, "Frame {} is out of bounds", idx),
+            FramePoolError::ReadOnly => write!(fmt, "Pool is read-only"),
+            FramePoolError::Unsupported(what) => write!(fmt, "Unsupported: {}", what),
+            FramePoolError::CapacityExceeded { needed, limit } => write!(
+                fmt,
+                "Capacity exceeded: {} bytes needed, limit is {}",
+                needed, limit
+            ),
+            FramePoolError::Locked(what) => write!(fmt, "Locked: {}", what),
+            FramePoolError::TypeMismatch { stored, requested } => write!(
+                fmt,
+                "Type mismatch: pool holds {} pages, not {}",
+                stored, requested
+            ),
+            FramePoolError::Poisoned(panic) => {
+                write!(
+                    fmt,
+                    "Poisoned: a modification of the page panicked: {}",
+                    panic
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for FramePoolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FramePoolError::Io(e) => Some(e),
+            FramePoolError::Serde(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// Neither io::Error nor serde_json::Error is Clone, so a clone keeps their kind and message
+// but not their source.
+impl Clone for FramePoolError {
+    fn clone(&self) -> Self {
+        match self {
+            FramePoolError::NotFound(what) => FramePoolError::NotFound(what.clone()),
+            FramePoolError::Io(e) => FramePoolError::Io(io::Error::new(e.kind(), e.to_string())),
+            FramePoolError::Serde(e) => {
+                FramePoolError::Serde(serde::de::Error::custom(e.to_string()))
+            }
+            FramePoolError::Codec(msg) => FramePoolError::Codec(msg.clone()),
+            FramePoolError::Corruption(msg) => FramePoolError::Corruption(msg.clone()),
+            FramePoolError::CorruptPage { idx } => FramePoolError::CorruptPage { idx: *idx },
+            FramePoolError::OutOfBounds(idx) => FramePoolError::OutOfBounds(*idx),
+            FramePoolError::ReadOnly => FramePoolError::ReadOnly,
+            FramePoolError::Unsupported(what) => FramePoolError::Unsupported(what.clone()),
+            FramePoolError::CapacityExceeded { needed, limit } => {
+                FramePoolError::CapacityExceeded {
+                    needed: *needed,
+                    limit: *limit,
+                }
+            }
+            FramePoolError::Locked(what) => FramePoolError::Locked(what.clone()),
+            FramePoolError::TypeMismatch { stored, requested } => FramePoolError::TypeMismatch {
+                stored: stored.clone(),
+                requested: requested.clone(),
+            },
+            FramePoolError::Poisoned(panic) => FramePoolError::Poisoned(panic.clone()),
+        }
+    }
+}
+
+impl From<io::Error> for FramePoolError {
+    fn from(e: io::Error) -> Self {
+        FramePoolError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for FramePoolError {
+    fn from(e: serde_json::Error) -> Self {
+        FramePoolError::Serde(e)
+    }
+}
+
+impl FramePoolError {
+    // Whether the same operation might succeed if tried again: I/O failures other than those
+    // that say the request itself is wrong. Everything else (missing frames, pages that don't
+    // decode, read-only pools) fails the same way every time.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            FramePoolError::Io(e) => !matches!(
+                e.kind(),
+                io::ErrorKind::NotFound
+                    | io::ErrorKind::PermissionDenied
+                    | io::ErrorKind::AlreadyExists
+                    | io::ErrorKind::InvalidInput
+                    | io::ErrorKind::InvalidData
+                    | io::ErrorKind::Unsupported
+                    | io::ErrorKind::ReadOnlyFilesystem
+                    | io::ErrorKind::IsADirectory
+                    | io::ErrorKind::NotADirectory
+                    | io::ErrorKind::DirectoryNotEmpty
+                    | io::ErrorKind::InvalidFilename
+            ),
+            _ => false,
+        }
+    }
+
+    // Io for most failures, but NotFound naming `what` when the file isn't there.
+    pub(crate) fn from_io(e: io::Error, what: impl FnOnce() -> String) -> Self {
+        if e.kind() == io::ErrorKind::NotFound {
+            FramePoolError::NotFound(what())
+        } else {
+            FramePoolError::Io(e)
+        }
+    }
+}
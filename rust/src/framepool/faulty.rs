@@ -0,0 +1,353 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState, KeyIter, StorageBackend};
+
+// Wraps a FramePool and makes its reads and writes fail, or slow down, on demand, to test how
+// code above it (a BufferPool's eviction and flush paths, say) copes with failing storage.
+// Faults are configured through a FaultInjector, which can be kept and changed while a
+// BufferPool holds the pool. Injected failures are I/O errors.
+pub struct FaultyPool<P> {
+    inner: P,
+    faults: FaultInjector,
+}
+
+// The same, for a StorageBackend. Reads and writes are faulted; deletes count as writes.
+pub struct FaultyBackend<B> {
+    inner: B,
+    faults: FaultInjector,
+}
+
+// Decides which operations of a FaultyPool or FaultyBackend fail. Handles are cheap to clone
+// and share one configuration. Nothing fails until configured to.
+#[derive(Clone)]
+pub struct FaultInjector {
+    state: Arc<Mutex<Faults>>,
+}
+
+struct Faults {
+    rng: StdRng,
+    read_failure_rate: f64,
+    write_failure_rate: f64,
+    // operations allowed before every operation fails
+    fail_after: Option<u64>,
+    latency: Option<Duration>,
+    operations: u64,
+    injected: u64,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Read,
+    Write,
+}
+
+impl FaultInjector {
+    // Random failures are drawn from a generator seeded with seed, so a run can be repeated.
+    pub fn new(seed: u64) -> Self {
+        FaultInjector {
+            state: Arc::new(Mutex::new(Faults {
+                rng: StdRng::seed_from_u64(seed),
+                read_failure_rate: 0.0,
+                write_failure_rate: 0.0,
+                fail_after: None,
+                latency: None,
+                operations: 0,
+                injected: 0,
+            })),
+        }
+    }
+
+    // Fails each read with probability rate, between 0 and 1.
+    pub fn set_read_failure_rate(&self, rate: f64) {
+        self.state.lock().unwrap().read_failure_rate = rate;
+    }
+
+    // Fails each write with probability rate, between 0 and 1.
+    pub fn set_write_failure_rate(&self, rate: f64) {
+        self.state.lock().unwrap().write_failure_rate = rate;
+    }
+
+    // Lets `operations` more reads and writes through, then fails every one after them. None
+    // lifts the limit.
+    pub fn fail_after(&self, operations: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        state.fail_after = operations.map(|n| state.operations + n);
+    }
+
+    // Sleeps this long before every read and write.
+    pub fn set_latency(&self, latency: Option<Duration>) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    // The number of reads and writes seen so far, failed or not.
+    pub fn operations(&self) -> u64 {
+        self.state.lock().unwrap().operations
+    }
+
+    // The number of failures injected so far.
+    pub fn injected(&self) -> u64 {
+        self.state.lock().unwrap().injected
+    }
+
+    // Counts an operation, sleeps if asked to, and decides whether it fails.
+    fn check(&self, op: Op) -> Result<(), FramePoolError> {
+        let (latency, fail) = {
+            let mut state = self.state.lock().unwrap();
+            state.operations += 1;
+            let rate = match op {
+                Op::Read => state.read_failure_rate,
+                Op::Write => state.write_failure_rate,
+            };
+            let exhausted = state.fail_after.is_some_and(|n| state.operations > n);
+            let fail = exhausted || (rate > 0.0 && state.rng.gen_bool(rate.min(1.0)));
+            if fail {
+                state.injected += 1;
+            }
+            (state.latency, fail)
+        };
+        if let Some(latency) = latency {
+            thread::sleep(latency);
+        }
+        if fail {
+            let what = match op {
+                Op::Read => "injected read fault",
+                Op::Write => "injected write fault",
+            };
+            return Err(io::Error::other(what).into());
+        }
+        Ok(())
+    }
+}
+
+impl<P> FaultyPool<P> {
+    pub fn new(inner: P, faults: FaultInjector) -> Self {
+        FaultyPool { inner, faults }
+    }
+
+    pub fn injector(&self) -> FaultInjector {
+        self.faults.clone()
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+impl<T, P> FramePool<T> for FaultyPool<P>
+where
+    T: Clone,
+    P: FramePool<T>,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        self.faults.check(Op::Read)?;
+        self.inner.get_frame_ref(idx)
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.faults.check(Op::Write)?;
+        self.inner.put_frame(idx, data)
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.inner.resize(count)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.inner.truncate(count)
+    }
+
+    // A discard counts as a write.
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.faults.check(Op::Write)?;
+        self.inner.discard_frame(idx)
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.inner.assess_size()
+    }
+
+    // A sync counts as a write.
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.faults.check(Op::Write)?;
+        self.inner.sync()
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        self.inner.frame_state(idx)
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        self.inner.frame_ids()
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        self.inner.frame_meta(idx)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+}
+
+impl<B> FaultyBackend<B> {
+    pub fn new(inner: B, faults: FaultInjector) -> Self {
+        FaultyBackend { inner, faults }
+    }
+
+    pub fn injector(&self) -> FaultInjector {
+        self.faults.clone()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<T, B> StorageBackend<T> for FaultyBackend<B>
+where
+    T: Clone,
+    B: StorageBackend<T>,
+{
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        self.faults.check(Op::Read)?;
+        self.inner.read(key)
+    }
+
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.faults.check(Op::Write)?;
+        self.inner.write(key, data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.inner.exists(key)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        self.faults.check(Op::Write)?;
+        self.inner.delete(key)
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        self.inner.list_keys()
+    }
+
+    fn iter_keys(&self) -> Result<KeyIter<'_>, FramePoolError> {
+        self.inner.iter_keys()
+    }
+
+    fn list_keys_with_prefix(
+        &self,
+        prefix: &str,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Vec<String>, FramePoolError> {
+        self.inner.list_keys_with_prefix(prefix, limit, cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, BufferPoolErrors, bottom_evictor};
+    use crate::framepool::{FileBackend, MemPool};
+
+    #[test]
+    fn test_faulty_pool_under_bufferpool() {
+        let mut mem = MemPool::<u64>::new();
+        mem.resize(4).unwrap();
+        for i in 0..4 {
+            mem.put_frame(i, Arc::new(i)).unwrap();
+        }
+        let faults = FaultInjector::new(1);
+        let mut pool = FaultyPool::new(mem, faults.clone());
+        let mut bp = BufferPool::<u64>::new(1, &mut pool, bottom_evictor);
+
+        bp.put_page(0, 100).unwrap();
+        // The dirty page can't be written back, so nothing else can be loaded
+        faults.set_write_failure_rate(1.0);
+        assert!(bp.get_page(1).is_none());
+        assert!(matches!(
+            bp.try_get_page(1),
+            Err(BufferPoolErrors::FlushFailed(FramePoolError::Io(_)))
+        ));
+        assert_eq!(bp.get_cached(&0).as_deref(), Some(&100));
+        assert!(bp.flush_all().is_err());
+
+        // A failed read leaves the cache alone
+        faults.set_write_failure_rate(0.0);
+        faults.set_read_failure_rate(1.0);
+        assert!(matches!(
+            bp.try_get_page(2),
+            Err(BufferPoolErrors::ReadFailed(_))
+        ));
+        assert_eq!(bp.get_cached(&0).as_deref(), Some(&100));
+
+        faults.set_read_failure_rate(0.0);
+        assert_eq!(bp.get_page(2).unwrap().data(), 2);
+        drop(bp);
+        assert_eq!(*pool.get_frame_ref(0).unwrap(), 100);
+        assert_eq!(faults.injected(), 4);
+    }
+
+    #[test]
+    fn test_fault_injector_rates_and_limits() {
+        let faults = FaultInjector::new(42);
+        let mut pool = FaultyPool::new(MemPool::<u64>::new(), faults.clone());
+        pool.put_frame(0, Arc::new(0)).unwrap();
+
+        faults.set_read_failure_rate(0.25);
+        let failed = (0..1000).filter(|_| pool.get_frame_ref(0).is_err()).count();
+        assert!((150..350).contains(&failed), "{} reads failed", failed);
+
+        // The same seed fails the same reads
+        let again = FaultInjector::new(42);
+        let mut other = FaultyPool::new(MemPool::<u64>::new(), again.clone());
+        other.put_frame(0, Arc::new(0)).unwrap();
+        again.set_read_failure_rate(0.25);
+        let failed_again = (0..1000)
+            .filter(|_| other.get_frame_ref(0).is_err())
+            .count();
+        assert_eq!(failed, failed_again);
+
+        faults.set_read_failure_rate(0.0);
+        faults.fail_after(Some(2));
+        assert!(pool.get_frame_ref(0).is_ok());
+        assert!(pool.put_frame(0, Arc::new(1)).is_ok());
+        assert!(pool.get_frame_ref(0).is_err());
+        assert!(pool.sync().is_err());
+        faults.fail_after(None);
+        assert_eq!(*pool.get_frame_ref(0).unwrap(), 1);
+        assert_eq!(faults.operations(), 1006);
+    }
+
+    #[test]
+    fn test_faulty_backend() {
+        let test_dir = "/tmp/test_faulty_backend";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let faults = FaultInjector::new(0);
+        let mut backend = FaultyBackend::new(FileBackend::new(test_dir), faults.clone());
+        StorageBackend::<u8>::write(&mut backend, "a", Arc::new(1)).unwrap();
+        faults.set_latency(Some(Duration::from_millis(5)));
+        faults.set_write_failure_rate(1.0);
+        let started = std::time::Instant::now();
+        assert!(StorageBackend::<u8>::write(&mut backend, "a", Arc::new(2)).is_err());
+        assert!(StorageBackend::<u8>::delete(&mut backend, "a").is_err());
+        assert!(started.elapsed() >= Duration::from_millis(10));
+        assert_eq!(*StorageBackend::<u8>::read(&mut backend, "a").unwrap(), 1);
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+}
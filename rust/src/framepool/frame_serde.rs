@@ -0,0 +1,69 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+
+use super::PageFrame;
+
+// A PageFrame serializes as its data, whether it is dirty and its version, so a frame can be
+// persisted and restored as it was, unsaved changes and all. Pins and read-only marks belong
+// to the running pool and are not kept: a restored frame is unpinned and writable.
+#[derive(Serialize)]
+struct FrameOut<'a, T> {
+    data: &'a T,
+    dirty: bool,
+    version: u64,
+}
+
+#[derive(Deserialize)]
+struct FrameIn<T> {
+    data: T,
+    dirty: bool,
+    version: u64,
+}
+
+impl<T: Serialize> Serialize for PageFrame<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // under the lock, so the data and its version are of the same moment
+        let inner = self.read_inner();
+        let data = self
+            .data
+            .load_full()
+            .expect("page data missing outside of with_data");
+        FrameOut {
+            data: &*data,
+            dirty: inner.dirty,
+            version: inner.version,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for PageFrame<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let frame = FrameIn::deserialize(deserializer)?;
+        let restored = PageFrame::new_with_arc(Arc::new(frame.data));
+        restored.set_dirty(frame.dirty);
+        restored.set_version(frame.version);
+        Ok(restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pageframe_serde() {
+        let frame = PageFrame::new(vec![1, 2]);
+        frame.with_data(|data| data.push(3));
+        frame.pin();
+        let json = serde_json::to_string(&frame).unwrap();
+        assert_eq!(json, r#"{"data":[1,2,3],"dirty":true,"version":1}"#);
+
+        let restored: PageFrame<Vec<i32>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.data(), vec![1, 2, 3]);
+        assert!(restored.is_dirty());
+        assert_eq!(restored.version(), 1);
+        assert!(!restored.is_pinned());
+        assert!(!restored.is_read_only());
+    }
+}
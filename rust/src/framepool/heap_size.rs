@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+
+// Bytes a value owns on the heap, beyond its own size_of: what PageFrame::with_heap_size adds
+// to the shallow size of a frame's data to weigh it.
+pub trait HeapSize {
+    fn heap_size(&self) -> u64;
+}
+
+macro_rules! no_heap {
+    ($($t:ty),*) => {
+        $(impl HeapSize for $t {
+            fn heap_size(&self) -> u64 {
+                0
+            }
+        })*
+    };
+}
+
+no_heap!(
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    ()
+);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> u64 {
+        self.capacity() as u64
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> u64 {
+        let spare = (self.capacity() - self.len()) * size_of::<T>();
+        self.iter()
+            .map(|item| size_of::<T>() as u64 + item.heap_size())
+            .sum::<u64>()
+            + spare as u64
+    }
+}
+
+impl<T: HeapSize> HeapSize for Box<T> {
+    fn heap_size(&self) -> u64 {
+        size_of::<T>() as u64 + (**self).heap_size()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> u64 {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<T: HeapSize, const N: usize> HeapSize for [T; N] {
+    fn heap_size(&self) -> u64 {
+        self.iter().map(HeapSize::heap_size).sum()
+    }
+}
+
+impl<A: HeapSize, B: HeapSize> HeapSize for (A, B) {
+    fn heap_size(&self) -> u64 {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+// Counts the entries, not the table's spare capacity, which HashMap doesn't expose.
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> u64 {
+        self.iter()
+            .map(|(k, v)| (size_of::<(K, V)>() as u64) + k.heap_size() + v.heap_size())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heap_size() {
+        assert_eq!(7u32.heap_size(), 0);
+        let mut s = String::with_capacity(16);
+        s.push_str("abc");
+        assert_eq!(s.heap_size(), 16);
+
+        let v: Vec<u32> = Vec::with_capacity(4);
+        assert_eq!(v.heap_size(), 16);
+        let v = vec![String::from("ab"), String::from("cde")];
+        assert_eq!(v.heap_size(), 2 * size_of::<String>() as u64 + 5);
+        assert_eq!(Some(Box::new(3u64)).heap_size(), 8);
+        assert_eq!(None::<String>.heap_size(), 0);
+    }
+}
@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::{DiskPool, FrameMeta, FramePool, FramePoolError, FrameState, Weigher};
+use crate::unique_stack::UniqueStack;
+
+// A FramePool that keeps frames in memory up to a byte budget and spills the least recently
+// used ones to a DiskPool directory when the budget is exceeded. Spilled frames are read back
+// from disk on demand and become resident again, spilling others in turn.
+//
+// The disk pool is the pool of record for allocation: resize, size and frame_ids describe it.
+// Frames held only in memory are not durable until sync, which writes them to disk (keeping
+// them resident) before syncing it.
+pub struct HybridPool<T> {
+    disk: DiskPool,
+    resident: HashMap<u64, Resident<T>>,
+    // resident frames, coldest at the bottom
+    lru: UniqueStack<u64>,
+    bytes: u64,
+    budget: u64,
+    weigher: Option<Weigher<T>>,
+    spills: u64,
+}
+
+struct Resident<T> {
+    data: Arc<T>,
+    weight: u64,
+    // written since it was last read from or spilled to disk
+    dirty: bool,
+}
+
+impl<T> HybridPool<T>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+{
+    // A pool spilling to dirname once resident frames weigh more than budget bytes. Frames
+    // weigh the shallow size of T until set_weigher says otherwise.
+    pub fn new(dirname: &str, budget: u64) -> Self {
+        HybridPool {
+            disk: DiskPool::new::<T>(dirname),
+            resident: HashMap::new(),
+            lru: UniqueStack::new(),
+            bytes: 0,
+            budget,
+            weigher: None,
+            spills: 0,
+        }
+    }
+
+    // Replaces how frames are weighed, as for MemPool. Resident frames are weighed again and
+    // spilled if they no longer fit.
+    pub fn set_weigher<F>(&mut self, weigher: F) -> Result<(), FramePoolError>
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        self.weigher = Some(Arc::new(weigher));
+        let mut bytes = 0;
+        for resident in self.resident.values_mut() {
+            resident.weight = (self.weigher.as_ref().unwrap())(&resident.data);
+            bytes += resident.weight;
+        }
+        self.bytes = bytes;
+        self.spill_over_budget()
+    }
+
+    // Approximate bytes held in memory.
+    pub fn resident_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    // The number of frames held in memory.
+    pub fn resident_frames(&self) -> usize {
+        self.resident.len()
+    }
+
+    pub fn is_resident(&self, idx: u64) -> bool {
+        self.resident.contains_key(&idx)
+    }
+
+    // How many frames have been written to disk to make room, since the pool was created.
+    pub fn spills(&self) -> u64 {
+        self.spills
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    // Changes the budget, spilling at once if the resident frames no longer fit.
+    pub fn set_budget(&mut self, budget: u64) -> Result<(), FramePoolError> {
+        self.budget = budget;
+        self.spill_over_budget()
+    }
+
+    // Writes every resident frame that disk doesn't have yet, without evicting any of them.
+    pub fn flush(&mut self) -> Result<(), FramePoolError> {
+        for (idx, resident) in self.resident.iter_mut() {
+            if resident.dirty {
+                FramePool::<T>::put_frame(&mut self.disk, *idx, Arc::clone(&resident.data))?;
+                resident.dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn admit(&mut self, idx: u64, data: Arc<T>, dirty: bool) -> Result<(), FramePoolError> {
+        let weight = self.weigh(&data);
+        if let Some(old) = self.resident.insert(
+            idx,
+            Resident {
+                data,
+                weight,
+                dirty,
+            },
+        ) {
+            self.bytes -= old.weight;
+        }
+        self.bytes += weight;
+        self.lru.push(idx);
+        self.spill_over_budget()
+    }
+
+    fn weigh(&self, data: &T) -> u64 {
+        match &self.weigher {
+            Some(weigher) => weigher(data),
+            None => std::mem::size_of::<T>() as u64,
+        }
+    }
+
+    // Spills the coldest frames until the resident ones fit the budget. Clean frames are
+    // dropped; dirty ones are written to disk first.
+    fn spill_over_budget(&mut self) -> Result<(), FramePoolError> {
+        while self.bytes > self.budget {
+            let Some(victim) = self.lru.bottom() else {
+                break;
+            };
+            if let Some(resident) = self.resident.get(&victim)
+                && resident.dirty
+            {
+                FramePool::<T>::put_frame(&mut self.disk, victim, Arc::clone(&resident.data))?;
+                self.spills += 1;
+            }
+            self.drop_resident(victim);
+        }
+        Ok(())
+    }
+
+    // Drops the copy of idx held in memory, if there is one.
+    fn drop_resident(&mut self, idx: u64) {
+        self.lru.delete(idx);
+        if let Some(resident) = self.resident.remove(&idx) {
+            self.bytes -= resident.weight;
+        }
+    }
+}
+
+impl<T> FramePool<T> for HybridPool<T>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        if let Some(resident) = self.resident.get(&idx) {
+            let data = Arc::clone(&resident.data);
+            self.lru.push(idx);
+            return Ok(data);
+        }
+        let data = FramePool::<T>::get_frame_ref(&mut self.disk, idx)?;
+        self.admit(idx, Arc::clone(&data), false)?;
+        Ok(data)
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.admit(idx, data, true)
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        FramePool::<T>::resize(&mut self.disk, count)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        FramePool::<T>::truncate(&mut self.disk, count)?;
+        let doomed: Vec<u64> = self
+            .resident
+            .keys()
+            .filter(|idx| **idx >= count)
+            .copied()
+            .collect();
+        for idx in doomed {
+            self.drop_resident(idx);
+        }
+        Ok(())
+    }
+
+    // The frame is dropped from memory, unwritten changes and all, and from disk.
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.drop_resident(*idx);
+        FramePool::<T>::discard_frame(&mut self.disk, idx)
+    }
+
+    fn size(&self) -> u64 {
+        FramePool::<T>::size(&self.disk)
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        FramePool::<T>::assess_size(&mut self.disk)
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.flush()?;
+        FramePool::<T>::sync(&mut self.disk)
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        if self.resident.contains_key(idx) {
+            return FrameState::Populated;
+        }
+        FramePool::<T>::frame_state(&self.disk, idx)
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids = FramePool::<T>::frame_ids(&self.disk)?;
+        ids.extend(self.resident.keys().copied());
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    // Resident frames report their weight and nothing else; spilled ones report the disk file.
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        match self.resident.get(idx) {
+            Some(resident) => Ok(FrameMeta {
+                size: resident.weight,
+                modified: None,
+                checksum: None,
+            }),
+            None => FramePool::<T>::frame_meta(&self.disk, idx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use std::fs;
+
+    #[test]
+    fn test_hybrid_pool_spills_coldest() {
+        let test_dir = "/tmp/test_hybrid_pool_spill";
+        let _ = fs::remove_dir_all(test_dir);
+
+        // Room for two u64 frames
+        let mut pool = HybridPool::<u64>::new(test_dir, 16);
+        pool.resize(4).unwrap();
+        for i in 0..3 {
+            pool.put_frame(i, Arc::new(i * 10)).unwrap();
+        }
+        assert_eq!(pool.resident_frames(), 2);
+        assert_eq!(pool.resident_bytes(), 16);
+        assert!(!pool.is_resident(0));
+        assert_eq!(pool.spills(), 1);
+
+        // Reading the spilled frame brings it back and spills the next coldest
+        assert_eq!(*pool.get_frame_ref(0).unwrap(), 0);
+        assert!(pool.is_resident(0));
+        assert!(!pool.is_resident(1));
+        assert_eq!(pool.spills(), 2);
+        assert_eq!(*pool.get_frame_ref(1).unwrap(), 10);
+        // Frame 2 was never written to disk, frame 0 was read back clean
+        assert_eq!(pool.spills(), 3);
+        assert_eq!(pool.frame_state(&3), FrameState::Empty);
+
+        pool.flush().unwrap();
+        drop(pool);
+        let mut disk = DiskPool::new::<u64>(test_dir);
+        for i in 0..3 {
+            assert_eq!(
+                *FramePool::<u64>::get_frame_ref(&mut disk, i).unwrap(),
+                i * 10
+            );
+        }
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_hybrid_pool_under_bufferpool() {
+        let test_dir = "/tmp/test_hybrid_pool_bufferpool";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = HybridPool::<Vec<u64>>::new(test_dir, 64);
+        pool.set_weigher(|data| 8 * data.len() as u64).unwrap();
+        pool.resize(10).unwrap();
+        for i in 0..10 {
+            pool.put_frame(i, Arc::new(vec![])).unwrap();
+        }
+        {
+            let mut bp = BufferPool::<Vec<u64>>::new(2, &mut pool, bottom_evictor);
+            for i in 0..10 {
+                bp.put_page(i, vec![i; 4]).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        assert!(pool.resident_bytes() <= 64);
+        assert_eq!(pool.frame_ids().unwrap(), (0..10).collect::<Vec<_>>());
+        for i in 0..10 {
+            assert_eq!(*pool.get_frame_ref(i).unwrap(), vec![i; 4]);
+        }
+
+        pool.set_budget(0).unwrap();
+        assert_eq!(pool.resident_frames(), 0);
+        pool.truncate(5).unwrap();
+        assert_eq!(pool.size(), 5);
+        assert!(pool.get_frame_ref(7).is_err());
+
+        assert_eq!(*pool.get_frame_ref(3).unwrap(), vec![3; 4]);
+        pool.discard_frame(&3).unwrap();
+        assert!(!pool.is_resident(3));
+        assert_eq!(pool.frame_state(&3), FrameState::Empty);
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_hybrid_pool_flush_all_is_durable() {
+        let test_dir = "/tmp/test_hybrid_pool_durable";
+        let _ = fs::remove_dir_all(test_dir);
+
+        // Room for every frame, so nothing spills
+        let mut pool = HybridPool::<u64>::new(test_dir, 1024);
+        pool.resize(4).unwrap();
+        for i in 0..4 {
+            pool.put_frame(i, Arc::new(0)).unwrap();
+        }
+        {
+            let mut bp = BufferPool::<u64>::new(4, &mut pool, bottom_evictor);
+            for i in 0..4 {
+                bp.put_page(i, i * 10).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        // flush_all's writes are complete, so sync must make them durable though none spilled
+        pool.sync().unwrap();
+        assert_eq!(pool.spills(), 0);
+        assert_eq!(pool.resident_frames(), 4);
+        drop(pool);
+
+        let mut reopened = HybridPool::<u64>::new(test_dir, 1024);
+        assert_eq!(reopened.size(), 4);
+        for i in 0..4 {
+            assert_eq!(*reopened.get_frame_ref(i).unwrap(), i * 10);
+        }
+        let _ = fs::remove_dir_all(test_dir);
+    }
+}
@@ -0,0 +1,373 @@
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState, Weigher};
+
+// Wraps a FramePool and records how many reads, writes, syncs and resizes go through it, how
+// many fail, how many bytes they carry and how long they take. Set alongside a BufferPool's
+// own hit and miss counts, it tells whether time goes to the cache or to the storage below.
+// Metrics are read through a PoolMetrics handle, which can be kept while a BufferPool holds
+// the pool.
+pub struct InstrumentedPool<T, P> {
+    inner: P,
+    metrics: PoolMetrics,
+    weigher: Option<Weigher<T>>,
+    _data: PhantomData<fn() -> T>,
+}
+
+// A live view of an InstrumentedPool's metrics. Handles are cheap to clone.
+#[derive(Clone, Default)]
+pub struct PoolMetrics {
+    recorded: Arc<Mutex<MetricsSnapshot>>,
+}
+
+// Metrics at one moment, per kind of operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub reads: OpStats,
+    pub writes: OpStats,
+    pub syncs: OpStats,
+    // resize and truncate
+    pub resizes: OpStats,
+}
+
+// Counts for one kind of operation. A batch (get_frames, put_frames) counts each frame it
+// carries, but takes one latency sample.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpStats {
+    pub count: u64,
+    pub errors: u64,
+    // weight of the frames read or written successfully
+    pub bytes: u64,
+    pub latency: LatencyHistogram,
+}
+
+// Latencies in power-of-two buckets of microseconds: bucket 0 counts samples under 1µs, and
+// bucket i those from 2^(i-1) up to 2^i µs. The last bucket takes everything longer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    pub buckets: [u64; 32],
+    pub samples: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros() as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()).min(31) as usize;
+        self.buckets[bucket] += 1;
+        self.samples += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.samples {
+            0 => Duration::ZERO,
+            n => self.total / n as u32,
+        }
+    }
+
+    // An upper bound on the q-th quantile (0 to 1): the top of the bucket it falls in.
+    pub fn quantile(&self, q: f64) -> Duration {
+        if self.samples == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.samples as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros(1 << bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+impl PoolMetrics {
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.recorded.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        *self.recorded.lock().unwrap() = MetricsSnapshot::default();
+    }
+
+    fn record(&self, op: fn(&mut MetricsSnapshot) -> &mut OpStats, sample: Sample) {
+        let mut recorded = self.recorded.lock().unwrap();
+        let stats = op(&mut recorded);
+        stats.count += sample.count;
+        stats.errors += sample.errors;
+        stats.bytes += sample.bytes;
+        stats.latency.record(sample.latency);
+    }
+}
+
+// What one call, or one batch, did.
+struct Sample {
+    count: u64,
+    errors: u64,
+    bytes: u64,
+    latency: Duration,
+}
+
+fn reads(m: &mut MetricsSnapshot) -> &mut OpStats {
+    &mut m.reads
+}
+
+fn writes(m: &mut MetricsSnapshot) -> &mut OpStats {
+    &mut m.writes
+}
+
+fn syncs(m: &mut MetricsSnapshot) -> &mut OpStats {
+    &mut m.syncs
+}
+
+fn resizes(m: &mut MetricsSnapshot) -> &mut OpStats {
+    &mut m.resizes
+}
+
+impl<T, P> InstrumentedPool<T, P> {
+    pub fn new(inner: P) -> Self {
+        InstrumentedPool {
+            inner,
+            metrics: PoolMetrics::default(),
+            weigher: None,
+            _data: PhantomData,
+        }
+    }
+
+    // Frames weigh the shallow size of T unless told otherwise, as in MemPool.
+    pub fn set_weigher<F>(&mut self, weigher: F)
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        self.weigher = Some(Arc::new(weigher));
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        self.metrics.clone()
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn weigh(&self, data: &T) -> u64 {
+        match &self.weigher {
+            Some(weigher) => weigher(data),
+            None => std::mem::size_of::<T>() as u64,
+        }
+    }
+
+    // Times op and records it as a single operation moving no bytes.
+    fn timed<R>(
+        &mut self,
+        kind: fn(&mut MetricsSnapshot) -> &mut OpStats,
+        op: impl FnOnce(&mut P) -> Result<R, FramePoolError>,
+    ) -> Result<R, FramePoolError> {
+        let started = Instant::now();
+        let result = op(&mut self.inner);
+        self.metrics.record(
+            kind,
+            Sample {
+                count: 1,
+                errors: result.is_err() as u64,
+                bytes: 0,
+                latency: started.elapsed(),
+            },
+        );
+        result
+    }
+}
+
+impl<T, P> FramePool<T> for InstrumentedPool<T, P>
+where
+    T: Clone,
+    P: FramePool<T>,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        let started = Instant::now();
+        let result = self.inner.get_frame_ref(idx);
+        let latency = started.elapsed();
+        self.metrics.record(
+            reads,
+            Sample {
+                count: 1,
+                errors: result.is_err() as u64,
+                bytes: result.as_ref().map_or(0, |data| self.weigh(data)),
+                latency,
+            },
+        );
+        result
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let bytes = self.weigh(&data);
+        let started = Instant::now();
+        let result = self.inner.put_frame(idx, data);
+        let latency = started.elapsed();
+        self.metrics.record(
+            writes,
+            Sample {
+                count: 1,
+                errors: result.is_err() as u64,
+                bytes: if result.is_ok() { bytes } else { 0 },
+                latency,
+            },
+        );
+        result
+    }
+
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        let started = Instant::now();
+        let results = self.inner.get_frames(idxs);
+        let latency = started.elapsed();
+        let bytes = results.iter().flatten().map(|data| self.weigh(data)).sum();
+        self.metrics.record(
+            reads,
+            Sample {
+                count: results.len() as u64,
+                errors: results.iter().filter(|r| r.is_err()).count() as u64,
+                bytes,
+                latency,
+            },
+        );
+        results
+    }
+
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let weights: Vec<u64> = frames.iter().map(|(_, data)| self.weigh(data)).collect();
+        let started = Instant::now();
+        let results = self.inner.put_frames(frames);
+        let latency = started.elapsed();
+        let bytes = weights
+            .iter()
+            .zip(results.iter())
+            .filter(|(_, result)| result.is_ok())
+            .map(|(weight, _)| weight)
+            .sum();
+        self.metrics.record(
+            writes,
+            Sample {
+                count: results.len() as u64,
+                errors: results.iter().filter(|r| r.is_err()).count() as u64,
+                bytes,
+                latency,
+            },
+        );
+        results
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.timed(resizes, |inner| inner.resize(count))
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.timed(resizes, |inner| inner.truncate(count))
+    }
+
+    // A discard counts as a write moving no bytes.
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.timed(writes, |inner| inner.discard_frame(idx))
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.inner.assess_size()
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.timed(syncs, |inner| inner.sync())
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        self.inner.frame_state(idx)
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        self.inner.frame_ids()
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        self.inner.frame_meta(idx)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use crate::framepool::{FaultInjector, FaultyPool, MemPool};
+
+    #[test]
+    fn test_instrumented_pool_counts() {
+        let faults = FaultInjector::new(0);
+        let mut pool = InstrumentedPool::new(FaultyPool::new(MemPool::new(), faults.clone()));
+        pool.set_weigher(|data: &Vec<u8>| data.len() as u64);
+        let metrics = pool.metrics();
+        pool.resize(4).unwrap();
+        for i in 0..4 {
+            pool.put_frame(i, Arc::new(vec![0; 100])).unwrap();
+        }
+        {
+            let mut bp = BufferPool::<Vec<u8>>::new(2, &mut pool, bottom_evictor);
+            for i in 0..4 {
+                bp.get_page(i).unwrap();
+            }
+            bp.put_page(3, vec![1; 10]).unwrap();
+            bp.get_page(0).unwrap();
+            // Seen live, while the buffer pool holds the pool
+            assert_eq!(metrics.snapshot().reads.count, 5);
+            bp.flush_all().unwrap();
+        }
+        faults.set_read_failure_rate(1.0);
+        assert!(pool.get_frame_ref(1).is_err());
+        pool.get_frames(&[0, 1]);
+        pool.sync().unwrap();
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.reads.count, 8);
+        assert_eq!(snapshot.reads.errors, 3);
+        assert_eq!(snapshot.reads.bytes, 500);
+        assert_eq!(snapshot.reads.latency.samples, 7);
+        assert_eq!(snapshot.writes.count, 5);
+        assert_eq!(snapshot.writes.bytes, 410);
+        assert_eq!(snapshot.syncs.count, 1);
+        assert_eq!(snapshot.resizes.count, 1);
+
+        metrics.reset();
+        assert_eq!(metrics.snapshot(), MetricsSnapshot::default());
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), Duration::ZERO);
+        for micros in [0, 3, 3, 3, 100, 5000] {
+            histogram.record(Duration::from_micros(micros));
+        }
+        assert_eq!(histogram.buckets[0], 1);
+        assert_eq!(histogram.buckets[2], 3);
+        assert_eq!(histogram.buckets[7], 1);
+        assert_eq!(histogram.quantile(0.5), Duration::from_micros(4));
+        assert_eq!(histogram.quantile(0.8), Duration::from_micros(128));
+        assert_eq!(histogram.quantile(1.0), Duration::from_micros(5000));
+        assert_eq!(histogram.max, Duration::from_micros(5000));
+        assert_eq!(histogram.mean(), Duration::from_micros(5109) / 6);
+    }
+}
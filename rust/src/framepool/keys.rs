@@ -0,0 +1,127 @@
+use std::collections::BinaryHeap;
+use std::fs::{self, ReadDir};
+use std::path::Path;
+
+use super::FramePoolError;
+
+// A lazy listing of a StorageBackend's keys, as StorageBackend::iter_keys returns.
+pub type KeyIter<'a> = Box<dyn Iterator<Item = Result<String, FramePoolError>> + 'a>;
+
+// The page of keys list_keys_with_prefix returns: the first limit keys, in ascending order,
+// of those starting with prefix and coming after cursor. Only limit keys are held at a time,
+// however many there are.
+pub(super) fn page_keys(
+    keys: impl Iterator<Item = Result<String, FramePoolError>>,
+    prefix: &str,
+    limit: Option<usize>,
+    cursor: Option<&str>,
+) -> Result<Vec<String>, FramePoolError> {
+    let limit = limit.unwrap_or(usize::MAX);
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+    // the smallest keys so far, the largest of them on top
+    let mut page = BinaryHeap::new();
+    for key in keys {
+        let key = key?;
+        if !key.starts_with(prefix) || cursor.is_some_and(|cursor| key.as_str() <= cursor) {
+            continue;
+        }
+        if page.len() < limit {
+            page.push(key);
+        } else if page.peek().is_some_and(|largest| key < *largest) {
+            page.pop();
+            page.push(key);
+        }
+    }
+    Ok(page.into_sorted_vec())
+}
+
+// FileBackend's keys under a directory, read one directory entry at a time: the files with the
+// codec's extension, in the directory and those under it, in no particular order.
+pub(super) struct KeyWalk<'a> {
+    extension: &'a str,
+    // the directories being read, innermost last, each with its key path
+    open: Vec<(ReadDir, String)>,
+}
+
+impl<'a> KeyWalk<'a> {
+    // Walks dir, whose keys start with under. A missing directory has no keys.
+    pub(super) fn new(dir: &Path, under: &str, extension: &'a str) -> Result<Self, FramePoolError> {
+        let mut walk = KeyWalk {
+            extension,
+            open: Vec::new(),
+        };
+        match fs::read_dir(dir) {
+            Ok(entries) => walk.open.push((entries, under.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(walk)
+    }
+
+    // The key of the next file in the innermost directory, if it has more entries.
+    fn next_entry(&mut self) -> Option<Result<Option<String>, FramePoolError>> {
+        let (entries, under) = self.open.last_mut()?;
+        let entry = match entries.next()? {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            return Some(Ok(None));
+        };
+        let key = name
+            .strip_suffix(self.extension)
+            .and_then(|name| name.strip_suffix('.'))
+            .map(|key| format!("{}{}", under, key));
+        let under = format!("{}{}/", under, name);
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => match fs::read_dir(entry.path()) {
+                Ok(entries) => {
+                    self.open.push((entries, under));
+                    Some(Ok(None))
+                }
+                Err(e) => Some(Err(e.into())),
+            },
+            Ok(_) => Some(Ok(key)),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+impl Iterator for KeyWalk<'_> {
+    type Item = Result<String, FramePoolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.open.is_empty() {
+            match self.next_entry() {
+                Some(Ok(Some(key))) => return Some(Ok(key)),
+                Some(Ok(None)) => {}
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.open.pop();
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_keys() {
+        let keys = ["b/2", "a", "b/1", "c", "b/3", "b/10"].map(|k| Ok(k.to_string()));
+        let page = |limit, cursor| page_keys(keys.iter().cloned(), "b/", limit, cursor).unwrap();
+        assert_eq!(page(None, None), vec!["b/1", "b/10", "b/2", "b/3"]);
+        assert_eq!(page(Some(2), None), vec!["b/1", "b/10"]);
+        assert_eq!(page(Some(2), Some("b/10")), vec!["b/2", "b/3"]);
+        assert!(page(Some(2), Some("b/3")).is_empty());
+        assert!(page(Some(0), None).is_empty());
+
+        let failing = vec![Ok("a".to_string()), Err(FramePoolError::ReadOnly)];
+        assert!(page_keys(failing.into_iter(), "", None, None).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{Codec, FrameMeta, FramePool, FramePoolError, FrameState, JsonCodec, fnv1a};
+
+// A FramePool keeping its pages in an embedded sled database rather than a file each, for
+// pools of many small pages. Pages are JSON unless another codec is chosen with with_codec.
+//
+// put_frames, and so BufferPool::flush_all, writes its whole batch atomically: after a crash
+// either every page of the batch is there or none is. sync flushes the database to disk.
+pub struct KvPool<C = JsonCodec> {
+    db: sled::Db,
+    // page id, big-endian so pages sort by id, to encoded page
+    pages: sled::Tree,
+    codec: C,
+    size: u64,
+}
+
+// The key under which the pool's size is kept in the database's default tree.
+const SIZE_KEY: &[u8] = b"bufferpool.size";
+
+fn kv_error(e: sled::Error) -> FramePoolError {
+    FramePoolError::Io(io::Error::from(e))
+}
+
+fn page_key(idx: u64) -> [u8; 8] {
+    idx.to_be_bytes()
+}
+
+fn page_idx(key: &[u8]) -> Result<u64, FramePoolError> {
+    let bytes: [u8; 8] = key
+        .try_into()
+        .map_err(|_| FramePoolError::Corruption(format!("bad page key {:?}", key)))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+impl KvPool {
+    // Opens, or creates, the database at path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FramePoolError> {
+        Self::with_db(sled::open(path).map_err(kv_error)?)
+    }
+
+    // A pool over an open database, which other code may use for other trees.
+    pub fn with_db(db: sled::Db) -> Result<Self, FramePoolError> {
+        let pages = db.open_tree("bufferpool.pages").map_err(kv_error)?;
+        let mut pool = KvPool {
+            db,
+            pages,
+            codec: JsonCodec::default(),
+            size: 0,
+        };
+        pool.size = pool.stored_size()?;
+        Ok(pool)
+    }
+}
+
+impl<C: Codec> KvPool<C> {
+    // The same pool, reading and writing pages with codec instead.
+    pub fn with_codec<D: Codec>(self, codec: D) -> KvPool<D> {
+        KvPool {
+            db: self.db,
+            pages: self.pages,
+            codec,
+            size: self.size,
+        }
+    }
+
+    // The recorded size, or one past the highest page stored if that is larger.
+    fn stored_size(&self) -> Result<u64, FramePoolError> {
+        let recorded = match self.db.get(SIZE_KEY).map_err(kv_error)? {
+            Some(bytes) => page_idx(&bytes)?,
+            None => 0,
+        };
+        let highest = match self.pages.last().map_err(kv_error)? {
+            Some((key, _)) => page_idx(&key)? + 1,
+            None => 0,
+        };
+        Ok(recorded.max(highest))
+    }
+
+    fn set_size(&mut self, size: u64) -> Result<(), FramePoolError> {
+        self.db
+            .insert(SIZE_KEY, &size.to_be_bytes())
+            .map_err(kv_error)?;
+        self.size = size;
+        Ok(())
+    }
+}
+
+impl<T, C> FramePool<T> for KvPool<C>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Codec,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        match self.pages.get(page_key(idx)).map_err(kv_error)? {
+            Some(bytes) => Ok(Arc::new(self.codec.decode(&bytes)?)),
+            None => Err(FramePoolError::NotFound(format!("page {}", idx))),
+        }
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let bytes = self.codec.encode(&*data)?;
+        self.pages.insert(page_key(idx), bytes).map_err(kv_error)?;
+        if idx >= self.size {
+            self.set_size(idx + 1)?;
+        }
+        Ok(())
+    }
+
+    // One atomic batch. Every frame reports the batch's result.
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let mut batch = sled::Batch::default();
+        let mut end = self.size;
+        for (idx, data) in frames.iter() {
+            match self.codec.encode(&**data) {
+                Ok(bytes) => batch.insert(&page_key(*idx), bytes),
+                Err(e) => return frames.iter().map(|_| Err(e.clone())).collect(),
+            }
+            end = end.max(idx + 1);
+        }
+        let result = self
+            .pages
+            .apply_batch(batch)
+            .map_err(kv_error)
+            .and_then(|_| match end > self.size {
+                true => self.set_size(end),
+                false => Ok(()),
+            });
+        frames.iter().map(|_| result.clone()).collect()
+    }
+
+    // Pages are stored only once written, so resizing only records the new end of the pool.
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.set_size(self.size + count)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let mut batch = sled::Batch::default();
+        for entry in self.pages.range(page_key(count)..) {
+            let (key, _) = entry.map_err(kv_error)?;
+            batch.remove(key);
+        }
+        self.pages.apply_batch(batch).map_err(kv_error)?;
+        self.set_size(self.size.min(count))
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.size = self.stored_size()?;
+        Ok(self.size)
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.db.flush().map_err(kv_error)?;
+        Ok(())
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        match self.pages.contains_key(page_key(*idx)) {
+            Ok(true) => FrameState::Populated,
+            _ if *idx < self.size => FrameState::Empty,
+            _ => FrameState::Absent,
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids: Vec<u64> = (0..self.size).collect();
+        for entry in self.pages.range(page_key(self.size)..) {
+            let (key, _) = entry.map_err(kv_error)?;
+            ids.push(page_idx(&key)?);
+        }
+        Ok(ids)
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        match self.pages.get(page_key(*idx)).map_err(kv_error)? {
+            Some(bytes) => Ok(FrameMeta {
+                size: bytes.len() as u64,
+                modified: None,
+                checksum: Some(fnv1a(&bytes)),
+            }),
+            None => Err(FramePoolError::NotFound(format!("page {}", idx))),
+        }
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.pages.remove(page_key(*idx)).map_err(kv_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+
+    fn reopen(path: &str) -> KvPool {
+        for _ in 0..50 {
+            match KvPool::open(path) {
+                Ok(pool) => return pool,
+                Err(FramePoolError::Io(e)) if e.to_string().contains("could not acquire lock") => {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+                Err(e) => panic!("reopening {}: {}", path, e),
+            }
+        }
+        panic!("{} is still locked", path);
+    }
+
+    #[test]
+    fn test_kv_pool_behind_bufferpool() {
+        let test_dir = "/tmp/test_kv_pool";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut pool = KvPool::open(test_dir).unwrap();
+        FramePool::<u64>::resize(&mut pool, 1000).unwrap();
+        {
+            let mut bp = BufferPool::<u64>::new(100, &mut pool, bottom_evictor);
+            for i in 0..1000 {
+                bp.get_or_insert_with(i, || i * 3).unwrap();
+            }
+            bp.checkpoint().unwrap();
+        }
+        FramePool::<u64>::sync(&mut pool).unwrap();
+        drop(pool);
+
+        // Reopened from disk, the pool has its size and pages. sled's flusher thread lets go of
+        // the file lock a little after the last handle is dropped, so opening is retried
+        let mut pool = reopen(test_dir);
+        assert_eq!(FramePool::<u64>::size(&pool), 1000);
+        assert_eq!(
+            *FramePool::<u64>::get_frame_ref(&mut pool, 999).unwrap(),
+            2997
+        );
+        let meta = FramePool::<u64>::frame_meta(&pool, &10).unwrap();
+        assert_eq!(meta.checksum, Some(fnv1a(b"30")));
+
+        FramePool::<u64>::truncate(&mut pool, 10).unwrap();
+        assert_eq!(
+            FramePool::<u64>::frame_ids(&pool).unwrap(),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            FramePool::<u64>::frame_state(&pool, &10),
+            FrameState::Absent
+        );
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_kv_pool_batches() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let mut pool = KvPool::with_db(db).unwrap();
+        FramePool::<String>::resize(&mut pool, 2).unwrap();
+        let results = pool.put_frames(vec![
+            (0, Arc::new("a".to_string())),
+            (5, Arc::new("b".to_string())),
+        ]);
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(FramePool::<String>::size(&pool), 6);
+        assert_eq!(
+            FramePool::<String>::frame_state(&pool, &3),
+            FrameState::Empty
+        );
+        assert_eq!(
+            *FramePool::<String>::get_frame_ref(&mut pool, 5).unwrap(),
+            "b"
+        );
+        FramePool::<String>::discard_frame(&mut pool, &5).unwrap();
+        assert!(matches!(
+            FramePool::<String>::get_frame_ref(&mut pool, 5),
+            Err(FramePoolError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::marker::PhantomData;
+
+use super::{Codec, DiskPool, JsonCodec};
+
+// Upgrades for the pages of a DiskPool written under older versions of its page type, so the
+// type can change without converting the pool first. Built from the oldest version up, each
+// step turning one version into the next and the last into the page type T:
+//
+//     Migrations::<PointV1>::new()
+//         .then(|p: PointV1| PointV2 { x: p.x, y: p.y, z: 0 })
+//         .then(|p: PointV2| Point { pos: [p.x, p.y, p.z] })
+//
+// A page that doesn't decode as T is tried as each older version in turn, newest first, and
+// upgraded from the first it decodes as through the steps after it. The versions must tell
+// themselves apart: an old page must fail to decode as a newer version, as it does under a
+// self-describing codec (JSON, CBOR, MessagePack) when a field is added without a default or
+// renamed. Under bincode a page may well decode as the wrong version.
+pub struct Migrations<T, C = JsonCodec> {
+    // the older versions, oldest first
+    versions: Vec<Version<C>>,
+    rewrite: bool,
+    current: PhantomData<fn() -> T>,
+}
+
+// A page of one of the versions, its type erased.
+type AnyPage = Box<dyn Any>;
+
+struct Version<C> {
+    decode: fn(&C, &[u8]) -> Option<AnyPage>,
+    // into the next version
+    upgrade: Box<dyn Fn(AnyPage) -> AnyPage + Send + Sync>,
+}
+
+impl<T: 'static, C: Codec> Migrations<T, C> {
+    // Migrations from T, the oldest version, with no steps yet.
+    pub fn new() -> Self {
+        Migrations {
+            versions: Vec::new(),
+            rewrite: false,
+            current: PhantomData,
+        }
+    }
+
+    // Adds the version after T: pages of T, and of the versions before it, are upgraded with
+    // upgrade.
+    pub fn then<U: 'static>(
+        mut self,
+        upgrade: impl Fn(T) -> U + Send + Sync + 'static,
+    ) -> Migrations<U, C>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        self.versions.push(Version {
+            decode: |codec: &C, bytes: &[u8]| {
+                let page: T = codec.decode(bytes).ok()?;
+                Some(Box::new(page))
+            },
+            upgrade: Box::new(move |page| Box::new(upgrade(*page.downcast::<T>().unwrap()))),
+        });
+        Migrations {
+            versions: self.versions,
+            rewrite: self.rewrite,
+            current: PhantomData,
+        }
+    }
+
+    // The same migrations, writing each page they upgrade back as T when it is read, so it is
+    // upgraded only once and the pool goes over to the new type as its pages are read. A
+    // page that can't be written back is still returned, and upgraded again when next read;
+    // a read-only pool writes nothing.
+    pub fn rewriting(mut self) -> Self {
+        self.rewrite = true;
+        self
+    }
+
+    // The page in bytes upgraded to T, if it decodes as one of the older versions.
+    fn upgrade(&self, codec: &C, bytes: &[u8]) -> Option<T> {
+        let (from, mut page) = self
+            .versions
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(at, version)| Some((at, (version.decode)(codec, bytes)?)))?;
+        for version in &self.versions[from..] {
+            page = (version.upgrade)(page);
+        }
+        Some(*page.downcast::<T>().unwrap())
+    }
+}
+
+impl<T: 'static, C: Codec> Default for Migrations<T, C> {
+    fn default() -> Self {
+        Migrations::new()
+    }
+}
+
+// A DiskPool's Migrations, their page type erased: the page in the bytes given upgraded, and
+// encoded again for the pool to decode as it would a page of the current version.
+pub(super) type Upgrade<C> = Box<dyn Fn(&C, &[u8]) -> Option<Vec<u8>> + Send + Sync>;
+
+impl<C: Codec> DiskPool<C> {
+    // The same pool, upgrading pages that don't decode as its page type T with migrations
+    // (see Migrations). Set the codec first: with_codec refuses a pool with migrations.
+    pub fn with_migrations<T>(mut self, migrations: Migrations<T, C>) -> Self
+    where
+        T: Serialize + 'static,
+        C: 'static,
+    {
+        self.rewrite_upgraded = migrations.rewrite;
+        self.migrations = Some(Box::new(move |codec: &C, body: &[u8]| {
+            codec.encode(&migrations.upgrade(codec, body)?).ok()
+        }));
+        self
+    }
+
+    // The page in body upgraded to T by the pool's migrations.
+    pub(super) fn upgrade<T>(&self, body: &[u8]) -> Option<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let upgraded = (self.migrations.as_ref()?)(&self.codec, body)?;
+        self.codec.decode(&upgraded).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{FramePool, FramePoolError};
+    use serde::Serialize;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct PointV1 {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct PointV2 {
+        x: i32,
+        y: i32,
+        z: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        pos: [i32; 3],
+    }
+
+    fn migrations() -> Migrations<Point> {
+        Migrations::<PointV1>::new()
+            .then(|p: PointV1| PointV2 {
+                x: p.x,
+                y: p.y,
+                z: 0,
+            })
+            .then(|p: PointV2| Point {
+                pos: [p.x, p.y, p.z],
+            })
+    }
+
+    #[test]
+    fn test_diskpool_migrations() {
+        let test_dir = "/tmp/test_diskpool_migrations";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut old = DiskPool::new::<PointV1>(test_dir).with_schema("point");
+        old.put_frame(0, Arc::new(PointV1 { x: 1, y: 2 })).unwrap();
+        drop(old);
+        let mut old = DiskPool::new::<PointV2>(test_dir).with_schema("point");
+        old.put_frame(1, Arc::new(PointV2 { x: 3, y: 4, z: 5 }))
+            .unwrap();
+        drop(old);
+
+        // Without migrations old pages don't read; with them every version does
+        let mut pool = DiskPool::new::<Point>(test_dir).with_schema("point");
+        pool.put_frame(2, Arc::new(Point { pos: [6, 7, 8] }))
+            .unwrap();
+        let read: Result<Arc<Point>, _> = pool.get_frame_ref(0);
+        assert!(matches!(read, Err(FramePoolError::Serde(_))));
+        let mut pool = pool.with_migrations(migrations());
+        let points: Vec<[i32; 3]> = FramePool::<Point>::get_frames(&mut pool, &[0, 1, 2])
+            .into_iter()
+            .map(|p| p.unwrap().pos)
+            .collect();
+        assert_eq!(points, vec![[1, 2, 0], [3, 4, 5], [6, 7, 8]]);
+        assert!(pool.check::<Point>(false).unwrap().is_clean());
+        // nothing is written back unless asked
+        assert!(pool.read_page::<PointV1>(0).is_ok());
+        drop(pool);
+
+        let mut pool = DiskPool::new::<Point>(test_dir)
+            .with_schema("point")
+            .with_migrations(migrations().rewriting());
+        let point: Arc<Point> = pool.get_frame_ref(0).unwrap();
+        assert_eq!(point.pos, [1, 2, 0]);
+        FramePool::<Point>::get_frames(&mut pool, &[1, 1]);
+        let plain = DiskPool::new::<Point>(test_dir)
+            .with_schema("point")
+            .unlocked();
+        assert_eq!(plain.read_page::<Point>(0).unwrap().0.pos, [1, 2, 0]);
+        assert_eq!(plain.read_page::<Point>(1).unwrap().0.pos, [3, 4, 5]);
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_diskpool_codec_before_migrations() {
+        let test_dir = "/tmp/test_diskpool_codec_before_migrations";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut old = DiskPool::new::<PointV1>(test_dir).with_schema("point");
+        old.put_frame(0, Arc::new(PointV1 { x: 1, y: 2 })).unwrap();
+        drop(old);
+
+        let mut pool = DiskPool::new::<Point>(test_dir)
+            .with_schema("point")
+            .with_codec(JsonCodec::pretty())
+            .with_migrations(migrations());
+        let point: Arc<Point> = pool.get_frame_ref(0).unwrap();
+        assert_eq!(point.pos, [1, 2, 0]);
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    #[should_panic(expected = "with_codec must come before with_migrations")]
+    fn test_diskpool_codec_after_migrations() {
+        let _ = DiskPool::new::<Point>("/tmp/test_diskpool_codec_after_migrations")
+            .with_migrations(migrations())
+            .with_codec(JsonCodec::pretty());
+    }
+}
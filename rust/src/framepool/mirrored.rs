@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState};
+
+// Writes every frame to two FramePools, a primary and a mirror, and reads from the primary,
+// turning to the mirror when a read fails there: simple redundancy for data that matters, such
+// as two DiskPools on different disks.
+//
+// Writes, resizes, truncates and syncs go to both pools, and fail if either does, even when
+// the other succeeded, so a lost copy is noticed. With repair on, a frame read from the mirror
+// is written back to the primary; a failed repair is counted, not reported.
+pub struct MirroredPool<P, M> {
+    primary: P,
+    mirror: M,
+    repair: bool,
+    // reads the mirror answered, and frames written back to the primary
+    fallbacks: u64,
+    repairs: u64,
+    failed_repairs: u64,
+}
+
+impl<P, M> MirroredPool<P, M> {
+    pub fn new(primary: P, mirror: M) -> Self {
+        MirroredPool {
+            primary,
+            mirror,
+            repair: false,
+            fallbacks: 0,
+            repairs: 0,
+            failed_repairs: 0,
+        }
+    }
+
+    // The same pool, writing frames read from the mirror back to the primary.
+    pub fn with_repair(mut self) -> Self {
+        self.repair = true;
+        self
+    }
+
+    // The number of reads the primary failed and the mirror answered.
+    pub fn fallbacks(&self) -> u64 {
+        self.fallbacks
+    }
+
+    // The number of frames written back to the primary, and of those that failed to be.
+    pub fn repairs(&self) -> (u64, u64) {
+        (self.repairs, self.failed_repairs)
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn mirror(&self) -> &M {
+        &self.mirror
+    }
+
+    pub fn into_inner(self) -> (P, M) {
+        (self.primary, self.mirror)
+    }
+}
+
+// The primary's result, unless it succeeded and the mirror's didn't.
+fn both<R>(
+    primary: Result<R, FramePoolError>,
+    mirror: Result<(), FramePoolError>,
+) -> Result<R, FramePoolError> {
+    let value = primary?;
+    mirror?;
+    Ok(value)
+}
+
+impl<P, M> MirroredPool<P, M> {
+    // Reads idx from the mirror after the primary failed with error, repairing the primary
+    // if asked to. If the mirror fails too, the primary's error is the one returned.
+    fn read_mirror<T>(&mut self, idx: u64, error: FramePoolError) -> Result<Arc<T>, FramePoolError>
+    where
+        T: Clone,
+        P: FramePool<T>,
+        M: FramePool<T>,
+    {
+        let data = self.mirror.get_frame_ref(idx).map_err(|_| error)?;
+        self.fallbacks += 1;
+        if self.repair {
+            match self.primary.put_frame(idx, Arc::clone(&data)) {
+                Ok(()) => self.repairs += 1,
+                Err(_) => self.failed_repairs += 1,
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl<T, P, M> FramePool<T> for MirroredPool<P, M>
+where
+    T: Clone,
+    P: FramePool<T>,
+    M: FramePool<T>,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        match self.primary.get_frame_ref(idx) {
+            Ok(data) => Ok(data),
+            Err(e) => self.read_mirror(idx, e),
+        }
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let primary = self.primary.put_frame(idx, Arc::clone(&data));
+        both(primary, self.mirror.put_frame(idx, data))
+    }
+
+    // The batch is read from the primary, and only the frames it failed from the mirror.
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        let results = self.primary.get_frames(idxs);
+        idxs.iter()
+            .zip(results)
+            .map(|(idx, result)| match result {
+                Ok(data) => Ok(data),
+                Err(e) => self.read_mirror(*idx, e),
+            })
+            .collect()
+    }
+
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let primary = self.primary.put_frames(frames.clone());
+        let mirror = self.mirror.put_frames(frames);
+        primary
+            .into_iter()
+            .zip(mirror)
+            .map(|(primary, mirror)| both(primary, mirror))
+            .collect()
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        both(self.primary.resize(count), self.mirror.resize(count))
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        both(self.primary.truncate(count), self.mirror.truncate(count))
+    }
+
+    fn size(&self) -> u64 {
+        self.primary.size()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        let mirror = self.mirror.assess_size().map(|_| ());
+        both(self.primary.assess_size(), mirror)
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        both(self.primary.sync(), self.mirror.sync())
+    }
+
+    // A frame the primary lost but the mirror holds is populated: a read returns it.
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        match self.primary.frame_state(idx) {
+            FrameState::Populated => FrameState::Populated,
+            state => match self.mirror.frame_state(idx) {
+                FrameState::Populated => FrameState::Populated,
+                _ => state,
+            },
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        self.primary.frame_ids()
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        self.primary
+            .frame_meta(idx)
+            .or_else(|e| self.mirror.frame_meta(idx).map_err(|_| e))
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        both(
+            self.primary.discard_frame(idx),
+            self.mirror.discard_frame(idx),
+        )
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.primary.is_read_only() || self.mirror.is_read_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use crate::framepool::{DiskPool, FaultInjector, FaultyPool, MemPool};
+    use std::fs;
+
+    #[test]
+    fn test_mirrored_pool_falls_back_and_repairs() {
+        let test_dir = "/tmp/test_mirrored_pool";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool =
+            MirroredPool::new(DiskPool::new::<u64>(test_dir), MemPool::<u64>::new()).with_repair();
+        pool.resize(10).unwrap();
+        {
+            let mut bp = BufferPool::<u64>::new(3, &mut pool, bottom_evictor);
+            for i in 0..10 {
+                bp.get_or_insert_with(i, || i + 100).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        assert_eq!(pool.mirror().frame_state(&9), FrameState::Populated);
+
+        // Page 4 is lost from disk; reading it falls back to the mirror and puts it back
+        fs::remove_file(format!("{}/page_4", test_dir)).unwrap();
+        assert_eq!(pool.frame_state(&4), FrameState::Populated);
+        assert_eq!(*pool.get_frame_ref(4).unwrap(), 104);
+        assert_eq!(pool.fallbacks(), 1);
+        assert_eq!(pool.repairs(), (1, 0));
+        assert!(fs::metadata(format!("{}/page_4", test_dir)).is_ok());
+        assert_eq!(*pool.get_frame_ref(4).unwrap(), 104);
+        assert_eq!(pool.fallbacks(), 1);
+
+        // A frame neither holds fails with the primary's error
+        assert!(matches!(
+            pool.get_frame_ref(20),
+            Err(FramePoolError::NotFound(_))
+        ));
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_mirrored_pool_reports_failed_mirror_writes() {
+        let faults = FaultInjector::new(3);
+        let mut pool = MirroredPool::new(
+            MemPool::<u64>::new(),
+            FaultyPool::new(MemPool::<u64>::new(), faults.clone()),
+        );
+        pool.resize(4).unwrap();
+        faults.set_write_failure_rate(1.0);
+        assert!(pool.put_frame(0, Arc::new(1)).is_err());
+        let results = pool.put_frames(vec![(1, Arc::new(2)), (2, Arc::new(3))]);
+        assert!(results.iter().all(|r| r.is_err()));
+        // The primary took the writes all the same
+        assert_eq!(pool.primary().frame_state(&2), FrameState::Populated);
+        let reads = pool.get_frames(&[0, 1, 3]);
+        assert_eq!(*reads[1].as_ref().unwrap().as_ref(), 2);
+        assert!(reads[2].is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use memmap2::MmapMut;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+use std::sync::Arc;
+
+use super::space::{SpaceStats, allocate_blocks};
+use super::{FrameMeta, FramePool, FramePoolError, FrameState, fnv1a};
+
+// A FramePool of byte pages (Vec<u8>, Box<[u8]> and the like) kept in fixed-size slots of a
+// single memory-mapped file, for fixed-size binary pages where a JSON file per page is far
+// too slow. Page i is at slot i; pages may be shorter than a slot but not longer.
+//
+// Pages are neither encoded nor decoded, and reads and writes are copies to and from the
+// mapping with no system call. page() goes further and borrows a page straight from the
+// mapping. sync flushes the mapping to disk; the file must not be changed by anything else
+// while the pool has it open.
+pub struct MmapPool {
+    file: File,
+    map: MmapMut,
+    page_size: usize,
+    size: u64,
+}
+
+// Each slot starts with the length of its page plus one, little-endian, or 0 if it is empty.
+const SLOT_HEADER: usize = 8;
+
+impl MmapPool {
+    // Opens the pool file at path, creating it if needed, with slots for pages of at most
+    // page_size bytes. An existing file must have been written with the same page size.
+    pub fn open(path: impl AsRef<Path>, page_size: usize) -> Result<Self, FramePoolError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let stride = (SLOT_HEADER + page_size) as u64;
+        let len = file.metadata()?.len();
+        if len % stride != 0 {
+            return Err(FramePoolError::Corruption(format!(
+                "{} is {} bytes, not a whole number of {} byte slots",
+                path.display(),
+                len,
+                stride
+            )));
+        }
+        let map = Self::map(&file)?;
+        Ok(MmapPool {
+            file,
+            map,
+            page_size,
+            size: len / stride,
+        })
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    // Page idx, borrowed from the mapping without copying, or None if it is empty or beyond
+    // the end of the pool.
+    pub fn page(&self, idx: u64) -> Option<&[u8]> {
+        if idx >= self.size {
+            return None;
+        }
+        let start = self.slot(idx);
+        let header = u64::from_le_bytes(self.map[start..start + SLOT_HEADER].try_into().unwrap());
+        let len = header.checked_sub(1)? as usize;
+        let data = start + SLOT_HEADER;
+        Some(&self.map[data..data + len.min(self.page_size)])
+    }
+
+    // Adds empty slots until there are at least n_pages, and allocates disk for the whole
+    // file. Writing a page through the mapping to a block the filesystem has no room for
+    // kills the process with SIGBUS rather than failing, which this rules out.
+    pub fn preallocate(&mut self, n_pages: u64) -> Result<(), FramePoolError> {
+        let count = n_pages.max(self.size);
+        allocate_blocks(&self.file, count * self.stride() as u64)?;
+        self.set_slots(count)
+    }
+
+    // The disk the file takes, and how much of it the slots holding pages use.
+    pub fn space_stats(&self) -> Result<SpaceStats, FramePoolError> {
+        let pages = (0..self.size)
+            .filter(|idx| self.page(*idx).is_some())
+            .count() as u64;
+        Ok(SpaceStats::of(&self.file, pages * self.stride() as u64)?)
+    }
+
+    fn map(file: &File) -> Result<MmapMut, FramePoolError> {
+        // SAFETY: the pool holds the file open for as long as the mapping lives, and the
+        // documented contract is that nothing else changes it meanwhile.
+        Ok(unsafe { MmapMut::map_mut(file)? })
+    }
+
+    fn stride(&self) -> usize {
+        SLOT_HEADER + self.page_size
+    }
+
+    fn slot(&self, idx: u64) -> usize {
+        idx as usize * self.stride()
+    }
+
+    // Sets the file to count slots and maps it again. New slots are empty.
+    fn set_slots(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.map.flush()?;
+        self.file.set_len(count * self.stride() as u64)?;
+        self.map = Self::map(&self.file)?;
+        self.size = count;
+        Ok(())
+    }
+
+    fn write_slot(&mut self, idx: u64, data: &[u8]) -> Result<(), FramePoolError> {
+        if data.len() > self.page_size {
+            return Err(FramePoolError::CapacityExceeded {
+                needed: data.len() as u64,
+                limit: self.page_size as u64,
+            });
+        }
+        if idx >= self.size {
+            self.set_slots(idx + 1)?;
+        }
+        let start = self.slot(idx);
+        let header = (data.len() as u64 + 1).to_le_bytes();
+        self.map[start..start + SLOT_HEADER].copy_from_slice(&header);
+        self.map[start + SLOT_HEADER..start + SLOT_HEADER + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl<T> FramePool<T> for MmapPool
+where
+    T: AsRef<[u8]> + for<'a> From<&'a [u8]> + Clone,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        match self.page(idx) {
+            Some(data) => Ok(Arc::new(T::from(data))),
+            None => Err(FramePoolError::NotFound(format!("page {}", idx))),
+        }
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.write_slot(idx, (*data).as_ref())
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.set_slots(self.size + count)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        if count < self.size {
+            self.set_slots(count)?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        Ok(self.size)
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.map.flush()?;
+        Ok(())
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        match self.page(*idx) {
+            Some(_) => FrameState::Populated,
+            None if *idx < self.size => FrameState::Empty,
+            None => FrameState::Absent,
+        }
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        match self.page(*idx) {
+            Some(data) => Ok(FrameMeta {
+                size: data.len() as u64,
+                modified: None,
+                checksum: Some(fnv1a(data)),
+            }),
+            None => Err(FramePoolError::NotFound(format!("page {}", idx))),
+        }
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        if *idx < self.size {
+            let start = self.slot(*idx);
+            self.map[start..start + SLOT_HEADER].fill(0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+
+    #[test]
+    fn test_mmap_pool_behind_bufferpool() {
+        let path = "/tmp/test_mmap_pool/pages.db";
+        let _ = fs::remove_dir_all("/tmp/test_mmap_pool");
+
+        let mut pool = MmapPool::open(path, 64).unwrap();
+        FramePool::<Vec<u8>>::resize(&mut pool, 100).unwrap();
+        {
+            let mut bp = BufferPool::<Vec<u8>>::new(10, &mut pool, bottom_evictor);
+            for i in 0..100 {
+                bp.get_or_insert_with(i, || vec![i as u8; 64]).unwrap();
+            }
+            bp.modify_page(7, |page| page.truncate(3)).unwrap();
+            bp.flush_all().unwrap();
+        }
+        FramePool::<Vec<u8>>::sync(&mut pool).unwrap();
+        drop(pool);
+
+        let mut pool = MmapPool::open(path, 64).unwrap();
+        assert_eq!(FramePool::<Vec<u8>>::size(&pool), 100);
+        assert_eq!(pool.page(7), Some(&[7u8; 3][..]));
+        assert_eq!(
+            *FramePool::<Box<[u8]>>::get_frame_ref(&mut pool, 99).unwrap(),
+            vec![99u8; 64].into_boxed_slice()
+        );
+        assert!(matches!(
+            pool.put_frame(0, Arc::new(vec![0u8; 65])),
+            Err(FramePoolError::CapacityExceeded { .. })
+        ));
+
+        // A file that isn't a whole number of slots was written with another page size
+        drop(pool);
+        assert!(matches!(
+            MmapPool::open(path, 50),
+            Err(FramePoolError::Corruption(_))
+        ));
+        let _ = fs::remove_dir_all("/tmp/test_mmap_pool");
+    }
+
+    #[test]
+    fn test_mmap_pool_grows_and_shrinks() {
+        let path = "/tmp/test_mmap_pool_resize.db";
+        let _ = fs::remove_file(path);
+
+        let mut pool = MmapPool::open(path, 16).unwrap();
+        pool.put_frame(4, Arc::new(b"four".to_vec())).unwrap();
+        assert_eq!(FramePool::<Vec<u8>>::size(&pool), 5);
+        assert_eq!(
+            FramePool::<Vec<u8>>::frame_state(&pool, &2),
+            FrameState::Empty
+        );
+        pool.put_frame(2, Arc::new(Vec::new())).unwrap();
+        assert_eq!(pool.page(2), Some(&[][..]));
+        assert_eq!(FramePool::<Vec<u8>>::frame_meta(&pool, &4).unwrap().size, 4);
+
+        FramePool::<Vec<u8>>::discard_frame(&mut pool, &4).unwrap();
+        assert_eq!(pool.page(4), None);
+        FramePool::<Vec<u8>>::truncate(&mut pool, 3).unwrap();
+        assert_eq!(
+            FramePool::<Vec<u8>>::frame_state(&pool, &4),
+            FrameState::Absent
+        );
+        assert_eq!(fs::metadata(path).unwrap().len(), 3 * 24);
+
+        pool.preallocate(50).unwrap();
+        assert_eq!(FramePool::<Vec<u8>>::size(&pool), 50);
+        assert_eq!(pool.page(2), Some(&[][..]));
+        let stats = pool.space_stats().unwrap();
+        assert_eq!((stats.file_len, stats.used), (50 * 24, 24));
+        assert!(stats.allocated >= stats.file_len);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -1,63 +1,349 @@
+use arc_swap::ArcSwapOption;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::hash::Hash;
+use std::io::Write;
+use std::panic::{AssertUnwindSafe, Location};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::time::{Duration, Instant, SystemTime};
+
+mod backend_pool;
+#[cfg(feature = "backup")]
+mod backup;
+mod check;
+mod chunked;
+mod codec;
+mod compact;
+mod compress;
+mod copy;
+mod dense;
+#[cfg(all(feature = "direct-io", target_os = "linux"))]
+mod direct;
+#[cfg(feature = "encryption")]
+mod encrypt;
+mod error;
+mod faulty;
+#[cfg(feature = "frame-serde")]
+mod frame_serde;
+mod heap_size;
+mod hybrid;
+mod instrument;
+mod keys;
+#[cfg(feature = "sled")]
+mod kv;
+mod migrate;
+mod mirrored;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "object-store")]
+mod object;
+mod page_format;
+mod paged;
+mod prefetch;
+#[cfg(feature = "redis")]
+mod redis_store;
+#[cfg(feature = "remote")]
+mod remote;
+mod retry;
+mod space;
+mod tiered;
+#[cfg(feature = "watch")]
+mod watch;
+mod write_behind;
+pub use backend_pool::{BackendFramePool, FormatKeyFn, ParseKeyFn};
+pub use check::CheckReport;
+pub use chunked::ChunkedPool;
+#[cfg(feature = "bincode")]
+pub use codec::BincodeCodec;
+#[cfg(feature = "cbor")]
+pub use codec::CborCodec;
+#[cfg(feature = "msgpack")]
+pub use codec::MessagePackCodec;
+pub use codec::{Codec, JsonCodec};
+pub use compact::{CompactProgress, Compaction};
+pub use compress::{Compressed, Compression};
+pub use copy::{CopyProgress, copy_pool, resume_copy};
+pub use dense::DenseMemPool;
+#[cfg(all(feature = "direct-io", target_os = "linux"))]
+pub use direct::DIRECT_IO_ALIGN;
+#[cfg(feature = "encryption")]
+pub use encrypt::{Cipher, Encrypted, KeyProvider, StaticKey};
+pub use error::FramePoolError;
+pub use faulty::{FaultInjector, FaultyBackend, FaultyPool};
+pub use heap_size::HeapSize;
+pub use hybrid::HybridPool;
+pub use instrument::{InstrumentedPool, LatencyHistogram, MetricsSnapshot, OpStats, PoolMetrics};
+pub use keys::KeyIter;
+#[cfg(feature = "sled")]
+pub use kv::KvPool;
+pub use migrate::Migrations;
+pub use mirrored::MirroredPool;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapPool;
+#[cfg(feature = "object-store")]
+pub use object::{ObjectStoreBackend, ObjectStorePool};
+pub use page_format::{PAGE_FORMAT_VERSION, PageHeader, codec_id};
+pub use paged::PagedFile;
+pub use prefetch::{PrefetchPool, Prefetcher};
+#[cfg(feature = "redis")]
+pub use redis_store::{RedisBackend, RedisPool};
+#[cfg(feature = "remote")]
+pub use remote::{PageServer, RemotePool};
+pub use retry::{RetryPolicy, RetryingPool};
+pub use space::SpaceStats;
+pub use tiered::TieredPool;
+#[cfg(feature = "watch")]
+pub use watch::ChangeWatch;
+pub use write_behind::{WriteBehindBackend, WriteErrorFn};
+
+#[cfg(feature = "async")]
+mod async_disk;
+#[cfg(feature = "async")]
+pub use async_disk::{AsyncDiskPool, AsyncFramePool};
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub use uring::UringPool;
+
+// One outstanding pin on a frame: when and where it was taken.
+#[derive(Debug, Clone)]
+pub struct PinInfo {
+    pub since: Instant,
+    pub site: &'static Location<'static>,
+    pub label: Option<String>,
+    // set once the pin has been reported as overdue
+    warned: bool,
+}
+
+impl PinInfo {
+    pub fn held_for(&self) -> Duration {
+        self.since.elapsed()
+    }
+}
 
-struct InnerFrame<T> {
-    data: Arc<T>,
-    pins: u32,
+struct InnerFrame {
+    // outstanding pins, oldest first
+    pins: Vec<PinInfo>,
     dirty: bool,
+    // bumped on every modification of data
+    version: u64,
+    // set for frames served by a read-only pool; modifying such a frame is a bug
+    read_only: bool,
+    // the message of a with_data closure that panicked, leaving the data as far as it got,
+    // until the data is replaced
+    panicked: Option<String>,
 }
 
 // A frame is a container for data to be written.
+// With the frame-serde feature a frame serializes, as its data, dirtiness and version.
+// Reading the data takes no lock: it sits in an atomically swapped pointer, and writers publish
+// a new Arc in one store. The lock guards the frame's bookkeeping: readers of pins, dirtiness
+// and version share it, and writers of data or bookkeeping take it exclusively.
 pub struct PageFrame<T> {
-    mutex: Mutex<InnerFrame<T>>,
+    // None only while with_data is modifying the data in place, under the write lock
+    data: ArcSwapOption<T>,
+    inner: RwLock<InnerFrame>,
+    created: Instant,
+    // when the frame was last accessed, in nanoseconds after created; kept outside the lock
+    // so that lock-free reads can record themselves
+    accessed: AtomicU64,
+    // accesses so far, saturating
+    accesses: AtomicU64,
+    // the CLOCK reference bit, set on access and cleared as the clock hand passes
+    referenced: AtomicBool,
+    // what the data weighs, kept up to date as it changes
+    size: AtomicU64,
+    // None weighs the data at the shallow size of T
+    weigher: Option<Weigher<T>>,
 }
 
+// Estimates the bytes a frame's data holds, for PageFrame::with_weigher and the pools'
+// set_weigher. Shared, so one weigher serves every frame of a pool.
+pub type Weigher<T> = Arc<dyn Fn(&T) -> u64 + Send + Sync>;
+
 impl<T> PageFrame<T> {
     pub fn new(data: T) -> Self {
-        PageFrame {
-            mutex: Mutex::new(InnerFrame {
-                data: Arc::new(data),
-                pins: 0,
-                dirty: false,
-            }),
-        }
+        Self::new_with_arc(Arc::new(data))
     }
 
     pub fn new_with_arc(data: Arc<T>) -> Self {
         PageFrame {
-            mutex: Mutex::new(InnerFrame {
-                data,
-                pins: 0,
+            data: ArcSwapOption::from(Some(data)),
+            inner: RwLock::new(InnerFrame {
+                pins: Vec::new(),
                 dirty: false,
+                version: 0,
+                read_only: false,
+                panicked: None,
             }),
+            created: Instant::now(),
+            accessed: AtomicU64::new(0),
+            accesses: AtomicU64::new(0),
+            referenced: AtomicBool::new(false),
+            size: AtomicU64::new(std::mem::size_of::<T>() as u64),
+            weigher: None,
+        }
+    }
+
+    // The same frame, weighing its data with weigher: now, and again whenever the data is
+    // replaced or modified through the frame, so byte_size never measures it.
+    pub fn with_weigher(mut self, weigher: Weigher<T>) -> Self {
+        let size = self.load(|data| weigher(data));
+        self.size.store(size, Ordering::Relaxed);
+        self.weigher = Some(weigher);
+        self
+    }
+
+    // The bytes the data holds, as last weighed: by the frame's weigher, or else the shallow
+    // size of T.
+    pub fn byte_size(&self) -> u64 {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    // Weighs data, the frame's new data, if the frame has a weigher.
+    fn reweigh(&self, data: &T) {
+        if let Some(weigher) = &self.weigher {
+            self.size.store(weigher(data), Ordering::Relaxed);
+        }
+    }
+
+    // Records an access to the frame now, counts it and marks the frame referenced. Reading or
+    // writing its data through the frame does this, as does read_arc; get_data_arc, which
+    // flushes and evictions use, does not.
+    pub fn touch(&self) {
+        self.mark_accessed();
+        let _ = self
+            .accesses
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_add(1));
+    }
+
+    // Records an access to the frame now and marks it referenced, without counting it: what a
+    // BufferPool serving the page does, leaving the read or write that follows to be counted.
+    pub fn mark_accessed(&self) {
+        let nanos = self.created.elapsed().as_nanos() as u64;
+        self.accessed.fetch_max(nanos, Ordering::Relaxed);
+        self.mark_referenced();
+    }
+
+    // Sets the frame's reference bit, as CLOCK, GCLOCK and SIEVE evictors do on an access.
+    pub fn mark_referenced(&self) {
+        self.referenced.store(true, Ordering::Relaxed);
+    }
+
+    // Clears the reference bit, returning whether it was set: what a clock hand does passing
+    // the frame, sparing it this time round if it was.
+    pub fn test_and_clear_referenced(&self) -> bool {
+        self.referenced.swap(false, Ordering::Relaxed)
+    }
+
+    // How many times the frame has been accessed (see touch), for frequency-based eviction
+    // and reports of hot pages. The count stops at u64::MAX rather than wrapping.
+    pub fn access_count(&self) -> u64 {
+        self.accesses.load(Ordering::Relaxed)
+    }
+
+    // When the frame was last accessed, or created if it never has been.
+    pub fn last_access(&self) -> Instant {
+        self.created + Duration::from_nanos(self.accessed.load(Ordering::Relaxed))
+    }
+
+    // How long since the frame was last accessed, for time-based eviction and expiry.
+    pub fn idle_for(&self) -> Duration {
+        self.last_access().elapsed()
+    }
+
+    // The frame's bookkeeping, even if a thread panicked holding the lock: nothing under it is
+    // left half updated, as with_data's closure runs before the bookkeeping changes.
+    fn read_inner(&self) -> RwLockReadGuard<'_, InnerFrame> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_inner(&self) -> RwLockWriteGuard<'_, InnerFrame> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Runs f on the current data. Falls back to waiting on the lock only if a writer is
+    // modifying the data in place at that moment.
+    fn load<R>(&self, f: impl FnOnce(&Arc<T>) -> R) -> R {
+        if let Some(data) = self.data.load().as_ref() {
+            return f(data);
         }
+        let _writer_done = self.read_inner();
+        f(self
+            .data
+            .load()
+            .as_ref()
+            .expect("page data missing outside of with_data"))
     }
 
+    // Pins the frame, recording the caller's location for pin diagnostics.
+    #[track_caller]
     pub fn pin(&self) {
-        let mut inner = self.mutex.lock().unwrap();
-        inner.pins += 1;
+        self.pin_at(Location::caller(), None);
+    }
+
+    // Like pin, with a label to tell this pin apart in pin reports.
+    #[track_caller]
+    pub fn pin_with_label(&self, label: &str) {
+        self.pin_at(Location::caller(), Some(label.to_string()));
+    }
+
+    fn pin_at(&self, site: &'static Location<'static>, label: Option<String>) {
+        let mut inner = self.write_inner();
+        inner.pins.push(PinInfo {
+            since: Instant::now(),
+            site,
+            label,
+            warned: false,
+        });
     }
 
+    // Pins are counted, not identified, so unpin releases the oldest outstanding pin.
     pub fn unpin(&self) {
-        let mut inner = self.mutex.lock().unwrap();
-        inner.pins -= 1;
+        let mut inner = self.write_inner();
+        assert!(!inner.pins.is_empty(), "unpin of a page that is not pinned");
+        inner.pins.remove(0);
     }
 
     pub fn is_pinned(&self) -> bool {
-        let inner = self.mutex.lock().unwrap();
-        inner.pins > 0
+        let inner = self.read_inner();
+        !inner.pins.is_empty()
+    }
+
+    pub fn pin_count(&self) -> u32 {
+        let inner = self.read_inner();
+        inner.pins.len() as u32
+    }
+
+    // The outstanding pins, oldest first.
+    pub fn pins(&self) -> Vec<PinInfo> {
+        let inner = self.read_inner();
+        inner.pins.clone()
+    }
+
+    // Returns the pins held longer than max that have not been returned by an earlier call.
+    pub(crate) fn take_overdue_pins(&self, max: Duration) -> Vec<PinInfo> {
+        let mut inner = self.write_inner();
+        let mut overdue = Vec::new();
+        for pin in inner.pins.iter_mut() {
+            if !pin.warned && pin.held_for() > max {
+                pin.warned = true;
+                overdue.push(pin.clone());
+            }
+        }
+        overdue
     }
 
     pub fn is_dirty(&self) -> bool {
-        let inner = self.mutex.lock().unwrap();
+        let inner = self.read_inner();
         inner.dirty
     }
 
     pub fn set_dirty(&self, dirty: bool) {
-        let mut inner = self.mutex.lock().unwrap();
+        let mut inner = self.write_inner();
         inner.dirty = dirty;
     }
 
@@ -65,60 +351,343 @@ impl<T> PageFrame<T> {
     where
         T: Clone,
     {
-        let inner = self.mutex.lock().unwrap();
-        (*inner.data).clone()
+        self.touch();
+        self.load(|data| (**data).clone())
     }
 
     pub fn put(&self, data: T) {
-        let mut inner = self.mutex.lock().unwrap();
-        inner.data = Arc::new(data);
+        self.put_arc(Arc::new(data));
+    }
+
+    // Replace the data with an existing shared handle, without copying it
+    pub fn put_arc(&self, data: Arc<T>) {
+        let mut inner = self.write_inner();
+        assert!(!inner.read_only, "page is read-only");
+        self.reweigh(&data);
+        self.data.store(Some(data));
+        inner.version += 1;
+        inner.panicked = None;
+        self.touch();
+    }
+
+    // The modification count of this frame, for optimistic concurrency control.
+    pub fn version(&self) -> u64 {
+        let inner = self.read_inner();
+        inner.version
+    }
+
+    // Starts the version count from the given value; used when a frame is (re)loaded.
+    pub(crate) fn set_version(&self, version: u64) {
+        let mut inner = self.write_inner();
+        inner.version = version;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        let inner = self.read_inner();
+        inner.read_only
+    }
+
+    pub(crate) fn set_read_only(&self, read_only: bool) {
+        let mut inner = self.write_inner();
+        inner.read_only = read_only;
+    }
+
+    // Replaces the data and marks the frame dirty only if the version is still `expected`.
+    // Returns the new version on success, or the current version on conflict.
+    pub fn put_if_version(&self, expected: u64, data: T) -> Result<u64, u64> {
+        let mut inner = self.write_inner();
+        assert!(!inner.read_only, "page is read-only");
+        if inner.version != expected {
+            return Err(inner.version);
+        }
+        self.reweigh(&data);
+        self.data.store(Some(Arc::new(data)));
+        inner.dirty = true;
+        inner.version += 1;
+        inner.panicked = None;
+        self.touch();
+        Ok(inner.version)
     }
 
-    // with_data uses copy-on-write semantics for efficient modification
+    // with_data uses copy-on-write semantics for efficient modification.
+    // Panics if the frame is read-only; BufferPool::modify_page reports that as an error instead.
     pub fn with_data<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&mut T) -> R,
         T: Clone,
     {
-        let mut inner = self.mutex.lock().unwrap();
-        // Use Arc::make_mut for copy-on-write - only clones if there are other references
-        let mut_data = Arc::make_mut(&mut inner.data);
-        let result = f(mut_data);
+        self.modify(self.write_inner(), f)
+    }
+
+    // Like with_data, but returns None rather than wait if another thread holds the frame's
+    // lock, as it does through a with_data or put in progress.
+    pub fn try_with_data<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+        T: Clone,
+    {
+        let inner = match self.inner.try_write() {
+            Ok(inner) => inner,
+            Err(TryLockError::WouldBlock) => return None,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        };
+        Some(self.modify(inner, f))
+    }
+
+    fn modify<F, R>(&self, mut inner: RwLockWriteGuard<InnerFrame>, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+        T: Clone,
+    {
+        assert!(!inner.read_only, "page is read-only");
+        // Take the data out while we hold the write lock, so Arc::make_mut only clones if
+        // someone else holds a reference; readers arriving meanwhile wait for the lock.
+        let mut data = self
+            .data
+            .swap(None)
+            .expect("page data missing outside of with_data");
+        // A panicking f still gets its data put back, so that readers carry on; the panic is
+        // recorded, for flushes to refuse the half-made change, and then resumed.
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| f(Arc::make_mut(&mut data))));
+        self.reweigh(&data);
+        self.data.store(Some(data));
         inner.dirty = true;
-        result
+        inner.version += 1;
+        self.touch();
+        match result {
+            Ok(result) => result,
+            Err(panic) => {
+                inner.panicked = Some(panic_message(&*panic));
+                drop(inner);
+                std::panic::resume_unwind(panic)
+            }
+        }
+    }
+
+    // Fails with Poisoned if a with_data closure panicked part way through modifying the data,
+    // until the data is replaced with put, put_arc or put_if_version. The data stays readable
+    // meanwhile, as far as the closure got.
+    pub fn check_poisoned(&self) -> Result<(), FramePoolError> {
+        match &self.read_inner().panicked {
+            Some(message) => Err(FramePoolError::Poisoned(message.clone())),
+            None => Ok(()),
+        }
     }
 
-    // For read-only access (most common in read-heavy workloads) - zero-copy
+    // The data, for writing back to storage: fails with Poisoned rather than persist a change a
+    // panic cut short.
+    pub fn data_to_flush(&self) -> Result<Arc<T>, FramePoolError> {
+        self.check_poisoned()?;
+        Ok(self.get_data_arc())
+    }
+
+    // For read-only access (most common in read-heavy workloads) - zero-copy and lock-free
     pub fn read_data<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&T) -> R,
     {
-        let inner = self.mutex.lock().unwrap();
-        f(&inner.data)
+        self.touch();
+        self.load(|data| f(data))
+    }
+
+    // Like read_data, but returns None rather than wait while with_data is modifying the data
+    // in place, the one time a read blocks.
+    pub fn try_read_data<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let data = self.data.load();
+        let data = data.as_ref()?;
+        self.touch();
+        Some(f(data))
     }
 
     // Get a clone of the Arc<T> for sharing with the backing store
     pub fn get_data_arc(&self) -> Arc<T> {
-        let inner = self.mutex.lock().unwrap();
-        Arc::clone(&inner.data)
+        self.load(Arc::clone)
+    }
+
+    // A shared handle to the data, for a reader: unlike get_data_arc, this is an access.
+    pub fn read_arc(&self) -> Arc<T> {
+        self.touch();
+        self.get_data_arc()
+    }
+}
+
+impl<T: HeapSize + 'static> PageFrame<T> {
+    // The same frame, weighing its data at its shallow size plus what it holds on the heap.
+    pub fn with_heap_size(self) -> Self {
+        self.with_weigher(Arc::new(|data: &T| {
+            std::mem::size_of::<T>() as u64 + data.heap_size()
+        }))
     }
 }
 
+// What a panic's payload says, if it is a message.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => panic
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "panic without a message".to_string()),
+    }
+}
+
+// A FrameKey addresses a frame in a FramePool. Keys that map onto a dense range of slot
+// numbers (like u64) report them through slot/from_slot, which lets pools bounds-check them
+// and allocate them with resize. Other keys, such as (table_id, page_no) pairs or strings, keep
+// the defaults: they are never out of bounds and frames exist once they have been written.
+pub trait FrameKey: Eq + Hash + Clone {
+    fn slot(&self) -> Option<u64> {
+        None
+    }
+    fn from_slot(_slot: u64) -> Option<Self> {
+        None
+    }
+}
+
+impl FrameKey for u64 {
+    fn slot(&self) -> Option<u64> {
+        Some(*self)
+    }
+    fn from_slot(slot: u64) -> Option<Self> {
+        Some(slot)
+    }
+}
+
+impl FrameKey for String {}
+impl FrameKey for (u64, u64) {}
+impl FrameKey for (u32, u64) {}
+
+// Whether a frame has been allocated, and whether it holds data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameState {
+    // never allocated or written
+    Absent,
+    // allocated by resize or grow_to, but never written
+    Empty,
+    // written, so get_frame_ref can return its data
+    Populated,
+}
+
+// What a pool knows about a stored frame, available without deserializing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameMeta {
+    // bytes the frame occupies in storage; in-memory pools report an estimate
+    pub size: u64,
+    // when the frame was last written, if the pool knows
+    pub modified: Option<SystemTime>,
+    // FNV-1a hash of the stored bytes, for pools that store bytes
+    pub checksum: Option<u64>,
+}
+
+// 64-bit FNV-1a, used for frame checksums.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 // A FramePool is a pool of, obviously, frames of <T>.
 // A frame can be nominally considered to be a "block" of data.
 // From a distance, it might be said that a T is really a "Vec<U>", with an upper abstraction, a "slab",
 // simply providing an interface that is vec'y.
-pub trait FramePool<T>
+// Frames are addressed by u64 unless another FrameKey is given.
+pub trait FramePool<T, K = u64>
 where
     T: Clone,
 {
-    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, String>;
-    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), String>;
-    fn resize(&mut self, count: u64) -> Result<(), String>;
+    fn get_frame_ref(&mut self, idx: K) -> Result<Arc<T>, FramePoolError>;
+    fn put_frame(&mut self, idx: K, data: Arc<T>) -> Result<(), FramePoolError>;
+    // resize allocates count more frames after the current end of the pool.
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError>;
+    // grow_to allocates frames until the pool holds count of them. A pool already that large
+    // is left alone.
+    fn grow_to(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let size = self.size();
+        if count > size {
+            self.resize(count - size)
+        } else {
+            Ok(())
+        }
+    }
+    // truncate frees every frame at or beyond count, leaving a pool of at most count frames.
+    // Pools that cannot shrink need not override it.
+    fn truncate(&mut self, _count: u64) -> Result<(), FramePoolError> {
+        Err(FramePoolError::Unsupported("truncate".to_string()))
+    }
     // internally known size of the pool.
     fn size(&self) -> u64;
+    // frame_state reports whether idx is allocated and whether it holds data. The default
+    // cannot tell empty frames from written ones, so it takes every frame within size, and
+    // every frame whose key has no slot number, to be populated.
+    fn frame_state(&self, idx: &K) -> FrameState
+    where
+        K: FrameKey,
+    {
+        match idx.slot() {
+            Some(slot) if slot >= self.size() => FrameState::Absent,
+            _ => FrameState::Populated,
+        }
+    }
+    // frame_ids lists every frame that is allocated or written, including ids past size that
+    // were created outside this pool. Keys with slot numbers come in ascending order. The
+    // default assumes the frames are exactly the slots below size.
+    fn frame_ids(&self) -> Result<Vec<K>, FramePoolError>
+    where
+        K: FrameKey,
+    {
+        (0..self.size())
+            .map(|slot| {
+                K::from_slot(slot)
+                    .ok_or_else(|| FramePoolError::Unsupported("frame_ids".to_string()))
+            })
+            .collect()
+    }
+    // frame_meta describes the stored frame idx. Pools that keep no metadata need not override it.
+    fn frame_meta(&self, _idx: &K) -> Result<FrameMeta, FramePoolError> {
+        Err(FramePoolError::Unsupported("frame_meta".to_string()))
+    }
+    // exists is true when idx holds data that get_frame_ref can return.
+    fn exists(&self, idx: &K) -> bool
+    where
+        K: FrameKey,
+    {
+        self.frame_state(idx) == FrameState::Populated
+    }
+    // discard_frame drops the data held for idx, leaving the frame allocated but empty. Pools
+    // that cannot free single frames need not override it.
+    fn discard_frame(&mut self, _idx: &K) -> Result<(), FramePoolError> {
+        Err(FramePoolError::Unsupported("discard_frame".to_string()))
+    }
     // assess_size retrieves the real-world data size of the pool and updates it
-    fn assess_size(&mut self) -> Result<u64, String>;
+    fn assess_size(&mut self) -> Result<u64, FramePoolError>;
+    // sync makes every completed put_frame durable. Pools without durable state need not override it.
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        Ok(())
+    }
+    // whether the pool rejects writes. Pools that are always writable need not override it.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+    // get_frames reads several frames, returning one result per index, in order. Pools that
+    // can batch their I/O override it.
+    fn get_frames(&mut self, idxs: &[K]) -> Vec<Result<Arc<T>, FramePoolError>>
+    where
+        K: Clone,
+    {
+        idxs.iter()
+            .map(|idx| self.get_frame_ref(idx.clone()))
+            .collect()
+    }
+    // put_frames writes several frames, returning one result per frame, in order.
+    fn put_frames(&mut self, frames: Vec<(K, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        frames
+            .into_iter()
+            .map(|(idx, data)| self.put_frame(idx, data))
+            .collect()
+    }
 }
 
 // Storage backend abstraction for different storage systems
@@ -126,46 +695,107 @@ pub trait StorageBackend<T>
 where
     T: Clone,
 {
-    fn read(&mut self, key: &str) -> Result<Arc<T>, String>;
-    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), String>;
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError>;
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError>;
     fn exists(&self, key: &str) -> bool;
-    fn delete(&mut self, key: &str) -> Result<(), String>;
-    fn list_keys(&self) -> Result<Vec<String>, String>;
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError>;
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError>;
+    // write_if_absent writes data under key unless the key exists already, returning whether
+    // it wrote. The default checks, then writes; backends that can do both at once, so that
+    // of two writers racing to create a key exactly one wins, override it.
+    fn write_if_absent(&mut self, key: &str, data: Arc<T>) -> Result<bool, FramePoolError> {
+        if self.exists(key) {
+            return Ok(false);
+        }
+        self.write(key, data)?;
+        Ok(true)
+    }
+    // iter_keys lists every key lazily, in no particular order, for backends with too many keys
+    // to list at once. The default lists them all with list_keys first.
+    fn iter_keys(&self) -> Result<KeyIter<'_>, FramePoolError> {
+        Ok(Box::new(self.list_keys()?.into_iter().map(Ok)))
+    }
+    // list_keys_with_prefix lists a page of the keys starting with prefix: in ascending order,
+    // those after cursor, at most limit of them. The last key of a page is the cursor for the
+    // next, and a page shorter than limit is the last. The default goes through iter_keys,
+    // holding at most limit keys at a time; backends that can look up a prefix without
+    // listing every key override it.
+    fn list_keys_with_prefix(
+        &self,
+        prefix: &str,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Vec<String>, FramePoolError> {
+        keys::page_keys(self.iter_keys()?, prefix, limit, cursor)
+    }
 }
 
-// File-based storage backend implementation
-pub struct FileBackend {
+// File-based storage backend implementation, one file per key. Files are pretty-printed JSON
+// unless another codec is chosen with with_codec.
+//
+// Keys may be paths, such as "users/42/profile": each '/'-separated name but the last is a
+// directory under the base path, created as needed, and the last names the file. Keys with
+// names that are empty, "." or "..", or would otherwise lead outside the base path, are
+// refused.
+pub struct FileBackend<C = JsonCodec> {
     base_path: PathBuf,
+    codec: C,
+    // fsync each file, and the directory, as it is written
+    sync_writes: bool,
 }
 
 impl FileBackend {
     pub fn new(base_path: &str) -> Self {
         FileBackend {
             base_path: PathBuf::from(base_path),
+            codec: JsonCodec::pretty(),
+            sync_writes: false,
         }
     }
+}
 
-    fn ensure_directory(&self) -> Result<(), String> {
-        if !self.base_path.exists() {
-            fs::create_dir_all(&self.base_path)
-                .map_err(|e| format!("Failed to create directory: {}", e))?;
+impl<C: Codec> FileBackend<C> {
+    // The same backend, reading and writing files with codec instead. File names take the
+    // codec's extension, so files written with another codec are not seen.
+    pub fn with_codec<D: Codec>(self, codec: D) -> FileBackend<D> {
+        FileBackend {
+            base_path: self.base_path,
+            codec,
+            sync_writes: self.sync_writes,
         }
-        Ok(())
     }
 
-    fn get_file_path(&self, key: &str) -> PathBuf {
-        self.base_path.join(format!("{}.json", key))
+    // The same backend, making every write durable before it returns by fsyncing the file and
+    // the directory. Writes are atomic either way; this decides whether they survive a crash.
+    pub fn with_fsync(mut self) -> Self {
+        self.sync_writes = true;
+        self
+    }
+
+    fn get_file_path(&self, key: &str) -> Result<PathBuf, FramePoolError> {
+        let mut path = self.base_path.clone();
+        let mut names = key.split('/').peekable();
+        while let Some(name) = names.next() {
+            if !is_key_name(name) {
+                return Err(FramePoolError::Unsupported(format!("key {:?}", key)));
+            }
+            match names.peek() {
+                Some(_) => path.push(name),
+                None => path.push(format!("{}.{}", name, self.codec.extension())),
+            }
+        }
+        Ok(path)
     }
 
     // Ergonomic helper methods that don't require explicit type annotations
-    pub fn read_data<T>(&mut self, key: &str) -> Result<Arc<T>, String>
+    pub fn read_data<T>(&mut self, key: &str) -> Result<Arc<T>, FramePoolError>
     where
         T: Clone + for<'de> Deserialize<'de> + Serialize,
     {
         <Self as StorageBackend<T>>::read(self, key)
     }
 
-    pub fn write_data<T>(&mut self, key: &str, data: Arc<T>) -> Result<(), String>
+    pub fn write_data<T>(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError>
     where
         T: Clone + for<'de> Deserialize<'de> + Serialize,
     {
@@ -179,708 +809,3762 @@ impl FileBackend {
         <Self as StorageBackend<T>>::exists(self, key)
     }
 
-    pub fn delete_data<T>(&mut self, key: &str) -> Result<(), String>
+    pub fn delete_data<T>(&mut self, key: &str) -> Result<(), FramePoolError>
     where
         T: Clone + for<'de> Deserialize<'de> + Serialize,
     {
         <Self as StorageBackend<T>>::delete(self, key)
     }
 
-    pub fn list_data_keys<T>(&self) -> Result<Vec<String>, String>
+    pub fn write_data_if_absent<T>(
+        &mut self,
+        key: &str,
+        data: Arc<T>,
+    ) -> Result<bool, FramePoolError>
+    where
+        T: Clone + for<'de> Deserialize<'de> + Serialize,
+    {
+        <Self as StorageBackend<T>>::write_if_absent(self, key, data)
+    }
+
+    pub fn list_data_keys<T>(&self) -> Result<Vec<String>, FramePoolError>
     where
         T: Clone + for<'de> Deserialize<'de> + Serialize,
     {
         <Self as StorageBackend<T>>::list_keys(self)
     }
+
+    pub fn iter_data_keys<T>(&self) -> Result<KeyIter<'_>, FramePoolError>
+    where
+        T: Clone + for<'de> Deserialize<'de> + Serialize,
+    {
+        <Self as StorageBackend<T>>::iter_keys(self)
+    }
+
+    pub fn list_data_keys_with_prefix<T>(
+        &self,
+        prefix: &str,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Vec<String>, FramePoolError>
+    where
+        T: Clone + for<'de> Deserialize<'de> + Serialize,
+    {
+        <Self as StorageBackend<T>>::list_keys_with_prefix(self, prefix, limit, cursor)
+    }
 }
 
-impl<T> StorageBackend<T> for FileBackend
+impl<T, C> StorageBackend<T> for FileBackend<C>
 where
     T: Clone + for<'de> Deserialize<'de> + Serialize,
+    C: Codec,
 {
-    fn read(&mut self, key: &str) -> Result<Arc<T>, String> {
-        self.ensure_directory()?;
-        let file_path = self.get_file_path(key);
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        let file_path = self.get_file_path(key)?;
 
-        let content = fs::read_to_string(&file_path)
-            .map_err(|e| format!("Failed to read file {}: {}", file_path.display(), e))?;
+        let content =
+            fs::read(&file_path).map_err(|e| FramePoolError::from_io(e, || key.to_string()))?;
 
-        let data: T = serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to deserialize data: {}", e))?;
+        let data: T = self.codec.decode(&content)?;
 
         Ok(Arc::new(data))
     }
 
-    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), String> {
-        self.ensure_directory()?;
-        let file_path = self.get_file_path(key);
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        let file_path = self.get_file_path(key)?;
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
 
-        let content = serde_json::to_string_pretty(&*data)
-            .map_err(|e| format!("Failed to serialize data: {}", e))?;
+        let content = self.codec.encode(&*data)?;
 
-        fs::write(&file_path, content)
-            .map_err(|e| format!("Failed to write file {}: {}", file_path.display(), e))?;
+        write_atomic(&file_path, &content, self.sync_writes)?;
 
         Ok(())
     }
 
+    // The file is written whole beside its final path and then linked into place, so another
+    // process creating the same key at the same time either wins or finds this one's file.
+    fn write_if_absent(&mut self, key: &str, data: Arc<T>) -> Result<bool, FramePoolError> {
+        let file_path = self.get_file_path(key)?;
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = self.codec.encode(&*data)?;
+        create_atomic(&file_path, &content, self.sync_writes)
+    }
+
     fn exists(&self, key: &str) -> bool {
-        self.get_file_path(key).exists()
+        self.get_file_path(key).is_ok_and(|path| path.exists())
     }
 
-    fn delete(&mut self, key: &str) -> Result<(), String> {
-        let file_path = self.get_file_path(key);
+    // The directories the key was in are removed too, once empty.
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        let file_path = self.get_file_path(key)?;
         if file_path.exists() {
-            fs::remove_file(&file_path)
-                .map_err(|e| format!("Failed to delete file {}: {}", file_path.display(), e))?;
+            fs::remove_file(&file_path)?;
+        }
+        let mut dir = file_path.parent();
+        while let Some(path) = dir.filter(|path| *path != self.base_path) {
+            if fs::remove_dir(path).is_err() {
+                break;
+            }
+            dir = path.parent();
         }
         Ok(())
     }
 
-    fn list_keys(&self) -> Result<Vec<String>, String> {
-        if !self.base_path.exists() {
-            return Ok(vec![]);
-        }
+    // Keys come in ascending order.
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        <Self as StorageBackend<T>>::list_keys_with_prefix(self, "", None, None)
+    }
 
-        let entries = fs::read_dir(&self.base_path)
-            .map_err(|e| format!("Failed to read directory: {}", e))?;
+    // Directories are read an entry at a time, whatever their size.
+    fn iter_keys(&self) -> Result<KeyIter<'_>, FramePoolError> {
+        let walk = keys::KeyWalk::new(&self.base_path, "", self.codec.extension())?;
+        Ok(Box::new(walk))
+    }
 
-        let mut keys = Vec::new();
-        for entry in entries {
-            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-            if let Some(filename) = entry.file_name().to_str()
-                && filename.ends_with(".json")
-            {
-                let key = filename.strip_suffix(".json").unwrap().to_string();
-                keys.push(key);
+    // Only the directory the prefix leads to is walked: "users/42/" walks users/42, and
+    // "users/4" walks users, keeping the keys starting with it.
+    fn list_keys_with_prefix(
+        &self,
+        prefix: &str,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Vec<String>, FramePoolError> {
+        let under = match prefix.rfind('/') {
+            Some(at) => &prefix[..at + 1],
+            None => "",
+        };
+        let mut dir = self.base_path.clone();
+        for name in under.split('/').filter(|name| !name.is_empty()) {
+            if !is_key_name(name) {
+                return Ok(vec![]);
             }
+            dir.push(name);
         }
-
-        Ok(keys)
+        let walk = keys::KeyWalk::new(&dir, under, self.codec.extension())?;
+        keys::page_keys(walk, prefix, limit, cursor)
     }
 }
 
-// Implement MemPool, a memory-only FramePool implementation
-pub struct MemPool<T> {
-    pool: HashMap<u64, Option<PageFrame<T>>>,
+// A StorageBackend holding values in memory, for tests and for composing with the other
+// backend wrappers without touching the filesystem.
+pub struct MemoryBackend<T> {
+    entries: HashMap<String, Arc<T>>,
 }
 
-impl<T> MemPool<T> {
+impl<T> MemoryBackend<T> {
     pub fn new() -> Self {
-        MemPool {
-            pool: HashMap::new(),
+        MemoryBackend {
+            entries: HashMap::new(),
         }
     }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 }
 
-impl<T> Default for MemPool<T> {
+impl<T> Default for MemoryBackend<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T> FramePool<T> for MemPool<T>
+impl<T> StorageBackend<T> for MemoryBackend<T>
 where
     T: Clone,
 {
-    fn get_frame_ref(&mut self, id: u64) -> Result<Arc<T>, String> {
-        match self.pool.get(&id) {
-            Some(Some(frame)) => Ok(Arc::clone(&frame.mutex.lock().unwrap().data)),
-            Some(None) => Err("Frame slot exists but is empty".to_string()),
-            None => Err("No such frame".to_string()),
-        }
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        self.entries
+            .get(key)
+            .cloned()
+            .ok_or_else(|| FramePoolError::NotFound(key.to_string()))
     }
 
-    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), String> {
-        let frame = PageFrame {
-            mutex: Mutex::new(InnerFrame {
-                data,
-                pins: 0,
-                dirty: false,
-            }),
-        };
-        self.pool.insert(idx, Some(frame));
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.entries.insert(key.to_string(), data);
         Ok(())
     }
 
-    fn resize(&mut self, count: u64) -> Result<(), String> {
-        let old_sz = self.size();
-        // from i from 0 to count, insert a None into the pool at pageid = prior_size + i
-        for i in 0..count {
-            self.pool.insert(old_sz + i, None);
+    fn write_if_absent(&mut self, key: &str, data: Arc<T>) -> Result<bool, FramePoolError> {
+        match self.entries.entry(key.to_string()) {
+            std::collections::hash_map::Entry::Occupied(_) => Ok(false),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(data);
+                Ok(true)
+            }
         }
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    // Deleting a missing key succeeds, as for FileBackend.
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        self.entries.remove(key);
         Ok(())
     }
 
-    fn size(&self) -> u64 {
-        self.pool.len() as u64
+    fn iter_keys(&self) -> Result<KeyIter<'_>, FramePoolError> {
+        Ok(Box::new(self.entries.keys().cloned().map(Ok)))
     }
 
-    fn assess_size(&mut self) -> Result<u64, String> {
-        Ok(self.size())
+    // Keys come in ascending order.
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        let mut keys: Vec<String> = self.entries.keys().cloned().collect();
+        keys.sort();
+        Ok(keys)
     }
 }
 
-pub struct DiskPool {
-    initialized: bool,
-    dirname: PathBuf,
-    size: u64,
+// Implement MemPool, a memory-only FramePool implementation
+pub struct MemPool<T, K = u64> {
+    pool: HashMap<K, Option<PageFrame<T>>>,
+    // when each written frame was last written, and what it weighed
+    written: HashMap<K, Written>,
+    // approximate bytes held by written frames, as reported by the weigher
+    bytes: u64,
+    max_bytes: Option<u64>,
+    // None weighs every frame at the shallow size of T
+    weigher: Option<Weigher<T>>,
 }
 
-impl DiskPool {
-    pub fn new<T>(dirname: &str) -> Self {
-        DiskPool {
-            initialized: false,
-            dirname: PathBuf::from(dirname),
-            size: 0,
-        }
+struct Written {
+    at: SystemTime,
+    weight: u64,
+}
+
+impl<T> MemPool<T> {
+    pub fn new() -> Self {
+        MemPool::new_keyed()
     }
+}
 
-    // initialize the pool, if it hasn't been already.
-    // this will create the path
-    fn initialize(&mut self) -> Result<(), String> {
-        if self.initialized {
-            return Ok(());
+impl<T, K> MemPool<T, K> {
+    // A MemPool addressed by keys of type K. For keys without slot numbers, resize
+    // allocates nothing and size counts the frames written.
+    pub fn new_keyed() -> Self {
+        MemPool {
+            pool: HashMap::new(),
+            written: HashMap::new(),
+            bytes: 0,
+            max_bytes: None,
+            weigher: None,
         }
-        fs::create_dir_all(&self.dirname).map_err(|_| "Error creating directory".to_string())?;
-        self.initialized = true;
-        Ok(())
     }
 
-    fn page_path(&self, pageid: u64) -> PathBuf {
-        let path = self.dirname.clone();
-        path.join(format!("page_{}", pageid))
+    // Approximate bytes held by the frames written to this pool.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
     }
-}
-
-impl<T> FramePool<T> for DiskPool
-where
-    T: for<'de> Deserialize<'de> + Serialize + Clone,
-{
-    fn get_frame_ref(&mut self, id: u64) -> Result<Arc<T>, String> {
-        self.initialize()?;
 
-        let result: T = fs::read_to_string(self.page_path(id))
-            .map_err(|_| "Error reading file".to_string())
-            .and_then(|s| {
-                serde_json::from_str(&s).map_err(|_| "Error deserializing".to_string())
-            })?;
-
-        Ok(Arc::new(result))
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_bytes
     }
 
-    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), String> {
-        self.initialize()?;
+    // Caps the bytes the pool may hold; writes that would go past the cap fail with
+    // CapacityExceeded. Lowering the cap below the bytes already held evicts nothing, but
+    // only writes that shrink the pool succeed until it is back under the cap.
+    pub fn set_max_bytes(&mut self, max_bytes: Option<u64>) {
+        self.max_bytes = max_bytes;
+    }
 
-        serde_json::to_string(&*data)
-            .map_err(|_| "Error serializing".to_string())
-            .and_then(|s| {
-                fs::write(self.page_path(idx), s)
-                    .map_err(|x| format!("Error writing file: ${:?}", x))
-            })
+    fn weigh(&self, data: &T) -> u64 {
+        match &self.weigher {
+            Some(weigher) => weigher(data),
+            None => std::mem::size_of::<T>() as u64,
+        }
     }
+}
 
-    fn resize(&mut self, count: u64) -> Result<(), String> {
-        self.initialize()?;
-        let old_sz = <DiskPool as FramePool<T>>::size(self);
-        // from i from 0 to count, insert a None into the pool at pageid = prior_size + i
-        for i in 0..count {
-            let path = self.page_path(old_sz + i);
-            let b = path.exists();
-            if !b {
-                match fs::write(path, "{}") {
-                    Ok(_) => (),
-                    Err(e) => return Err(format!("Error writing file: {:?}", e)),
-                }
+impl<T, K> MemPool<T, K>
+where
+    T: Clone,
+    K: FrameKey,
+{
+    // Replaces how frames are weighed, for instance to count memory T owns on the heap. The
+    // frames already written are weighed again.
+    pub fn set_weigher<F>(&mut self, weigher: F)
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        self.weigher = Some(Arc::new(weigher));
+        let mut bytes = 0;
+        for (key, written) in self.written.iter_mut() {
+            if let Some(Some(frame)) = self.pool.get(key) {
+                written.weight = frame.read_data(|data| (self.weigher.as_ref().unwrap())(data));
             }
+            bytes += written.weight;
         }
-        self.size = old_sz + count;
-        Ok(())
+        self.bytes = bytes;
     }
+}
 
-    fn size(&self) -> u64 {
-        self.size
-    }
+// The file format of MemPool::save_to: allocated-but-empty keys, then written frames.
+#[derive(Serialize)]
+struct SnapshotOut<'s, K, T> {
+    empty: Vec<&'s K>,
+    frames: Vec<(&'s K, &'s T)>,
+}
 
-    // assess the size of the pool, by counting the number of files in the directory
-    fn assess_size(&mut self) -> Result<u64, String> {
-        self.initialize()?;
+#[derive(Deserialize)]
+struct SnapshotIn<K, T> {
+    empty: Vec<K>,
+    frames: Vec<(K, T)>,
+}
+
+impl<T, K> MemPool<T, K>
+where
+    T: Clone + Serialize + for<'de> Deserialize<'de>,
+    K: FrameKey + Serialize + for<'de> Deserialize<'de>,
+{
+    // Writes every frame to a single JSON file at path. The snapshot is written beside path and
+    // renamed over it, so a crash mid-save leaves the previous snapshot intact.
+    pub fn save_to(&self, path: impl AsRef<Path>) -> Result<(), FramePoolError> {
+        self.save_to_with(path, &JsonCodec::default())
+    }
+
+    // save_to, encoding the snapshot with codec.
+    pub fn save_to_with<C: Codec>(
+        &self,
+        path: impl AsRef<Path>,
+        codec: &C,
+    ) -> Result<(), FramePoolError> {
+        let mut empty = Vec::new();
+        let mut arcs = Vec::new();
+        for (key, frame) in self.pool.iter() {
+            match frame {
+                Some(frame) => arcs.push((key, frame.get_data_arc())),
+                None => empty.push(key),
+            }
+        }
+        let snapshot = SnapshotOut {
+            empty,
+            frames: arcs.iter().map(|(key, data)| (*key, &**data)).collect(),
+        };
+        write_atomic(path.as_ref(), &codec.encode(&snapshot)?, true)
+    }
+
+    // Reads a pool written by save_to. Frames are weighed with the default weigher; call
+    // set_weigher afterwards to weigh them differently.
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, FramePoolError> {
+        Self::load_from_with(path, &JsonCodec::default())
+    }
+
+    // Reads a pool written by save_to_with with the same codec.
+    pub fn load_from_with<C: Codec>(
+        path: impl AsRef<Path>,
+        codec: &C,
+    ) -> Result<Self, FramePoolError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|e| FramePoolError::from_io(e, || path.display().to_string()))?;
+        let snapshot: SnapshotIn<K, T> = codec.decode(&bytes)?;
+        let mut pool = MemPool::new_keyed();
+        for key in snapshot.empty {
+            pool.pool.insert(key, None);
+        }
+        for (key, data) in snapshot.frames {
+            pool.put_frame(key, Arc::new(data))?;
+        }
+        Ok(pool)
+    }
+}
+
+impl<T> Default for MemPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, K> FramePool<T, K> for MemPool<T, K>
+where
+    T: Clone,
+    K: FrameKey,
+{
+    fn get_frame_ref(&mut self, id: K) -> Result<Arc<T>, FramePoolError> {
+        match self.pool.get(&id) {
+            Some(Some(frame)) => Ok(frame.get_data_arc()),
+            Some(None) => Err(FramePoolError::NotFound(
+                "frame slot exists but is empty".to_string(),
+            )),
+            None => Err(FramePoolError::NotFound("no such frame".to_string())),
+        }
+    }
+
+    fn put_frame(&mut self, idx: K, data: Arc<T>) -> Result<(), FramePoolError> {
+        let weight = self.weigh(&data);
+        let replaced = self.written.get(&idx).map_or(0, |written| written.weight);
+        let bytes = self.bytes - replaced + weight;
+        if let Some(limit) = self.max_bytes
+            && weight > replaced
+            && bytes > limit
+        {
+            return Err(FramePoolError::CapacityExceeded {
+                needed: bytes,
+                limit,
+            });
+        }
+        self.bytes = bytes;
+        self.written.insert(
+            idx.clone(),
+            Written {
+                at: SystemTime::now(),
+                weight,
+            },
+        );
+        self.pool.insert(idx, Some(PageFrame::new_with_arc(data)));
+        Ok(())
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let old_sz = <Self as FramePool<T, K>>::size(self);
+        // from i from 0 to count, insert a None into the pool at pageid = prior_size + i
+        for i in 0..count {
+            if let Some(key) = K::from_slot(old_sz + i) {
+                self.pool.insert(key, None);
+            }
+        }
+        Ok(())
+    }
+
+    // Frames whose keys have no slot number are never truncated.
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.pool
+            .retain(|key, _| key.slot().is_none_or(|slot| slot < count));
+        let mut freed = 0;
+        self.written.retain(|key, written| {
+            let keep = key.slot().is_none_or(|slot| slot < count);
+            if !keep {
+                freed += written.weight;
+            }
+            keep
+        });
+        self.bytes -= freed;
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.pool.len() as u64
+    }
+
+    fn frame_ids(&self) -> Result<Vec<K>, FramePoolError> {
+        let mut ids: Vec<K> = self.pool.keys().cloned().collect();
+        ids.sort_by_key(|key| key.slot());
+        Ok(ids)
+    }
+
+    fn frame_state(&self, idx: &K) -> FrameState {
+        match self.pool.get(idx) {
+            Some(Some(_)) => FrameState::Populated,
+            Some(None) => FrameState::Empty,
+            None => FrameState::Absent,
+        }
+    }
+
+    // Sizes are what the weigher reported when the frame was written.
+    fn frame_meta(&self, idx: &K) -> Result<FrameMeta, FramePoolError> {
+        match self.pool.get(idx) {
+            Some(_) => Ok(FrameMeta {
+                size: self.written.get(idx).map_or(0, |written| written.weight),
+                modified: self.written.get(idx).map(|written| written.at),
+                checksum: None,
+            }),
+            None => Err(FramePoolError::NotFound("no such frame".to_string())),
+        }
+    }
+
+    fn discard_frame(&mut self, idx: &K) -> Result<(), FramePoolError> {
+        match self.pool.get_mut(idx) {
+            Some(frame) => {
+                *frame = None;
+                if let Some(written) = self.written.remove(idx) {
+                    self.bytes -= written.weight;
+                }
+                Ok(())
+            }
+            None => Err(FramePoolError::NotFound("no such frame".to_string())),
+        }
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        Ok(<Self as FramePool<T, K>>::size(self))
+    }
+}
+
+// A FramePool keeping each frame in its own file, page_<id>, under one directory (or spread
+// over subdirectories of it, with with_fanout). Pages are JSON unless another codec is chosen
+// with with_codec.
+//
+// The directory also holds manifest.json, recording the pool's size, codec and page type and
+// the format version. new loads the size from it, so a reopened pool has its size without
+// assess_size. The rest is checked when the pool first touches its directory: a pool opened
+// with another page type or codec than the manifest records fails with Corruption. The
+// manifest is rewritten, atomically, whenever the size changes, as it does when a page is
+// written past the end.
+//
+// A pool locks its directory, through the lock file pool.lock, for as long as it is open: a
+// writable pool exclusively, from when it first touches the directory, and a read-only one
+// shared, from open_read_only, unless a writer already has it. A writable pool that can't
+// take the lock fails with Locked rather than waiting, so two processes never write the same
+// directory unawares. The locks are advisory (flock on Unix): only other DiskPools honor
+// them. A second pool a process opens on a directory it already has open needs unlocked.
+pub struct DiskPool<C = JsonCodec> {
+    codec: C,
+    initialized: bool,
+    dirname: PathBuf,
+    size: u64,
+    // pages written since the last sync
+    unsynced: HashSet<u64>,
+    // reject put_frame and resize, and never create the directory
+    read_only: bool,
+    // write a checksum header on every page and verify it on read
+    checksums: bool,
+    // write every page in the binary page format, and read only pages in it
+    page_headers: bool,
+    durability: Durability,
+    last_sync: Instant,
+    // when the oldest of the unsynced pages was written
+    unsynced_since: Option<Instant>,
+    // levels of subdirectories pages are spread over; 0 keeps them all in dirname
+    fanout: u8,
+    // the type the pool was made for
+    page_type: &'static str,
+    // the name with_schema gave the page type, recorded in the manifest in place of page_type
+    schema: Option<String>,
+    // with_migrations' upgrades for pages of older versions of the page type
+    migrations: Option<migrate::Upgrade<C>>,
+    // write pages the migrations upgrade back as read
+    rewrite_upgraded: bool,
+    // take the directory's lock at all
+    locking: bool,
+    // the open lock file, locked until the pool is dropped
+    lock: Option<fs::File>,
+}
+
+// What a DiskPool records about itself in manifest.json. The codec is named by its extension.
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format_version: u32,
+    size: u64,
+    codec: String,
+    page_type: String,
+}
+
+const MANIFEST: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+const LOCK_FILE: &str = "pool.lock";
+
+// When a DiskPool makes written pages durable. Whatever the setting, sync makes every page
+// written so far durable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    // fsync each page, and the directory, before the write returns
+    Always,
+    // sync after a write once this long has passed since the last sync
+    Interval(Duration),
+    // group commit: writes are left unsynced and made durable together, by one sync, at the
+    // end of each put_frames batch (a BufferPool's flush_all, say), and otherwise once the
+    // oldest of them has waited this long, as the next write finds
+    Group(Duration),
+    // only when sync is called; a crash may lose pages written since
+    #[default]
+    Never,
+}
+
+// The header a DiskPool with checksums puts in front of every page: a marker, then the CRC32
+// of the rest of the page, little-endian.
+const CHECKSUM_MAGIC: &[u8; 4] = b"BPC1";
+const CHECKSUM_HEADER_LEN: usize = 8;
+
+impl DiskPool {
+    pub fn new<T>(dirname: &str) -> Self {
+        let mut pool = DiskPool {
+            codec: JsonCodec::default(),
+            initialized: false,
+            dirname: PathBuf::from(dirname),
+            size: 0,
+            unsynced: HashSet::new(),
+            read_only: false,
+            checksums: false,
+            page_headers: false,
+            durability: Durability::Never,
+            last_sync: Instant::now(),
+            unsynced_since: None,
+            fanout: 0,
+            page_type: std::any::type_name::<T>(),
+            schema: None,
+            migrations: None,
+            rewrite_upgraded: false,
+            locking: true,
+            lock: None,
+        };
+        if let Ok(Some(manifest)) = pool.read_manifest() {
+            pool.size = manifest.size;
+        }
+        pool
+    }
+
+    // Opens an existing pool directory for reading only: a snapshot, say, or the live
+    // directory of a pool another process is writing. Nothing under the directory is ever
+    // created or modified, and writes, resizes and truncates fail with ReadOnly.
+    //
+    // Pages and the manifest are replaced atomically, so a reader never sees a torn page, only
+    // the old version or the new. A reader's size is the manifest's when it was opened;
+    // assess_size reads the manifest again. While no writer has the directory, the reader
+    // holds a shared lock on it, if there is a lock file, keeping writers out until it is
+    // dropped.
+    pub fn open_read_only<T>(dirname: &str) -> Result<Self, FramePoolError> {
+        let mut pool = DiskPool::new::<T>(dirname);
+        if !pool.dirname.is_dir() {
+            return Err(FramePoolError::NotFound(format!(
+                "pool directory {}",
+                pool.dirname.display()
+            )));
+        }
+        pool.initialized = true;
+        pool.read_only = true;
+        pool.take_lock()?;
+        pool.size = match pool.read_manifest()? {
+            Some(manifest) => {
+                pool.check_manifest(&manifest)?;
+                manifest.size
+            }
+            None => pool.stored_size()?,
+        };
+        Ok(pool)
+    }
+
+    // Deletes the lock file of the pool at dirname, for a lock left stale. Locks go with the
+    // process holding them, so that should only happen on file systems that don't release
+    // them, such as some network file systems. A pool still open on the directory keeps its
+    // lock on the deleted file and no longer keeps anyone out.
+    pub fn force_unlock(dirname: &str) -> Result<(), FramePoolError> {
+        match fs::remove_file(Path::new(dirname).join(LOCK_FILE)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<C: Codec> DiskPool<C> {
+    // The same pool, reading and writing pages with codec instead. Pages already written are
+    // not converted.
+    //
+    // Panics if the pool already has migrations: they decode with the old codec, so they
+    // can't be kept, and dropping them would leave old pages unreadable. Choose the codec
+    // before with_migrations.
+    pub fn with_codec<D: Codec>(self, codec: D) -> DiskPool<D> {
+        assert!(
+            self.migrations.is_none(),
+            "with_codec must come before with_migrations"
+        );
+        DiskPool {
+            codec,
+            initialized: self.initialized,
+            dirname: self.dirname,
+            size: self.size,
+            unsynced: self.unsynced,
+            read_only: self.read_only,
+            checksums: self.checksums,
+            page_headers: self.page_headers,
+            durability: self.durability,
+            last_sync: self.last_sync,
+            unsynced_since: self.unsynced_since,
+            fanout: self.fanout,
+            page_type: self.page_type,
+            schema: self.schema,
+            migrations: None,
+            rewrite_upgraded: false,
+            locking: self.locking,
+            lock: self.lock,
+        }
+    }
+
+    // The same pool, spreading pages over `levels` levels of subdirectories named from a hash
+    // of the page id (ab/cd/page_N for two levels; at most 8), so no directory holds more
+    // than a few thousand pages even in very large pools. Each level divides the pages among
+    // 256 directories.
+    //
+    // Pages already written in another layout, such as the flat one, are moved into place
+    // when the pool first touches its directory. A read-only pool moves nothing and reads
+    // pages where it finds them in its own layout or the flat one.
+    pub fn with_fanout(mut self, levels: u8) -> Self {
+        self.fanout = levels.min(8);
+        self
+    }
+
+    // The same pool, neither taking nor honoring the directory's lock: for another pool on a
+    // directory this process already holds open, such as the readers of a PrefetchPool over
+    // a DiskPool. Nothing then keeps the pools from overwriting each other's pages.
+    pub fn unlocked(mut self) -> Self {
+        self.locking = false;
+        self
+    }
+
+    // The same pool, recording schema in the manifest as the type of its pages in place of the
+    // Rust type's name, and refusing a directory recorded with any other. Unlike the type's
+    // name, a schema name survives the type being renamed or moved to another module; choose
+    // a new one when the type changes in a way old pages won't decode as.
+    pub fn with_schema(mut self, schema: &str) -> Self {
+        self.schema = Some(schema.to_string());
+        self
+    }
+
+    // The page type the manifest records: the schema name, or else the Rust type's name.
+    pub fn schema(&self) -> &str {
+        self.schema.as_deref().unwrap_or(self.page_type)
+    }
+
+    // Fails with TypeMismatch unless T is the type the pool was made for, so that pages are
+    // never decoded as, or written from, another type that happens to serialize alike.
+    fn check_type<T>(&self) -> Result<(), FramePoolError> {
+        let requested = std::any::type_name::<T>();
+        if requested != self.page_type {
+            return Err(FramePoolError::TypeMismatch {
+                stored: self.page_type.to_string(),
+                requested: requested.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    // The same pool, making writes durable as durability says.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    // fsync every page written since the last sync, then the directories holding them. On
+    // Linux, many pages are made durable by one syncfs of the pool's filesystem instead.
+    pub fn sync(&mut self) -> Result<(), FramePoolError> {
+        if !self.initialized {
+            return Ok(());
+        }
+        #[cfg(target_os = "linux")]
+        if self.unsynced.len() >= SYNCFS_MIN_PAGES {
+            sync_filesystem(&self.dirname)?;
+            self.synced();
+            return Ok(());
+        }
+        let mut dirs = HashSet::new();
+        for idx in self.unsynced.iter() {
+            let path = self.page_path(*idx);
+            fs::File::open(&path).and_then(|f| f.sync_all())?;
+            let mut dir = path.parent();
+            while let Some(parent) = dir.filter(|d| d.starts_with(&self.dirname)) {
+                dirs.insert(parent.to_path_buf());
+                dir = parent.parent();
+            }
+        }
+        dirs.insert(self.dirname.clone());
+        for dir in dirs {
+            fs::File::open(&dir).and_then(|d| d.sync_all())?;
+        }
+        self.synced();
+        Ok(())
+    }
+
+    fn synced(&mut self) {
+        self.unsynced.clear();
+        self.unsynced_since = None;
+        self.last_sync = Instant::now();
+    }
+
+    // Records a page written without an fsync, for the next sync.
+    fn mark_unsynced(&mut self, idx: u64) {
+        self.unsynced.insert(idx);
+        self.unsynced_since.get_or_insert_with(Instant::now);
+    }
+
+    // Syncs if the durability interval has run out since the last sync, or the oldest write
+    // of a group has waited as long as it may.
+    fn sync_if_due(&mut self) -> Result<(), FramePoolError> {
+        match self.durability {
+            Durability::Interval(interval) if self.last_sync.elapsed() >= interval => self.sync(),
+            Durability::Group(max_latency)
+                if self
+                    .unsynced_since
+                    .is_some_and(|since| since.elapsed() >= max_latency) =>
+            {
+                self.sync()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // sync_if_due at the end of a batch of writes, which a group commits whole.
+    fn sync_batch(&mut self) -> Result<(), FramePoolError> {
+        match self.durability {
+            Durability::Group(_) => self.sync(),
+            _ => self.sync_if_due(),
+        }
+    }
+
+    // The same pool, writing a CRC32 with every page and checking it on every read, so a page
+    // damaged on disk fails with CorruptPage instead of decoding to garbage or failing to
+    // deserialize. Once on, every page but the `{}` placeholders resize used to write must
+    // carry a checksum: pages written without one count as corrupt.
+    pub fn with_checksums(mut self) -> Self {
+        self.checksums = true;
+        self
+    }
+
+    // The same pool, writing every page in the binary page format (see PageHeader), which
+    // records the format version, the codec and a CRC32 of the page. Reading a page from a
+    // later format version fails with Unsupported, one written by another codec with
+    // Corruption, and one damaged, or without a header, with CorruptPage. This supersedes
+    // with_checksums. convert_pages brings pages written without headers into the format.
+    pub fn with_page_headers(mut self) -> Self {
+        self.page_headers = true;
+        self
+    }
+
+    // Rewrites every page not in the binary page format into it, returning how many it
+    // converted: raw pages, as the codec wrote them, and pages with a (matching) checksum
+    // header from with_checksums. The `{}` placeholders resize used to write are deleted,
+    // leaving their frames allocated but unwritten. Needs page headers on.
+    pub fn convert_pages(&mut self) -> Result<u64, FramePoolError> {
+        if !self.page_headers {
+            return Err(FramePoolError::Unsupported(
+                "convert_pages without page headers".to_string(),
+            ));
+        }
+        self.check_writable()?;
+        self.initialize()?;
+        let codec = codec_id(self.codec.extension());
+        let always = self.durability == Durability::Always;
+        let mut converted = 0;
+        for (idx, path) in page_files(&self.dirname)? {
+            let bytes = fs::read(&path)?;
+            if PageHeader::is_headed(&bytes) {
+                continue;
+            }
+            if bytes == b"{}" {
+                fs::remove_file(&path)?;
+            } else {
+                let body = match bytes.starts_with(CHECKSUM_MAGIC) {
+                    true => checksummed_body(idx, &bytes)?,
+                    false => &bytes[..],
+                };
+                write_atomic(&path, &PageHeader::wrap(codec, 0, body), always)?;
+                if !always {
+                    self.mark_unsynced(idx);
+                }
+            }
+            converted += 1;
+        }
+        Ok(converted)
+    }
+
+    // Deletes the files in the pool's directory it has no use for, returning the bytes they
+    // took: pages at or past the end of the pool, such as those a truncate cut short left
+    // behind, temporaries of writes a crash interrupted, and fanout directories left empty.
+    pub fn gc(&mut self) -> Result<u64, FramePoolError> {
+        self.check_writable()?;
+        self.initialize()?;
+        let dirname = self.dirname.clone();
+        self.collect_garbage(&dirname)
+    }
+
+    fn collect_garbage(&mut self, dir: &Path) -> Result<u64, FramePoolError> {
+        let mut reclaimed = 0;
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() && is_fanout_dir(&name) {
+                reclaimed += self.collect_garbage(&path)?;
+                if fs::read_dir(&path)?.next().is_none() {
+                    fs::remove_dir(&path)?;
+                }
+                continue;
+            }
+            let pageid = name
+                .strip_prefix("page_")
+                .and_then(|id| id.parse::<u64>().ok());
+            let garbage = match pageid {
+                Some(pageid) => pageid >= self.size,
+                None => name.starts_with('.') && name.ends_with(".tmp"),
+            };
+            if garbage && file_type.is_file() {
+                reclaimed += entry.metadata()?.len();
+                fs::remove_file(&path)?;
+                if let Some(pageid) = pageid {
+                    self.unsynced.remove(&pageid);
+                }
+            }
+        }
+        Ok(reclaimed)
+    }
+
+    // Checks the checksum of every page in the directory, returning the ids of the pages that
+    // fail. Needs checksums or page headers on.
+    pub fn verify_all(&self) -> Result<Vec<u64>, FramePoolError> {
+        if !self.checksums && !self.page_headers {
+            return Err(FramePoolError::Unsupported(
+                "verify_all without checksums".to_string(),
+            ));
+        }
+        let mut corrupt = Vec::new();
+        for (idx, path) in page_files(&self.dirname)? {
+            let bytes = fs::read(path)?;
+            if self.checked_body(idx, &bytes).is_err() {
+                corrupt.push(idx);
+            }
+        }
+        Ok(corrupt)
+    }
+
+    // The page contents past the page or checksum header, if the pool writes one and it
+    // matches.
+    fn checked_body<'b>(&self, idx: u64, bytes: &'b [u8]) -> Result<&'b [u8], FramePoolError> {
+        if self.page_headers {
+            let (header, payload) = PageHeader::parse(idx, bytes)?;
+            let codec = codec_id(self.codec.extension());
+            if header.codec != codec {
+                return Err(FramePoolError::Corruption(format!(
+                    "page {} was written by codec {}, not {}",
+                    idx, header.codec, codec
+                )));
+            }
+            return Ok(payload);
+        }
+        if !self.checksums || bytes == b"{}" {
+            return Ok(bytes);
+        }
+        checksummed_body(idx, bytes)
+    }
+
+    fn check_writable(&self) -> Result<(), FramePoolError> {
+        if self.read_only {
+            return Err(FramePoolError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    // The size of the pool, or one past the highest page in the directory if that is larger.
+    // A read-only pool takes the size from the manifest, which a writer may have changed.
+    fn stored_size(&self) -> Result<u64, FramePoolError> {
+        // unlike page_files, a missing directory is an error here
+        fs::metadata(&self.dirname)?;
+        let recorded = match self.read_only {
+            true => self.read_manifest()?.map_or(0, |manifest| manifest.size),
+            false => self.size,
+        };
+        let highest = page_files(&self.dirname)?
+            .last()
+            .map_or(0, |(id, _)| id + 1);
+        Ok(recorded.max(highest))
+    }
+
+    // initialize the pool, if it hasn't been already.
+    // this will create the path, and move pages laid out otherwise into place
+    fn initialize(&mut self) -> Result<(), FramePoolError> {
+        if self.initialized {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dirname)?;
+        self.take_lock()?;
+        // a pool that can't use the directory doesn't keep others out of it
+        if let Err(e) = self.adopt_directory() {
+            self.lock = None;
+            return Err(e);
+        }
+        self.initialized = true;
+        if !self.read_only {
+            self.finish_interrupted_compaction()?;
+        }
+        Ok(())
+    }
+
+    // Checks the manifest, or writes one, and moves pages into the pool's layout.
+    fn adopt_directory(&mut self) -> Result<(), FramePoolError> {
+        match self.read_manifest()? {
+            Some(manifest) => self.check_manifest(&manifest)?,
+            None => self.write_manifest()?,
+        }
+        for (idx, path) in page_files(&self.dirname)? {
+            let target = self.page_path(idx);
+            if path != target {
+                self.make_parent(&target)?;
+                fs::rename(&path, &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Locks the directory: exclusively, creating the lock file and recording this process in
+    // it, for a writable pool, and shared for a read-only one, if there is a lock file.
+    fn take_lock(&mut self) -> Result<(), FramePoolError> {
+        if !self.locking {
+            return Ok(());
+        }
+        let path = self.dirname.join(LOCK_FILE);
+        let file = match self.read_only {
+            true => match fs::File::open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e.into()),
+            },
+            false => fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?,
+        };
+        let locked = match self.read_only {
+            true => file.try_lock_shared(),
+            false => file.try_lock(),
+        };
+        match locked {
+            Ok(()) => (),
+            // reading a live directory is safe; the reader just doesn't keep the writer out
+            Err(fs::TryLockError::WouldBlock) if self.read_only => return Ok(()),
+            Err(fs::TryLockError::WouldBlock) => {
+                let holder = fs::read_to_string(&path).unwrap_or_default();
+                return Err(FramePoolError::Locked(format!(
+                    "{}, last locked for writing by process {}",
+                    self.dirname.display(),
+                    holder.trim()
+                )));
+            }
+            Err(fs::TryLockError::Error(e)) => return Err(e.into()),
+        }
+        if !self.read_only {
+            file.set_len(0)?;
+            (&file).write_all(std::process::id().to_string().as_bytes())?;
+        }
+        self.lock = Some(file);
+        Ok(())
+    }
+
+    // The directory's manifest, if it has one.
+    fn read_manifest(&self) -> Result<Option<Manifest>, FramePoolError> {
+        match fs::read(self.dirname.join(MANIFEST)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes).map_err(|e| {
+                FramePoolError::Corruption(format!("unreadable {}: {}", MANIFEST, e))
+            })?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn check_manifest(&self, manifest: &Manifest) -> Result<(), FramePoolError> {
+        if manifest.format_version > FORMAT_VERSION {
+            return Err(FramePoolError::Unsupported(format!(
+                "pool format version {}",
+                manifest.format_version
+            )));
+        }
+        if manifest.page_type != self.schema() {
+            return Err(FramePoolError::TypeMismatch {
+                stored: manifest.page_type.clone(),
+                requested: self.schema().to_string(),
+            });
+        }
+        if manifest.codec != self.codec.extension() {
+            return Err(FramePoolError::Corruption(format!(
+                "pool pages are {}, not {}",
+                manifest.codec,
+                self.codec.extension()
+            )));
+        }
+        Ok(())
+    }
+
+    fn write_manifest(&self) -> Result<(), FramePoolError> {
+        self.store_manifest(self.durability == Durability::Always)
+    }
+
+    fn store_manifest(&self, sync: bool) -> Result<(), FramePoolError> {
+        let manifest = Manifest {
+            format_version: FORMAT_VERSION,
+            size: self.size,
+            codec: self.codec.extension().to_string(),
+            page_type: self.schema().to_string(),
+        };
+        let bytes = serde_json::to_vec_pretty(&manifest)?;
+        write_atomic(&self.dirname.join(MANIFEST), &bytes, sync)
+    }
+
+    // Grows the pool to end frames, if it is smaller, for pages written past its end.
+    fn extend_to(&mut self, end: u64) -> Result<(), FramePoolError> {
+        if end <= self.size {
+            return Ok(());
+        }
+        self.size = end;
+        self.write_manifest()
+    }
+
+    // Where page pageid belongs in the pool's layout.
+    fn page_path(&self, pageid: u64) -> PathBuf {
+        let mut path = self.dirname.clone();
+        let hash = fnv1a(&pageid.to_le_bytes()).to_le_bytes();
+        for byte in hash.iter().take(self.fanout as usize) {
+            path.push(format!("{:02x}", byte));
+        }
+        path.join(format!("page_{}", pageid))
+    }
+
+    // Where page pageid is to be read from: its place in the layout, or, for a read-only pool
+    // that has not moved its pages, the flat layout.
+    fn stored_page_path(&self, pageid: u64) -> PathBuf {
+        let path = self.page_path(pageid);
+        if self.fanout > 0 && self.read_only && !path.exists() {
+            return self.dirname.join(format!("page_{}", pageid));
+        }
+        path
+    }
+
+    // Creates the fanout directories path goes in.
+    fn make_parent(&self, path: &Path) -> Result<(), FramePoolError> {
+        match path.parent() {
+            Some(dir) if self.fanout > 0 => Ok(fs::create_dir_all(dir)?),
+            _ => Ok(()),
+        }
+    }
+
+    // Page id, and whether it was upgraded by migrations that write what they upgrade back.
+    fn read_page<T>(&self, id: u64) -> Result<(Arc<T>, bool), FramePoolError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let bytes = fs::read(self.stored_page_path(id)).map_err(|e| {
+            FramePoolError::from_io(e, || match id < self.size {
+                true => format!("page {}, allocated but never written", id),
+                false => format!("page {}", id),
+            })
+        })?;
+        let body = self.checked_body(id, &bytes)?;
+        match self.codec.decode(body) {
+            Ok(page) => Ok((Arc::new(page), false)),
+            Err(e) => match self.upgrade(body) {
+                Some(page) => Ok((Arc::new(page), self.rewrite_upgraded)),
+                None => Err(e),
+            },
+        }
+    }
+
+    // Writes a page read_page upgraded back, unless the pool is read-only. The page was read
+    // whether or not this works, so failures are left for the next read to try again.
+    fn rewrite_upgraded<T: Serialize>(&mut self, id: u64, page: &T) {
+        if !self.read_only && self.write_page(id, page).is_ok() {
+            let _ = self.sync_if_due();
+        }
+    }
+
+    fn write_page<T: Serialize>(&mut self, idx: u64, data: &T) -> Result<(), FramePoolError> {
+        let mut bytes = self.codec.encode(data)?;
+        if self.page_headers {
+            bytes = PageHeader::wrap(codec_id(self.codec.extension()), 0, &bytes);
+        } else if self.checksums {
+            let mut page = Vec::with_capacity(CHECKSUM_HEADER_LEN + bytes.len());
+            page.extend_from_slice(CHECKSUM_MAGIC);
+            page.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+            page.extend_from_slice(&bytes);
+            bytes = page;
+        }
+        let always = self.durability == Durability::Always;
+        let path = self.page_path(idx);
+        self.make_parent(&path)?;
+        write_atomic(&path, &bytes, always)?;
+        if !always {
+            self.mark_unsynced(idx);
+        }
+        Ok(())
+    }
+}
+
+// The page past the checksum header with_checksums writes, if the checksum matches.
+fn checksummed_body(idx: u64, bytes: &[u8]) -> Result<&[u8], FramePoolError> {
+    if bytes.len() < CHECKSUM_HEADER_LEN || &bytes[..4] != CHECKSUM_MAGIC {
+        return Err(FramePoolError::CorruptPage { idx });
+    }
+    let (header, body) = bytes.split_at(CHECKSUM_HEADER_LEN);
+    let stored = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if crc32fast::hash(body) != stored {
+        return Err(FramePoolError::CorruptPage { idx });
+    }
+    Ok(body)
+}
+
+// Whether name can be one of the '/'-separated names of a FileBackend key: a single file or
+// directory name, not one that moves about the tree.
+fn is_key_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(part)), None) if part == name
+    )
+}
+
+// Writes bytes to path by way of a temporary file in the same directory, renamed over path
+// once complete, so a crash leaves the old contents or the new but never a torn file. The
+// temporary's name starts with a dot, so neither page counts nor key listings see one left
+// behind. With sync, the file is fsynced before the rename and the directory after it.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8], sync: bool) -> Result<(), FramePoolError> {
+    let staging = stage(path, bytes, sync)?;
+    if let Err(e) = fs::rename(&staging, path) {
+        let _ = fs::remove_file(&staging);
+        return Err(e.into());
+    }
+    if sync {
+        sync_parent(path)?;
+    }
+    Ok(())
+}
+
+// write_atomic, unless there is a file at path already: the staged file is linked into place,
+// which fails rather than replace one. Returns whether it was written.
+pub(crate) fn create_atomic(path: &Path, bytes: &[u8], sync: bool) -> Result<bool, FramePoolError> {
+    let staging = stage(path, bytes, sync)?;
+    let linked = fs::hard_link(&staging, path);
+    let _ = fs::remove_file(&staging);
+    match linked {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+        Err(e) => return Err(e.into()),
+    }
+    if sync {
+        sync_parent(path)?;
+    }
+    Ok(true)
+}
+
+// Writes bytes to a new temporary file beside path, returning the temporary's path.
+fn stage(path: &Path, bytes: &[u8], sync: bool) -> Result<PathBuf, FramePoolError> {
+    let staging = staging_path(path);
+    let staged = fs::File::create(&staging).and_then(|mut file| {
+        file.write_all(bytes)?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    });
+    if let Err(e) = staged {
+        let _ = fs::remove_file(&staging);
+        return Err(e.into());
+    }
+    Ok(staging)
+}
+
+// A new name for a temporary file beside path, unique within the process.
+pub(crate) fn staging_path(path: &Path) -> PathBuf {
+    static STAGED: AtomicU64 = AtomicU64::new(0);
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("page");
+    path.with_file_name(format!(
+        ".{}.{}.{}.tmp",
+        name,
+        std::process::id(),
+        STAGED.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+// The number of unsynced pages from which DiskPool::sync is one syncfs on Linux: cheaper than
+// an fsync per page, though it also writes out whatever else on the filesystem is dirty.
+#[cfg(target_os = "linux")]
+const SYNCFS_MIN_PAGES: usize = 16;
+
+// Writes out everything the OS holds for the filesystem dir is on, files and directories
+// alike, and waits for it to reach the device.
+#[cfg(target_os = "linux")]
+fn sync_filesystem(dir: &Path) -> Result<(), FramePoolError> {
+    use std::os::unix::io::AsRawFd;
+    let dir = fs::File::open(dir)?;
+    // SAFETY: syncfs takes no pointers, and the descriptor is open while dir lives
+    if unsafe { libc::syncfs(dir.as_raw_fd()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+fn sync_parent(path: &Path) -> Result<(), FramePoolError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+// The state of a page file: missing, holding the placeholder resize writes (or, for a
+// DiskPool, used to write), or written. A frame whose data encodes to the bytes `{}` is
+// indistinguishable from the placeholder, and reads as empty.
+pub(crate) fn page_file_state(path: &Path) -> FrameState {
+    match fs::metadata(path) {
+        Err(_) => FrameState::Absent,
+        Ok(meta) if meta.len() == 2 && fs::read(path).is_ok_and(|s| s == b"{}") => {
+            FrameState::Empty
+        }
+        Ok(_) => FrameState::Populated,
+    }
+}
+
+// Metadata of a page file. The checksum reads the file, but nothing is deserialized.
+pub(crate) fn page_file_meta(path: &Path, pageid: u64) -> Result<FrameMeta, FramePoolError> {
+    let not_found = |e| FramePoolError::from_io(e, || format!("page {}", pageid));
+    let meta = fs::metadata(path).map_err(not_found)?;
+    let bytes = fs::read(path).map_err(not_found)?;
+    Ok(FrameMeta {
+        size: meta.len(),
+        modified: meta.modified().ok(),
+        checksum: Some(fnv1a(&bytes)),
+    })
+}
+
+// Deletes every page file in dirname numbered count or above, including pages past the end the
+// pool knows about.
+pub(crate) fn remove_pages_from(dirname: &Path, count: u64) -> Result<(), FramePoolError> {
+    for (pageid, path) in page_files(dirname)? {
+        if pageid >= count {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+// The ids of every page file in dirname, in ascending order. A missing directory holds no pages.
+pub(crate) fn page_ids(dirname: &Path) -> Result<Vec<u64>, FramePoolError> {
+    Ok(page_files(dirname)?.into_iter().map(|(id, _)| id).collect())
+}
+
+// Whether name is that of a fanout directory: two hex digits.
+fn is_fanout_dir(name: &str) -> bool {
+    name.len() == 2 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// Every page file under dirname, flat or in fanout directories (those named with two hex
+// digits), with its id, in ascending order of id.
+pub(crate) fn page_files(dirname: &Path) -> Result<Vec<(u64, PathBuf)>, FramePoolError> {
+    fn walk(dir: &Path, files: &mut Vec<(u64, PathBuf)>) -> Result<(), FramePoolError> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            if let Some(pageid) = name
+                .strip_prefix("page_")
+                .and_then(|id| id.parse::<u64>().ok())
+            {
+                files.push((pageid, entry.path()));
+            } else if is_fanout_dir(&name) && entry.file_type()?.is_dir() {
+                walk(&entry.path(), files)?;
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    walk(dirname, &mut files)?;
+    files.sort_unstable();
+    Ok(files)
+}
+
+impl<T, C> FramePool<T> for DiskPool<C>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Codec,
+{
+    fn get_frame_ref(&mut self, id: u64) -> Result<Arc<T>, FramePoolError> {
+        self.check_type::<T>()?;
+        self.initialize()?;
+        let (page, upgraded) = self.read_page(id)?;
+        if upgraded {
+            self.rewrite_upgraded(id, &*page);
+        }
+        Ok(page)
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.check_type::<T>()?;
+        self.check_writable()?;
+        self.initialize()?;
+        self.write_page(idx, &*data)?;
+        self.extend_to(idx + 1)?;
+        self.sync_if_due()
+    }
+
+    // Sets up the directory once for the whole batch, and reads (and upgrades) a page
+    // requested more than once only once.
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        if let Err(e) = self.check_type::<T>().and_then(|_| self.initialize()) {
+            return idxs.iter().map(|_| Err(e.clone())).collect();
+        }
+        let mut read: HashMap<u64, Result<Arc<T>, FramePoolError>> = HashMap::new();
+        for idx in idxs {
+            if !read.contains_key(idx) {
+                let page = self.read_page(*idx).map(|(page, upgraded)| {
+                    if upgraded {
+                        self.rewrite_upgraded(*idx, &*page);
+                    }
+                    page
+                });
+                read.insert(*idx, page);
+            }
+        }
+        idxs.iter().map(|idx| read[idx].clone()).collect()
+    }
+
+    // Checks writability and sets up the directory once for the whole batch. A frame written
+    // more than once is only written in its final form; the earlier entries report the result
+    // of that write.
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let ready = self.check_type::<T>().and_then(|_| self.check_writable());
+        if let Err(e) = ready.and_then(|_| self.initialize()) {
+            return frames.iter().map(|_| Err(e.clone())).collect();
+        }
+        let last: HashMap<u64, usize> = frames
+            .iter()
+            .enumerate()
+            .map(|(pos, (idx, _))| (*idx, pos))
+            .collect();
+        let mut written: HashMap<u64, Result<(), FramePoolError>> = HashMap::new();
+        for (pos, (idx, data)) in frames.iter().enumerate() {
+            if last[idx] == pos {
+                let result = self.write_page(*idx, &**data);
+                written.insert(*idx, result);
+            }
+        }
+        // A failed sync fails the writes it should have made durable, and a failure to record
+        // the pool's new size fails those past the old end
+        let end = written
+            .iter()
+            .filter(|(_, result)| result.is_ok())
+            .map(|(idx, _)| idx + 1)
+            .max()
+            .unwrap_or(0);
+        if let Err(e) = self.extend_to(end).and_then(|_| self.sync_batch()) {
+            for result in written.values_mut() {
+                if result.is_ok() {
+                    *result = Err(e.clone());
+                }
+            }
+        }
+        frames.iter().map(|(idx, _)| written[idx].clone()).collect()
+    }
+
+    // Allocation is lazy: the new frames are only recorded in the manifest, and get a page file
+    // when first written.
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.check_writable()?;
+        self.initialize()?;
+        self.size += count;
+        self.write_manifest()
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.check_writable()?;
+        self.initialize()?;
+        remove_pages_from(&self.dirname, count)?;
+        self.unsynced.retain(|idx| *idx < count);
+        self.size = self.size.min(count);
+        self.write_manifest()
+    }
+
+    // Deletes the frame's page file; compact gives the space of frames left empty back.
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.check_writable()?;
+        self.initialize()?;
+        let path = self.page_path(*idx);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        self.unsynced.remove(idx);
+        if self.durability == Durability::Always {
+            sync_parent(&path)?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    // A frame within the pool without a page file was allocated and never written.
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        match page_file_state(&self.stored_page_path(*idx)) {
+            FrameState::Absent if *idx < self.size => FrameState::Empty,
+            state => state,
+        }
+    }
+
+    // Every frame within the pool, written or not, and any page files past its end.
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids: Vec<u64> = (0..self.size).collect();
+        ids.extend(
+            page_ids(&self.dirname)?
+                .into_iter()
+                .filter(|id| *id >= self.size),
+        );
+        Ok(ids)
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        page_file_meta(&self.stored_page_path(*idx), *idx)
+    }
+
+    // assess the size of the pool: its recorded size, grown to cover any pages written past
+    // its end by others
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.initialize()?;
+        self.size = self.stored_size()?;
+        Ok(self.size)
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        DiskPool::sync(self)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+}
+
+// Wraps a FramePool and remembers failed reads for a while, so repeated lookups of frames that
+// don't exist are answered without going back to the wrapped pool. Only NotFound and
+// OutOfBounds are remembered; any other failure may not happen again. A write or discard
+// through the wrapper clears the remembered failure for that frame, and growing the pool clears
+// all of them. At most max_misses failures are remembered: expired ones are dropped to make
+// room, then the oldest.
+pub struct NegativeCachePool<P, K = u64> {
+    inner: P,
+    ttl: Duration,
+    max_misses: usize,
+    // frame id -> when the read failed and the error it failed with
+    misses: HashMap<K, (Instant, FramePoolError)>,
+    // frame ids in the order their failures were remembered, oldest first; entries for failures
+    // since forgotten or remembered again are skipped
+    order: VecDeque<(K, Instant)>,
+}
+
+// The number of failures a NegativeCachePool remembers unless told otherwise.
+const DEFAULT_MAX_MISSES: usize = 65536;
+
+impl<P, K> NegativeCachePool<P, K>
+where
+    K: FrameKey,
+{
+    pub fn new(inner: P, ttl: Duration) -> Self {
+        NegativeCachePool {
+            inner,
+            ttl,
+            max_misses: DEFAULT_MAX_MISSES,
+            misses: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // A zero TTL disables negative caching.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    pub fn max_misses(&self) -> usize {
+        self.max_misses
+    }
+
+    // Caps the number of remembered failures, dropping the oldest beyond it. Zero disables
+    // negative caching.
+    pub fn set_max_misses(&mut self, max_misses: usize) {
+        self.max_misses = max_misses;
+        self.make_room(0);
+    }
+
+    // Forgets a remembered failure, e.g. after the frame was written behind the wrapper's back.
+    pub fn invalidate(&mut self, idx: &K) {
+        self.misses.remove(idx);
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.misses.clear();
+        self.order.clear();
+    }
+
+    // The number of remembered failures, including expired ones not yet looked up again.
+    pub fn cached_misses(&self) -> usize {
+        self.misses.len()
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    // Writes through this handle bypass invalidation; call invalidate for the frames touched.
+    pub fn inner_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    // The remembered failure for idx, unless it has expired.
+    fn cached_miss(&mut self, idx: &K) -> Option<FramePoolError> {
+        let (failed_at, err) = self.misses.get(idx)?;
+        if failed_at.elapsed() < self.ttl {
+            return Some(err.clone());
+        }
+        self.misses.remove(idx);
+        None
+    }
+
+    // Remembers a failed read of idx, if it failed because the frame isn't there.
+    fn remember(&mut self, idx: K, err: &FramePoolError) {
+        let missing = matches!(
+            err,
+            FramePoolError::NotFound(_) | FramePoolError::OutOfBounds(_)
+        );
+        if missing && !self.ttl.is_zero() && self.max_misses > 0 {
+            self.misses.remove(&idx);
+            self.make_room(1);
+            let failed_at = Instant::now();
+            self.misses.insert(idx.clone(), (failed_at, err.clone()));
+            self.order.push_back((idx, failed_at));
+        }
+    }
+
+    // Forgets expired failures, then the oldest, until `room` more fit under max_misses.
+    fn make_room(&mut self, room: usize) {
+        while let Some((idx, failed_at)) = self.order.front() {
+            let live = self.misses.get(idx).is_some_and(|(at, _)| at == failed_at);
+            let full = self.misses.len() + room > self.max_misses;
+            if live && !full && failed_at.elapsed() < self.ttl {
+                break;
+            }
+            if live {
+                self.misses.remove(idx);
+            }
+            self.order.pop_front();
+        }
+        // Keep the skipped entries from piling up behind a long-lived front
+        if self.order.len() > 2 * self.max_misses.max(self.misses.len()) {
+            let misses = &self.misses;
+            self.order
+                .retain(|(idx, failed_at)| misses.get(idx).is_some_and(|(at, _)| at == failed_at));
+        }
+    }
+}
+
+impl<T, K, P> FramePool<T, K> for NegativeCachePool<P, K>
+where
+    T: Clone,
+    K: FrameKey,
+    P: FramePool<T, K>,
+{
+    fn get_frame_ref(&mut self, idx: K) -> Result<Arc<T>, FramePoolError> {
+        if let Some(err) = self.cached_miss(&idx) {
+            return Err(err);
+        }
+        let result = self.inner.get_frame_ref(idx.clone());
+        if let Err(err) = &result {
+            self.remember(idx, err);
+        }
+        result
+    }
+
+    // Answers the remembered failures itself and reads the rest from the wrapped pool in one
+    // batch.
+    fn get_frames(&mut self, idxs: &[K]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        let mut results: Vec<Option<Result<Arc<T>, FramePoolError>>> = idxs
+            .iter()
+            .map(|idx| self.cached_miss(idx).map(Err))
+            .collect();
+        let to_read: Vec<K> = idxs
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_none())
+            .map(|(idx, _)| idx.clone())
+            .collect();
+        let mut read = self.inner.get_frames(&to_read).into_iter();
+        for (idx, result) in idxs.iter().zip(results.iter_mut()) {
+            if result.is_some() {
+                continue;
+            }
+            let got = read.next().unwrap_or_else(|| {
+                Err(FramePoolError::Unsupported("short get_frames".to_string()))
+            });
+            if let Err(err) = &got {
+                self.remember(idx.clone(), err);
+            }
+            *result = Some(got);
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    fn put_frame(&mut self, idx: K, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.misses.remove(&idx);
+        self.inner.put_frame(idx, data)
+    }
+
+    fn put_frames(&mut self, frames: Vec<(K, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        for (idx, _) in frames.iter() {
+            self.misses.remove(idx);
+        }
+        self.inner.put_frames(frames)
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.invalidate_all();
+        self.inner.resize(count)
+    }
+
+    fn grow_to(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.invalidate_all();
+        self.inner.grow_to(count)
+    }
+
+    // The frame is left empty, which the wrapped pool may report as another failure than the
+    // one remembered, so the next read goes to it.
+    fn discard_frame(&mut self, idx: &K) -> Result<(), FramePoolError> {
+        self.misses.remove(idx);
+        self.inner.discard_frame(idx)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.invalidate_all();
+        self.inner.truncate(count)
+    }
+
+    fn frame_state(&self, idx: &K) -> FrameState {
+        self.inner.frame_state(idx)
+    }
+
+    fn frame_ids(&self) -> Result<Vec<K>, FramePoolError> {
+        self.inner.frame_ids()
+    }
+
+    fn frame_meta(&self, idx: &K) -> Result<FrameMeta, FramePoolError> {
+        self.inner.frame_meta(idx)
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.inner.assess_size()
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_page_frame_new() {
+        let frame = PageFrame::new(42);
+        assert_eq!(frame.data(), 42);
+        assert!(!frame.is_pinned());
+        assert!(!frame.is_dirty());
+    }
+
+    #[test]
+    fn test_page_frame_pin_unpin() {
+        let frame = PageFrame::new(42);
+        assert!(!frame.is_pinned());
+
+        frame.pin();
+        assert!(frame.is_pinned());
+
+        frame.pin(); // Pin twice
+        assert!(frame.is_pinned());
+
+        frame.unpin();
+        assert!(frame.is_pinned()); // Still pinned (count = 1)
+
+        frame.unpin();
+        assert!(!frame.is_pinned()); // Now unpinned
+    }
+
+    #[test]
+    fn test_page_frame_dirty_flag() {
+        let frame = PageFrame::new(42);
+        assert!(!frame.is_dirty());
+
+        frame.set_dirty(true);
+        assert!(frame.is_dirty());
+
+        frame.set_dirty(false);
+        assert!(!frame.is_dirty());
+    }
+
+    #[test]
+    fn test_page_frame_put() {
+        let frame = PageFrame::new(42);
+        assert_eq!(frame.data(), 42);
+
+        frame.put(100);
+        assert_eq!(frame.data(), 100);
+    }
+
+    #[test]
+    fn test_page_frame_last_access() {
+        let frame = PageFrame::new(1);
+        let created = frame.last_access();
+        std::thread::sleep(Duration::from_millis(2));
+        // flushes and evictions don't count as accesses
+        frame.get_data_arc();
+        assert_eq!(frame.last_access(), created);
+        assert!(frame.idle_for() >= Duration::from_millis(2));
+
+        frame.read_data(|_| ());
+        let read = frame.last_access();
+        assert!(read > created);
+        std::thread::sleep(Duration::from_millis(2));
+        frame.put(2);
+        assert!(frame.last_access() > read);
+        assert!(frame.idle_for() < Duration::from_millis(2));
+    }
+
+    #[test]
+    fn test_page_frame_access_count() {
+        let frame = PageFrame::new(vec![1]);
+        assert_eq!(frame.access_count(), 0);
+        frame.data();
+        frame.read_data(|_| ());
+        frame.get_data_arc();
+        frame.read_arc();
+        frame.with_data(|v| v.push(2));
+        frame.put(vec![3]);
+        frame.mark_accessed();
+        assert_eq!(frame.access_count(), 5);
+
+        frame.accesses.store(u64::MAX, Ordering::Relaxed);
+        frame.touch();
+        assert_eq!(frame.access_count(), u64::MAX);
+    }
+
+    #[test]
+    fn test_page_frame_byte_size() {
+        let frame = PageFrame::new(vec![0u8; 4]);
+        assert_eq!(frame.byte_size(), std::mem::size_of::<Vec<u8>>() as u64);
+
+        let frame = frame.with_weigher(Arc::new(|data: &Vec<u8>| data.len() as u64));
+        assert_eq!(frame.byte_size(), 4);
+        frame.with_data(|data| data.push(1));
+        assert_eq!(frame.byte_size(), 5);
+        frame.put(vec![]);
+        assert_eq!(frame.byte_size(), 0);
+        frame.put_if_version(frame.version(), vec![1, 2]).unwrap();
+        assert_eq!(frame.byte_size(), 2);
+
+        let frame = PageFrame::new(String::with_capacity(10)).with_heap_size();
+        assert_eq!(frame.byte_size(), std::mem::size_of::<String>() as u64 + 10);
+    }
+
+    #[test]
+    fn test_page_frame_try_accessors() {
+        let frame = Arc::new(PageFrame::new(vec![1]));
+        assert_eq!(frame.try_read_data(|v| v.len()), Some(1));
+        assert_eq!(frame.try_with_data(|v| v.push(2)), Some(()));
+
+        // While a long with_data runs, the try_ accessors give up rather than wait
+        let (started, finish) = (
+            Arc::new(std::sync::Barrier::new(2)),
+            Arc::new(std::sync::Barrier::new(2)),
+        );
+        let writer = {
+            let (frame, started, finish) = (
+                Arc::clone(&frame),
+                Arc::clone(&started),
+                Arc::clone(&finish),
+            );
+            std::thread::spawn(move || {
+                frame.with_data(|v| {
+                    started.wait();
+                    finish.wait();
+                    v.push(3);
+                })
+            })
+        };
+        started.wait();
+        assert_eq!(frame.try_read_data(|v| v.len()), None);
+        assert_eq!(frame.try_with_data(|v| v.push(4)), None);
+        finish.wait();
+        writer.join().unwrap();
+        assert_eq!(frame.try_read_data(|v| v.clone()), Some(vec![1, 2, 3]));
+
+        // so does try_with_data while the frame's bookkeeping is locked for reading
+        let inner = frame.inner.read().unwrap();
+        assert_eq!(frame.try_with_data(|v| v.push(4)), None);
+        drop(inner);
+        assert_eq!(frame.version(), 2);
+    }
+
+    #[test]
+    fn test_page_frame_survives_panics() {
+        let frame = PageFrame::new(vec![1]);
+        let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            frame.with_data(|v| {
+                v.push(2);
+                panic!("half done: {}", v.len());
+            })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(frame.read_data(|v| v.clone()), vec![1, 2]);
+        assert_eq!(frame.version(), 1);
+        assert!(matches!(
+            frame.data_to_flush(),
+            Err(FramePoolError::Poisoned(message)) if message == "half done: 2"
+        ));
+        frame.with_data(|v| v.push(3));
+        assert!(frame.check_poisoned().is_err());
+        frame.put(vec![4]);
+        assert!(frame.check_poisoned().is_ok());
+
+        // nor does a panic elsewhere under the lock take the frame down
+        let unpinned = std::panic::catch_unwind(AssertUnwindSafe(|| frame.unpin()));
+        assert!(unpinned.is_err());
+        frame.pin();
+        assert_eq!(frame.pin_count(), 1);
+        assert_eq!(frame.try_with_data(|v| v.len()), Some(1));
+    }
+
+    #[test]
+    fn test_page_frame_reference_bit() {
+        let frame = PageFrame::new(1);
+        assert!(!frame.test_and_clear_referenced());
+        frame.mark_referenced();
+        assert!(frame.test_and_clear_referenced());
+        assert!(!frame.test_and_clear_referenced());
+        frame.get_data_arc();
+        assert!(!frame.test_and_clear_referenced());
+        frame.read_data(|_| ());
+        assert!(frame.test_and_clear_referenced());
+    }
+
+    #[test]
+    fn test_page_frame_with_data() {
+        let frame = PageFrame::new(vec![1, 2, 3]);
+        assert!(!frame.is_dirty());
+
+        frame.with_data(|v| {
+            v.push(4);
+        });
+
+        assert_eq!(frame.data(), vec![1, 2, 3, 4]);
+        assert!(frame.is_dirty()); // Should be marked dirty after modification
+    }
+
+    #[test]
+    fn test_mempool_new() {
+        let pool: MemPool<i32> = MemPool::new();
+        assert_eq!(pool.size(), 0);
+    }
+
+    #[test]
+    fn test_mempool_default() {
+        let pool: MemPool<i32> = MemPool::default();
+        assert_eq!(pool.size(), 0);
+    }
+
+    #[test]
+    fn test_mempool_read_write() {
+        let mut pool = MemPool::new();
+        let data_arc = Arc::new(vec![1, 2, 3]);
+        pool.put_frame(0, Arc::clone(&data_arc)).unwrap();
+
+        let retrieved_arc = pool.get_frame_ref(0).unwrap();
+        assert_eq!(*retrieved_arc, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_mempool_read_nonexistent() {
+        let mut pool: MemPool<i32> = MemPool::new();
+        let result = pool.get_frame_ref(0);
+        match result {
+            Err(e) => assert!(matches!(e, FramePoolError::NotFound(_))),
+            Ok(_) => panic!("Expected error"),
+        }
+    }
+
+    #[test]
+    fn test_mempool_resize() {
+        let mut pool: MemPool<i32> = MemPool::new();
+        assert_eq!(pool.size(), 0);
+
+        pool.resize(5).unwrap();
+        assert_eq!(pool.size(), 5);
+
+        pool.resize(3).unwrap();
+        assert_eq!(pool.size(), 8); // 5 + 3
+    }
+
+    #[test]
+    fn test_mempool_assess_size() {
+        let mut pool: MemPool<i32> = MemPool::new();
+        pool.resize(10).unwrap();
+
+        let size = pool.assess_size().unwrap();
+        assert_eq!(size, 10);
+    }
+
+    #[test]
+    fn test_mempool_overwrite() {
+        let mut pool = MemPool::new();
+
+        let data1 = Arc::new(100);
+        pool.put_frame(0, data1).unwrap();
+
+        let data2 = Arc::new(200);
+        pool.put_frame(0, data2).unwrap();
+
+        let retrieved_arc = pool.get_frame_ref(0).unwrap();
+        assert_eq!(*retrieved_arc, 200);
+    }
+
+    #[test]
+    fn test_diskpool_new() {
+        let pool = DiskPool::new::<i32>("/tmp/test_diskpool_new");
+        assert_eq!(pool.size, 0);
+
+        // Clean up
+        let _ = fs::remove_dir_all("/tmp/test_diskpool_new");
+    }
+
+    #[test]
+    fn test_diskpool_read_write() {
+        let test_dir = "/tmp/test_diskpool_rw";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = DiskPool::new::<Vec<i32>>(test_dir);
+        let data_arc = Arc::new(vec![1, 2, 3]);
+        <DiskPool as FramePool<Vec<i32>>>::put_frame(&mut pool, 0, data_arc).unwrap();
+
+        let retrieved_arc = <DiskPool as FramePool<Vec<i32>>>::get_frame_ref(&mut pool, 0).unwrap();
+        assert_eq!(*retrieved_arc, vec![1, 2, 3]);
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_diskpool_read_nonexistent() {
+        let test_dir = "/tmp/test_diskpool_nonexist";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = DiskPool::new::<i32>(test_dir);
+        <DiskPool as FramePool<i32>>::resize(&mut pool, 1).unwrap(); // Create directory
+
+        let result = <DiskPool as FramePool<i32>>::get_frame_ref(&mut pool, 5);
+        assert!(matches!(result, Err(FramePoolError::NotFound(_))));
+
+        // The allocated page was never written, which the error says
+        let result = <DiskPool as FramePool<i32>>::get_frame_ref(&mut pool, 0);
+        match result {
+            Err(FramePoolError::NotFound(what)) => assert!(what.contains("never written")),
+            _ => panic!("expected NotFound"),
+        }
+
+        // A page that isn't an i32
+        fs::write(format!("{}/page_0", test_dir), "\"zero\"").unwrap();
+        let result = <DiskPool as FramePool<i32>>::get_frame_ref(&mut pool, 0);
+        let err = result.unwrap_err();
+        assert!(matches!(err, FramePoolError::Serde(_)));
+        assert!(matches!(err.clone(), FramePoolError::Serde(_)));
+        assert!(std::error::Error::source(&err).is_some());
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_diskpool_resize() {
+        let test_dir = "/tmp/test_diskpool_resize";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = DiskPool::new::<i32>(test_dir);
+        assert_eq!(pool.size, 0);
+
+        <DiskPool as FramePool<i32>>::resize(&mut pool, 3).unwrap();
+        assert_eq!(pool.size, 3);
+
+        // No files are created until the pages are written
+        assert!(!Path::new(&format!("{}/page_0", test_dir)).exists());
+        assert_eq!(
+            <DiskPool as FramePool<i32>>::frame_state(&pool, &2),
+            FrameState::Empty
+        );
+        assert!(!<DiskPool as FramePool<i32>>::exists(&pool, &2));
+        assert_eq!(
+            <DiskPool as FramePool<i32>>::frame_state(&pool, &3),
+            FrameState::Absent
+        );
+        pool.put_frame(1, Arc::new(1)).unwrap();
+        assert!(Path::new(&format!("{}/page_1", test_dir)).exists());
+        assert!(<DiskPool as FramePool<i32>>::exists(&pool, &1));
+
+        <DiskPool as FramePool<i32>>::resize(&mut pool, 2).unwrap();
+        assert_eq!(pool.size, 5); // 3 + 2
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_diskpool_assess_size() {
+        let test_dir = "/tmp/test_diskpool_assess";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = DiskPool::new::<i32>(test_dir);
+        <DiskPool as FramePool<i32>>::resize(&mut pool, 5).unwrap();
+
+        let size = <DiskPool as FramePool<i32>>::assess_size(&mut pool).unwrap();
+        assert_eq!(size, 5);
+
+        // Manually create another page file
+        fs::write(format!("{}/page_10", test_dir), "{}").unwrap();
+
+        let size = <DiskPool as FramePool<i32>>::assess_size(&mut pool).unwrap();
+        assert_eq!(size, 11); // Should cover the manually created file
+        assert_eq!(<DiskPool as FramePool<i32>>::size(&pool), 11);
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_diskpool_page_path() {
+        let pool = DiskPool::new::<u8>("/tmp/x");
+        let path = pool.page_path(0);
+        assert_eq!(path, PathBuf::from("/tmp/x/page_0"));
+
+        let path = pool.page_path(42);
+        assert_eq!(path, PathBuf::from("/tmp/x/page_42"));
+    }
+
+    #[test]
+    fn test_diskpool_persistence() {
+        let test_dir = "/tmp/test_diskpool_persist";
+        let _ = fs::remove_dir_all(test_dir);
+
+        // Write data
+        {
+            let mut pool = DiskPool::new::<String>(test_dir);
+            let data_arc = Arc::new("Hello, World!".to_string());
+            <DiskPool as FramePool<String>>::put_frame(&mut pool, 0, data_arc).unwrap();
+        }
+
+        // Read data in new pool instance
+        {
+            let mut pool = DiskPool::new::<String>(test_dir);
+            let retrieved_arc =
+                <DiskPool as FramePool<String>>::get_frame_ref(&mut pool, 0).unwrap();
+            assert_eq!(*retrieved_arc, "Hello, World!");
+        }
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_page_frame_thread_safety() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let frame = Arc::new(PageFrame::new(0));
+        let mut handles = vec![];
+
+        for i in 0..10 {
+            let frame_clone = Arc::clone(&frame);
+            let handle = thread::spawn(move || {
+                frame_clone.pin();
+                frame_clone.put(i);
+                frame_clone.unpin();
+            });
+            handles.push(handle);
+        }
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Frame should not be pinned after all threads finish
+        assert!(!frame.is_pinned());
+    }
+
+    #[test]
+    fn test_storage_backend_file_operations() {
+        let test_dir = "/tmp/test_storage_backend";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut backend = FileBackend::new(test_dir);
+
+        // Test write operation
+        let data = vec![1, 2, 3, 4, 5];
+        let data_arc = Arc::new(data.clone());
+        backend.write_data("test_key", data_arc).unwrap();
+
+        // Test exists
+        assert!(backend.data_exists::<Vec<i32>>("test_key"));
+        assert!(!backend.data_exists::<Vec<i32>>("nonexistent_key"));
+
+        // Test read operation
+        let read_arc: Arc<Vec<i32>> = backend.read_data("test_key").unwrap();
+        assert_eq!(*read_arc, data);
+
+        // Test list_keys
+        let keys = backend.list_data_keys::<Vec<i32>>().unwrap();
+        assert_eq!(keys, vec!["test_key"]);
+
+        // Test delete operation
+        backend.delete_data::<Vec<i32>>("test_key").unwrap();
+        assert!(!backend.data_exists::<Vec<i32>>("test_key"));
+
+        let keys_after_delete = backend.list_data_keys::<Vec<i32>>().unwrap();
+        assert!(keys_after_delete.is_empty());
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_memory_backend() {
+        let mut backend = MemoryBackend::new();
+        assert!(backend.is_empty());
+        backend.write("b", Arc::new(2)).unwrap();
+        backend.write("a", Arc::new(1)).unwrap();
+        assert_eq!(*backend.read("a").unwrap(), 1);
+        assert!(matches!(
+            backend.read("c"),
+            Err(FramePoolError::NotFound(_))
+        ));
+        assert_eq!(backend.list_keys().unwrap(), vec!["a", "b"]);
+
+        backend.delete("a").unwrap();
+        backend.delete("a").unwrap();
+        assert!(!backend.exists("a"));
+        assert_eq!(backend.len(), 1);
+
+        // Behind the FramePool adapter
+        let mut pool = BackendFramePool::new::<u64>(backend);
+        pool.put_frame(3, Arc::new(30)).unwrap();
+        assert_eq!(FramePool::<u64>::size(&pool), 4);
+        let mut backend = pool.into_inner();
+        assert_eq!(*backend.read("frame_3").unwrap(), 30);
+        assert_eq!(backend.len(), 2);
+    }
+
+    #[test]
+    fn test_storage_backend_multiple_files() {
+        let test_dir = "/tmp/test_storage_multi";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut backend = FileBackend::new(test_dir);
+
+        // Write multiple files
+        for i in 0..5 {
+            let data = format!("data_{}", i);
+            let data_arc = Arc::new(data);
+            backend.write_data(&format!("key_{}", i), data_arc).unwrap();
+        }
+
+        // Test list_keys returns all keys
+        let mut keys = backend.list_data_keys::<String>().unwrap();
+        keys.sort();
+        let expected_keys: Vec<String> = (0..5).map(|i| format!("key_{}", i)).collect();
+        assert_eq!(keys, expected_keys);
+
+        // Test reading all files
+        for i in 0..5 {
+            let data_arc: Arc<String> = backend.read_data(&format!("key_{}", i)).unwrap();
+            assert_eq!(*data_arc, format!("data_{}", i));
+        }
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_storage_backend_read_nonexistent() {
+        let test_dir = "/tmp/test_storage_nonexist";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut backend = FileBackend::new(test_dir);
+
+        let result: Result<Arc<String>, FramePoolError> = backend.read_data("nonexistent_key");
+        assert!(matches!(result, Err(FramePoolError::NotFound(key)) if key == "nonexistent_key"));
+
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_storage_backend_delete_nonexistent() {
+        let test_dir = "/tmp/test_storage_delete_nonexist";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut backend = FileBackend::new(test_dir);
+
+        // Deleting nonexistent file should not error
+        let result = backend.delete_data::<String>("nonexistent_key");
+        assert!(result.is_ok());
 
-        let paths = fs::read_dir(self.dirname.clone()).unwrap();
-        let mut count = 0;
-        for p in paths.flatten() {
-            if let Some(filename) = p.file_name().to_str()
-                && filename.starts_with("page_")
-            {
-                count += 1;
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_storage_backend_empty_directory() {
+        let test_dir = "/tmp/test_storage_empty";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let backend = FileBackend::new(test_dir);
+
+        // list_keys on nonexistent directory should return empty vec
+        let keys = backend.list_data_keys::<String>().unwrap();
+        assert!(keys.is_empty());
+
+        // exists on nonexistent directory should return false
+        assert!(!backend.data_exists::<String>("any_key"));
+
+        // Clean up not needed as directory was never created
+    }
+
+    #[test]
+    fn test_page_frame_read_data() {
+        let frame = PageFrame::new(vec![1, 2, 3, 4]);
+
+        let result = frame.read_data(|data| data.iter().sum::<i32>());
+
+        assert_eq!(result, 10);
+        assert!(!frame.is_dirty()); // read_data should not mark as dirty
+    }
+
+    #[test]
+    fn test_page_frame_copy_on_write() {
+        let original_data = vec![1, 2, 3];
+        let frame = PageFrame::new(original_data.clone());
+
+        // Get Arc reference to track sharing
+        let arc_before = frame.get_data_arc();
+        assert_eq!(Arc::strong_count(&arc_before), 2); // frame + our reference
+
+        // Modify with with_data - should trigger copy-on-write
+        frame.with_data(|data| {
+            data.push(4);
+        });
+
+        // Original arc should still have the old data (copy-on-write worked)
+        assert_eq!(*arc_before, vec![1, 2, 3]);
+
+        // Frame should have the new data
+        assert_eq!(frame.data(), vec![1, 2, 3, 4]);
+        assert!(frame.is_dirty()); // Should be marked dirty after modification
+    }
+
+    #[test]
+    fn test_page_frame_new_with_arc() {
+        let data = vec![10, 20, 30];
+        let data_arc = Arc::new(data.clone());
+        let frame = PageFrame::new_with_arc(Arc::clone(&data_arc));
+
+        assert_eq!(frame.data(), data);
+        assert!(!frame.is_dirty());
+        assert!(!frame.is_pinned());
+
+        // Both the original arc and the frame should reference the same data
+        assert_eq!(Arc::strong_count(&data_arc), 2);
+    }
+
+    #[test]
+    fn test_diskpool_size_access() {
+        let temp_dir = "/tmp/test_diskpool_size";
+        let _ = fs::remove_dir_all(temp_dir);
+
+        let pool = DiskPool::new::<String>(temp_dir);
+        // Test size method - we can access it through the struct field
+        assert_eq!(pool.size, 0);
+
+        // Clean up
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn test_filebackend_get_file_path() {
+        let test_dir = "/tmp/test_filebackend_path";
+        let backend = FileBackend::new(test_dir);
+
+        // Test get_file_path method
+        let path = backend.get_file_path("test_key").unwrap();
+        let expected = format!("{}/test_key.json", test_dir);
+        assert_eq!(path.to_str().unwrap(), expected);
+        let path = backend.get_file_path("users/42/profile").unwrap();
+        assert_eq!(path, Path::new(test_dir).join("users/42/profile.json"));
+        for bad in [
+            "",
+            "/etc/passwd",
+            "../up",
+            "a/../../up",
+            "a//b",
+            "a/./b",
+            "a/",
+        ] {
+            assert!(
+                matches!(
+                    backend.get_file_path(bad),
+                    Err(FramePoolError::Unsupported(_))
+                ),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_filebackend_hierarchical_keys() {
+        let test_dir = "/tmp/test_filebackend_hierarchy";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut backend = FileBackend::new(test_dir);
+        for key in [
+            "users/42/profile",
+            "users/42/avatar",
+            "users/7/profile",
+            "users",
+        ] {
+            backend.write_data(key, Arc::new(key.to_string())).unwrap();
+        }
+        assert_eq!(
+            *backend.read_data::<String>("users/42/profile").unwrap(),
+            "users/42/profile"
+        );
+        assert!(backend.data_exists::<String>("users/7/profile"));
+        assert!(!backend.data_exists::<String>("users/../users"));
+        assert!(backend.write_data("../escaped", Arc::new(0)).is_err());
+        assert!(!Path::new("/tmp/escaped.json").exists());
+
+        assert_eq!(
+            backend.list_data_keys::<String>().unwrap(),
+            vec![
+                "users",
+                "users/42/avatar",
+                "users/42/profile",
+                "users/7/profile"
+            ]
+        );
+        assert_eq!(
+            backend
+                .list_data_keys_with_prefix::<String>("users/42/", None, None)
+                .unwrap(),
+            vec!["users/42/avatar", "users/42/profile"]
+        );
+        assert_eq!(
+            backend
+                .list_data_keys_with_prefix::<String>("users/4", None, None)
+                .unwrap(),
+            vec!["users/42/avatar", "users/42/profile"]
+        );
+        assert_eq!(
+            backend
+                .list_data_keys_with_prefix::<String>("user", None, None)
+                .unwrap(),
+            backend.list_data_keys::<String>().unwrap()
+        );
+        assert!(
+            backend
+                .list_data_keys_with_prefix::<String>("groups/", None, None)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Listed lazily, or a page at a time
+        let mut all: Vec<String> = backend
+            .iter_data_keys::<String>()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        all.sort();
+        assert_eq!(all, backend.list_data_keys::<String>().unwrap());
+        let mut pages = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = backend
+                .list_data_keys_with_prefix::<String>("users/", Some(2), cursor.as_deref())
+                .unwrap();
+            cursor = page.last().cloned();
+            pages.push(page);
+            if pages.last().unwrap().len() < 2 {
+                break;
             }
         }
-        Ok(count)
+        assert_eq!(
+            pages,
+            vec![
+                vec!["users/42/avatar", "users/42/profile"],
+                vec!["users/7/profile"]
+            ]
+        );
+
+        // Deleting the last key in a directory removes the directory
+        backend.delete_data::<String>("users/7/profile").unwrap();
+        assert!(!Path::new(&format!("{}/users/7", test_dir)).exists());
+        assert!(Path::new(&format!("{}/users/42", test_dir)).exists());
+        let _ = fs::remove_dir_all(test_dir);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use std::path::Path;
+    #[test]
+    fn test_filebackend_write_if_absent() {
+        let test_dir = "/tmp/test_filebackend_create";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut backend = FileBackend::new(test_dir).with_fsync();
+        assert!(backend.write_data_if_absent("a/b", Arc::new(1u32)).unwrap());
+        assert!(!backend.write_data_if_absent("a/b", Arc::new(2u32)).unwrap());
+        assert_eq!(*backend.read_data::<u32>("a/b").unwrap(), 1);
+
+        // Of several writers creating one key at once, one wins and the rest see its value
+        let winners: Vec<bool> = std::thread::scope(|scope| {
+            let racers: Vec<_> = (0..8u32)
+                .map(|i| {
+                    scope.spawn(move || {
+                        let mut backend = FileBackend::new(test_dir);
+                        backend.write_data_if_absent("race", Arc::new(i)).unwrap()
+                    })
+                })
+                .collect();
+            racers.into_iter().map(|r| r.join().unwrap()).collect()
+        });
+        assert_eq!(winners.iter().filter(|won| **won).count(), 1);
+        let winner = winners.iter().position(|won| *won).unwrap() as u32;
+        assert_eq!(*backend.read_data::<u32>("race").unwrap(), winner);
+        assert_eq!(
+            backend.list_data_keys::<u32>().unwrap(),
+            vec!["a/b", "race"]
+        );
+        let leftovers = fs::read_dir(test_dir)
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".tmp")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+
+        let mut memory = MemoryBackend::new();
+        assert!(memory.write_if_absent("k", Arc::new(1)).unwrap());
+        assert!(!memory.write_if_absent("k", Arc::new(2)).unwrap());
+        assert_eq!(*memory.read("k").unwrap(), 1);
+        let _ = fs::remove_dir_all(test_dir);
+    }
 
     #[test]
-    fn test_page_frame_new() {
-        let frame = PageFrame::new(42);
-        assert_eq!(frame.data(), 42);
-        assert!(!frame.is_pinned());
-        assert!(!frame.is_dirty());
+    fn test_page_frame_get_data_arc() {
+        let frame = PageFrame::new(vec![42, 43, 44]);
+        let arc = frame.get_data_arc();
+        assert_eq!(*arc, vec![42, 43, 44]);
     }
 
     #[test]
-    fn test_page_frame_pin_unpin() {
-        let frame = PageFrame::new(42);
-        assert!(!frame.is_pinned());
+    fn test_diskpool_sync() {
+        let test_dir = "/tmp/test_diskpool_sync";
+        let _ = fs::remove_dir_all(test_dir);
 
-        frame.pin();
-        assert!(frame.is_pinned());
+        let mut pool = DiskPool::new::<i32>(test_dir);
+        // Nothing written yet, nothing to do
+        <DiskPool as FramePool<i32>>::sync(&mut pool).unwrap();
 
-        frame.pin(); // Pin twice
-        assert!(frame.is_pinned());
+        <DiskPool as FramePool<i32>>::put_frame(&mut pool, 0, Arc::new(1)).unwrap();
+        <DiskPool as FramePool<i32>>::put_frame(&mut pool, 1, Arc::new(2)).unwrap();
+        assert_eq!(pool.unsynced.len(), 2);
 
-        frame.unpin();
-        assert!(frame.is_pinned()); // Still pinned (count = 1)
+        <DiskPool as FramePool<i32>>::sync(&mut pool).unwrap();
+        assert!(pool.unsynced.is_empty());
 
-        frame.unpin();
-        assert!(!frame.is_pinned()); // Now unpinned
+        // Clean up
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_page_frame_dirty_flag() {
-        let frame = PageFrame::new(42);
-        assert!(!frame.is_dirty());
+    fn test_page_frame_put_arc() {
+        let frame = PageFrame::new(1);
+        let data_arc = Arc::new(2);
+        frame.put_arc(Arc::clone(&data_arc));
+        assert_eq!(frame.data(), 2);
+        assert!(Arc::ptr_eq(&frame.get_data_arc(), &data_arc));
+    }
 
-        frame.set_dirty(true);
-        assert!(frame.is_dirty());
+    #[test]
+    fn test_page_frame_version() {
+        let frame = PageFrame::new(vec![1]);
+        assert_eq!(frame.version(), 0);
+
+        frame.put(vec![2]);
+        assert_eq!(frame.version(), 1);
+
+        frame.with_data(|v| v.push(3));
+        assert_eq!(frame.version(), 2);
 
+        frame.put_arc(Arc::new(vec![4]));
+        assert_eq!(frame.version(), 3);
+
+        // Reads and flag changes leave the version alone
+        frame.read_data(|v| v.len());
         frame.set_dirty(false);
-        assert!(!frame.is_dirty());
+        assert_eq!(frame.version(), 3);
     }
 
     #[test]
-    fn test_page_frame_put() {
-        let frame = PageFrame::new(42);
-        assert_eq!(frame.data(), 42);
+    fn test_page_frame_put_if_version() {
+        let frame = PageFrame::new(10);
+        let seen = frame.version();
 
-        frame.put(100);
-        assert_eq!(frame.data(), 100);
+        assert_eq!(frame.put_if_version(seen, 11), Ok(seen + 1));
+        assert!(frame.is_dirty());
+
+        // A writer holding the stale version loses
+        assert_eq!(frame.put_if_version(seen, 12), Err(seen + 1));
+        assert_eq!(frame.data(), 11);
     }
 
     #[test]
-    fn test_page_frame_with_data() {
-        let frame = PageFrame::new(vec![1, 2, 3]);
-        assert!(!frame.is_dirty());
+    fn test_diskpool_open_read_only() {
+        let test_dir = "/tmp/test_diskpool_open_read_only";
+        let _ = fs::remove_dir_all(test_dir);
 
-        frame.with_data(|v| {
-            v.push(4);
-        });
+        assert!(DiskPool::open_read_only::<i32>(test_dir).is_err());
+        assert!(!std::path::Path::new(test_dir).exists());
+
+        let mut writer = DiskPool::new::<i32>(test_dir);
+        <DiskPool as FramePool<i32>>::resize(&mut writer, 2).unwrap();
+        writer.put_frame(1, Arc::new(42)).unwrap();
+        drop(writer);
+
+        let mut reader = DiskPool::open_read_only::<i32>(test_dir).unwrap();
+        assert!(<DiskPool as FramePool<i32>>::is_read_only(&reader));
+        assert_eq!(<DiskPool as FramePool<i32>>::size(&reader), 2);
+        assert_eq!(
+            *FramePool::<i32>::get_frame_ref(&mut reader, 1).unwrap(),
+            42
+        );
+        assert!(matches!(
+            reader.put_frame(1, Arc::new(7)),
+            Err(FramePoolError::ReadOnly)
+        ));
+        assert!(<DiskPool as FramePool<i32>>::resize(&mut reader, 1).is_err());
+        assert!(<DiskPool as FramePool<i32>>::sync(&mut reader).is_ok());
+        drop(reader);
+
+        // Nothing on disk changed
+        let mut writer = DiskPool::new::<i32>(test_dir);
+        assert_eq!(
+            *FramePool::<i32>::get_frame_ref(&mut writer, 1).unwrap(),
+            42
+        );
+        assert_eq!(
+            <DiskPool as FramePool<i32>>::assess_size(&mut writer).unwrap(),
+            2
+        );
 
-        assert_eq!(frame.data(), vec![1, 2, 3, 4]);
-        assert!(frame.is_dirty()); // Should be marked dirty after modification
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_mempool_new() {
-        let pool: MemPool<i32> = MemPool::new();
-        assert_eq!(pool.size(), 0);
+    fn test_diskpool_read_only_follows_live_writer() {
+        let test_dir = "/tmp/test_diskpool_read_only_live";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut writer = DiskPool::new::<String>(test_dir).with_fanout(1);
+        FramePool::<String>::resize(&mut writer, 2).unwrap();
+        writer.put_frame(0, Arc::new("a".to_string())).unwrap();
+
+        // The writer holds the lock, and a reader opens all the same
+        let mut reader = DiskPool::open_read_only::<String>(test_dir)
+            .unwrap()
+            .with_fanout(1);
+        assert_eq!(
+            *FramePool::<String>::get_frame_ref(&mut reader, 0).unwrap(),
+            "a"
+        );
+        assert!(matches!(
+            FramePool::<String>::get_frame_ref(&mut reader, 1),
+            Err(FramePoolError::NotFound(_))
+        ));
+
+        // It sees what the writer does next, and the size once it assesses it
+        writer.put_frame(1, Arc::new("b".to_string())).unwrap();
+        writer.put_frame(0, Arc::new("c".to_string())).unwrap();
+        FramePool::<String>::resize(&mut writer, 3).unwrap();
+        assert_eq!(
+            *FramePool::<String>::get_frame_ref(&mut reader, 1).unwrap(),
+            "b"
+        );
+        assert_eq!(
+            *FramePool::<String>::get_frame_ref(&mut reader, 0).unwrap(),
+            "c"
+        );
+        assert_eq!(FramePool::<String>::size(&reader), 2);
+        assert_eq!(FramePool::<String>::assess_size(&mut reader).unwrap(), 5);
+        FramePool::<String>::truncate(&mut writer, 1).unwrap();
+        assert_eq!(FramePool::<String>::assess_size(&mut reader).unwrap(), 1);
+
+        // Without a writer, a reader keeps writers out
+        drop(writer);
+        let reader = DiskPool::open_read_only::<String>(test_dir).unwrap();
+        let mut writer = DiskPool::new::<String>(test_dir);
+        assert!(matches!(
+            FramePool::<String>::resize(&mut writer, 1),
+            Err(FramePoolError::Locked(_))
+        ));
+        drop(reader);
+        FramePool::<String>::resize(&mut writer, 1).unwrap();
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_mempool_default() {
-        let pool: MemPool<i32> = MemPool::default();
-        assert_eq!(pool.size(), 0);
+    #[should_panic(expected = "page is read-only")]
+    fn test_page_frame_read_only_with_data() {
+        let frame = PageFrame::new(1);
+        frame.set_read_only(true);
+        assert_eq!(frame.read_data(|v| *v), 1);
+        frame.with_data(|v| *v = 2);
+    }
+
+    // Counts reads that reach the wrapped pool, failing them with an I/O error while failing.
+    struct CountingPool {
+        inner: MemPool<i32>,
+        reads: usize,
+        failing: bool,
+    }
+
+    impl FramePool<i32> for CountingPool {
+        fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<i32>, FramePoolError> {
+            self.reads += 1;
+            if self.failing {
+                return Err(std::io::Error::other("device error").into());
+            }
+            self.inner.get_frame_ref(idx)
+        }
+        fn put_frame(&mut self, idx: u64, data: Arc<i32>) -> Result<(), FramePoolError> {
+            self.inner.put_frame(idx, data)
+        }
+        fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+            self.inner.resize(count)
+        }
+        fn size(&self) -> u64 {
+            self.inner.size()
+        }
+        fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+            self.inner.assess_size()
+        }
+    }
+
+    fn counting_pool() -> CountingPool {
+        CountingPool {
+            inner: MemPool::new(),
+            reads: 0,
+            failing: false,
+        }
     }
 
     #[test]
-    fn test_mempool_read_write() {
-        let mut pool = MemPool::new();
-        let data_arc = Arc::new(vec![1, 2, 3]);
-        pool.put_frame(0, Arc::clone(&data_arc)).unwrap();
+    fn test_negative_cache_pool_remembers_misses() {
+        let mut pool = NegativeCachePool::new(counting_pool(), Duration::from_secs(60));
+
+        for _ in 0..5 {
+            assert!(matches!(
+                pool.get_frame_ref(3),
+                Err(FramePoolError::NotFound(_))
+            ));
+        }
+        assert_eq!(pool.inner().reads, 1);
+        assert_eq!(pool.cached_misses(), 1);
+
+        // Writing the frame through the wrapper clears the miss
+        pool.put_frame(3, Arc::new(30)).unwrap();
+        assert_eq!(*pool.get_frame_ref(3).unwrap(), 30);
+        assert_eq!(pool.inner().reads, 2);
+        assert_eq!(pool.cached_misses(), 0);
+
+        // Writes behind the wrapper need an explicit invalidate
+        assert!(pool.get_frame_ref(4).is_err());
+        pool.inner_mut().put_frame(4, Arc::new(40)).unwrap();
+        assert!(pool.get_frame_ref(4).is_err());
+        pool.invalidate(&4);
+        assert_eq!(*pool.get_frame_ref(4).unwrap(), 40);
+    }
 
-        let retrieved_arc = pool.get_frame_ref(0).unwrap();
-        assert_eq!(*retrieved_arc, vec![1, 2, 3]);
+    #[test]
+    fn test_negative_cache_pool_ttl() {
+        let mut pool = NegativeCachePool::new(counting_pool(), Duration::from_millis(20));
+        assert!(pool.get_frame_ref(0).is_err());
+        assert!(pool.get_frame_ref(0).is_err());
+        assert_eq!(pool.inner().reads, 1);
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(pool.get_frame_ref(0).is_err());
+        assert_eq!(pool.inner().reads, 2);
+
+        // A zero TTL turns caching off
+        pool.set_ttl(Duration::ZERO);
+        pool.invalidate_all();
+        assert!(pool.get_frame_ref(0).is_err());
+        assert!(pool.get_frame_ref(0).is_err());
+        assert_eq!(pool.inner().reads, 4);
+        assert_eq!(pool.cached_misses(), 0);
     }
 
     #[test]
-    fn test_mempool_read_nonexistent() {
-        let mut pool: MemPool<i32> = MemPool::new();
-        let result = pool.get_frame_ref(0);
-        match result {
-            Err(e) => assert_eq!(e, "No such frame"),
-            Ok(_) => panic!("Expected error"),
+    fn test_negative_cache_pool_is_bounded() {
+        let mut pool = NegativeCachePool::new(counting_pool(), Duration::from_secs(60));
+        pool.set_max_misses(3);
+        for i in 10..20 {
+            assert!(pool.get_frame_ref(i).is_err());
+        }
+        assert_eq!(pool.cached_misses(), 3);
+        assert_eq!(pool.order.len(), 3);
+        // The newest misses are the ones kept
+        let reads = pool.inner().reads;
+        assert!(pool.get_frame_ref(19).is_err());
+        assert_eq!(pool.inner().reads, reads);
+        assert!(pool.get_frame_ref(10).is_err());
+        assert_eq!(pool.inner().reads, reads + 1);
+
+        // Expired misses make room before live ones are dropped
+        pool.set_ttl(Duration::from_millis(20));
+        pool.invalidate_all();
+        assert!(pool.get_frame_ref(1).is_err());
+        assert!(pool.get_frame_ref(2).is_err());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(pool.get_frame_ref(3).is_err());
+        assert_eq!(pool.cached_misses(), 1);
+
+        // Repeated misses on one frame hold one entry
+        pool.set_ttl(Duration::from_secs(60));
+        for _ in 0..10 {
+            pool.invalidate(&4);
+            assert!(pool.get_frame_ref(4).is_err());
         }
+        assert!(pool.cached_misses() <= 3);
+        assert!(pool.order.len() <= 6);
     }
 
     #[test]
-    fn test_mempool_resize() {
-        let mut pool: MemPool<i32> = MemPool::new();
-        assert_eq!(pool.size(), 0);
+    fn test_negative_cache_pool_resize_clears() {
+        let mut pool = NegativeCachePool::new(counting_pool(), Duration::from_secs(60));
+        assert!(pool.get_frame_ref(0).is_err());
+        pool.resize(1).unwrap();
+        assert_eq!(pool.cached_misses(), 0);
+        assert_eq!(FramePool::<i32>::size(&pool), 1);
+
+        assert!(pool.get_frame_ref(5).is_err());
+        FramePool::<i32>::grow_to(&mut pool, 8).unwrap();
+        assert_eq!(pool.cached_misses(), 0);
+    }
 
-        pool.resize(5).unwrap();
-        assert_eq!(pool.size(), 5);
+    #[test]
+    fn test_negative_cache_pool_only_remembers_missing_frames() {
+        let mut pool = NegativeCachePool::new(counting_pool(), Duration::from_secs(60));
+        pool.inner_mut().failing = true;
+        assert!(matches!(pool.get_frame_ref(0), Err(FramePoolError::Io(_))));
+        assert_eq!(pool.cached_misses(), 0);
+        pool.inner_mut().failing = false;
+        pool.put_frame(0, Arc::new(1)).unwrap();
+        assert_eq!(*pool.get_frame_ref(0).unwrap(), 1);
+    }
 
-        pool.resize(3).unwrap();
-        assert_eq!(pool.size(), 8); // 5 + 3
+    #[test]
+    fn test_negative_cache_pool_batches() {
+        let mut pool = NegativeCachePool::new(counting_pool(), Duration::from_secs(60));
+        pool.put_frame(0, Arc::new(10)).unwrap();
+        assert!(pool.get_frame_ref(2).is_err());
+        assert_eq!(pool.inner().reads, 1);
+
+        let read = pool.get_frames(&[0, 2, 3]);
+        assert_eq!(*read[0].as_ref().unwrap().as_ref(), 10);
+        assert!(matches!(read[1], Err(FramePoolError::NotFound(_))));
+        assert!(read[2].is_err());
+        // frame 2's miss was answered from the cache, and frame 3's is now remembered
+        assert_eq!(pool.inner().reads, 3);
+        assert_eq!(pool.cached_misses(), 2);
+
+        let written = pool.put_frames(vec![(2, Arc::new(20)), (3, Arc::new(30))]);
+        assert!(written.iter().all(|r| r.is_ok()));
+        assert_eq!(pool.cached_misses(), 0);
+        assert_eq!(*pool.get_frame_ref(3).unwrap(), 30);
+
+        assert!(pool.get_frame_ref(9).is_err());
+        assert!(pool.discard_frame(&9).is_err());
+        assert_eq!(pool.cached_misses(), 0);
     }
 
     #[test]
-    fn test_mempool_assess_size() {
-        let mut pool: MemPool<i32> = MemPool::new();
-        pool.resize(10).unwrap();
+    fn test_page_frame_pin_tracking() {
+        let frame = PageFrame::new(1);
+        frame.pin();
+        let line = line!() - 1;
+        frame.pin_with_label("scan");
 
-        let size = pool.assess_size().unwrap();
-        assert_eq!(size, 10);
+        let pins = frame.pins();
+        assert_eq!(pins.len(), 2);
+        assert_eq!(pins[0].site.file(), file!());
+        assert_eq!(pins[0].site.line(), line);
+        assert_eq!(pins[0].label, None);
+        assert_eq!(pins[1].label.as_deref(), Some("scan"));
+
+        // unpin releases the oldest pin
+        frame.unpin();
+        assert_eq!(frame.pins()[0].label.as_deref(), Some("scan"));
+
+        // Overdue pins are reported once
+        assert_eq!(frame.take_overdue_pins(Duration::ZERO).len(), 1);
+        assert!(frame.take_overdue_pins(Duration::ZERO).is_empty());
+        frame.unpin();
+        assert!(!frame.is_pinned());
     }
 
     #[test]
-    fn test_mempool_overwrite() {
-        let mut pool = MemPool::new();
+    fn test_mempool_keyed() {
+        let mut pool = MemPool::<i32, (u64, u64)>::new_keyed();
+        // Keys without slot numbers can't be allocated up front
+        pool.resize(5).unwrap();
+        assert_eq!(FramePool::<i32, (u64, u64)>::size(&pool), 0);
 
-        let data1 = Arc::new(100);
-        pool.put_frame(0, data1).unwrap();
+        pool.put_frame((1, 0), Arc::new(10)).unwrap();
+        pool.put_frame((2, 0), Arc::new(20)).unwrap();
+        assert_eq!(*pool.get_frame_ref((2, 0)).unwrap(), 20);
+        assert!(pool.get_frame_ref((3, 0)).is_err());
+        assert_eq!(FramePool::<i32, (u64, u64)>::size(&pool), 2);
+    }
 
-        let data2 = Arc::new(200);
-        pool.put_frame(0, data2).unwrap();
+    #[test]
+    fn test_frame_key_slots() {
+        assert_eq!(7u64.slot(), Some(7));
+        assert_eq!(u64::from_slot(7), Some(7));
+        assert_eq!("page".to_string().slot(), None);
+        assert_eq!(<(u64, u64)>::from_slot(7), None);
+    }
 
-        let retrieved_arc = pool.get_frame_ref(0).unwrap();
-        assert_eq!(*retrieved_arc, 200);
+    #[test]
+    fn test_page_frame_reads_during_writes() {
+        let frame = Arc::new(PageFrame::new(vec![0u64; 8]));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let frame = Arc::clone(&frame);
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        // Every element is written together, so a reader never sees a mix
+                        let data = frame.get_data_arc();
+                        assert!(data.iter().all(|v| *v == data[0]));
+                        frame.read_data(|d| assert_eq!(d.len(), 8));
+                    }
+                })
+            })
+            .collect();
+        for i in 1..=1000 {
+            frame.with_data(|d| d.iter_mut().for_each(|v| *v = i));
+        }
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(frame.data(), vec![1000; 8]);
+        assert_eq!(frame.version(), 1000);
     }
 
     #[test]
-    fn test_diskpool_new() {
-        let pool = DiskPool::new::<i32>("/tmp/test_diskpool_new");
-        assert_eq!(pool.size, 0);
+    fn test_page_frame_with_data_unshared_does_not_copy() {
+        let frame = PageFrame::new(vec![1, 2, 3]);
+        let before = Arc::as_ptr(&frame.get_data_arc());
+        frame.with_data(|d| d.push(4));
+        assert_eq!(Arc::as_ptr(&frame.get_data_arc()), before);
+    }
+
+    #[test]
+    fn test_page_frame_readers_share_the_lock() {
+        let frame = PageFrame::new(5u32);
+        frame.pin();
+        // With a shared lock held, other readers of the bookkeeping and data still get in
+        let _held = frame.inner.read().unwrap();
+        assert!(frame.is_pinned());
+        assert!(!frame.is_dirty());
+        assert_eq!(frame.version(), 0);
+        assert_eq!(frame.read_data(|v| *v), 5);
+    }
+
+    #[test]
+    fn test_mempool_grow_to_and_truncate() {
+        let mut pool = MemPool::<i32>::new();
+        pool.grow_to(4).unwrap();
+        pool.grow_to(2).unwrap();
+        assert_eq!(<MemPool<i32> as FramePool<i32>>::size(&pool), 4);
+        for i in 0..4 {
+            pool.put_frame(i, Arc::new(i as i32)).unwrap();
+        }
 
-        // Clean up
-        let _ = fs::remove_dir_all("/tmp/test_diskpool_new");
+        pool.truncate(1).unwrap();
+        assert_eq!(<MemPool<i32> as FramePool<i32>>::size(&pool), 1);
+        assert_eq!(*pool.get_frame_ref(0).unwrap(), 0);
+        assert!(matches!(
+            pool.get_frame_ref(3),
+            Err(FramePoolError::NotFound(_))
+        ));
     }
 
     #[test]
-    fn test_diskpool_read_write() {
-        let test_dir = "/tmp/test_diskpool_rw";
+    fn test_diskpool_truncate() {
+        let test_dir = "/tmp/test_diskpool_truncate";
         let _ = fs::remove_dir_all(test_dir);
 
-        let mut pool = DiskPool::new::<Vec<i32>>(test_dir);
-        let data_arc = Arc::new(vec![1, 2, 3]);
-        <DiskPool as FramePool<Vec<i32>>>::put_frame(&mut pool, 0, data_arc).unwrap();
-
-        let retrieved_arc = <DiskPool as FramePool<Vec<i32>>>::get_frame_ref(&mut pool, 0).unwrap();
-        assert_eq!(*retrieved_arc, vec![1, 2, 3]);
+        let mut pool = DiskPool::new::<i32>(test_dir);
+        <DiskPool as FramePool<i32>>::grow_to(&mut pool, 5).unwrap();
+        for i in 0..5 {
+            pool.put_frame(i, Arc::new(i as i32)).unwrap();
+        }
+        // A page written past the end by someone else
+        fs::write(format!("{}/page_9", test_dir), "9").unwrap();
+
+        <DiskPool as FramePool<i32>>::truncate(&mut pool, 2).unwrap();
+        assert_eq!(<DiskPool as FramePool<i32>>::size(&pool), 2);
+        assert_eq!(
+            <DiskPool as FramePool<i32>>::assess_size(&mut pool).unwrap(),
+            2
+        );
+        assert!(!Path::new(&format!("{}/page_4", test_dir)).exists());
+        assert!(!Path::new(&format!("{}/page_9", test_dir)).exists());
+        assert_eq!(*FramePool::<i32>::get_frame_ref(&mut pool, 1).unwrap(), 1);
+        <DiskPool as FramePool<i32>>::sync(&mut pool).unwrap();
+        drop(pool);
+
+        let mut reader = DiskPool::open_read_only::<i32>(test_dir).unwrap();
+        assert!(matches!(
+            <DiskPool as FramePool<i32>>::truncate(&mut reader, 0),
+            Err(FramePoolError::ReadOnly)
+        ));
 
-        // Clean up
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_diskpool_read_nonexistent() {
-        let test_dir = "/tmp/test_diskpool_nonexist";
+    fn test_frame_state() {
+        let mut pool = MemPool::<i32>::new();
+        pool.resize(2).unwrap();
+        pool.put_frame(0, Arc::new(1)).unwrap();
+        assert_eq!(pool.frame_state(&0), FrameState::Populated);
+        assert_eq!(pool.frame_state(&1), FrameState::Empty);
+        assert_eq!(pool.frame_state(&2), FrameState::Absent);
+        assert!(pool.exists(&0));
+        assert!(!pool.exists(&1));
+
+        let test_dir = "/tmp/test_diskpool_frame_state";
         let _ = fs::remove_dir_all(test_dir);
+        let mut disk = DiskPool::new::<i32>(test_dir);
+        <DiskPool as FramePool<i32>>::resize(&mut disk, 2).unwrap();
+        disk.put_frame(0, Arc::new(5)).unwrap();
+        assert_eq!(
+            FramePool::<i32>::frame_state(&disk, &0),
+            FrameState::Populated
+        );
+        assert_eq!(FramePool::<i32>::frame_state(&disk, &1), FrameState::Empty);
+        assert_eq!(FramePool::<i32>::frame_state(&disk, &2), FrameState::Absent);
+        assert!(!FramePool::<i32>::exists(&disk, &1));
+        let _ = fs::remove_dir_all(test_dir);
+    }
 
-        let mut pool = DiskPool::new::<i32>(test_dir);
-        <DiskPool as FramePool<i32>>::resize(&mut pool, 1).unwrap(); // Create directory
+    #[test]
+    fn test_frame_ids() {
+        let mut pool = MemPool::<i32>::new();
+        pool.resize(3).unwrap();
+        assert_eq!(pool.frame_ids().unwrap(), vec![0, 1, 2]);
+        pool.truncate(1).unwrap();
+        assert_eq!(pool.frame_ids().unwrap(), vec![0]);
 
-        let result = <DiskPool as FramePool<i32>>::get_frame_ref(&mut pool, 5);
-        assert!(result.is_err());
+        let mut keyed = MemPool::<i32, String>::new_keyed();
+        keyed.put_frame("a".to_string(), Arc::new(1)).unwrap();
+        assert_eq!(keyed.frame_ids().unwrap(), vec!["a".to_string()]);
 
-        // Clean up
+        let test_dir = "/tmp/test_diskpool_frame_ids";
+        let _ = fs::remove_dir_all(test_dir);
+        let mut disk = DiskPool::new::<i32>(test_dir);
+        assert!(FramePool::<i32>::frame_ids(&disk).unwrap().is_empty());
+        <DiskPool as FramePool<i32>>::resize(&mut disk, 2).unwrap();
+        // Sparse pages written by someone else
+        fs::write(format!("{}/page_7", test_dir), "7").unwrap();
+        fs::write(format!("{}/page_12", test_dir), "12").unwrap();
+        fs::write(format!("{}/notes", test_dir), "").unwrap();
+        assert_eq!(
+            FramePool::<i32>::frame_ids(&disk).unwrap(),
+            vec![0, 1, 7, 12]
+        );
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_diskpool_resize() {
-        let test_dir = "/tmp/test_diskpool_resize";
+    fn test_diskpool_batched_frames() {
+        let test_dir = "/tmp/test_diskpool_batched";
         let _ = fs::remove_dir_all(test_dir);
-
         let mut pool = DiskPool::new::<i32>(test_dir);
-        assert_eq!(pool.size, 0);
-
-        <DiskPool as FramePool<i32>>::resize(&mut pool, 3).unwrap();
-        assert_eq!(pool.size, 3);
 
-        // Check files were created
-        assert!(Path::new(&format!("{}/page_0", test_dir)).exists());
-        assert!(Path::new(&format!("{}/page_1", test_dir)).exists());
-        assert!(Path::new(&format!("{}/page_2", test_dir)).exists());
-
-        <DiskPool as FramePool<i32>>::resize(&mut pool, 2).unwrap();
-        assert_eq!(pool.size, 5); // 3 + 2
-
-        // Clean up
+        let results = FramePool::<i32>::put_frames(
+            &mut pool,
+            vec![(0, Arc::new(1)), (1, Arc::new(2)), (0, Arc::new(3))],
+        );
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        let reads = FramePool::<i32>::get_frames(&mut pool, &[0, 1, 5, 0]);
+        assert_eq!(*reads[0].as_ref().unwrap().as_ref(), 3);
+        assert_eq!(*reads[1].as_ref().unwrap().as_ref(), 2);
+        assert!(matches!(reads[2], Err(FramePoolError::NotFound(_))));
+        assert!(Arc::ptr_eq(
+            reads[0].as_ref().unwrap(),
+            reads[3].as_ref().unwrap()
+        ));
+        drop(pool);
+
+        let mut reader = DiskPool::open_read_only::<i32>(test_dir).unwrap();
+        let results = FramePool::<i32>::put_frames(&mut reader, vec![(0, Arc::new(9))]);
+        assert!(matches!(results[0], Err(FramePoolError::ReadOnly)));
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_diskpool_assess_size() {
-        let test_dir = "/tmp/test_diskpool_assess";
+    fn test_frame_meta() {
+        let mut pool = MemPool::<u64>::new();
+        pool.resize(2).unwrap();
+        pool.put_frame(0, Arc::new(7)).unwrap();
+        let meta = pool.frame_meta(&0).unwrap();
+        assert_eq!(meta.size, 8);
+        assert!(meta.modified.is_some());
+        assert_eq!(meta.checksum, None);
+        assert_eq!(pool.frame_meta(&1).unwrap().size, 0);
+        assert!(matches!(
+            pool.frame_meta(&2),
+            Err(FramePoolError::NotFound(_))
+        ));
+
+        let test_dir = "/tmp/test_diskpool_frame_meta";
         let _ = fs::remove_dir_all(test_dir);
-
-        let mut pool = DiskPool::new::<i32>(test_dir);
-        <DiskPool as FramePool<i32>>::resize(&mut pool, 5).unwrap();
-
-        let size = <DiskPool as FramePool<i32>>::assess_size(&mut pool).unwrap();
-        assert_eq!(size, 5);
-
-        // Manually create another page file
-        fs::write(format!("{}/page_10", test_dir), "{}").unwrap();
-
-        let size = <DiskPool as FramePool<i32>>::assess_size(&mut pool).unwrap();
-        assert_eq!(size, 6); // Should count the manually created file
-
-        // Clean up
+        let mut disk = DiskPool::new::<Vec<u8>>(test_dir);
+        disk.put_frame(0, Arc::new(vec![1u8, 2])).unwrap();
+        disk.put_frame(1, Arc::new(vec![1u8, 3])).unwrap();
+        let first = FramePool::<Vec<u8>>::frame_meta(&disk, &0).unwrap();
+        let second = FramePool::<Vec<u8>>::frame_meta(&disk, &1).unwrap();
+        assert_eq!(first.size, "[1,2]".len() as u64);
+        assert!(first.modified.is_some());
+        assert_eq!(first.checksum, Some(fnv1a(b"[1,2]")));
+        assert_ne!(first.checksum, second.checksum);
+        assert!(matches!(
+            FramePool::<Vec<u8>>::frame_meta(&disk, &5),
+            Err(FramePoolError::NotFound(_))
+        ));
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_diskpool_page_path() {
-        let pool = DiskPool::new::<u8>("/tmp/x");
-        let path = pool.page_path(0);
-        assert_eq!(path, PathBuf::from("/tmp/x/page_0"));
-
-        let path = pool.page_path(42);
-        assert_eq!(path, PathBuf::from("/tmp/x/page_42"));
+    fn test_mempool_byte_accounting() {
+        let mut pool = MemPool::<Vec<u8>>::new();
+        pool.set_weigher(|data: &Vec<u8>| data.len() as u64);
+        pool.resize(4).unwrap();
+        pool.put_frame(0, Arc::new(vec![0; 10])).unwrap();
+        pool.put_frame(1, Arc::new(vec![0; 20])).unwrap();
+        assert_eq!(pool.bytes(), 30);
+        assert_eq!(pool.frame_meta(&1).unwrap().size, 20);
+
+        // Overwrites replace the old weight
+        pool.put_frame(1, Arc::new(vec![0; 5])).unwrap();
+        assert_eq!(pool.bytes(), 15);
+
+        pool.set_max_bytes(Some(20));
+        match pool.put_frame(2, Arc::new(vec![0; 10])) {
+            Err(FramePoolError::CapacityExceeded { needed, limit }) => {
+                assert_eq!(needed, 25);
+                assert_eq!(limit, 20);
+            }
+            _ => panic!("Expected CapacityExceeded error"),
+        }
+        assert_eq!(pool.bytes(), 15);
+        assert!(!pool.exists(&2));
+        pool.put_frame(2, Arc::new(vec![0; 5])).unwrap();
+
+        // Under a lowered cap, shrinking writes still succeed
+        pool.set_max_bytes(Some(1));
+        pool.put_frame(0, Arc::new(vec![0; 2])).unwrap();
+        assert_eq!(pool.bytes(), 12);
+
+        pool.truncate(1).unwrap();
+        assert_eq!(pool.bytes(), 2);
+
+        // The default weigher counts the shallow size
+        let mut plain = MemPool::<u64>::new();
+        plain.put_frame(0, Arc::new(1)).unwrap();
+        assert_eq!(plain.bytes(), 8);
     }
 
     #[test]
-    fn test_diskpool_persistence() {
-        let test_dir = "/tmp/test_diskpool_persist";
+    fn test_mempool_save_load() {
+        let test_dir = "/tmp/test_mempool_snapshot";
         let _ = fs::remove_dir_all(test_dir);
+        fs::create_dir_all(test_dir).unwrap();
+        let path = format!("{}/pool.json", test_dir);
 
-        // Write data
-        {
-            let mut pool = DiskPool::new::<String>(test_dir);
-            let data_arc = Arc::new("Hello, World!".to_string());
-            <DiskPool as FramePool<String>>::put_frame(&mut pool, 0, data_arc).unwrap();
-        }
-
-        // Read data in new pool instance
-        {
-            let mut pool = DiskPool::new::<String>(test_dir);
-            let retrieved_arc =
-                <DiskPool as FramePool<String>>::get_frame_ref(&mut pool, 0).unwrap();
-            assert_eq!(*retrieved_arc, "Hello, World!");
+        let mut pool = MemPool::<Option<u32>>::new();
+        pool.resize(3).unwrap();
+        pool.put_frame(0, Arc::new(Some(5))).unwrap();
+        pool.put_frame(2, Arc::new(None)).unwrap();
+        pool.save_to(&path).unwrap();
+
+        let mut loaded = MemPool::<Option<u32>>::load_from(&path).unwrap();
+        assert_eq!(loaded.size(), 3);
+        assert_eq!(*loaded.get_frame_ref(0).unwrap(), Some(5));
+        assert_eq!(loaded.frame_state(&1), FrameState::Empty);
+        // A frame holding None is not confused with an empty frame
+        assert_eq!(*loaded.get_frame_ref(2).unwrap(), None);
+
+        // Any codec will do, and saves racing to one path each stage their own file
+        let savers: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                let pool = MemPool::<Option<u32>>::load_from(&path).unwrap();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        pool.save_to_with(&path, &JsonCodec::pretty()).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for saver in savers {
+            saver.join().unwrap();
         }
-
-        // Clean up
+        assert!(fs::read_to_string(&path).unwrap().contains('\n'));
+        let mut pretty =
+            MemPool::<Option<u32>>::load_from_with(&path, &JsonCodec::pretty()).unwrap();
+        assert_eq!(*pretty.get_frame_ref(0).unwrap(), Some(5));
+        assert_eq!(fs::read_dir(test_dir).unwrap().count(), 1);
+
+        let mut keyed = MemPool::<u32, String>::new_keyed();
+        keyed.put_frame("a".to_string(), Arc::new(1)).unwrap();
+        keyed.save_to(&path).unwrap();
+        let mut keyed = MemPool::<u32, String>::load_from(&path).unwrap();
+        assert_eq!(*keyed.get_frame_ref("a".to_string()).unwrap(), 1);
+
+        assert!(matches!(
+            MemPool::<u32>::load_from(format!("{}/missing.json", test_dir)),
+            Err(FramePoolError::NotFound(_))
+        ));
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_page_frame_thread_safety() {
-        use std::sync::Arc;
-        use std::thread;
-
-        let frame = Arc::new(PageFrame::new(0));
-        let mut handles = vec![];
-
-        for i in 0..10 {
-            let frame_clone = Arc::clone(&frame);
-            let handle = thread::spawn(move || {
-                frame_clone.pin();
-                frame_clone.put(i);
-                frame_clone.unpin();
-            });
-            handles.push(handle);
-        }
+    fn test_diskpool_with_codec() {
+        let test_dir = "/tmp/test_diskpool_with_codec";
+        let _ = fs::remove_dir_all(test_dir);
 
-        for handle in handles {
-            handle.join().unwrap();
+        // A codec whose pages are not JSON
+        struct Reversed;
+        impl Codec for Reversed {
+            fn encode<T: Serialize>(&self, data: &T) -> Result<Vec<u8>, FramePoolError> {
+                let mut bytes = serde_json::to_vec(data)?;
+                bytes.reverse();
+                Ok(bytes)
+            }
+            fn decode<T: for<'de> Deserialize<'de>>(
+                &self,
+                bytes: &[u8],
+            ) -> Result<T, FramePoolError> {
+                let reversed: Vec<u8> = bytes.iter().rev().copied().collect();
+                Ok(serde_json::from_slice(&reversed)?)
+            }
+            fn extension(&self) -> &'static str {
+                "rev"
+            }
         }
 
-        // Frame should not be pinned after all threads finish
-        assert!(!frame.is_pinned());
+        let mut pool = DiskPool::new::<Vec<i32>>(test_dir).with_codec(Reversed);
+        FramePool::<Vec<i32>>::resize(&mut pool, 2).unwrap();
+        pool.put_frame(0, Arc::new(vec![1, 2])).unwrap();
+        assert_eq!(
+            *FramePool::<Vec<i32>>::get_frame_ref(&mut pool, 0).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(fs::read(format!("{}/page_0", test_dir)).unwrap(), b"]2,1[");
+
+        // Read with the wrong codec, the page doesn't decode
+        let mut json = DiskPool::new::<Vec<i32>>(test_dir);
+        assert!(FramePool::<Vec<i32>>::get_frame_ref(&mut json, 0).is_err());
+
+        let mut backend = FileBackend::new(test_dir).with_codec(Reversed);
+        backend
+            .write_data("key", Arc::new("abc".to_string()))
+            .unwrap();
+        assert!(Path::new(test_dir).join("key.rev").exists());
+        assert_eq!(*backend.read_data::<String>("key").unwrap(), "abc");
+        assert_eq!(backend.list_data_keys::<String>().unwrap(), vec!["key"]);
+        let _ = fs::remove_dir_all(test_dir);
     }
 
+    #[cfg(feature = "bincode")]
     #[test]
-    fn test_storage_backend_file_operations() {
-        let test_dir = "/tmp/test_storage_backend";
+    fn test_diskpool_bincode_pages_are_smaller() {
+        let test_dir = "/tmp/test_diskpool_bincode";
         let _ = fs::remove_dir_all(test_dir);
 
-        let mut backend = FileBackend::new(test_dir);
-
-        // Test write operation
-        let data = vec![1, 2, 3, 4, 5];
-        let data_arc = Arc::new(data.clone());
-        backend.write_data("test_key", data_arc).unwrap();
-
-        // Test exists
-        assert!(backend.data_exists::<Vec<i32>>("test_key"));
-        assert!(!backend.data_exists::<Vec<i32>>("nonexistent_key"));
-
-        // Test read operation
-        let read_arc: Arc<Vec<i32>> = backend.read_data("test_key").unwrap();
-        assert_eq!(*read_arc, data);
-
-        // Test list_keys
-        let keys = backend.list_data_keys::<Vec<i32>>().unwrap();
-        assert_eq!(keys, vec!["test_key"]);
-
-        // Test delete operation
-        backend.delete_data::<Vec<i32>>("test_key").unwrap();
-        assert!(!backend.data_exists::<Vec<i32>>("test_key"));
-
-        let keys_after_delete = backend.list_data_keys::<Vec<i32>>().unwrap();
-        assert!(keys_after_delete.is_empty());
-
-        // Clean up
+        let data: Vec<f64> = (0..100).map(|i| i as f64 / 3.0).collect();
+        let mut pool =
+            DiskPool::new::<Vec<f64>>(&format!("{}/bincode", test_dir)).with_codec(BincodeCodec);
+        pool.put_frame(0, Arc::new(data.clone())).unwrap();
+        // A pool's manifest records its codec, so the JSON pages go elsewhere
+        let mut json = DiskPool::new::<Vec<f64>>(&format!("{}/json", test_dir));
+        json.put_frame(0, Arc::new(data.clone())).unwrap();
+
+        assert_eq!(
+            *FramePool::<Vec<f64>>::get_frame_ref(&mut pool, 0).unwrap(),
+            data
+        );
+        let bincode_size = FramePool::<Vec<f64>>::frame_meta(&pool, &0).unwrap().size;
+        let json_size = FramePool::<Vec<f64>>::frame_meta(&json, &0).unwrap().size;
+        assert_eq!(bincode_size, 8 + 8 * 100);
+        assert!(json_size > bincode_size);
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_storage_backend_multiple_files() {
-        let test_dir = "/tmp/test_storage_multi";
+    fn test_diskpool_checksums() {
+        let test_dir = "/tmp/test_diskpool_checksums";
         let _ = fs::remove_dir_all(test_dir);
 
-        let mut backend = FileBackend::new(test_dir);
-
-        // Write multiple files
-        for i in 0..5 {
-            let data = format!("data_{}", i);
-            let data_arc = Arc::new(data);
-            backend.write_data(&format!("key_{}", i), data_arc).unwrap();
-        }
-
-        // Test list_keys returns all keys
-        let mut keys = backend.list_data_keys::<String>().unwrap();
-        keys.sort();
-        let expected_keys: Vec<String> = (0..5).map(|i| format!("key_{}", i)).collect();
-        assert_eq!(keys, expected_keys);
-
-        // Test reading all files
-        for i in 0..5 {
-            let data_arc: Arc<String> = backend.read_data(&format!("key_{}", i)).unwrap();
-            assert_eq!(*data_arc, format!("data_{}", i));
+        let mut pool = DiskPool::new::<Vec<u32>>(test_dir).with_checksums();
+        FramePool::<Vec<u32>>::resize(&mut pool, 4).unwrap();
+        for i in 0..3 {
+            pool.put_frame(i, Arc::new(vec![i as u32; 10])).unwrap();
         }
-
-        // Clean up
+        assert_eq!(pool.verify_all().unwrap(), Vec::<u64>::new());
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 2).unwrap(),
+            vec![2; 10]
+        );
+
+        // Damage a byte of page 1's data, and replace page 2 with one without a checksum
+        let path = format!("{}/page_1", test_dir);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 2;
+        bytes[last] = b'7';
+        fs::write(&path, bytes).unwrap();
+        fs::write(format!("{}/page_2", test_dir), "[2]").unwrap();
+
+        assert!(matches!(
+            FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 1),
+            Err(FramePoolError::CorruptPage { idx: 1 })
+        ));
+        assert!(matches!(
+            FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 2),
+            Err(FramePoolError::CorruptPage { idx: 2 })
+        ));
+        assert_eq!(pool.verify_all().unwrap(), vec![1, 2]);
+        drop(pool);
+
+        // Without checksums the damage goes unnoticed
+        let mut plain = DiskPool::new::<Vec<u32>>(test_dir);
+        assert!(plain.verify_all().is_err());
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut plain, 2).unwrap(),
+            vec![2]
+        );
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_storage_backend_read_nonexistent() {
-        let test_dir = "/tmp/test_storage_nonexist";
+    fn test_atomic_page_writes() {
+        let test_dir = "/tmp/test_atomic_page_writes";
         let _ = fs::remove_dir_all(test_dir);
 
-        let mut backend = FileBackend::new(test_dir);
-
-        let result: Result<Arc<String>, String> = backend.read_data("nonexistent_key");
-        assert!(result.is_err());
-
-        // Clean up
+        let mut pool = DiskPool::new::<String>(test_dir);
+        FramePool::<String>::resize(&mut pool, 2).unwrap();
+        pool.put_frame(0, Arc::new("a".repeat(1000))).unwrap();
+        pool.put_frame(0, Arc::new("b".to_string())).unwrap();
+
+        // A temporary left by a crash is not a page
+        fs::write(format!("{}/.page_1.99.0.tmp", test_dir), "[tor").unwrap();
+        assert_eq!(FramePool::<String>::assess_size(&mut pool).unwrap(), 2);
+        assert_eq!(FramePool::<String>::frame_ids(&pool).unwrap(), vec![0, 1]);
+        assert_eq!(
+            *FramePool::<String>::get_frame_ref(&mut pool, 0).unwrap(),
+            "b"
+        );
+
+        let mut backend = FileBackend::new(test_dir).with_fsync();
+        backend.write_data("k", Arc::new(1u8)).unwrap();
+        backend.write_data("k", Arc::new(2u8)).unwrap();
+        assert_eq!(*backend.read_data::<u8>("k").unwrap(), 2);
+        // The pool's manifest is a JSON file too
+        assert_eq!(
+            backend.list_data_keys::<u8>().unwrap(),
+            vec!["k", "manifest"]
+        );
+
+        let names: Vec<String> = fs::read_dir(test_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect();
+        assert_eq!(names, vec![".page_1.99.0.tmp"]);
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_storage_backend_delete_nonexistent() {
-        let test_dir = "/tmp/test_storage_delete_nonexist";
+    fn test_diskpool_durability() {
+        let test_dir = "/tmp/test_diskpool_durability";
         let _ = fs::remove_dir_all(test_dir);
 
-        let mut backend = FileBackend::new(test_dir);
-
-        // Deleting nonexistent file should not error
-        let result = backend.delete_data::<String>("nonexistent_key");
-        assert!(result.is_ok());
-
-        // Clean up
+        let mut pool = DiskPool::new::<u32>(test_dir);
+        assert_eq!(pool.durability(), Durability::Never);
+        pool.put_frame(0, Arc::new(1u32)).unwrap();
+        assert_eq!(pool.unsynced.len(), 1);
+        pool.sync().unwrap();
+        assert!(pool.unsynced.is_empty());
+
+        let mut pool = pool.with_durability(Durability::Always);
+        pool.put_frame(1, Arc::new(2u32)).unwrap();
+        assert!(pool.unsynced.is_empty());
+
+        let mut pool = pool.with_durability(Durability::Interval(Duration::from_secs(60)));
+        pool.put_frames(vec![(2, Arc::new(3u32)), (3, Arc::new(4u32))]);
+        assert_eq!(pool.unsynced.len(), 2);
+        // Once the interval is up, the next write syncs everything
+        let mut pool = pool.with_durability(Durability::Interval(Duration::ZERO));
+        pool.put_frame(4, Arc::new(5u32)).unwrap();
+        assert!(pool.unsynced.is_empty());
+        assert_eq!(*FramePool::<u32>::get_frame_ref(&mut pool, 3).unwrap(), 4);
         let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_storage_backend_empty_directory() {
-        let test_dir = "/tmp/test_storage_empty";
+    fn test_diskpool_group_commit() {
+        use crate::bufferpool::{BufferPool, bottom_evictor};
+        let test_dir = "/tmp/test_diskpool_group_commit";
         let _ = fs::remove_dir_all(test_dir);
 
-        let backend = FileBackend::new(test_dir);
-
-        // list_keys on nonexistent directory should return empty vec
-        let keys = backend.list_data_keys::<String>().unwrap();
-        assert!(keys.is_empty());
-
-        // exists on nonexistent directory should return false
-        assert!(!backend.data_exists::<String>("any_key"));
+        let mut pool = DiskPool::new::<u32>(test_dir)
+            .with_durability(Durability::Group(Duration::from_secs(60)));
+        // A lone write waits for others to commit with
+        pool.put_frame(0, Arc::new(0u32)).unwrap();
+        pool.put_frame(1, Arc::new(1u32)).unwrap();
+        assert_eq!(pool.unsynced.len(), 2);
+        assert!(pool.unsynced_since.is_some());
 
-        // Clean up not needed as directory was never created
+        // A batch commits before it returns, with the writes waiting before it
+        FramePool::<u32>::resize(&mut pool, 28).unwrap();
+        {
+            let mut bp = BufferPool::<u32>::new(32, &mut pool, bottom_evictor);
+            for i in 2..30 {
+                bp.get_or_insert_with(i, || i as u32).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        assert!(pool.unsynced.is_empty());
+        assert!(pool.unsynced_since.is_none());
+        assert_eq!(*FramePool::<u32>::get_frame_ref(&mut pool, 29).unwrap(), 29);
+
+        // Once the oldest write has waited long enough, the next one commits them all
+        let mut pool = pool.with_durability(Durability::Group(Duration::ZERO));
+        pool.put_frame(1, Arc::new(10u32)).unwrap();
+        assert!(pool.unsynced.is_empty());
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_page_frame_read_data() {
-        let frame = PageFrame::new(vec![1, 2, 3, 4]);
-
-        let result = frame.read_data(|data| data.iter().sum::<i32>());
+    fn test_diskpool_manifest() {
+        let test_dir = "/tmp/test_diskpool_manifest";
+        let _ = fs::remove_dir_all(test_dir);
 
-        assert_eq!(result, 10);
-        assert!(!frame.is_dirty()); // read_data should not mark as dirty
+        let mut pool = DiskPool::new::<Vec<u32>>(test_dir);
+        FramePool::<Vec<u32>>::resize(&mut pool, 10).unwrap();
+        FramePool::<Vec<u32>>::truncate(&mut pool, 7).unwrap();
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(format!("{}/manifest.json", test_dir)).unwrap())
+                .unwrap();
+        assert_eq!(manifest["size"], 7);
+        assert_eq!(manifest["codec"], "json");
+        assert_eq!(manifest["format_version"], FORMAT_VERSION);
+
+        // Reopened, the pool has its size without assess_size
+        drop(pool);
+        let mut pool = DiskPool::new::<Vec<u32>>(test_dir);
+        assert_eq!(FramePool::<Vec<u32>>::size(&pool), 7);
+        pool.put_frame(3, Arc::new(vec![3u32])).unwrap();
+        drop(pool);
+        let reader = DiskPool::open_read_only::<Vec<u32>>(test_dir).unwrap();
+        assert_eq!(FramePool::<Vec<u32>>::size(&reader), 7);
+        drop(reader);
+
+        // Opened for another type, it fails rather than misreading pages
+        let mut wrong = DiskPool::new::<String>(test_dir);
+        assert!(matches!(
+            FramePool::<String>::get_frame_ref(&mut wrong, 3),
+            Err(FramePoolError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            DiskPool::open_read_only::<String>(test_dir),
+            Err(FramePoolError::TypeMismatch { .. })
+        ));
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_page_frame_copy_on_write() {
-        let original_data = vec![1, 2, 3];
-        let frame = PageFrame::new(original_data.clone());
-
-        // Get Arc reference to track sharing
-        let arc_before = frame.get_data_arc();
-        assert_eq!(Arc::strong_count(&arc_before), 2); // frame + our reference
-
-        // Modify with with_data - should trigger copy-on-write
-        frame.with_data(|data| {
-            data.push(4);
-        });
-
-        // Original arc should still have the old data (copy-on-write worked)
-        assert_eq!(*arc_before, vec![1, 2, 3]);
+    fn test_diskpool_schema() {
+        let test_dir = "/tmp/test_diskpool_schema";
+        let _ = fs::remove_dir_all(test_dir);
 
-        // Frame should have the new data
-        assert_eq!(frame.data(), vec![1, 2, 3, 4]);
-        assert!(frame.is_dirty()); // Should be marked dirty after modification
+        let mut pool = DiskPool::new::<Vec<u32>>(test_dir).with_schema("points/v1");
+        pool.put_frame(0, Arc::new(vec![1u32, 2])).unwrap();
+        let manifest = pool.read_manifest().unwrap().unwrap();
+        assert_eq!(manifest.page_type, "points/v1");
+
+        // A view of another type is refused, whatever its pages would decode as
+        let err = FramePool::<Vec<u64>>::get_frame_ref(&mut pool, 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Type mismatch: pool holds alloc::vec::Vec<u32> pages, not alloc::vec::Vec<u64>"
+        );
+        assert!(matches!(
+            pool.put_frames(vec![(1, Arc::new("one".to_string()))])[0],
+            Err(FramePoolError::TypeMismatch { .. })
+        ));
+        drop(pool);
+
+        // The directory opens under its schema name, whatever the Rust type is called now
+        let mut plain = DiskPool::new::<Vec<u32>>(test_dir);
+        assert!(matches!(
+            FramePool::<Vec<u32>>::get_frame_ref(&mut plain, 0),
+            Err(FramePoolError::TypeMismatch { stored, .. }) if stored == "points/v1"
+        ));
+        drop(plain);
+        let mut moved = DiskPool::new::<Vec<u16>>(test_dir).with_schema("points/v1");
+        assert_eq!(
+            *FramePool::<Vec<u16>>::get_frame_ref(&mut moved, 0).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(moved.schema(), "points/v1");
+        drop(moved);
+        assert!(matches!(
+            DiskPool::open_read_only::<Vec<u32>>(test_dir),
+            Err(FramePoolError::TypeMismatch { .. })
+        ));
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_page_frame_new_with_arc() {
-        let data = vec![10, 20, 30];
-        let data_arc = Arc::new(data.clone());
-        let frame = PageFrame::new_with_arc(Arc::clone(&data_arc));
-
-        assert_eq!(frame.data(), data);
-        assert!(!frame.is_dirty());
-        assert!(!frame.is_pinned());
+    fn test_diskpool_locking() {
+        let test_dir = "/tmp/test_diskpool_locking";
+        let _ = fs::remove_dir_all(test_dir);
 
-        // Both the original arc and the frame should reference the same data
-        assert_eq!(Arc::strong_count(&data_arc), 2);
+        let mut writer = DiskPool::new::<u32>(test_dir);
+        writer.put_frame(0, Arc::new(1u32)).unwrap();
+        let lock = fs::read_to_string(format!("{}/pool.lock", test_dir)).unwrap();
+        assert_eq!(lock, std::process::id().to_string());
+
+        // Another writer doesn't get in while the writer is open
+        let mut other = DiskPool::new::<u32>(test_dir);
+        assert!(matches!(
+            other.put_frame(1, Arc::new(2u32)),
+            Err(FramePoolError::Locked(_))
+        ));
+        let mut unlocked = DiskPool::new::<u32>(test_dir).unlocked();
+        assert_eq!(
+            *FramePool::<u32>::get_frame_ref(&mut unlocked, 0).unwrap(),
+            1
+        );
+
+        // Readers share the directory, but keep writers out
+        drop(writer);
+        let reader = DiskPool::open_read_only::<u32>(test_dir).unwrap();
+        let _second = DiskPool::open_read_only::<u32>(test_dir).unwrap();
+        assert!(matches!(
+            other.put_frame(1, Arc::new(2u32)),
+            Err(FramePoolError::Locked(_))
+        ));
+        drop(reader);
+
+        // Forcing the lock lets a writer in past a pool still holding it
+        DiskPool::force_unlock(test_dir).unwrap();
+        other.put_frame(1, Arc::new(2u32)).unwrap();
+        DiskPool::force_unlock(test_dir).unwrap();
+        DiskPool::force_unlock(test_dir).unwrap();
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_diskpool_size_access() {
-        let temp_dir = "/tmp/test_diskpool_size";
-        let _ = fs::remove_dir_all(temp_dir);
-
-        let pool = DiskPool::new::<String>(temp_dir);
-        // Test size method - we can access it through the struct field
-        assert_eq!(pool.size, 0);
+    fn test_diskpool_page_headers() {
+        let test_dir = "/tmp/test_diskpool_page_headers";
+        let _ = fs::remove_dir_all(test_dir);
 
-        // Clean up
-        let _ = fs::remove_dir_all(temp_dir);
+        // Pages from before: raw, checksummed, and the placeholder resize used to write
+        let mut old = DiskPool::new::<Vec<u32>>(test_dir).with_checksums();
+        old.put_frame(0, Arc::new(vec![0u32])).unwrap();
+        drop(old);
+        let mut old = DiskPool::new::<Vec<u32>>(test_dir);
+        old.put_frame(1, Arc::new(vec![1u32, 1])).unwrap();
+        FramePool::<Vec<u32>>::resize(&mut old, 1).unwrap();
+        fs::write(format!("{}/page_2", test_dir), "{}").unwrap();
+        drop(old);
+
+        let mut pool = DiskPool::new::<Vec<u32>>(test_dir).with_page_headers();
+        assert!(matches!(
+            FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 1),
+            Err(FramePoolError::CorruptPage { idx: 1 })
+        ));
+        assert_eq!(pool.convert_pages().unwrap(), 3);
+        assert_eq!(pool.convert_pages().unwrap(), 0);
+        assert_eq!(pool.verify_all().unwrap(), Vec::<u64>::new());
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 0).unwrap(),
+            vec![0]
+        );
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 1).unwrap(),
+            vec![1, 1]
+        );
+        assert_eq!(
+            FramePool::<Vec<u32>>::frame_state(&pool, &2),
+            FrameState::Empty
+        );
+        pool.put_frame(3, Arc::new(vec![3u32])).unwrap();
+        let bytes = fs::read(format!("{}/page_3", test_dir)).unwrap();
+        let (header, payload) = PageHeader::parse(3, &bytes).unwrap();
+        assert_eq!(header.codec, codec_id("json"));
+        assert_eq!(payload, b"[3]");
+
+        // A page from a later version of the format is refused, not misread
+        let mut later = bytes.clone();
+        later[4] += 1;
+        fs::write(format!("{}/page_3", test_dir), later).unwrap();
+        assert!(matches!(
+            FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 3),
+            Err(FramePoolError::Unsupported(_))
+        ));
+        assert_eq!(pool.verify_all().unwrap(), vec![3]);
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_filebackend_get_file_path() {
-        let test_dir = "/tmp/test_filebackend_path";
-        let backend = FileBackend::new(test_dir);
+    fn test_diskpool_gc() {
+        let test_dir = "/tmp/test_diskpool_gc";
+        let _ = fs::remove_dir_all(test_dir);
 
-        // Test get_file_path method
-        let path = backend.get_file_path("test_key");
-        let expected = format!("{}/test_key.json", test_dir);
-        assert_eq!(path.to_str().unwrap(), expected);
+        let mut pool = DiskPool::new::<u32>(test_dir).with_fanout(1);
+        for i in 0..4 {
+            pool.put_frame(i, Arc::new(i as u32)).unwrap();
+        }
+        assert_eq!(FramePool::<u32>::size(&pool), 4);
+        assert_eq!(pool.gc().unwrap(), 0);
+
+        // Junk: pages past the end, a temporary left by a crash, and a file of someone else's
+        let past_end = pool.page_path(9);
+        fs::create_dir_all(past_end.parent().unwrap()).unwrap();
+        fs::write(&past_end, "999").unwrap();
+        fs::write(format!("{}/page_12", test_dir), "12").unwrap();
+        fs::write(format!("{}/.page_2.1.0.tmp", test_dir), "2222").unwrap();
+        fs::write(format!("{}/notes", test_dir), "keep").unwrap();
+
+        assert_eq!(pool.gc().unwrap(), 3 + 2 + 4);
+        assert!(!past_end.exists());
+        assert!(Path::new(&format!("{}/notes", test_dir)).exists());
+        assert_eq!(
+            FramePool::<u32>::frame_ids(&pool).unwrap(),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(*FramePool::<u32>::get_frame_ref(&mut pool, 2).unwrap(), 2);
+
+        // Fanout directories emptied by a truncate go too
+        FramePool::<u32>::truncate(&mut pool, 0).unwrap();
+        pool.gc().unwrap();
+        let left: Vec<_> = fs::read_dir(test_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| is_fanout_dir(name))
+            .collect();
+        assert!(left.is_empty());
+
+        drop(pool);
+        let mut reader = DiskPool::open_read_only::<u32>(test_dir).unwrap();
+        assert!(matches!(reader.gc(), Err(FramePoolError::ReadOnly)));
+        let _ = fs::remove_dir_all(test_dir);
     }
 
     #[test]
-    fn test_page_frame_get_data_arc() {
-        let frame = PageFrame::new(vec![42, 43, 44]);
-        let arc = frame.get_data_arc();
-        assert_eq!(*arc, vec![42, 43, 44]);
+    fn test_diskpool_fanout() {
+        let test_dir = "/tmp/test_diskpool_fanout";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut flat = DiskPool::new::<u32>(test_dir);
+        for i in 0..40 {
+            flat.put_frame(i, Arc::new(i as u32)).unwrap();
+        }
+        drop(flat);
+
+        // A read-only fanned-out pool reads the flat pages where they are
+        let mut reader = DiskPool::open_read_only::<u32>(test_dir)
+            .unwrap()
+            .with_fanout(2);
+        assert_eq!(*FramePool::<u32>::get_frame_ref(&mut reader, 7).unwrap(), 7);
+        assert!(Path::new(&format!("{}/page_7", test_dir)).exists());
+        drop(reader);
+
+        // A writable one moves them into place
+        let mut pool = DiskPool::new::<u32>(test_dir).with_fanout(2);
+        assert_eq!(FramePool::<u32>::assess_size(&mut pool).unwrap(), 40);
+        assert!(!Path::new(&format!("{}/page_7", test_dir)).exists());
+        let path = pool.page_path(7);
+        assert!(path.exists());
+        assert_eq!(
+            path.components().count(),
+            Path::new(test_dir).components().count() + 3
+        );
+        assert_eq!(*FramePool::<u32>::get_frame_ref(&mut pool, 7).unwrap(), 7);
+        pool.put_frame(40, Arc::new(40u32)).unwrap();
+        pool.sync().unwrap();
+        assert_eq!(
+            FramePool::<u32>::frame_state(&pool, &40),
+            FrameState::Populated
+        );
+
+        FramePool::<u32>::truncate(&mut pool, 20).unwrap();
+        assert_eq!(
+            FramePool::<u32>::frame_ids(&pool).unwrap(),
+            (0..20).collect::<Vec<_>>()
+        );
+
+        // And going back to the flat layout moves them back
+        drop(pool);
+        let mut flat = DiskPool::new::<u32>(test_dir);
+        assert_eq!(FramePool::<u32>::assess_size(&mut flat).unwrap(), 20);
+        assert!(Path::new(&format!("{}/page_7", test_dir)).exists());
+        let _ = fs::remove_dir_all(test_dir);
     }
 }
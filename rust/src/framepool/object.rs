@@ -0,0 +1,479 @@
+use futures::stream::{self, StreamExt};
+use object_store::aws::AmazonS3Builder;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+use super::{Codec, FrameMeta, FramePool, FramePoolError, FrameState, JsonCodec, StorageBackend};
+
+// A FramePool keeping each frame as an object, page_<id>, under a prefix of an object store
+// (S3, or anything else the object_store crate reaches), so a BufferPool can cache a dataset
+// that lives in a bucket. Pages are JSON unless another codec is chosen with with_codec.
+//
+// Calls block on a runtime of the pool's own and must not be made from within an async
+// runtime. Batched reads and writes (get_frames, put_frames) run up to `concurrency` requests
+// at once, and pages larger than the part size, if one is set, are fetched as that many
+// ranged requests in parallel.
+pub struct ObjectStorePool<C = JsonCodec> {
+    bucket: Bucket<C>,
+    size: u64,
+    concurrency: usize,
+    part_size: Option<u64>,
+}
+
+// The same store as a StorageBackend, one object per key.
+pub struct ObjectStoreBackend<C = JsonCodec> {
+    bucket: Bucket<C>,
+}
+
+// What the pool and the backend share: where objects go, and how to reach them.
+struct Bucket<C> {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    codec: C,
+    runtime: Runtime,
+}
+
+fn store_error(e: object_store::Error) -> FramePoolError {
+    match e {
+        object_store::Error::NotFound { path, .. } => FramePoolError::NotFound(path),
+        e => FramePoolError::Io(io::Error::from(e)),
+    }
+}
+
+impl Bucket<JsonCodec> {
+    fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> Result<Self, FramePoolError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Bucket {
+            store,
+            prefix: Path::from(prefix),
+            codec: JsonCodec::default(),
+            runtime,
+        })
+    }
+
+    // An S3 bucket, configured from the usual AWS_* environment variables.
+    fn s3(bucket: &str, prefix: &str) -> Result<Self, FramePoolError> {
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()
+            .map_err(store_error)?;
+        Self::new(Arc::new(store), prefix)
+    }
+}
+
+impl<C: Codec> Bucket<C> {
+    fn with_codec<D: Codec>(self, codec: D) -> Bucket<D> {
+        Bucket {
+            store: self.store,
+            prefix: self.prefix,
+            codec,
+            runtime: self.runtime,
+        }
+    }
+
+    fn path(&self, name: &str) -> Path {
+        self.prefix.child(name)
+    }
+
+    async fn fetch(
+        store: &dyn ObjectStore,
+        path: &Path,
+        part_size: Option<u64>,
+    ) -> Result<Vec<u8>, FramePoolError> {
+        let Some(part_size) = part_size.filter(|size| *size > 0) else {
+            let result = store.get(path).await.map_err(store_error)?;
+            return Ok(result.bytes().await.map_err(store_error)?.to_vec());
+        };
+        let len = store.head(path).await.map_err(store_error)?.size;
+        let ranges: Vec<Range<u64>> = (0..len)
+            .step_by(part_size as usize)
+            .map(|start| start..(start + part_size).min(len))
+            .collect();
+        let parts = store.get_ranges(path, &ranges).await.map_err(store_error)?;
+        Ok(parts.iter().flat_map(|part| part.iter().copied()).collect())
+    }
+
+    fn read<T>(&self, name: &str, part_size: Option<u64>) -> Result<Arc<T>, FramePoolError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let path = self.path(name);
+        let bytes = self
+            .runtime
+            .block_on(Self::fetch(self.store.as_ref(), &path, part_size))?;
+        Ok(Arc::new(self.codec.decode(&bytes)?))
+    }
+
+    fn write<T: Serialize>(&self, name: &str, data: &T) -> Result<(), FramePoolError> {
+        let payload = PutPayload::from(self.codec.encode(data)?);
+        self.runtime
+            .block_on(self.store.put(&self.path(name), payload))
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.runtime
+            .block_on(self.store.head(&self.path(name)))
+            .is_ok()
+    }
+
+    // Deleting an object that isn't there succeeds.
+    fn delete(&self, name: &str) -> Result<(), FramePoolError> {
+        match self.runtime.block_on(self.store.delete(&self.path(name))) {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(store_error(e)),
+        }
+    }
+
+    // The names of the objects directly under the prefix.
+    fn names(&self) -> Result<Vec<String>, FramePoolError> {
+        let listing = self
+            .runtime
+            .block_on(self.store.list_with_delimiter(Some(&self.prefix)))
+            .map_err(store_error)?;
+        Ok(listing
+            .objects
+            .iter()
+            .filter_map(|meta| meta.location.filename().map(str::to_string))
+            .collect())
+    }
+}
+
+fn page_name(idx: u64) -> String {
+    format!("page_{}", idx)
+}
+
+impl ObjectStorePool {
+    // A pool of the objects under prefix in store. The pool's size is found by listing them.
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> Result<Self, FramePoolError> {
+        Self::over(Bucket::new(store, prefix)?)
+    }
+
+    // A pool of the objects under prefix in an S3 bucket, with credentials and region taken
+    // from the AWS_* environment variables.
+    pub fn s3(bucket: &str, prefix: &str) -> Result<Self, FramePoolError> {
+        Self::over(Bucket::s3(bucket, prefix)?)
+    }
+
+    fn over(bucket: Bucket<JsonCodec>) -> Result<Self, FramePoolError> {
+        let mut pool = ObjectStorePool {
+            bucket,
+            size: 0,
+            concurrency: 8,
+            part_size: None,
+        };
+        pool.size = pool.stored_size()?;
+        Ok(pool)
+    }
+}
+
+impl<C: Codec> ObjectStorePool<C> {
+    // The same pool, reading and writing pages with codec instead.
+    pub fn with_codec<D: Codec>(self, codec: D) -> ObjectStorePool<D> {
+        ObjectStorePool {
+            bucket: self.bucket.with_codec(codec),
+            size: self.size,
+            concurrency: self.concurrency,
+            part_size: self.part_size,
+        }
+    }
+
+    // Runs at most this many requests at once for a batch. The default is 8.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Fetches pages in ranged requests of at most part_size bytes, in parallel. This costs a
+    // request for the page's size first, so it pays off only for large pages.
+    pub fn with_part_size(mut self, part_size: Option<u64>) -> Self {
+        self.part_size = part_size;
+        self
+    }
+
+    fn stored_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids: Vec<u64> = self
+            .bucket
+            .names()?
+            .iter()
+            .filter_map(|name| name.strip_prefix("page_")?.parse().ok())
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn stored_size(&self) -> Result<u64, FramePoolError> {
+        Ok(self.stored_ids()?.last().map_or(0, |idx| idx + 1))
+    }
+}
+
+impl<T, C> FramePool<T> for ObjectStorePool<C>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Codec,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        self.bucket.read(&page_name(idx), self.part_size)
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.bucket.write(&page_name(idx), &*data)?;
+        self.size = self.size.max(idx + 1);
+        Ok(())
+    }
+
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        let bucket = &self.bucket;
+        let part_size = self.part_size;
+        let fetched: Vec<Result<Vec<u8>, FramePoolError>> = bucket.runtime.block_on(
+            stream::iter(idxs)
+                .map(|idx| {
+                    let path = bucket.path(&page_name(*idx));
+                    async move { Bucket::<C>::fetch(bucket.store.as_ref(), &path, part_size).await }
+                })
+                .buffered(self.concurrency)
+                .collect(),
+        );
+        fetched
+            .into_iter()
+            .map(|bytes| Ok(Arc::new(bucket.codec.decode(&bytes?)?)))
+            .collect()
+    }
+
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let bucket = &self.bucket;
+        let results: Vec<Result<(), FramePoolError>> = bucket.runtime.block_on(
+            stream::iter(frames.iter())
+                .map(|(idx, data)| async move {
+                    let payload = PutPayload::from(bucket.codec.encode(&**data)?);
+                    let path = bucket.path(&page_name(*idx));
+                    bucket
+                        .store
+                        .put(&path, payload)
+                        .await
+                        .map_err(store_error)?;
+                    Ok(())
+                })
+                .buffered(self.concurrency)
+                .collect(),
+        );
+        for ((idx, _), result) in frames.iter().zip(results.iter()) {
+            if result.is_ok() {
+                self.size = self.size.max(idx + 1);
+            }
+        }
+        results
+    }
+
+    // Objects are created only once written, so resizing only moves the end of the pool.
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.size += count;
+        Ok(())
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        for idx in self.stored_ids()? {
+            if idx >= count {
+                self.bucket.delete(&page_name(idx))?;
+            }
+        }
+        self.size = self.size.min(count);
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.size = self.size.max(self.stored_size()?);
+        Ok(self.size)
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        if self.bucket.exists(&page_name(*idx)) {
+            FrameState::Populated
+        } else if *idx < self.size {
+            FrameState::Empty
+        } else {
+            FrameState::Absent
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids = self.stored_ids()?;
+        ids.extend(0..self.size);
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        let path = self.bucket.path(&page_name(*idx));
+        let meta = self
+            .bucket
+            .runtime
+            .block_on(self.bucket.store.head(&path))
+            .map_err(store_error)?;
+        Ok(FrameMeta {
+            size: meta.size,
+            modified: Some(meta.last_modified.into()),
+            checksum: None,
+        })
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.bucket.delete(&page_name(*idx))
+    }
+}
+
+impl ObjectStoreBackend {
+    // A backend keeping each key as an object, <key>.json, under prefix in store.
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: &str) -> Result<Self, FramePoolError> {
+        Ok(ObjectStoreBackend {
+            bucket: Bucket::new(store, prefix)?,
+        })
+    }
+
+    // The same, in an S3 bucket configured from the environment.
+    pub fn s3(bucket: &str, prefix: &str) -> Result<Self, FramePoolError> {
+        Ok(ObjectStoreBackend {
+            bucket: Bucket::s3(bucket, prefix)?,
+        })
+    }
+}
+
+impl<C: Codec> ObjectStoreBackend<C> {
+    // The same backend, writing objects with codec instead. Object names take the codec's
+    // extension, as FileBackend's files do.
+    pub fn with_codec<D: Codec>(self, codec: D) -> ObjectStoreBackend<D> {
+        ObjectStoreBackend {
+            bucket: self.bucket.with_codec(codec),
+        }
+    }
+
+    fn object_name(&self, key: &str) -> String {
+        format!("{}.{}", key, self.bucket.codec.extension())
+    }
+}
+
+impl<T, C> StorageBackend<T> for ObjectStoreBackend<C>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Codec,
+{
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        self.bucket.read(&self.object_name(key), None)
+    }
+
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.bucket.write(&self.object_name(key), &*data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.bucket.exists(&self.object_name(key))
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        self.bucket.delete(&self.object_name(key))
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        let extension = self.bucket.codec.extension();
+        Ok(self
+            .bucket
+            .names()?
+            .iter()
+            .filter_map(|name| name.strip_suffix(extension)?.strip_suffix('.'))
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use object_store::memory::InMemory;
+
+    #[test]
+    fn test_object_store_pool() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let mut pool = ObjectStorePool::new(Arc::clone(&store), "datasets/a")
+            .unwrap()
+            .with_concurrency(4);
+        FramePool::<Vec<u32>>::resize(&mut pool, 10).unwrap();
+        let frames = (0..10).map(|i| (i, Arc::new(vec![i as u32; 50]))).collect();
+        assert!(pool.put_frames(frames).iter().all(|r| r.is_ok()));
+        {
+            let mut bp = BufferPool::<Vec<u32>>::new(4, &mut pool, bottom_evictor);
+            assert_eq!(bp.get_page(7).unwrap().data()[0], 7);
+            bp.modify_page(2, |v| v.push(99)).unwrap();
+            bp.flush_all().unwrap();
+        }
+
+        // Another pool over the same prefix sees the pages and their size
+        let mut other = ObjectStorePool::new(Arc::clone(&store), "datasets/a")
+            .unwrap()
+            .with_part_size(Some(16));
+        assert_eq!(FramePool::<Vec<u32>>::size(&other), 10);
+        let read: Vec<Arc<Vec<u32>>> = other
+            .get_frames(&[2, 9, 11])
+            .into_iter()
+            .take(2)
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(read[0].last(), Some(&99));
+        assert_eq!(*read[1], vec![9; 50]);
+        assert!(matches!(
+            FramePool::<Vec<u32>>::get_frame_ref(&mut other, 11),
+            Err(FramePoolError::NotFound(_))
+        ));
+        let meta = FramePool::<Vec<u32>>::frame_meta(&other, &9).unwrap();
+        assert!(meta.size > 50 && meta.modified.is_some());
+
+        FramePool::<Vec<u32>>::truncate(&mut other, 5).unwrap();
+        assert_eq!(
+            FramePool::<Vec<u32>>::frame_ids(&other).unwrap(),
+            (0..5).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            FramePool::<Vec<u32>>::frame_state(&other, &7),
+            FrameState::Absent
+        );
+    }
+
+    #[test]
+    fn test_object_store_backend() {
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let mut backend = ObjectStoreBackend::new(Arc::clone(&store), "kv").unwrap();
+        StorageBackend::<String>::write(&mut backend, "a", Arc::new("x".to_string())).unwrap();
+        StorageBackend::<String>::write(&mut backend, "b", Arc::new("y".to_string())).unwrap();
+        assert_eq!(
+            *StorageBackend::<String>::read(&mut backend, "b").unwrap(),
+            "y"
+        );
+        let mut keys = StorageBackend::<String>::list_keys(&backend).unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["a", "b"]);
+        StorageBackend::<String>::delete(&mut backend, "a").unwrap();
+        StorageBackend::<String>::delete(&mut backend, "a").unwrap();
+        assert!(!StorageBackend::<String>::exists(&backend, "a"));
+
+        // Pages of a pool under another prefix are not keys here
+        let mut pool = ObjectStorePool::new(store, "kv/pages").unwrap();
+        pool.put_frame(0, Arc::new(1u8)).unwrap();
+        assert_eq!(
+            StorageBackend::<String>::list_keys(&backend).unwrap().len(),
+            1
+        );
+    }
+}
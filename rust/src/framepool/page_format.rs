@@ -0,0 +1,141 @@
+use super::FramePoolError;
+
+// The header of the binary page format, which DiskPool::with_page_headers writes in front of
+// every page and PagedFile's records carry. Laid out, little-endian, in PageHeader::LEN bytes:
+//
+//   magic "BPPG", format version (u8), codec id (u8), flags (u16),
+//   CRC32 of the payload (u32), payload length (u32)
+//
+// and followed by the payload, the page as its codec encoded it. Anything after the payload,
+// such as the zero padding of a fixed-size page, is not part of the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageHeader {
+    pub version: u8,
+    // which codec encoded the payload; see codec_id
+    pub codec: u8,
+    // none are defined yet, and a reader rejects pages with flags it doesn't know
+    pub flags: u16,
+    pub checksum: u32,
+    pub len: u32,
+}
+
+const PAGE_MAGIC: &[u8; 4] = b"BPPG";
+pub const PAGE_FORMAT_VERSION: u8 = 1;
+const KNOWN_FLAGS: u16 = 0;
+
+// The codec id of the codec with the given extension, 0 for bytes in no codec this crate
+// knows.
+pub fn codec_id(extension: &str) -> u8 {
+    match extension {
+        "json" => 1,
+        "bin" => 2,
+        "cbor" => 3,
+        "msgpack" => 4,
+        _ => 0,
+    }
+}
+
+impl PageHeader {
+    pub const LEN: usize = 16;
+
+    // The page in the binary format: a header for payload, then payload.
+    pub fn wrap(codec: u8, flags: u16, payload: &[u8]) -> Vec<u8> {
+        let mut page = Vec::with_capacity(Self::LEN + payload.len());
+        page.extend_from_slice(PAGE_MAGIC);
+        page.push(PAGE_FORMAT_VERSION);
+        page.push(codec);
+        page.extend_from_slice(&flags.to_le_bytes());
+        page.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        page.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        page.extend_from_slice(payload);
+        page
+    }
+
+    // Whether bytes start like a page in the binary format, of whatever version.
+    pub fn is_headed(bytes: &[u8]) -> bool {
+        bytes.starts_with(PAGE_MAGIC)
+    }
+
+    // The header of page idx and its payload. A page from a later format version, or with
+    // flags this version doesn't know, is Unsupported; one without a header, cut short or not
+    // matching its checksum is a CorruptPage.
+    pub fn parse(idx: u64, bytes: &[u8]) -> Result<(PageHeader, &[u8]), FramePoolError> {
+        if bytes.len() < Self::LEN || !Self::is_headed(bytes) {
+            return Err(FramePoolError::CorruptPage { idx });
+        }
+        let header = PageHeader {
+            version: bytes[4],
+            codec: bytes[5],
+            flags: u16::from_le_bytes(bytes[6..8].try_into().unwrap()),
+            checksum: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            len: u32::from_le_bytes(bytes[12..16].try_into().unwrap()),
+        };
+        if header.version != PAGE_FORMAT_VERSION {
+            return Err(FramePoolError::Unsupported(format!(
+                "page {} is in page format version {}",
+                idx, header.version
+            )));
+        }
+        if header.flags & !KNOWN_FLAGS != 0 {
+            return Err(FramePoolError::Unsupported(format!(
+                "page {} has flags {:#06x}",
+                idx, header.flags
+            )));
+        }
+        let payload = bytes
+            .get(Self::LEN..Self::LEN + header.len as usize)
+            .ok_or(FramePoolError::CorruptPage { idx })?;
+        if crc32fast::hash(payload) != header.checksum {
+            return Err(FramePoolError::CorruptPage { idx });
+        }
+        Ok((header, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_header_round_trip() {
+        let page = PageHeader::wrap(codec_id("json"), 0, b"[1,2,3]");
+        assert_eq!(page.len(), PageHeader::LEN + 7);
+        let (header, payload) = PageHeader::parse(4, &page).unwrap();
+        assert_eq!(header.version, PAGE_FORMAT_VERSION);
+        assert_eq!(header.codec, 1);
+        assert_eq!(payload, b"[1,2,3]");
+
+        // Padding after the payload is ignored
+        let mut padded = page.clone();
+        padded.resize(64, 0);
+        assert_eq!(PageHeader::parse(4, &padded).unwrap().1, b"[1,2,3]");
+
+        let mut damaged = page.clone();
+        damaged[PageHeader::LEN] = b'{';
+        assert!(matches!(
+            PageHeader::parse(4, &damaged),
+            Err(FramePoolError::CorruptPage { idx: 4 })
+        ));
+        assert!(matches!(
+            PageHeader::parse(4, &page[..20]),
+            Err(FramePoolError::CorruptPage { idx: 4 })
+        ));
+        assert!(matches!(
+            PageHeader::parse(4, b"[1,2,3]"),
+            Err(FramePoolError::CorruptPage { idx: 4 })
+        ));
+
+        // Later versions and unknown flags are refused rather than misread
+        let mut later = page.clone();
+        later[4] = PAGE_FORMAT_VERSION + 1;
+        assert!(matches!(
+            PageHeader::parse(4, &later),
+            Err(FramePoolError::Unsupported(_))
+        ));
+        let flagged = PageHeader::wrap(1, 0x8000, b"[]");
+        assert!(matches!(
+            PageHeader::parse(4, &flagged),
+            Err(FramePoolError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,739 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::compact::{CompactProgress, Compaction, plan_moves, staging_path};
+#[cfg(all(feature = "direct-io", target_os = "linux"))]
+use super::direct::{self, DIRECT_IO_ALIGN};
+use super::space::{SpaceStats, allocate_blocks};
+use super::{FrameMeta, FramePool, FramePoolError, FrameState, PageHeader, fnv1a, sync_parent};
+
+// A single file of fixed-size pages, laid out as a database would lay it out: a superblock
+// first, then one slot per page, with freed pages kept on a free list in the file so they are
+// reused by later allocations. Millions of pages cost one file rather than a file each.
+//
+// The superblock takes the first slot and records a magic number, the format version, the
+// page size, the page count and the head and length of the free list, with a CRC of those.
+// Each free page holds the id of the next one in its first eight bytes. The file is written
+// in place; sync makes the writes durable.
+//
+// As a FramePool, page ids are frame ids and pages are byte pages of exactly page_size bytes
+// (shorter pages are written zero-padded). Allocated pages are populated and free pages
+// empty; resize adds free pages, put_frame claims the page it writes and discard_frame frees
+// it.
+//
+// With the direct-io feature on Linux, create_direct and open_direct open the file with
+// O_DIRECT, so pages go between the device and the BufferPool without also being cached by
+// the OS. Their page size must be a multiple of DIRECT_IO_ALIGN.
+pub struct PagedFile {
+    file: File,
+    path: PathBuf,
+    // opened with O_DIRECT
+    direct: bool,
+    page_size: usize,
+    page_count: u64,
+    // free page ids in list order from the tail, so the head is last
+    free: Vec<u64>,
+    // the same ids, to look them up
+    free_set: HashSet<u64>,
+}
+
+const MAGIC: &[u8; 8] = b"BPPAGED\0";
+const VERSION: u32 = 1;
+// magic, version, page size, page count, free head, free count, then a CRC of them all
+const HEADER_LEN: usize = 40;
+// marks the end of the free list
+const NO_PAGE: u64 = u64::MAX;
+// large enough for the superblock and a free page's link
+const MIN_PAGE_SIZE: usize = 64;
+
+// Reads or writes the whole buffer at offset. A &File seeks and reads like a File, so these
+// work through a shared reference.
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    let mut file = file;
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    let mut file = file;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(buf)
+}
+
+fn open_options(direct: bool) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true);
+    #[cfg(all(feature = "direct-io", target_os = "linux"))]
+    if direct {
+        direct::o_direct(&mut options);
+    }
+    #[cfg(not(all(feature = "direct-io", target_os = "linux")))]
+    debug_assert!(!direct);
+    options
+}
+
+#[cfg(all(feature = "direct-io", target_os = "linux"))]
+fn check_direct_page_size(page_size: usize) -> Result<(), FramePoolError> {
+    if !page_size.is_multiple_of(DIRECT_IO_ALIGN) {
+        return Err(FramePoolError::Unsupported(format!(
+            "page size {} for direct I/O (a multiple of {} bytes)",
+            page_size, DIRECT_IO_ALIGN
+        )));
+    }
+    Ok(())
+}
+
+fn field(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+impl PagedFile {
+    // Creates a file of pages of page_size bytes at path, replacing any file there.
+    pub fn create(path: impl AsRef<Path>, page_size: usize) -> Result<Self, FramePoolError> {
+        Self::create_with(path.as_ref(), page_size, false)
+    }
+
+    // Opens a file made by create, checking its superblock and free list.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FramePoolError> {
+        Self::open_with(path.as_ref(), false)
+    }
+
+    // create, with the file opened for direct I/O.
+    #[cfg(all(feature = "direct-io", target_os = "linux"))]
+    pub fn create_direct(path: impl AsRef<Path>, page_size: usize) -> Result<Self, FramePoolError> {
+        check_direct_page_size(page_size)?;
+        Self::create_with(path.as_ref(), page_size, true)
+    }
+
+    // open, with the file opened for direct I/O.
+    #[cfg(all(feature = "direct-io", target_os = "linux"))]
+    pub fn open_direct(path: impl AsRef<Path>) -> Result<Self, FramePoolError> {
+        let paged = Self::open_with(path.as_ref(), true)?;
+        check_direct_page_size(paged.page_size)?;
+        Ok(paged)
+    }
+
+    fn create_with(path: &Path, page_size: usize, direct: bool) -> Result<Self, FramePoolError> {
+        if page_size < MIN_PAGE_SIZE || page_size > u32::MAX as usize {
+            return Err(FramePoolError::Unsupported(format!(
+                "page size {} (at least {} bytes)",
+                page_size, MIN_PAGE_SIZE
+            )));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = open_options(direct)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let mut paged = PagedFile {
+            file,
+            path: path.to_path_buf(),
+            direct,
+            page_size,
+            page_count: 0,
+            free: Vec::new(),
+            free_set: HashSet::new(),
+        };
+        paged.file.set_len(page_size as u64)?;
+        paged.write_superblock()?;
+        Ok(paged)
+    }
+
+    fn open_with(path: &Path, direct: bool) -> Result<Self, FramePoolError> {
+        let mut paged = PagedFile {
+            file: open_options(direct).open(path)?,
+            path: path.to_path_buf(),
+            direct,
+            page_size: 0,
+            page_count: 0,
+            free: Vec::new(),
+            free_set: HashSet::new(),
+        };
+        let mut header = [0u8; HEADER_LEN + 4];
+        paged.read_at(&mut header, 0)?;
+        if &header[..8] != MAGIC {
+            return Err(FramePoolError::Corruption("not a paged file".to_string()));
+        }
+        let stored_crc = u32::from_le_bytes(header[HEADER_LEN..].try_into().unwrap());
+        if crc32fast::hash(&header[..HEADER_LEN]) != stored_crc {
+            return Err(FramePoolError::Corruption(
+                "superblock does not match its checksum".to_string(),
+            ));
+        }
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != VERSION {
+            return Err(FramePoolError::Unsupported(format!(
+                "paged file version {}",
+                version
+            )));
+        }
+        paged.page_size = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+        paged.page_count = field(&header, 16);
+        paged.free = paged.read_free_list(field(&header, 24), field(&header, 32))?;
+        paged.free_set = paged.free.iter().copied().collect();
+        Ok(paged)
+    }
+
+    // Whether the file was opened for direct I/O.
+    pub fn is_direct(&self) -> bool {
+        self.direct
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    // The number of page slots, allocated or free.
+    pub fn page_count(&self) -> u64 {
+        self.page_count
+    }
+
+    pub fn free_count(&self) -> u64 {
+        self.free.len() as u64
+    }
+
+    pub fn is_free(&self, id: u64) -> bool {
+        self.free_set.contains(&id)
+    }
+
+    // A new page of zeroes, reusing a freed page if there is one.
+    pub fn allocate(&mut self) -> Result<u64, FramePoolError> {
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.free_set.remove(&id);
+                id
+            }
+            None => {
+                self.page_count += 1;
+                self.file.set_len(self.offset(self.page_count))?;
+                self.page_count - 1
+            }
+        };
+        self.write_at(&vec![0; self.page_size], self.offset(id))?;
+        self.write_superblock()?;
+        Ok(id)
+    }
+
+    // Returns page id to the free list.
+    pub fn free(&mut self, id: u64) -> Result<(), FramePoolError> {
+        self.check_allocated(id)?;
+        self.push_free(id)?;
+        self.write_superblock()
+    }
+
+    pub fn read(&self, id: u64) -> Result<Vec<u8>, FramePoolError> {
+        self.check_allocated(id)?;
+        let mut page = vec![0; self.page_size];
+        self.read_at(&mut page, self.offset(id))?;
+        Ok(page)
+    }
+
+    // Writes data, at most a page long, to page id, zero-padding the rest of the page.
+    pub fn write(&mut self, id: u64, data: &[u8]) -> Result<(), FramePoolError> {
+        self.check_allocated(id)?;
+        if data.len() > self.page_size {
+            return Err(FramePoolError::CapacityExceeded {
+                needed: data.len() as u64,
+                limit: self.page_size as u64,
+            });
+        }
+        let mut page = data.to_vec();
+        page.resize(self.page_size, 0);
+        self.write_at(&page, self.offset(id))?;
+        Ok(())
+    }
+
+    // Writes payload to page id in the binary page format (see PageHeader), so it reads back
+    // at its own length, checked against its CRC, rather than zero-padded. The header takes
+    // PageHeader::LEN bytes of the page.
+    pub fn write_record(
+        &mut self,
+        id: u64,
+        codec: u8,
+        payload: &[u8],
+    ) -> Result<(), FramePoolError> {
+        self.write(id, &PageHeader::wrap(codec, 0, payload))
+    }
+
+    // The header and payload of a page write_record wrote.
+    pub fn read_record(&self, id: u64) -> Result<(PageHeader, Vec<u8>), FramePoolError> {
+        let page = self.read(id)?;
+        let (header, payload) = PageHeader::parse(id, &page)?;
+        Ok((header, payload.to_vec()))
+    }
+
+    pub fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    // Adds free pages until there are at least n_pages, and allocates disk for the whole
+    // file, so writing any of its pages cannot fail midway through a flush for want of space.
+    pub fn preallocate(&mut self, n_pages: u64) -> Result<(), FramePoolError> {
+        allocate_blocks(&self.file, self.offset(n_pages.max(self.page_count)))?;
+        if n_pages > self.page_count {
+            self.add_free_pages(n_pages - self.page_count)?;
+        }
+        Ok(())
+    }
+
+    // The disk the file takes, and how much of it the superblock and allocated pages use.
+    pub fn space_stats(&self) -> Result<SpaceStats, FramePoolError> {
+        let used = self.offset(self.page_count - self.free_count());
+        Ok(SpaceStats::of(&self.file, used)?)
+    }
+
+    // Rewrites the file with its allocated pages in the first slots and no free pages, so it
+    // takes only the disk they need. Pages past the new end move into the free slots before
+    // it, and are listed in the Compaction returned, for whatever records their ids. The
+    // compacted file is built beside this one, synced and renamed over it, so a crash leaves
+    // one or the other whole. progress is told how many pages have been copied after each.
+    //
+    // Not for the file under a ChunkedPool, whose chunks name one another by page id.
+    pub fn compact(
+        &mut self,
+        mut progress: impl FnMut(&CompactProgress),
+    ) -> Result<Compaction, FramePoolError> {
+        let allocated: Vec<u64> = (0..self.page_count)
+            .filter(|id| !self.is_free(*id))
+            .collect();
+        let moved = plan_moves(&allocated);
+        // the page each slot of the compacted file is copied from
+        let mut sources: Vec<u64> = (0..allocated.len() as u64).collect();
+        for (from, to) in moved.iter() {
+            sources[*to as usize] = *from;
+        }
+        let staging = staging_path(&self.path);
+        let mut compacted = match self.copy_pages(&staging, &sources, &mut progress) {
+            Ok(compacted) => compacted,
+            Err(e) => {
+                let _ = fs::remove_file(&staging);
+                return Err(e);
+            }
+        };
+        if let Err(e) = fs::rename(&staging, &self.path) {
+            let _ = fs::remove_file(&staging);
+            return Err(e.into());
+        }
+        sync_parent(&self.path)?;
+        compacted.path = self.path.clone();
+        let before = self.file.metadata()?.len();
+        let reclaimed = before.saturating_sub(compacted.file.metadata()?.len());
+        *self = compacted;
+        Ok(Compaction { moved, reclaimed })
+    }
+
+    // A new file at path holding the pages sources names, in order, synced.
+    fn copy_pages(
+        &self,
+        path: &Path,
+        sources: &[u64],
+        progress: &mut dyn FnMut(&CompactProgress),
+    ) -> Result<PagedFile, FramePoolError> {
+        let mut copy = Self::create_with(path, self.page_size, self.direct)?;
+        copy.page_count = sources.len() as u64;
+        copy.file.set_len(copy.offset(copy.page_count))?;
+        let mut done = CompactProgress {
+            done: 0,
+            total: copy.page_count,
+        };
+        for (to, from) in sources.iter().enumerate() {
+            copy.write_at(&self.read(*from)?, copy.offset(to as u64))?;
+            done.done += 1;
+            progress(&done);
+        }
+        copy.write_superblock()?;
+        copy.file.sync_all()?;
+        Ok(copy)
+    }
+
+    // Adds count free pages at the end of the file.
+    fn add_free_pages(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let start = self.page_count;
+        self.page_count += count;
+        self.file.set_len(self.offset(self.page_count))?;
+        for id in (start..self.page_count).rev() {
+            self.push_free(id)?;
+        }
+        self.write_superblock()
+    }
+
+    // read_at and write_at, directly if the file was opened for direct I/O.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        #[cfg(all(feature = "direct-io", target_os = "linux"))]
+        if self.direct {
+            return direct::read_at(&self.file, buf, offset);
+        }
+        read_at(&self.file, buf, offset)
+    }
+
+    fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        #[cfg(all(feature = "direct-io", target_os = "linux"))]
+        if self.direct {
+            return direct::write_at(&self.file, buf, offset);
+        }
+        write_at(&self.file, buf, offset)
+    }
+
+    // Slot 0 is the superblock, so page id is in slot id + 1.
+    fn offset(&self, id: u64) -> u64 {
+        (id + 1) * self.page_size as u64
+    }
+
+    fn check_allocated(&self, id: u64) -> Result<(), FramePoolError> {
+        if id >= self.page_count {
+            return Err(FramePoolError::OutOfBounds(id));
+        }
+        if self.is_free(id) {
+            return Err(FramePoolError::NotFound(format!("page {} is free", id)));
+        }
+        Ok(())
+    }
+
+    fn write_superblock(&mut self) -> Result<(), FramePoolError> {
+        let mut header = Vec::with_capacity(HEADER_LEN + 4);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.page_size as u32).to_le_bytes());
+        header.extend_from_slice(&self.page_count.to_le_bytes());
+        header.extend_from_slice(&self.free.last().copied().unwrap_or(NO_PAGE).to_le_bytes());
+        header.extend_from_slice(&(self.free.len() as u64).to_le_bytes());
+        header.extend_from_slice(&crc32fast::hash(&header).to_le_bytes());
+        self.write_at(&header, 0)?;
+        Ok(())
+    }
+
+    // Links id in as the new head of the free list.
+    fn push_free(&mut self, id: u64) -> Result<(), FramePoolError> {
+        let next = self.free.last().copied().unwrap_or(NO_PAGE);
+        self.write_at(&next.to_le_bytes(), self.offset(id))?;
+        self.free.push(id);
+        self.free_set.insert(id);
+        Ok(())
+    }
+
+    // Takes the page at position at out of the free list, linking the page that pointed to
+    // it to the one after it.
+    fn unlink_free(&mut self, at: usize) -> Result<(), FramePoolError> {
+        let next = match at {
+            0 => NO_PAGE,
+            at => self.free[at - 1],
+        };
+        if let Some(prev) = self.free.get(at + 1) {
+            self.write_at(&next.to_le_bytes(), self.offset(*prev))?;
+        }
+        let id = self.free.remove(at);
+        self.free_set.remove(&id);
+        Ok(())
+    }
+
+    // Writes every free page's link again, after pages were taken out of the middle of the
+    // list.
+    fn relink_free(&mut self) -> Result<(), FramePoolError> {
+        let mut next = NO_PAGE;
+        for id in self.free.iter() {
+            self.write_at(&next.to_le_bytes(), self.offset(*id))?;
+            next = *id;
+        }
+        Ok(())
+    }
+
+    fn read_free_list(&self, head: u64, count: u64) -> Result<Vec<u64>, FramePoolError> {
+        let mut free = Vec::new();
+        let mut id = head;
+        while id != NO_PAGE {
+            if id >= self.page_count || free.len() as u64 >= count {
+                return Err(FramePoolError::Corruption(format!(
+                    "free list is broken at page {}",
+                    id
+                )));
+            }
+            free.push(id);
+            let mut link = [0u8; 8];
+            self.read_at(&mut link, self.offset(id))?;
+            id = u64::from_le_bytes(link);
+        }
+        if free.len() as u64 != count {
+            return Err(FramePoolError::Corruption(format!(
+                "free list has {} pages, superblock says {}",
+                free.len(),
+                count
+            )));
+        }
+        free.reverse();
+        Ok(free)
+    }
+}
+
+impl<T> FramePool<T> for PagedFile
+where
+    T: AsRef<[u8]> + for<'a> From<&'a [u8]> + Clone,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        Ok(Arc::new(T::from(&self.read(idx)?[..])))
+    }
+
+    // Writing a free page claims it; writing past the end adds free pages up to it.
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        if (*data).as_ref().len() > self.page_size {
+            return Err(FramePoolError::CapacityExceeded {
+                needed: (*data).as_ref().len() as u64,
+                limit: self.page_size as u64,
+            });
+        }
+        if idx >= self.page_count {
+            self.add_free_pages(idx + 1 - self.page_count)?;
+        }
+        if self.is_free(idx) {
+            let at = self.free.iter().position(|id| *id == idx).unwrap();
+            self.unlink_free(at)?;
+            self.write_superblock()?;
+        }
+        self.write(idx, (*data).as_ref())
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.add_free_pages(count)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        if count >= self.page_count {
+            return Ok(());
+        }
+        self.free.retain(|id| *id < count);
+        self.free_set.retain(|id| *id < count);
+        self.relink_free()?;
+        self.page_count = count;
+        self.file.set_len(self.offset(count))?;
+        self.write_superblock()
+    }
+
+    fn size(&self) -> u64 {
+        self.page_count
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        Ok(self.page_count)
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        PagedFile::sync(self)
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        if *idx >= self.page_count {
+            FrameState::Absent
+        } else if self.is_free(*idx) {
+            FrameState::Empty
+        } else {
+            FrameState::Populated
+        }
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        let page = self.read(*idx)?;
+        Ok(FrameMeta {
+            size: page.len() as u64,
+            modified: None,
+            checksum: Some(fnv1a(&page)),
+        })
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        match *idx < self.page_count && !self.is_free(*idx) {
+            true => self.free(*idx),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+
+    #[test]
+    fn test_paged_file_allocate_and_free() {
+        let path = "/tmp/test_paged_file/pages.db";
+        let _ = fs::remove_dir_all("/tmp/test_paged_file");
+
+        let mut file = PagedFile::create(path, 128).unwrap();
+        let ids: Vec<u64> = (0..5).map(|_| file.allocate().unwrap()).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4]);
+        for id in ids.iter() {
+            file.write(*id, &[*id as u8; 100]).unwrap();
+        }
+        file.free(1).unwrap();
+        file.free(3).unwrap();
+        assert!(matches!(file.read(3), Err(FramePoolError::NotFound(_))));
+        assert!(matches!(file.free(3), Err(FramePoolError::NotFound(_))));
+        assert!(matches!(file.read(9), Err(FramePoolError::OutOfBounds(9))));
+        file.sync().unwrap();
+        drop(file);
+
+        // The free list survives reopening, and freed pages are reused last freed first
+        let mut file = PagedFile::open(path).unwrap();
+        assert_eq!((file.page_size(), file.page_count()), (128, 5));
+        assert_eq!(file.free_count(), 2);
+        assert_eq!(file.allocate().unwrap(), 3);
+        assert_eq!(file.read(3).unwrap(), vec![0; 128]);
+        assert_eq!(file.allocate().unwrap(), 1);
+        assert_eq!(file.allocate().unwrap(), 5);
+        let page = file.read(4).unwrap();
+        assert_eq!(
+            (&page[..100], &page[100..]),
+            (&[4u8; 100][..], &[0u8; 28][..])
+        );
+        assert!(matches!(
+            file.write(4, &[0; 129]),
+            Err(FramePoolError::CapacityExceeded { .. })
+        ));
+
+        // Records read back at their own length, and a page that isn't one is refused
+        file.write_record(4, 2, b"record").unwrap();
+        let (header, payload) = file.read_record(4).unwrap();
+        assert_eq!((header.codec, &payload[..]), (2, &b"record"[..]));
+        assert!(matches!(
+            file.read_record(3),
+            Err(FramePoolError::CorruptPage { idx: 3 })
+        ));
+        let _ = fs::remove_dir_all("/tmp/test_paged_file");
+    }
+
+    #[test]
+    fn test_paged_file_rejects_damage() {
+        let path = "/tmp/test_paged_file_damage.db";
+        let mut file = PagedFile::create(path, 64).unwrap();
+        file.allocate().unwrap();
+        drop(file);
+
+        let raw = OpenOptions::new().write(true).open(path).unwrap();
+        write_at(&raw, &7u64.to_le_bytes(), 16).unwrap();
+        assert!(matches!(
+            PagedFile::open(path),
+            Err(FramePoolError::Corruption(_))
+        ));
+        assert!(PagedFile::create(path, 16).is_err());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_paged_file_behind_bufferpool() {
+        let path = "/tmp/test_paged_file_pool.db";
+        let mut file = PagedFile::create(path, 64).unwrap();
+        FramePool::<Vec<u8>>::resize(&mut file, 20).unwrap();
+        assert_eq!(file.free_count(), 20);
+        {
+            let mut bp = BufferPool::<Vec<u8>>::new(4, &mut file, bottom_evictor);
+            for i in 0..20 {
+                bp.get_or_insert_with(i, || vec![i as u8; 64]).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        assert_eq!(file.free_count(), 0);
+        FramePool::<Vec<u8>>::discard_frame(&mut file, &6).unwrap();
+        assert_eq!(
+            FramePool::<Vec<u8>>::frame_state(&file, &6),
+            FrameState::Empty
+        );
+        FramePool::<Vec<u8>>::truncate(&mut file, 10).unwrap();
+        drop(file);
+
+        let mut file = PagedFile::open(path).unwrap();
+        assert_eq!((file.page_count(), file.free_count()), (10, 1));
+        assert_eq!(
+            *FramePool::<Vec<u8>>::get_frame_ref(&mut file, 9).unwrap(),
+            vec![9u8; 64]
+        );
+        assert_eq!(file.allocate().unwrap(), 6);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_paged_file_preallocate() {
+        let path = "/tmp/test_paged_file_prealloc.db";
+        let mut file = PagedFile::create(path, 4096).unwrap();
+        for _ in 0..3 {
+            file.allocate().unwrap();
+        }
+        file.preallocate(100).unwrap();
+        assert_eq!((file.page_count(), file.free_count()), (100, 97));
+        let stats = file.space_stats().unwrap();
+        assert_eq!(stats.file_len, 101 * 4096);
+        assert_eq!(stats.used, 4 * 4096);
+        assert!(stats.allocated >= stats.file_len);
+
+        // Preallocated pages are handed out before the file grows, and never shrink it
+        assert_eq!(file.allocate().unwrap(), 3);
+        file.preallocate(10).unwrap();
+        assert_eq!(file.page_count(), 100);
+        assert_eq!(file.space_stats().unwrap().used, 5 * 4096);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_paged_file_compact() {
+        let path = "/tmp/test_paged_file_compact.db";
+        let mut file = PagedFile::create(path, 64).unwrap();
+        for id in 0..6 {
+            assert_eq!(file.allocate().unwrap(), id);
+            file.write(id, &[id as u8; 8]).unwrap();
+        }
+        file.free(1).unwrap();
+        file.free(3).unwrap();
+        file.free(4).unwrap();
+
+        let mut reports = Vec::new();
+        let compaction = file.compact(|p| reports.push(p.done)).unwrap();
+        assert_eq!(compaction.moved, vec![(5, 1)]);
+        assert_eq!(compaction.reclaimed, 3 * 64);
+        assert_eq!(reports, vec![1, 2, 3]);
+        assert_eq!((file.page_count(), file.free_count()), (3, 0));
+        assert_eq!(file.read(1).unwrap()[..8], [5; 8]);
+        assert_eq!(file.allocate().unwrap(), 3);
+        assert!(!staging_path(Path::new(path)).exists());
+
+        // The compacted file is the one at path, and opens as written
+        drop(file);
+        let file = PagedFile::open(path).unwrap();
+        assert_eq!((file.page_count(), file.free_count()), (4, 0));
+        assert_eq!(file.read(2).unwrap()[..8], [2; 8]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[cfg(all(feature = "direct-io", target_os = "linux"))]
+    #[test]
+    fn test_paged_file_direct_io() {
+        let path = "/tmp/test_paged_file_direct.db";
+        assert!(matches!(
+            PagedFile::create_direct(path, 1000),
+            Err(FramePoolError::Unsupported(_))
+        ));
+        let mut file = PagedFile::create_direct(path, DIRECT_IO_ALIGN).unwrap();
+        assert!(file.is_direct());
+        FramePool::<Vec<u8>>::resize(&mut file, 4).unwrap();
+        FramePool::<Vec<u8>>::put_frame(&mut file, 2, Arc::new(vec![5; 300])).unwrap();
+        FramePool::<Vec<u8>>::put_frame(&mut file, 3, Arc::new(vec![9; 10])).unwrap();
+        file.free(3).unwrap();
+        file.sync().unwrap();
+        drop(file);
+
+        // Readable directly or through the page cache alike
+        let file = PagedFile::open(path).unwrap();
+        assert_eq!(file.free_count(), 3);
+        assert_eq!(&file.read(2).unwrap()[299..301], &[5, 0]);
+        drop(file);
+        let mut file = PagedFile::open_direct(path).unwrap();
+        assert_eq!(file.allocate().unwrap(), 3);
+        assert_eq!(file.read(3).unwrap(), vec![0; DIRECT_IO_ALIGN]);
+        let _ = fs::remove_file(path);
+        assert!(PagedFile::create(path, 64).is_ok());
+        assert!(PagedFile::open_direct(path).is_err());
+        let _ = fs::remove_file(path);
+    }
+}
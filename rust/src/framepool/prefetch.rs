@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState};
+
+// Wraps a FramePool with a small pool of worker threads that read frames ahead of use. Frames
+// requested through a Prefetcher are read by the workers, each through its own handle on the
+// storage, and held in a staging area; the next get_frame_ref for a staged frame (typically a
+// BufferPool miss) takes the data from there instead of reading it.
+//
+// A put_frame through the wrapper discards any staged copy of that frame, including one still
+// being read, so stale data is never handed out.
+pub struct PrefetchPool<T, P> {
+    inner: P,
+    staging: Arc<Mutex<Staging<T>>>,
+    workers: Workers,
+}
+
+enum Job {
+    Read(u64),
+    Stop,
+}
+
+// The worker threads, stopped and joined on drop.
+struct Workers {
+    jobs: mpsc::Sender<Job>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        // Prefetchers may still hold senders, so tell each worker to stop rather than waiting
+        // for the queue to close. Reads queued before the stops are still made.
+        for _ in self.handles.iter() {
+            let _ = self.jobs.send(Job::Stop);
+        }
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+// Queues frames for a PrefetchPool's workers. Handles are cheap to clone and can be used while
+// a BufferPool holds the pool itself.
+#[derive(Clone)]
+pub struct Prefetcher<T> {
+    staging: Arc<Mutex<Staging<T>>>,
+    jobs: mpsc::Sender<Job>,
+}
+
+struct Staging<T> {
+    staged: HashMap<u64, Arc<T>>,
+    // most frames held at once; further reads are dropped
+    capacity: usize,
+    // requested frames not yet staged
+    pending: HashSet<u64>,
+    // bumped by every write of a frame while reads are pending, so a read that overlapped a
+    // write is discarded
+    generation: HashMap<u64, u64>,
+    adopted: u64,
+}
+
+impl<T> Prefetcher<T> {
+    // Asks the workers to read the given frames. Frames already staged or requested are skipped.
+    pub fn prefetch(&self, idxs: &[u64]) {
+        let mut staging = self.staging.lock().unwrap();
+        for &idx in idxs {
+            if !staging.staged.contains_key(&idx)
+                && !staging.pending.contains(&idx)
+                && self.jobs.send(Job::Read(idx)).is_ok()
+            {
+                staging.pending.insert(idx);
+            }
+        }
+    }
+
+    // The number of frames read and waiting to be adopted.
+    pub fn staged(&self) -> usize {
+        self.staging.lock().unwrap().staged.len()
+    }
+
+    // The number of frames requested and not yet read.
+    pub fn pending(&self) -> usize {
+        self.staging.lock().unwrap().pending.len()
+    }
+
+    // The number of reads served from the staging area so far.
+    pub fn adopted(&self) -> u64 {
+        self.staging.lock().unwrap().adopted
+    }
+}
+
+impl<T, P> PrefetchPool<T, P>
+where
+    T: Send + Sync + 'static,
+{
+    // Starts `workers` threads, each reading through a pool made by `open` (e.g. another,
+    // unlocked, DiskPool on the same directory), staging at most `capacity` frames at a time.
+    pub fn new<Q, F>(inner: P, workers: usize, capacity: usize, open: F) -> Self
+    where
+        Q: FramePool<T> + Send + 'static,
+        F: Fn() -> Q,
+        T: Clone,
+    {
+        let staging = Arc::new(Mutex::new(Staging {
+            staged: HashMap::new(),
+            capacity,
+            pending: HashSet::new(),
+            generation: HashMap::new(),
+            adopted: 0,
+        }));
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let handles = (0..workers)
+            .map(|_| {
+                let mut pool = open();
+                let staging = Arc::clone(&staging);
+                let queue = Arc::clone(&queue);
+                thread::spawn(move || {
+                    loop {
+                        let job = queue.lock().unwrap().recv();
+                        let Ok(Job::Read(idx)) = job else {
+                            break;
+                        };
+                        let generation = staging.lock().unwrap().generation.get(&idx).copied();
+                        let read = pool.get_frame_ref(idx);
+
+                        let mut staging = staging.lock().unwrap();
+                        staging.pending.remove(&idx);
+                        if let Ok(data) = read
+                            && staging.generation.get(&idx).copied() == generation
+                            && staging.staged.len() < staging.capacity
+                        {
+                            staging.staged.insert(idx, data);
+                        }
+                        if staging.pending.is_empty() {
+                            staging.generation.clear();
+                        }
+                    }
+                })
+            })
+            .collect();
+        PrefetchPool {
+            inner,
+            staging,
+            workers: Workers { jobs, handles },
+        }
+    }
+
+    pub fn prefetcher(&self) -> Prefetcher<T> {
+        Prefetcher {
+            staging: Arc::clone(&self.staging),
+            jobs: self.workers.jobs.clone(),
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    // Stops the workers and returns the wrapped pool.
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+impl<T, P> PrefetchPool<T, P> {
+    // Drops the staged copy of idx, and discards the read of it if one is in flight.
+    fn drop_staged(&self, idx: u64) {
+        let mut staging = self.staging.lock().unwrap();
+        staging.staged.remove(&idx);
+        if staging.pending.contains(&idx) {
+            *staging.generation.entry(idx).or_insert(0) += 1;
+        }
+    }
+}
+
+impl<T, P> FramePool<T> for PrefetchPool<T, P>
+where
+    T: Clone,
+    P: FramePool<T>,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        {
+            let mut staging = self.staging.lock().unwrap();
+            if let Some(data) = staging.staged.remove(&idx) {
+                staging.adopted += 1;
+                return Ok(data);
+            }
+        }
+        self.inner.get_frame_ref(idx)
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.drop_staged(idx);
+        self.inner.put_frame(idx, data)
+    }
+
+    // Like put_frame, drops any staged copy, as it would outlive the data.
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.drop_staged(*idx);
+        self.inner.discard_frame(idx)
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.inner.resize(count)
+    }
+
+    // Staged copies of truncated frames are dropped, and reads still in flight are discarded.
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        {
+            let mut staging = self.staging.lock().unwrap();
+            let staging = &mut *staging;
+            staging.staged.retain(|idx, _| *idx < count);
+            for idx in staging.pending.iter().filter(|idx| **idx >= count) {
+                *staging.generation.entry(*idx).or_insert(0) += 1;
+            }
+        }
+        self.inner.truncate(count)
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        if self.staging.lock().unwrap().staged.contains_key(idx) {
+            return FrameState::Populated;
+        }
+        self.inner.frame_state(idx)
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        self.inner.frame_ids()
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        self.inner.frame_meta(idx)
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.inner.assess_size()
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        self.inner.sync()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use crate::framepool::DiskPool;
+    use std::time::Duration;
+
+    fn setup_dir(dir: &str, count: u64) -> DiskPool {
+        let _ = std::fs::remove_dir_all(dir);
+        let mut disk_pool = DiskPool::new::<u64>(dir);
+        FramePool::<u64>::resize(&mut disk_pool, count).unwrap();
+        for i in 0..count {
+            disk_pool.put_frame(i, Arc::new(i * 10)).unwrap();
+        }
+        disk_pool
+    }
+
+    fn wait_for(prefetcher: &Prefetcher<u64>, staged: usize) {
+        for _ in 0..500 {
+            if prefetcher.pending() == 0 && prefetcher.staged() >= staged {
+                return;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("prefetch did not finish");
+    }
+
+    #[test]
+    fn test_prefetch_pool_stages_frames() {
+        let dir = "/tmp/test_prefetch_pool_stages_frames";
+        let inner = setup_dir(dir, 20);
+        let mut pool = PrefetchPool::new(inner, 3, 8, || DiskPool::new::<u64>(dir).unlocked());
+        let prefetcher = pool.prefetcher();
+
+        prefetcher.prefetch(&[4, 5, 6, 99]);
+        wait_for(&prefetcher, 3);
+        assert_eq!(prefetcher.staged(), 3);
+
+        let mut bp = BufferPool::<u64>::new(4, &mut pool, bottom_evictor);
+        for i in 3..7 {
+            assert_eq!(bp.get_page(i).unwrap().data(), i * 10);
+        }
+        assert_eq!(prefetcher.adopted(), 3);
+        assert_eq!(prefetcher.staged(), 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_prefetch_pool_discards_stale_copies() {
+        let dir = "/tmp/test_prefetch_pool_discards_stale";
+        let inner = setup_dir(dir, 4);
+        let mut pool = PrefetchPool::new(inner, 1, 8, || DiskPool::new::<u64>(dir).unlocked());
+        let prefetcher = pool.prefetcher();
+        prefetcher.prefetch(&[1]);
+        wait_for(&prefetcher, 1);
+
+        {
+            let mut bp = BufferPool::<u64>::new(1, &mut pool, bottom_evictor);
+            bp.put_page(2, 200).unwrap();
+            // Writing frame 2 back discards nothing of frame 1's
+            bp.get_page(1).unwrap();
+            bp.put_page(1, 100).unwrap();
+            bp.flush_all().unwrap();
+        }
+        prefetcher.prefetch(&[2]);
+        wait_for(&prefetcher, 1);
+        pool.put_frame(2, Arc::new(222)).unwrap();
+        assert_eq!(prefetcher.staged(), 0);
+        assert_eq!(*pool.get_frame_ref(2).unwrap(), 222);
+        assert_eq!(*pool.get_frame_ref(1).unwrap(), 100);
+
+        let inner = pool.into_inner();
+        assert_eq!(FramePool::<u64>::size(&inner), 4);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,463 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use super::{
+    Codec, FrameMeta, FramePool, FramePoolError, FrameState, JsonCodec, StorageBackend, fnv1a,
+};
+
+// A FramePool keeping its pages in a Redis server, so several processes can share one store
+// of pages while each keeps a BufferPool of its own in front of it. Page i is the string at
+// <prefix>page:<i> and the pool's size is the integer at <prefix>size, which every process
+// sharing the prefix moves together. Pages are JSON unless another codec is chosen with
+// with_codec.
+//
+// get_frames, and so prefetching, reads its whole batch with one MGET; put_frames, and so
+// BufferPool::flush_all, writes its batch in one MULTI/EXEC pipeline, so other processes see
+// all of it or none of it. size() is this process's last view of the size; assess_size reads
+// it again.
+pub struct RedisPool<C = JsonCodec> {
+    store: Store<C>,
+    size: u64,
+}
+
+// The same server as a StorageBackend, keeping key at <prefix><key>.
+pub struct RedisBackend<C = JsonCodec> {
+    store: Store<C>,
+}
+
+// What the pool and the backend share: the connection, and how keys and values are made.
+struct Store<C> {
+    // behind a Mutex as even reads need the connection mutably
+    con: Mutex<redis::Connection>,
+    prefix: String,
+    codec: C,
+}
+
+// Raises the size at KEYS[1] to ARGV[1] if that is larger, returning the size.
+const RAISE_SIZE: &str = "local size = tonumber(redis.call('GET', KEYS[1]) or '0') \
+     local wanted = tonumber(ARGV[1]) \
+     if wanted > size then redis.call('SET', KEYS[1], wanted) return wanted end \
+     return size";
+
+// Lowers the size at KEYS[1] to ARGV[1] if that is smaller, returning the size.
+const LOWER_SIZE: &str = "local size = tonumber(redis.call('GET', KEYS[1]) or '0') \
+     local wanted = tonumber(ARGV[1]) \
+     if wanted < size then redis.call('SET', KEYS[1], wanted) return wanted end \
+     return size";
+
+fn redis_error(e: redis::RedisError) -> FramePoolError {
+    FramePoolError::Io(io::Error::other(e))
+}
+
+// Escapes the characters SCAN's MATCH pattern treats specially.
+fn glob_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl Store<JsonCodec> {
+    fn open(url: &str, prefix: &str) -> Result<Self, FramePoolError> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        Ok(Self::new(
+            client.get_connection().map_err(redis_error)?,
+            prefix,
+        ))
+    }
+
+    fn new(con: redis::Connection, prefix: &str) -> Self {
+        Store {
+            con: Mutex::new(con),
+            prefix: prefix.to_string(),
+            codec: JsonCodec::default(),
+        }
+    }
+}
+
+impl<C: Codec> Store<C> {
+    fn with_codec<D: Codec>(self, codec: D) -> Store<D> {
+        Store {
+            con: self.con,
+            prefix: self.prefix,
+            codec,
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    fn query<R: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<R, FramePoolError> {
+        cmd.query(&mut *self.con.lock().unwrap())
+            .map_err(redis_error)
+    }
+
+    fn read<T>(&self, name: &str) -> Result<Arc<T>, FramePoolError>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let bytes: Option<Vec<u8>> = self.query(redis::cmd("GET").arg(self.key(name)))?;
+        match bytes {
+            Some(bytes) => Ok(Arc::new(self.codec.decode(&bytes)?)),
+            None => Err(FramePoolError::NotFound(self.key(name))),
+        }
+    }
+
+    fn write<T: Serialize>(&self, name: &str, data: &T) -> Result<(), FramePoolError> {
+        let bytes = self.codec.encode(data)?;
+        self.query(redis::cmd("SET").arg(self.key(name)).arg(bytes))
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.query(redis::cmd("EXISTS").arg(self.key(name)))
+            .unwrap_or(false)
+    }
+
+    // Deleting a key that isn't there succeeds.
+    fn delete(&self, name: &str) -> Result<(), FramePoolError> {
+        self.query(redis::cmd("DEL").arg(self.key(name)))
+    }
+
+    // The names, without the prefix, of the keys starting with prefix followed by start.
+    fn names(&self, start: &str) -> Result<Vec<String>, FramePoolError> {
+        let pattern = format!("{}*", glob_escape(&self.key(start)));
+        let mut con = self.con.lock().unwrap();
+        let mut names = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(1000)
+                .query(&mut *con)
+                .map_err(redis_error)?;
+            names.extend(
+                keys.iter()
+                    .filter_map(|key| key.strip_prefix(&self.prefix))
+                    .map(str::to_string),
+            );
+            if next == 0 {
+                return Ok(names);
+            }
+            cursor = next;
+        }
+    }
+}
+
+fn page_name(idx: u64) -> String {
+    format!("page:{}", idx)
+}
+
+impl RedisPool {
+    // A pool of the pages under prefix in the server at url (redis://host:port/db).
+    pub fn open(url: &str, prefix: &str) -> Result<Self, FramePoolError> {
+        Self::over(Store::open(url, prefix)?)
+    }
+
+    // A pool over an open connection.
+    pub fn with_connection(con: redis::Connection, prefix: &str) -> Result<Self, FramePoolError> {
+        Self::over(Store::new(con, prefix))
+    }
+
+    fn over(store: Store<JsonCodec>) -> Result<Self, FramePoolError> {
+        let mut pool = RedisPool { store, size: 0 };
+        pool.size = pool.stored_size()?;
+        Ok(pool)
+    }
+}
+
+impl<C: Codec> RedisPool<C> {
+    // The same pool, reading and writing pages with codec instead.
+    pub fn with_codec<D: Codec>(self, codec: D) -> RedisPool<D> {
+        RedisPool {
+            store: self.store.with_codec(codec),
+            size: self.size,
+        }
+    }
+
+    fn stored_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids: Vec<u64> = self
+            .store
+            .names("page:")?
+            .iter()
+            .filter_map(|name| name.strip_prefix("page:")?.parse().ok())
+            .collect();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn stored_size(&self) -> Result<u64, FramePoolError> {
+        let size: Option<u64> = self
+            .store
+            .query(redis::cmd("GET").arg(self.store.key("size")))?;
+        Ok(size.unwrap_or(0))
+    }
+
+    fn size_script(&self, script: &str, size: u64) -> redis::Cmd {
+        let mut cmd = redis::cmd("EVAL");
+        cmd.arg(script).arg(1).arg(self.store.key("size")).arg(size);
+        cmd
+    }
+}
+
+impl<T, C> FramePool<T> for RedisPool<C>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Codec,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        self.store.read(&page_name(idx))
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.store.write(&page_name(idx), &*data)?;
+        if idx >= self.size {
+            self.size = self.store.query(&self.size_script(RAISE_SIZE, idx + 1))?;
+        }
+        Ok(())
+    }
+
+    // One MGET for the whole batch.
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        if idxs.is_empty() {
+            return Vec::new();
+        }
+        let mut cmd = redis::cmd("MGET");
+        for idx in idxs {
+            cmd.arg(self.store.key(&page_name(*idx)));
+        }
+        let fetched: Vec<Option<Vec<u8>>> = match self.store.query(&cmd) {
+            Ok(fetched) => fetched,
+            Err(e) => return idxs.iter().map(|_| Err(e.clone())).collect(),
+        };
+        idxs.iter()
+            .zip(fetched)
+            .map(|(idx, bytes)| match bytes {
+                Some(bytes) => Ok(Arc::new(self.store.codec.decode(&bytes)?)),
+                None => Err(FramePoolError::NotFound(self.store.key(&page_name(*idx)))),
+            })
+            .collect()
+    }
+
+    // One MULTI/EXEC pipeline. Every frame reports the batch's result.
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        let mut end = 0;
+        for (idx, data) in frames.iter() {
+            match self.store.codec.encode(&**data) {
+                Ok(bytes) => pipe
+                    .cmd("SET")
+                    .arg(self.store.key(&page_name(*idx)))
+                    .arg(bytes)
+                    .ignore(),
+                Err(e) => return frames.iter().map(|_| Err(e.clone())).collect(),
+            };
+            end = end.max(idx + 1);
+        }
+        pipe.add_command(self.size_script(RAISE_SIZE, end));
+        let result: Result<(u64,), FramePoolError> = pipe
+            .query(&mut *self.store.con.lock().unwrap())
+            .map_err(redis_error);
+        let result = result.map(|(size,)| self.size = size);
+        frames.iter().map(|_| result.clone()).collect()
+    }
+
+    // Pages are stored only once written, so resizing only moves the shared end of the pool.
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.size = self
+            .store
+            .query(redis::cmd("INCRBY").arg(self.store.key("size")).arg(count))?;
+        Ok(())
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        for idx in self.stored_ids()? {
+            if idx >= count {
+                self.store.delete(&page_name(idx))?;
+            }
+        }
+        self.size = self.store.query(&self.size_script(LOWER_SIZE, count))?;
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.size = self.stored_size()?;
+        Ok(self.size)
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        if self.store.exists(&page_name(*idx)) {
+            FrameState::Populated
+        } else if *idx < self.size {
+            FrameState::Empty
+        } else {
+            FrameState::Absent
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids = self.stored_ids()?;
+        ids.extend(0..self.size);
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        let key = self.store.key(&page_name(*idx));
+        let bytes: Option<Vec<u8>> = self.store.query(redis::cmd("GET").arg(&key))?;
+        match bytes {
+            Some(bytes) => Ok(FrameMeta {
+                size: bytes.len() as u64,
+                modified: None,
+                checksum: Some(fnv1a(&bytes)),
+            }),
+            None => Err(FramePoolError::NotFound(key)),
+        }
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        self.store.delete(&page_name(*idx))
+    }
+}
+
+impl RedisBackend {
+    // A backend keeping each key under prefix in the server at url.
+    pub fn open(url: &str, prefix: &str) -> Result<Self, FramePoolError> {
+        Ok(RedisBackend {
+            store: Store::open(url, prefix)?,
+        })
+    }
+
+    pub fn with_connection(con: redis::Connection, prefix: &str) -> Self {
+        RedisBackend {
+            store: Store::new(con, prefix),
+        }
+    }
+}
+
+impl<C: Codec> RedisBackend<C> {
+    // The same backend, writing values with codec instead.
+    pub fn with_codec<D: Codec>(self, codec: D) -> RedisBackend<D> {
+        RedisBackend {
+            store: self.store.with_codec(codec),
+        }
+    }
+}
+
+impl<T, C> StorageBackend<T> for RedisBackend<C>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+    C: Codec,
+{
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        self.store.read(key)
+    }
+
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.store.write(key, &*data)
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.store.exists(key)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        self.store.delete(key)
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        self.store.names("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+
+    // The server the ignored tests run against, from REDIS_URL.
+    fn url() -> String {
+        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string())
+    }
+
+    #[test]
+    fn test_glob_escape() {
+        assert_eq!(glob_escape("app:1"), "app:1");
+        assert_eq!(glob_escape("a*b?[c]\\"), "a\\*b\\?\\[c\\]\\\\");
+    }
+
+    #[test]
+    #[ignore = "needs a Redis server at REDIS_URL"]
+    fn test_redis_pool_shared_between_pools() {
+        let prefix = "bufferpool-test:pool:";
+        let mut pool = RedisPool::open(&url(), prefix).unwrap();
+        FramePool::<u64>::truncate(&mut pool, 0).unwrap();
+        FramePool::<u64>::resize(&mut pool, 100).unwrap();
+        {
+            let mut bp = BufferPool::<u64>::new(10, &mut pool, bottom_evictor);
+            for i in 0..100 {
+                bp.get_or_insert_with(i, || i * 2).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+
+        // A second pool, as another process would have, sees the pages and the size
+        let mut other = RedisPool::open(&url(), prefix).unwrap();
+        assert_eq!(FramePool::<u64>::size(&other), 100);
+        let read = FramePool::<u64>::get_frames(&mut other, &[3, 99, 150]);
+        assert_eq!(*read[0].as_ref().unwrap(), Arc::new(6));
+        assert_eq!(*read[1].as_ref().unwrap(), Arc::new(198));
+        assert!(matches!(read[2], Err(FramePoolError::NotFound(_))));
+        other.put_frame(149, Arc::new(1u64)).unwrap();
+        assert_eq!(FramePool::<u64>::assess_size(&mut pool).unwrap(), 150);
+        let meta = FramePool::<u64>::frame_meta(&other, &10).unwrap();
+        assert_eq!(meta.checksum, Some(fnv1a(b"20")));
+
+        FramePool::<u64>::truncate(&mut other, 10).unwrap();
+        assert_eq!(
+            FramePool::<u64>::frame_ids(&other).unwrap(),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            FramePool::<u64>::frame_state(&other, &10),
+            FrameState::Absent
+        );
+        FramePool::<u64>::truncate(&mut other, 0).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Redis server at REDIS_URL"]
+    fn test_redis_backend() {
+        let mut backend = RedisBackend::open(&url(), "bufferpool-test:kv:").unwrap();
+        for key in StorageBackend::<String>::list_keys(&backend).unwrap() {
+            StorageBackend::<String>::delete(&mut backend, &key).unwrap();
+        }
+        StorageBackend::<String>::write(&mut backend, "a", Arc::new("x".to_string())).unwrap();
+        StorageBackend::<String>::write(&mut backend, "b*", Arc::new("y".to_string())).unwrap();
+        assert_eq!(
+            *StorageBackend::<String>::read(&mut backend, "b*").unwrap(),
+            "y"
+        );
+        let mut keys = StorageBackend::<String>::list_keys(&backend).unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["a", "b*"]);
+        StorageBackend::<String>::delete(&mut backend, "a").unwrap();
+        StorageBackend::<String>::delete(&mut backend, "a").unwrap();
+        assert!(!StorageBackend::<String>::exists(&backend, "a"));
+        StorageBackend::<String>::delete(&mut backend, "b*").unwrap();
+    }
+}
@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use super::{FramePool, FramePoolError, FrameState};
+
+// Serves a FramePool over HTTP, so that RemotePools on other machines can share it as their
+// backing store while each keeps a BufferPool of its own in front of it. Pages travel as
+// JSON, so the served pool holds them as serde_json::Values whatever type the clients use.
+//
+// Requests are handled by a fixed number of threads; page operations on the pool itself are
+// made one at a time. The server stops when dropped, or with stop.
+//
+//   GET    /size              the pool's size
+//   POST   /resize            grows the pool by the count in the body, returning the size
+//   POST   /truncate          truncates the pool to the count in the body, returning the size
+//   POST   /sync              syncs the pool
+//   GET    /frames            the frame ids
+//   GET    /frames/<id>       a page
+//   HEAD   /frames/<id>       200 if the page is populated, 204 if empty, 404 if absent
+//   PUT    /frames/<id>       writes the page in the body
+//   DELETE /frames/<id>       discards a page
+//   POST   /frames/get        the pages whose ids are in the body, null for each not found
+//   POST   /frames/put        writes the [id, page] pairs in the body, returning an error
+//                             message or null for each
+pub struct PageServer {
+    server: Arc<tiny_http::Server>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+// A FramePool whose pages are kept by a PageServer. Clones share a pool of keep-alive
+// connections to the server, so threads can each take a clone.
+//
+// Errors from the server come back with their kind where HTTP carries it (not found,
+// read-only, unsupported), and as I/O errors with the server's message otherwise. size() is
+// this client's last view of the size; assess_size asks the server again.
+#[derive(Clone)]
+pub struct RemotePool {
+    agent: ureq::Agent,
+    base: String,
+    size: u64,
+}
+
+// What a handler sends back: a status code and a JSON body.
+type Reply = (u16, Vec<u8>);
+
+fn status_of(e: &FramePoolError) -> u16 {
+    match e {
+        FramePoolError::NotFound(_) => 404,
+        FramePoolError::ReadOnly => 403,
+        FramePoolError::Unsupported(_) => 501,
+        FramePoolError::OutOfBounds(_) => 416,
+        _ => 500,
+    }
+}
+
+fn error_of(status: u16, message: String) -> FramePoolError {
+    match status {
+        404 => FramePoolError::NotFound(message),
+        403 => FramePoolError::ReadOnly,
+        501 => FramePoolError::Unsupported(message),
+        _ => FramePoolError::Io(io::Error::other(message)),
+    }
+}
+
+fn json<V: Serialize>(status: u16, value: &V) -> Result<Reply, FramePoolError> {
+    Ok((status, serde_json::to_vec(value)?))
+}
+
+fn parse_id(id: &str) -> Result<u64, FramePoolError> {
+    id.parse()
+        .map_err(|_| FramePoolError::NotFound(format!("frame {}", id)))
+}
+
+// Answers one request against pool.
+fn handle<P>(pool: &Mutex<P>, request: &mut tiny_http::Request) -> Result<Reply, FramePoolError>
+where
+    P: FramePool<Value>,
+{
+    use tiny_http::Method;
+
+    let mut body = Vec::new();
+    request.as_reader().read_to_end(&mut body)?;
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let mut pool = pool.lock().unwrap();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["size"]) => json(200, &pool.size()),
+        (Method::Post, ["resize"]) => {
+            pool.resize(serde_json::from_slice(&body)?)?;
+            json(200, &pool.size())
+        }
+        (Method::Post, ["truncate"]) => {
+            pool.truncate(serde_json::from_slice(&body)?)?;
+            json(200, &pool.size())
+        }
+        (Method::Post, ["sync"]) => {
+            pool.sync()?;
+            Ok((204, Vec::new()))
+        }
+        (Method::Get, ["frames"]) => json(200, &pool.frame_ids()?),
+        (Method::Post, ["frames", "get"]) => {
+            let idxs: Vec<u64> = serde_json::from_slice(&body)?;
+            let pages = pool.get_frames(&idxs);
+            let pages: Vec<Option<&Value>> =
+                pages.iter().map(|page| page.as_deref().ok()).collect();
+            json(200, &pages)
+        }
+        (Method::Post, ["frames", "put"]) => {
+            let frames: Vec<(u64, Value)> = serde_json::from_slice(&body)?;
+            let frames = frames
+                .into_iter()
+                .map(|(idx, page)| (idx, Arc::new(page)))
+                .collect();
+            let errors: Vec<Option<String>> = pool
+                .put_frames(frames)
+                .into_iter()
+                .map(|result| result.err().map(|e| e.to_string()))
+                .collect();
+            json(200, &errors)
+        }
+        (Method::Get, ["frames", id]) => json(200, &*pool.get_frame_ref(parse_id(id)?)?),
+        (Method::Head, ["frames", id]) => match pool.frame_state(&parse_id(id)?) {
+            FrameState::Populated => Ok((200, Vec::new())),
+            FrameState::Empty => Ok((204, Vec::new())),
+            FrameState::Absent => Ok((404, Vec::new())),
+        },
+        (Method::Put, ["frames", id]) => {
+            let idx = parse_id(id)?;
+            pool.put_frame(idx, Arc::new(serde_json::from_slice(&body)?))?;
+            Ok((204, Vec::new()))
+        }
+        (Method::Delete, ["frames", id]) => {
+            pool.discard_frame(&parse_id(id)?)?;
+            Ok((204, Vec::new()))
+        }
+        _ => Err(FramePoolError::Unsupported(format!(
+            "{} {}",
+            request.method(),
+            path
+        ))),
+    }
+}
+
+impl PageServer {
+    // Serves pool at addr ("host:port"; port 0 picks a free one) on the given number of
+    // threads.
+    pub fn start<P>(addr: &str, pool: P, threads: usize) -> Result<Self, FramePoolError>
+    where
+        P: FramePool<Value> + Send + 'static,
+    {
+        let server = Arc::new(tiny_http::Server::http(addr).map_err(io::Error::other)?);
+        let pool = Arc::new(Mutex::new(pool));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let server = Arc::clone(&server);
+                let pool = Arc::clone(&pool);
+                thread::spawn(move || {
+                    for mut request in server.incoming_requests() {
+                        let (status, body) = match handle(&pool, &mut request) {
+                            Ok(reply) => reply,
+                            Err(e) => (status_of(&e), e.to_string().into_bytes()),
+                        };
+                        let response =
+                            tiny_http::Response::from_data(body).with_status_code(status);
+                        let _ = request.respond(response);
+                    }
+                })
+            })
+            .collect();
+        Ok(PageServer { server, workers })
+    }
+
+    // The address the server listens on.
+    pub fn addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    // Serves until the process ends.
+    pub fn wait(mut self) {
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+
+    // Stops taking requests, waiting for those being handled.
+    pub fn stop(self) {
+        drop(self)
+    }
+}
+
+impl Drop for PageServer {
+    fn drop(&mut self) {
+        for _ in 0..self.workers.len() {
+            self.server.unblock();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl RemotePool {
+    // A pool served at url ("http://host:port"), keeping up to 8 idle connections to it.
+    pub fn connect(url: &str) -> Result<Self, FramePoolError> {
+        let agent = ureq::AgentBuilder::new()
+            .max_idle_connections_per_host(8)
+            .build();
+        Self::with_agent(url, agent)
+    }
+
+    // The same, over an agent configured by the caller (timeouts, proxies, pool sizes).
+    pub fn with_agent(url: &str, agent: ureq::Agent) -> Result<Self, FramePoolError> {
+        let mut pool = RemotePool {
+            agent,
+            base: url.trim_end_matches('/').to_string(),
+            size: 0,
+        };
+        pool.size = pool.call(pool.request("GET", "size"), None)?;
+        Ok(pool)
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        self.agent
+            .request(method, &format!("{}/{}", self.base, path))
+    }
+
+    // Sends request with body, if any, decoding the reply as JSON.
+    fn call<R>(&self, request: ureq::Request, body: Option<Vec<u8>>) -> Result<R, FramePoolError>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        let response = send(request, body)?;
+        Ok(serde_json::from_reader(response.into_reader())?)
+    }
+}
+
+// Sends request, turning error statuses into FramePoolErrors.
+fn send(request: ureq::Request, body: Option<Vec<u8>>) -> Result<ureq::Response, FramePoolError> {
+    let sent = match body {
+        Some(body) => request.send_bytes(&body),
+        None => request.call(),
+    };
+    match sent {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(status, response)) => {
+            Err(error_of(status, response.into_string().unwrap_or_default()))
+        }
+        Err(e) => Err(FramePoolError::Io(io::Error::other(e))),
+    }
+}
+
+impl<T> FramePool<T> for RemotePool
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        let page: T = self.call(self.request("GET", &format!("frames/{}", idx)), None)?;
+        Ok(Arc::new(page))
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let body = serde_json::to_vec(&*data)?;
+        send(self.request("PUT", &format!("frames/{}", idx)), Some(body))?;
+        self.size = self.size.max(idx + 1);
+        Ok(())
+    }
+
+    // One request for the whole batch.
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        let pages: Result<Vec<Option<T>>, FramePoolError> = serde_json::to_vec(idxs)
+            .map_err(FramePoolError::from)
+            .and_then(|body| self.call(self.request("POST", "frames/get"), Some(body)));
+        match pages {
+            Ok(pages) => idxs
+                .iter()
+                .zip(pages)
+                .map(|(idx, page)| match page {
+                    Some(page) => Ok(Arc::new(page)),
+                    None => Err(FramePoolError::NotFound(format!("frame {}", idx))),
+                })
+                .collect(),
+            Err(e) => idxs.iter().map(|_| Err(e.clone())).collect(),
+        }
+    }
+
+    // One request for the whole batch.
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let pairs: Vec<(u64, &T)> = frames.iter().map(|(idx, data)| (*idx, &**data)).collect();
+        let errors: Result<Vec<Option<String>>, FramePoolError> = serde_json::to_vec(&pairs)
+            .map_err(FramePoolError::from)
+            .and_then(|body| self.call(self.request("POST", "frames/put"), Some(body)));
+        match errors {
+            Ok(errors) => frames
+                .iter()
+                .zip(errors)
+                .map(|((idx, _), error)| match error {
+                    Some(message) => Err(FramePoolError::Io(io::Error::other(message))),
+                    None => {
+                        self.size = self.size.max(idx + 1);
+                        Ok(())
+                    }
+                })
+                .collect(),
+            Err(e) => frames.iter().map(|_| Err(e.clone())).collect(),
+        }
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let body = serde_json::to_vec(&count)?;
+        self.size = self.call(self.request("POST", "resize"), Some(body))?;
+        Ok(())
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let body = serde_json::to_vec(&count)?;
+        self.size = self.call(self.request("POST", "truncate"), Some(body))?;
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.size = self.call(self.request("GET", "size"), None)?;
+        Ok(self.size)
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        send(self.request("POST", "sync"), Some(Vec::new()))?;
+        Ok(())
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        match send(self.request("HEAD", &format!("frames/{}", idx)), None) {
+            Ok(response) if response.status() == 200 => FrameState::Populated,
+            Ok(_) => FrameState::Empty,
+            Err(_) => FrameState::Absent,
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        self.call(self.request("GET", "frames"), None)
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        send(self.request("DELETE", &format!("frames/{}", idx)), None)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use crate::framepool::{DiskPool, MemPool};
+
+    #[test]
+    fn test_remote_pool_over_disk_pool() {
+        let test_dir = "/tmp/test_remote_pool";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let disk = DiskPool::new::<Value>(test_dir);
+        let server = PageServer::start("127.0.0.1:0", disk, 2).unwrap();
+        let url = format!("http://{}", server.addr().unwrap());
+
+        let mut pool = RemotePool::connect(&url).unwrap();
+        FramePool::<Vec<u32>>::resize(&mut pool, 20).unwrap();
+        {
+            let mut bp = BufferPool::<Vec<u32>>::new(5, &mut pool, bottom_evictor);
+            for i in 0..20 {
+                bp.get_or_insert_with(i, || vec![i as u32; 3]).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+
+        // A second client, as on another machine, shares the pages
+        let mut other = RemotePool::connect(&url).unwrap();
+        assert_eq!(FramePool::<Vec<u32>>::size(&other), 20);
+        let pages = FramePool::<Vec<u32>>::get_frames(&mut other, &[4, 19, 25]);
+        assert_eq!(**pages[0].as_ref().unwrap(), vec![4; 3]);
+        assert_eq!(**pages[1].as_ref().unwrap(), vec![19; 3]);
+        assert!(matches!(pages[2], Err(FramePoolError::NotFound(_))));
+        other.put_frame(4, Arc::new(vec![40u32])).unwrap();
+        assert_eq!(
+            *FramePool::<Vec<u32>>::get_frame_ref(&mut pool, 4).unwrap(),
+            vec![40]
+        );
+
+        FramePool::<Vec<u32>>::truncate(&mut other, 10).unwrap();
+        assert_eq!(FramePool::<Vec<u32>>::assess_size(&mut pool).unwrap(), 10);
+        assert_eq!(
+            FramePool::<Vec<u32>>::frame_state(&pool, &3),
+            FrameState::Populated
+        );
+        assert_eq!(
+            FramePool::<Vec<u32>>::frame_state(&pool, &12),
+            FrameState::Absent
+        );
+        FramePool::<Vec<u32>>::sync(&mut pool).unwrap();
+        server.stop();
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_remote_pool_errors() {
+        let server = PageServer::start("127.0.0.1:0", MemPool::<Value>::new(), 1).unwrap();
+        let url = format!("http://{}", server.addr().unwrap());
+        let mut pool = RemotePool::connect(&url).unwrap();
+        assert!(matches!(
+            FramePool::<String>::get_frame_ref(&mut pool, 0),
+            Err(FramePoolError::NotFound(_))
+        ));
+        // The served page is not a string
+        pool.put_frame(0, Arc::new(7u8)).unwrap();
+        assert!(matches!(
+            FramePool::<String>::get_frame_ref(&mut pool, 0),
+            Err(FramePoolError::Serde(_))
+        ));
+        FramePool::<u8>::discard_frame(&mut pool, &0).unwrap();
+        assert_eq!(FramePool::<u8>::frame_state(&pool, &0), FrameState::Empty);
+        server.stop();
+        assert!(matches!(
+            RemotePool::connect("http://127.0.0.1:1"),
+            Err(FramePoolError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,354 @@
+use rand::Rng;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState, KeyIter, StorageBackend};
+
+// How a RetryingPool retries: up to max_attempts tries in all, sleeping between them for a
+// backoff that starts at initial_backoff and doubles each time, up to max_backoff. Each sleep
+// is shortened by a random amount, up to the jitter fraction of it, so that clients failing
+// together don't retry together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // The sleep before the given retry, counting from 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        let doubled = self
+            .initial_backoff
+            .saturating_mul(1 << (retry - 1).min(31))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return doubled;
+        }
+        doubled.mul_f64(1.0 - rand::thread_rng().gen_range(0.0..=jitter))
+    }
+}
+
+// Wraps a FramePool or StorageBackend and retries operations that fail with a transient error
+// (see FramePoolError::is_transient), backing off between attempts. Permanent failures, such
+// as a missing frame or a page that doesn't deserialize, are returned at once.
+pub struct RetryingPool<P> {
+    inner: P,
+    policy: RetryPolicy,
+    retries: AtomicU64,
+}
+
+impl<P> RetryingPool<P> {
+    pub fn new(inner: P, policy: RetryPolicy) -> Self {
+        RetryingPool {
+            inner,
+            policy,
+            retries: AtomicU64::new(0),
+        }
+    }
+
+    pub fn policy(&self) -> RetryPolicy {
+        self.policy
+    }
+
+    // The number of retries made so far, over all operations.
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+// Runs op until it succeeds, fails permanently, or runs out of attempts.
+fn retry_with<R>(
+    policy: &RetryPolicy,
+    retries: &AtomicU64,
+    mut op: impl FnMut() -> Result<R, FramePoolError>,
+) -> Result<R, FramePoolError> {
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if e.is_transient() && attempt < policy.max_attempts => {
+                thread::sleep(policy.backoff(attempt));
+                retries.fetch_add(1, Ordering::Relaxed);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+impl<T, P> FramePool<T> for RetryingPool<P>
+where
+    T: Clone,
+    P: FramePool<T>,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || inner.get_frame_ref(idx))
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || {
+            inner.put_frame(idx, Arc::clone(&data))
+        })
+    }
+
+    // The batch goes to the wrapped pool whole; frames that fail transiently are then retried
+    // one at a time.
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        let mut results = self.inner.get_frames(idxs);
+        for (idx, result) in idxs.iter().zip(results.iter_mut()) {
+            if result.as_ref().is_err_and(|e| e.is_transient()) {
+                self.retries.fetch_add(1, Ordering::Relaxed);
+                *result = self.get_frame_ref(*idx);
+            }
+        }
+        results
+    }
+
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let mut results = self.inner.put_frames(frames.clone());
+        for ((idx, data), result) in frames.into_iter().zip(results.iter_mut()) {
+            if result.as_ref().is_err_and(|e| e.is_transient()) {
+                self.retries.fetch_add(1, Ordering::Relaxed);
+                *result = self.put_frame(idx, data);
+            }
+        }
+        results
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.inner.resize(count)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.inner.truncate(count)
+    }
+
+    fn discard_frame(&mut self, idx: &u64) -> Result<(), FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || inner.discard_frame(idx))
+    }
+
+    fn size(&self) -> u64 {
+        self.inner.size()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || inner.assess_size())
+    }
+
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || inner.sync())
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        self.inner.frame_state(idx)
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        retry_with(&self.policy, &self.retries, || self.inner.frame_ids())
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        retry_with(&self.policy, &self.retries, || self.inner.frame_meta(idx))
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.inner.is_read_only()
+    }
+}
+
+impl<T, P> StorageBackend<T> for RetryingPool<P>
+where
+    T: Clone,
+    P: StorageBackend<T>,
+{
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || inner.read(key))
+    }
+
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || {
+            inner.write(key, Arc::clone(&data))
+        })
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        self.inner.exists(key)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || inner.delete(key))
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        retry_with(&self.policy, &self.retries, || self.inner.list_keys())
+    }
+
+    fn write_if_absent(&mut self, key: &str, data: Arc<T>) -> Result<bool, FramePoolError> {
+        let inner = &mut self.inner;
+        retry_with(&self.policy, &self.retries, || {
+            inner.write_if_absent(key, Arc::clone(&data))
+        })
+    }
+
+    fn iter_keys(&self) -> Result<KeyIter<'_>, FramePoolError> {
+        retry_with(&self.policy, &self.retries, || self.inner.iter_keys())
+    }
+
+    fn list_keys_with_prefix(
+        &self,
+        prefix: &str,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Vec<String>, FramePoolError> {
+        retry_with(&self.policy, &self.retries, || {
+            self.inner.list_keys_with_prefix(prefix, limit, cursor)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{FaultInjector, FaultyBackend, FaultyPool, FileBackend, MemPool};
+    use std::io;
+
+    fn quick(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_micros(10),
+            max_backoff: Duration::from_micros(40),
+            jitter: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_retrying_pool_recovers_from_transient_faults() {
+        let faults = FaultInjector::new(3);
+        let mut mem = MemPool::<u64>::new();
+        mem.put_frame(0, Arc::new(10)).unwrap();
+        let mut pool = RetryingPool::new(FaultyPool::new(mem, faults.clone()), quick(3));
+
+        // Two failures, then success
+        faults.fail_after(Some(0));
+        let mut attempts = 0;
+        let result = retry_with(&quick(3), &pool.retries, || {
+            attempts += 1;
+            if attempts == 3 {
+                faults.fail_after(None);
+            }
+            FramePool::<u64>::get_frame_ref(&mut pool.inner, 0)
+        });
+        assert_eq!(*result.unwrap(), 10);
+        assert_eq!(pool.retries(), 2);
+
+        faults.set_write_failure_rate(0.5);
+        for i in 0..20 {
+            pool.put_frame(i, Arc::new(i)).unwrap_or_default();
+        }
+        assert!(pool.retries() > 2);
+        faults.set_write_failure_rate(0.0);
+
+        // Out of attempts, the last error is returned
+        faults.fail_after(Some(0));
+        let before = pool.retries();
+        assert!(matches!(
+            FramePool::<u64>::get_frame_ref(&mut pool, 0),
+            Err(FramePoolError::Io(_))
+        ));
+        assert_eq!(pool.retries() - before, 2);
+        faults.fail_after(None);
+
+        // Permanent failures are not retried
+        let before = pool.retries();
+        assert!(matches!(
+            FramePool::<u64>::get_frame_ref(&mut pool, 99),
+            Err(FramePoolError::NotFound(_))
+        ));
+        assert_eq!(pool.retries(), before);
+    }
+
+    #[test]
+    fn test_retrying_batches_and_backends() {
+        let faults = FaultInjector::new(9);
+        let mut pool = RetryingPool::new(
+            FaultyPool::new(MemPool::<u64>::new(), faults.clone()),
+            quick(10),
+        );
+        faults.set_write_failure_rate(0.3);
+        faults.set_read_failure_rate(0.3);
+        let written = pool.put_frames((0..50).map(|i| (i, Arc::new(i))).collect());
+        assert!(written.iter().all(|r| r.is_ok()));
+        let read = pool.get_frames(&(0..50).collect::<Vec<_>>());
+        for (i, frame) in read.into_iter().enumerate() {
+            assert_eq!(*frame.unwrap(), i as u64);
+        }
+        assert!(faults.injected() > 0);
+
+        let test_dir = "/tmp/test_retrying_backend";
+        let _ = std::fs::remove_dir_all(test_dir);
+        let faults = FaultInjector::new(1);
+        let mut backend = RetryingPool::new(
+            FaultyBackend::new(FileBackend::new(test_dir), faults.clone()),
+            quick(10),
+        );
+        faults.set_write_failure_rate(0.5);
+        for i in 0..10 {
+            StorageBackend::<u32>::write(&mut backend, &format!("k{}", i), Arc::new(i)).unwrap();
+        }
+        assert_eq!(
+            StorageBackend::<u32>::list_keys(&backend).unwrap().len(),
+            10
+        );
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        let io = |kind| FramePoolError::Io(io::Error::from(kind));
+        assert!(io(io::ErrorKind::Interrupted).is_transient());
+        assert!(io(io::ErrorKind::TimedOut).is_transient());
+        assert!(FramePoolError::Io(io::Error::other("device busy")).is_transient());
+        assert!(!io(io::ErrorKind::PermissionDenied).is_transient());
+        assert!(!FramePoolError::NotFound("x".to_string()).is_transient());
+        assert!(!FramePoolError::CorruptPage { idx: 1 }.is_transient());
+
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(10));
+        assert_eq!(policy.backoff(3), Duration::from_millis(40));
+        assert_eq!(policy.backoff(40), Duration::from_secs(1));
+    }
+}
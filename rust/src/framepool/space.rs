@@ -0,0 +1,59 @@
+use std::fs::File;
+use std::io;
+
+// How much disk a single-file pool (PagedFile, MmapPool) takes and how much of that holds
+// pages in use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpaceStats {
+    // the file's length
+    pub file_len: u64,
+    // bytes of disk allocated to the file: less than its length where it is sparse, all of it
+    // once preallocated
+    pub allocated: u64,
+    // bytes of the file holding pages in use, with their headers and any superblock
+    pub used: u64,
+}
+
+impl SpaceStats {
+    pub(super) fn of(file: &File, used: u64) -> io::Result<Self> {
+        let meta = file.metadata()?;
+        #[cfg(unix)]
+        let allocated = std::os::unix::fs::MetadataExt::blocks(&meta) * 512;
+        #[cfg(not(unix))]
+        let allocated = meta.len();
+        Ok(SpaceStats {
+            file_len: meta.len(),
+            allocated,
+            used,
+        })
+    }
+}
+
+// Allocates disk blocks for the first len bytes of file, extending it to len bytes if it is
+// shorter, so later writes there cannot fail for want of space. On Linux this is fallocate,
+// which also lays the blocks out as contiguously as the filesystem can; elsewhere the file is
+// only extended, and may be left sparse.
+pub(super) fn allocate_blocks(file: &File, len: u64) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::io::AsRawFd;
+        loop {
+            // SAFETY: fallocate takes no pointers, and the descriptor is open while file lives
+            let ret = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len as libc::off_t) };
+            if ret == 0 {
+                return Ok(());
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        if file.metadata()?.len() < len {
+            file.set_len(len)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState};
+use crate::unique_stack::UniqueStack;
+
+// A stack of FramePools used as one: small fast tiers over larger slower ones, say a MemPool
+// over a DiskPool over an object store. The bottom tier is the pool of record and holds every
+// frame; each tier above holds at most a set number of frames.
+//
+// Reads look from the top down and promote what they find to the top tier. Writes go to the
+// top tier. A tier that is full, or whose pool rejects a write with CapacityExceeded, demotes
+// its least recently used frame to the tier below; frames written since they were last
+// demoted carry their new data down with them, so nothing is lost until it reaches the
+// bottom. sync pushes every such frame straight to the bottom tier and syncs it.
+//
+// Resize, size and frame_ids describe the bottom tier. Upper tiers free demoted frames with
+// discard_frame where their pool supports it.
+pub struct TieredPool<T> {
+    // top first; the last is the pool of record
+    tiers: Vec<Tier<T>>,
+    promotions: u64,
+    demotions: u64,
+}
+
+struct Tier<T> {
+    pool: Box<dyn FramePool<T> + Send>,
+    // most frames held at once; unused for the bottom tier
+    capacity: usize,
+    // frames held, coldest at the bottom
+    resident: UniqueStack<u64>,
+    // frames whose data in this tier is newer than in the tiers below
+    dirty: HashSet<u64>,
+}
+
+impl<T> TieredPool<T>
+where
+    T: Clone,
+{
+    // A pool with a single tier, bottom, to be topped with with_tier.
+    pub fn new<P>(bottom: P) -> Self
+    where
+        P: FramePool<T> + Send + 'static,
+    {
+        TieredPool {
+            tiers: vec![Tier::new(Box::new(bottom), usize::MAX)],
+            promotions: 0,
+            demotions: 0,
+        }
+    }
+
+    // Adds a tier above the existing ones, holding at most capacity frames (at least one).
+    pub fn with_tier<P>(mut self, pool: P, capacity: usize) -> Self
+    where
+        P: FramePool<T> + Send + 'static,
+    {
+        self.tiers
+            .insert(0, Tier::new(Box::new(pool), capacity.max(1)));
+        self
+    }
+
+    pub fn tier_count(&self) -> usize {
+        self.tiers.len()
+    }
+
+    // The number of frames held by the given tier, counting from the top. The bottom tier
+    // holds every frame and reports none.
+    pub fn resident(&self, tier: usize) -> usize {
+        self.tiers[tier].resident.len() as usize
+    }
+
+    // The number of reads that copied a frame up to the top tier.
+    pub fn promotions(&self) -> u64 {
+        self.promotions
+    }
+
+    // The number of frames moved down a tier to make room.
+    pub fn demotions(&self) -> u64 {
+        self.demotions
+    }
+
+    fn bottom(&self) -> usize {
+        self.tiers.len() - 1
+    }
+
+    // The topmost tier holding idx.
+    fn locate(&self, idx: u64) -> usize {
+        (0..self.bottom())
+            .find(|tier| self.tiers[*tier].resident.contains(&idx))
+            .unwrap_or(self.bottom())
+    }
+
+    // Writes data for idx into the given tier, demoting frames from it to make room.
+    fn place(
+        &mut self,
+        tier: usize,
+        idx: u64,
+        data: Arc<T>,
+        dirty: bool,
+    ) -> Result<(), FramePoolError> {
+        if tier == self.bottom() {
+            return self.tiers[tier].pool.put_frame(idx, data);
+        }
+        while !self.tiers[tier].resident.contains(&idx)
+            && self.tiers[tier].resident.len() as usize >= self.tiers[tier].capacity
+        {
+            self.demote_coldest(tier, idx)?;
+        }
+        loop {
+            match self.tiers[tier].pool.put_frame(idx, Arc::clone(&data)) {
+                Ok(()) => break,
+                Err(FramePoolError::CapacityExceeded { .. })
+                    if self.tiers[tier].resident.order().iter().any(|v| *v != idx) =>
+                {
+                    self.demote_coldest(tier, idx)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let target = &mut self.tiers[tier];
+        target.resident.push(idx);
+        if dirty {
+            target.dirty.insert(idx);
+        }
+        Ok(())
+    }
+
+    // Moves the least recently used frame other than keep down to the next tier. A frame with
+    // no new data is only copied down if that tier doesn't already hold it.
+    fn demote_coldest(&mut self, tier: usize, keep: u64) -> Result<(), FramePoolError> {
+        let Some(victim) = self.tiers[tier]
+            .resident
+            .order()
+            .into_iter()
+            .find(|v| *v != keep)
+        else {
+            return Err(FramePoolError::Unsupported(
+                "no frame to demote from the tier".to_string(),
+            ));
+        };
+        let data = self.tiers[tier].pool.get_frame_ref(victim)?;
+        let dirty = self.tiers[tier].dirty.contains(&victim);
+        let next = tier + 1;
+        if dirty || (next != self.bottom() && !self.tiers[next].resident.contains(&victim)) {
+            self.place(next, victim, data, dirty)?;
+        }
+        self.tiers[tier].forget(victim)?;
+        self.demotions += 1;
+        Ok(())
+    }
+}
+
+impl<T> Tier<T>
+where
+    T: Clone,
+{
+    fn new(pool: Box<dyn FramePool<T> + Send>, capacity: usize) -> Self {
+        Tier {
+            pool,
+            capacity,
+            resident: UniqueStack::new(),
+            dirty: HashSet::new(),
+        }
+    }
+
+    // Stops holding idx, freeing it from the pool if the pool can. A pool that can't free
+    // single frames keeps the data, which nothing reads any more.
+    fn forget(&mut self, idx: u64) -> Result<(), FramePoolError> {
+        self.resident.delete(idx);
+        self.dirty.remove(&idx);
+        match self.pool.discard_frame(&idx) {
+            Err(FramePoolError::Unsupported(_)) => Ok(()),
+            result => result,
+        }
+    }
+}
+
+impl<T> FramePool<T> for TieredPool<T>
+where
+    T: Clone,
+{
+    // A frame that can't be promoted, because making room failed, is still returned.
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        let tier = self.locate(idx);
+        let data = self.tiers[tier].pool.get_frame_ref(idx)?;
+        if tier == 0 && self.bottom() > 0 {
+            self.tiers[0].resident.push(idx);
+        } else if tier > 0 && self.place(0, idx, Arc::clone(&data), false).is_ok() {
+            self.promotions += 1;
+        }
+        Ok(data)
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.place(0, idx, data, true)
+    }
+
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let bottom = self.bottom();
+        self.tiers[bottom].pool.resize(count)
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        let bottom = self.bottom();
+        self.tiers[bottom].pool.truncate(count)?;
+        for tier in self.tiers[..bottom].iter_mut() {
+            for idx in tier.resident.order() {
+                if idx >= count {
+                    tier.forget(idx)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.tiers[self.bottom()].pool.size()
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        let bottom = self.bottom();
+        self.tiers[bottom].pool.assess_size()
+    }
+
+    // Frames with new data in an upper tier are written to the bottom tier, and the stale
+    // copies in the tiers between are dropped.
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        let bottom = self.bottom();
+        for tier in 0..bottom {
+            let mut dirty: Vec<u64> = self.tiers[tier].dirty.iter().copied().collect();
+            dirty.sort_unstable();
+            for idx in dirty {
+                let data = self.tiers[tier].pool.get_frame_ref(idx)?;
+                self.tiers[bottom].pool.put_frame(idx, data)?;
+                self.tiers[tier].dirty.remove(&idx);
+                for lower in self.tiers[tier + 1..bottom].iter_mut() {
+                    if lower.resident.contains(&idx) {
+                        lower.forget(idx)?;
+                    }
+                }
+            }
+        }
+        self.tiers[bottom].pool.sync()
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        match self.locate(*idx) {
+            tier if tier == self.bottom() => self.tiers[tier].pool.frame_state(idx),
+            _ => FrameState::Populated,
+        }
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        let mut ids = self.tiers[self.bottom()].pool.frame_ids()?;
+        for tier in self.tiers[..self.bottom()].iter() {
+            ids.extend(tier.resident.order());
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        self.tiers[self.locate(*idx)].pool.frame_meta(idx)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.tiers[self.bottom()].pool.is_read_only()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor, filled_mem_pool};
+    use crate::framepool::{DiskPool, FaultInjector, FaultyPool, InstrumentedPool, MemPool};
+
+    #[test]
+    fn test_tiered_promotion_and_demotion() {
+        let mut pool = TieredPool::new(filled_mem_pool(10, |i| i))
+            .with_tier(MemPool::new(), 4)
+            .with_tier(MemPool::new(), 2);
+        assert_eq!(pool.tier_count(), 3);
+
+        for i in 0..4 {
+            assert_eq!(*pool.get_frame_ref(i).unwrap(), i);
+        }
+        assert_eq!(pool.promotions(), 4);
+        // Two frames fell out of the top tier into the middle one
+        assert_eq!((pool.resident(0), pool.resident(1)), (2, 2));
+
+        // A write stays on top until pushed down
+        pool.put_frame(0, Arc::new(100)).unwrap();
+        for i in 4..9 {
+            pool.get_frame_ref(i).unwrap();
+        }
+        assert_eq!(*pool.get_frame_ref(0).unwrap(), 100);
+        pool.sync().unwrap();
+        let mut bottom = pool.tiers.pop().unwrap();
+        assert_eq!(*bottom.pool.get_frame_ref(0).unwrap(), 100);
+    }
+
+    #[test]
+    fn test_tiered_dirty_frames_survive_demotion() {
+        let mut pool = TieredPool::new(filled_mem_pool(8, |i| i)).with_tier(MemPool::new(), 2);
+        {
+            let mut bp = BufferPool::<u64>::new(2, &mut pool, bottom_evictor);
+            for i in 0..8 {
+                bp.modify_page(i, |v| *v += 10).unwrap();
+            }
+            bp.flush_all().unwrap();
+        }
+        // Only two frames fit on top; the rest went down when demoted
+        assert_eq!(pool.resident(0), 2);
+        for i in 0..8 {
+            assert_eq!(*pool.get_frame_ref(i).unwrap(), i + 10);
+        }
+        assert!(pool.demotions() >= 6);
+    }
+
+    #[test]
+    fn test_tiered_frees_demoted_frames_through_wrappers() {
+        let faults = FaultInjector::new(7);
+        let top = InstrumentedPool::new(MemPool::new());
+        let metrics = top.metrics();
+        let mut pool = TieredPool::new(filled_mem_pool(4, |i| i))
+            .with_tier(FaultyPool::new(top, faults.clone()), 1);
+        pool.get_frame_ref(0).unwrap();
+        pool.get_frame_ref(1).unwrap();
+        // Frame 0 made way for frame 1 and was discarded from the top tier's pool
+        assert_eq!(metrics.snapshot().writes.count, 3);
+        assert_eq!(pool.tiers[0].pool.frame_state(&0), FrameState::Empty);
+
+        // A discard that fails stops the write that needed the room
+        faults.set_write_failure_rate(1.0);
+        assert!(pool.put_frame(2, Arc::new(20)).is_err());
+        assert_eq!(faults.injected(), 1);
+    }
+
+    #[test]
+    fn test_tiered_capacity_pressure_over_disk() {
+        let test_dir = "/tmp/test_tiered_disk";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut disk = DiskPool::new::<Vec<u8>>(test_dir);
+        FramePool::<Vec<u8>>::resize(&mut disk, 6).unwrap();
+        let mut top = MemPool::new();
+        top.set_weigher(|data: &Vec<u8>| data.len() as u64);
+        top.set_max_bytes(Some(250));
+        let mut pool = TieredPool::new(disk).with_tier(top, 100);
+        for i in 0..6 {
+            pool.put_frame(i, Arc::new(vec![i as u8; 100])).unwrap();
+        }
+        // The byte limit, not the frame count, bounds the top tier
+        assert_eq!(pool.resident(0), 2);
+        assert_eq!(pool.frame_ids().unwrap(), (0..6).collect::<Vec<_>>());
+        for i in 0..6 {
+            assert_eq!(*pool.get_frame_ref(i).unwrap(), vec![i as u8; 100]);
+        }
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+}
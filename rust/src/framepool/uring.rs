@@ -0,0 +1,413 @@
+use io_uring::{IoUring, opcode, squeue, types};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::{FrameMeta, FramePool, FramePoolError, FrameState, LOCK_FILE, MANIFEST};
+
+// A pool of JSON pages, one file per page in a directory, whose page reads, writes and fsyncs
+// go through io_uring. Batches of pages, from get_frames and put_frames, are submitted together
+// and cost one wait for the whole batch rather than a read or write call per page. Pages are
+// written to a temporary file and renamed into place, so a crash never leaves a torn one.
+//
+// It keeps no manifest or lock file, so its size is one past the highest page written, and it
+// has none of DiskPool's codecs, checksums or fanout. It refuses a directory a DiskPool has
+// used, rather than write pages the DiskPool would not expect.
+pub struct UringPool<T> {
+    ring: IoUring,
+    dirname: PathBuf,
+    size: u64,
+    // pages written since the last sync
+    unsynced: HashSet<u64>,
+    // the ring failed in a way that may have left entries in it; see complete
+    failed: bool,
+    _data: PhantomData<fn() -> T>,
+}
+
+impl<T> UringPool<T> {
+    // Opens the pool directory, creating it if needed, with a ring of `queue_depth` entries.
+    // Fails if the kernel does not support io_uring or it is disabled.
+    pub fn open(dirname: &str, queue_depth: u32) -> Result<Self, FramePoolError> {
+        let ring = IoUring::new(queue_depth)?;
+        let dirname = PathBuf::from(dirname);
+        fs::create_dir_all(&dirname)?;
+        for name in [MANIFEST, LOCK_FILE] {
+            if dirname.join(name).exists() {
+                return Err(FramePoolError::Unsupported(format!(
+                    "{} belongs to a DiskPool",
+                    dirname.display()
+                )));
+            }
+        }
+        let mut pool = UringPool {
+            ring,
+            dirname,
+            size: 0,
+            unsynced: HashSet::new(),
+            failed: false,
+            _data: PhantomData,
+        };
+        pool.size = pool.count_pages()?;
+        Ok(pool)
+    }
+
+    // One past the highest page in the directory.
+    fn count_pages(&self) -> Result<u64, FramePoolError> {
+        Ok(super::page_ids(&self.dirname)?
+            .last()
+            .map_or(0, |id| id + 1))
+    }
+
+    fn page_path(&self, pageid: u64) -> PathBuf {
+        self.dirname.join(format!("page_{}", pageid))
+    }
+
+    // Submits the operations, as many at a time as the ring holds, and returns each one's
+    // result in order. Every operation pushed has completed by the time this returns, error or
+    // not, and none is left queued to be submitted by a later call.
+    //
+    // Safety: every buffer and file descriptor the operations refer to must stay valid until
+    // this returns.
+    unsafe fn run(&mut self, ops: Vec<squeue::Entry>) -> Result<Vec<i32>, FramePoolError> {
+        if self.failed {
+            return Err(io::Error::other("io_uring ring failed earlier").into());
+        }
+        let mut results = vec![0; ops.len()];
+        let depth = self.ring.params().sq_entries() as usize;
+        for (batch_no, batch) in ops.chunks(depth).enumerate() {
+            let mut pushed = 0;
+            {
+                let mut queue = self.ring.submission();
+                for (i, op) in batch.iter().enumerate() {
+                    let op = op.clone().user_data((batch_no * depth + i) as u64);
+                    // SAFETY: upheld by the caller
+                    if unsafe { queue.push(&op) }.is_err() {
+                        break;
+                    }
+                    pushed += 1;
+                }
+            }
+            // what was pushed is seen through even if not all of it could be
+            self.complete(pushed, &mut results)?;
+            if pushed < batch.len() {
+                return Err(io::Error::other("io_uring submission queue is full").into());
+            }
+        }
+        Ok(results)
+    }
+
+    // Submits the queued entries and waits for count operations to complete, recording each
+    // one's result. If the ring fails outright, the pool stops using it, so the entries it had
+    // not taken are never submitted, and this waits for those it had.
+    fn complete(&mut self, count: usize, results: &mut [i32]) -> Result<(), FramePoolError> {
+        let mut completed = 0;
+        let mut failure = None;
+        while completed < count {
+            if failure.is_none() {
+                match self.ring.submit_and_wait(count - completed) {
+                    Ok(_) => {}
+                    // retried once the completions below have made room
+                    Err(e) if is_retryable(&e) => {}
+                    Err(e) => {
+                        self.failed = true;
+                        failure = Some(e);
+                    }
+                }
+            }
+            let before = completed;
+            for cqe in self.ring.completion() {
+                results[cqe.user_data() as usize] = cqe.result();
+                completed += 1;
+            }
+            if failure.is_some() {
+                let untaken = self.ring.submission().len();
+                if completed + untaken >= count {
+                    break;
+                }
+            }
+            if completed == before {
+                // lets the kernel post completions, which it does on return from a syscall
+                std::thread::yield_now();
+            }
+        }
+        match failure {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+// Whether io_uring_enter failed only for now: interrupted, or short of resources or room for
+// completions until some are reaped.
+fn is_retryable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::ResourceBusy
+    )
+}
+
+// A page being written: its id, the temporary file its serialized data goes to, and the data.
+type PendingWrite = (u64, PathBuf, fs::File, Vec<u8>);
+
+// The outcome of an io_uring operation that should have transferred `expected` bytes.
+fn transferred(result: i32, expected: usize) -> io::Result<usize> {
+    if result < 0 {
+        return Err(io::Error::from_raw_os_error(-result));
+    }
+    let done = result as usize;
+    if done > expected {
+        return Err(io::Error::other("transferred more than requested"));
+    }
+    Ok(done)
+}
+
+impl<T> FramePool<T> for UringPool<T>
+where
+    T: for<'de> Deserialize<'de> + Serialize + Clone,
+{
+    fn get_frame_ref(&mut self, idx: u64) -> Result<Arc<T>, FramePoolError> {
+        self.get_frames(&[idx]).remove(0)
+    }
+
+    fn put_frame(&mut self, idx: u64, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.put_frames(vec![(idx, data)]).remove(0)
+    }
+
+    fn get_frames(&mut self, idxs: &[u64]) -> Vec<Result<Arc<T>, FramePoolError>> {
+        // Open and size every page first, then read them all in one batch
+        let mut pages: Vec<Result<(fs::File, Vec<u8>), FramePoolError>> = idxs
+            .iter()
+            .map(|&idx| {
+                let file = fs::File::open(self.page_path(idx))
+                    .map_err(|e| FramePoolError::from_io(e, || format!("page {}", idx)))?;
+                let len = file.metadata()?.len() as usize;
+                Ok((file, vec![0; len]))
+            })
+            .collect();
+        let ops: Vec<squeue::Entry> = pages
+            .iter_mut()
+            .flatten()
+            .map(|(file, buf)| {
+                opcode::Read::new(
+                    types::Fd(file.as_raw_fd()),
+                    buf.as_mut_ptr(),
+                    buf.len() as u32,
+                )
+                .offset(0)
+                .build()
+            })
+            .collect();
+        // SAFETY: the files and buffers in pages outlive the call
+        let results = match unsafe { self.run(ops) } {
+            Ok(results) => results,
+            Err(e) => return idxs.iter().map(|_| Err(e.clone())).collect(),
+        };
+
+        let mut results = results.into_iter();
+        pages
+            .into_iter()
+            .map(|page| {
+                let (file, mut buf) = page?;
+                let done = transferred(results.next().unwrap_or(-1), buf.len())?;
+                if done < buf.len() {
+                    // Short read: finish it the ordinary way
+                    file.read_exact_at(&mut buf[done..], done as u64)?;
+                }
+                let result: T = serde_json::from_slice(&buf)?;
+                Ok(Arc::new(result))
+            })
+            .collect()
+    }
+
+    // Writes each page to a temporary file, all in one batch, then renames those written in
+    // full over the pages.
+    fn put_frames(&mut self, frames: Vec<(u64, Arc<T>)>) -> Vec<Result<(), FramePoolError>> {
+        let pages: Vec<Result<PendingWrite, FramePoolError>> = frames
+            .iter()
+            .map(|(idx, data)| {
+                let buf = serde_json::to_vec(&**data)?;
+                let staging = super::staging_path(&self.page_path(*idx));
+                let file = fs::File::create(&staging)?;
+                Ok((*idx, staging, file, buf))
+            })
+            .collect();
+        let ops: Vec<squeue::Entry> = pages
+            .iter()
+            .flatten()
+            .map(|(_, _, file, buf)| {
+                opcode::Write::new(types::Fd(file.as_raw_fd()), buf.as_ptr(), buf.len() as u32)
+                    .offset(0)
+                    .build()
+            })
+            .collect();
+        // SAFETY: the files and buffers in pages outlive the call
+        let results = match unsafe { self.run(ops) } {
+            Ok(results) => results,
+            Err(e) => {
+                for (_, staging, _, _) in pages.iter().flatten() {
+                    let _ = fs::remove_file(staging);
+                }
+                return frames.iter().map(|_| Err(e.clone())).collect();
+            }
+        };
+
+        let mut results = results.into_iter();
+        pages
+            .into_iter()
+            .map(|page| {
+                let (idx, staging, file, buf) = page?;
+                let placed = transferred(results.next().unwrap_or(-1), buf.len())
+                    // Short write: finish it the ordinary way
+                    .and_then(|done| file.write_all_at(&buf[done..], done as u64))
+                    .and_then(|()| fs::rename(&staging, self.page_path(idx)));
+                if let Err(e) = placed {
+                    let _ = fs::remove_file(&staging);
+                    return Err(e.into());
+                }
+                self.unsynced.insert(idx);
+                self.size = self.size.max(idx + 1);
+                Ok(())
+            })
+            .collect()
+    }
+
+    // The frames added have no file until written, and a pool opened later only counts up to
+    // the highest page written.
+    fn resize(&mut self, count: u64) -> Result<(), FramePoolError> {
+        self.size += count;
+        Ok(())
+    }
+
+    fn truncate(&mut self, count: u64) -> Result<(), FramePoolError> {
+        super::remove_pages_from(&self.dirname, count)?;
+        self.unsynced.retain(|idx| *idx < count);
+        self.size = self.size.min(count);
+        Ok(())
+    }
+
+    fn frame_state(&self, idx: &u64) -> FrameState {
+        super::page_file_state(&self.page_path(*idx))
+    }
+
+    fn frame_ids(&self) -> Result<Vec<u64>, FramePoolError> {
+        super::page_ids(&self.dirname)
+    }
+
+    fn frame_meta(&self, idx: &u64) -> Result<FrameMeta, FramePoolError> {
+        super::page_file_meta(&self.page_path(*idx), *idx)
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn assess_size(&mut self) -> Result<u64, FramePoolError> {
+        self.count_pages()
+    }
+
+    // fsync every page written since the last sync, as one batch, then the directory.
+    fn sync(&mut self) -> Result<(), FramePoolError> {
+        let mut files = Vec::new();
+        for idx in self.unsynced.iter() {
+            let path = self.page_path(*idx);
+            files.push(fs::File::open(&path)?);
+        }
+        files.push(fs::File::open(&self.dirname)?);
+        let ops = files
+            .iter()
+            .map(|file| opcode::Fsync::new(types::Fd(file.as_raw_fd())).build())
+            .collect();
+        // SAFETY: the files outlive the call
+        for result in unsafe { self.run(ops) }? {
+            transferred(result, 0)?;
+        }
+        self.unsynced.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::DiskPool;
+
+    // io_uring is often disabled in containers and sandboxes; skip rather than fail there.
+    fn open_or_skip(dir: &str) -> Option<UringPool<Vec<u32>>> {
+        let _ = fs::remove_dir_all(dir);
+        match UringPool::open(dir, 4) {
+            Ok(pool) => Some(pool),
+            Err(e) => {
+                eprintln!("skipping io_uring test: {}", e);
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_uring_pool_batches() {
+        let temp_dir = "/tmp/test_uring_pool_batches";
+        let Some(mut pool) = open_or_skip(temp_dir) else {
+            return;
+        };
+        pool.resize(10).unwrap();
+        assert_eq!(pool.size(), 10);
+
+        // More pages than the ring holds
+        let results = pool.put_frames((0..10).map(|i| (i, Arc::new(vec![i as u32; 50]))).collect());
+        assert!(results.iter().all(|r| r.is_ok()));
+        pool.sync().unwrap();
+
+        let read = pool.get_frames(&[9, 0, 42, 3]);
+        assert_eq!(*read[0].as_ref().unwrap().as_ref(), vec![9; 50]);
+        assert_eq!(*read[1].as_ref().unwrap().as_ref(), vec![0; 50]);
+        assert!(read[2].is_err());
+        assert_eq!(*read[3].as_ref().unwrap().as_ref(), vec![3; 50]);
+
+        // Shorter data replaces longer data cleanly
+        pool.put_frame(9, Arc::new(vec![1])).unwrap();
+        assert_eq!(*pool.get_frame_ref(9).unwrap(), vec![1]);
+
+        // Every page was renamed into place, leaving no temporary file behind
+        let names: Vec<_> = fs::read_dir(temp_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 10);
+        assert!(
+            names
+                .iter()
+                .all(|name| name.to_string_lossy().starts_with("page_"))
+        );
+
+        // A pool reopened counts up to the highest page written, and no further
+        pool.resize(5).unwrap();
+        drop(pool);
+        let pool = UringPool::<Vec<u32>>::open(temp_dir, 4).unwrap();
+        assert_eq!(pool.size(), 10);
+        assert_eq!(pool.frame_state(&12), FrameState::Absent);
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn test_uring_pool_refuses_diskpool_directory() {
+        let temp_dir = "/tmp/test_uring_pool_refuses_diskpool_directory";
+        if open_or_skip(temp_dir).is_none() {
+            return;
+        }
+        let _ = fs::remove_dir_all(temp_dir);
+        let mut disk_pool = DiskPool::new::<Vec<u32>>(temp_dir);
+        disk_pool.put_frame(0, Arc::new(vec![1u32])).unwrap();
+        assert!(matches!(
+            UringPool::<Vec<u32>>::open(temp_dir, 4),
+            Err(FramePoolError::Unsupported(_))
+        ));
+        drop(disk_pool);
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,265 @@
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::{Codec, DiskPool, FileBackend, FramePoolError, is_key_name};
+
+// Changes to a DiskPool's pages or a FileBackend's keys made by anyone, noticed through the
+// OS's file notifications (inotify, FSEvents, ...), so that processes sharing a directory can
+// keep their caches coherent. Each changed page id or key is passed to the callback the watch
+// was made with as it is noticed, and collected for take_changes; a BufferPool drops its stale
+// copies with invalidate. Watching stops when the ChangeWatch is dropped.
+//
+// Notifications arrive shortly after a change, not with it, and every change is reported,
+// this process's own writes included.
+pub struct ChangeWatch<K> {
+    _watcher: notify::RecommendedWatcher,
+    changes: Arc<Mutex<BTreeSet<K>>>,
+    // set when the OS dropped notifications, so any page may have changed
+    missed: Arc<AtomicBool>,
+}
+
+impl<K: Ord + Clone + Send + 'static> ChangeWatch<K> {
+    fn new(
+        dir: &Path,
+        key_of: impl Fn(&Path) -> Option<K> + Send + 'static,
+        mut on_change: impl FnMut(&K) + Send + 'static,
+    ) -> Result<Self, FramePoolError> {
+        let changes = Arc::new(Mutex::new(BTreeSet::new()));
+        let missed = Arc::new(AtomicBool::new(false));
+        let (seen, lost) = (Arc::clone(&changes), Arc::clone(&missed));
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let event = match event {
+                Ok(event) if !event.need_rescan() => event,
+                _ => return lost.store(true, Ordering::Relaxed),
+            };
+            let mut paths = Vec::new();
+            match event.kind {
+                EventKind::Access(_) => return,
+                // a directory's files may be written before it is watched too
+                EventKind::Create(_) => event.paths.iter().for_each(|p| files_in(p, &mut paths)),
+                _ => paths = event.paths,
+            }
+            for key in paths.iter().filter_map(|path| key_of(path)) {
+                // recorded first, so take_changes called from on_change sees it
+                seen.lock().unwrap().insert(key.clone());
+                on_change(&key);
+            }
+        })
+        .map_err(watch_error)?;
+        watcher
+            .watch(dir, RecursiveMode::Recursive)
+            .map_err(watch_error)?;
+        Ok(ChangeWatch {
+            _watcher: watcher,
+            changes,
+            missed,
+        })
+    }
+
+    // The page ids or keys changed since the last call, in ascending order.
+    pub fn take_changes(&self) -> Vec<K> {
+        std::mem::take(&mut *self.changes.lock().unwrap())
+            .into_iter()
+            .collect()
+    }
+
+    // Whether notifications were lost since the last call, as when the OS's queue of them
+    // overflows. Anything may have changed since, and a cache should be dropped whole.
+    pub fn take_missed(&self) -> bool {
+        self.missed.swap(false, Ordering::Relaxed)
+    }
+}
+
+// Adds path to paths, or if it is a directory every file under it.
+fn files_in(path: &Path, paths: &mut Vec<PathBuf>) {
+    match fs::read_dir(path) {
+        Ok(entries) => entries
+            .flatten()
+            .for_each(|entry| files_in(&entry.path(), paths)),
+        Err(_) => paths.push(path.to_path_buf()),
+    }
+}
+
+fn watch_error(e: notify::Error) -> FramePoolError {
+    match e.kind {
+        notify::ErrorKind::Io(e) => FramePoolError::Io(e),
+        kind => FramePoolError::Unsupported(format!("watching for changes: {:?}", kind)),
+    }
+}
+
+impl<C: Codec> DiskPool<C> {
+    // Watches the pool's directory for pages written, replaced or removed, by this process or
+    // any other, calling on_change with the id of each. See ChangeWatch.
+    pub fn watch(
+        &mut self,
+        on_change: impl FnMut(&u64) + Send + 'static,
+    ) -> Result<ChangeWatch<u64>, FramePoolError> {
+        self.initialize()?;
+        ChangeWatch::new(&self.dirname, page_of, on_change)
+    }
+}
+
+fn page_of(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("page_")?
+        .parse()
+        .ok()
+}
+
+impl<C: Codec> FileBackend<C> {
+    // Watches the backend's files for keys written, replaced or deleted, by this process or
+    // any other, calling on_change with each. See ChangeWatch.
+    pub fn watch(
+        &self,
+        on_change: impl FnMut(&String) + Send + 'static,
+    ) -> Result<ChangeWatch<String>, FramePoolError> {
+        fs::create_dir_all(&self.base_path)?;
+        let base = fs::canonicalize(&self.base_path)?;
+        let extension = format!(".{}", self.codec.extension());
+        let dir = base.clone();
+        let key_of = move |path: &Path| key_of(&base, &extension, path);
+        ChangeWatch::new(&dir, key_of, on_change)
+    }
+}
+
+// The key of the file at path, under base.
+fn key_of(base: &Path, extension: &str, path: &Path) -> Option<String> {
+    let mut names = Vec::new();
+    for component in path.strip_prefix(base).ok()?.components() {
+        match component {
+            Component::Normal(name) => names.push(name.to_str()?),
+            _ => return None,
+        }
+    }
+    let last = names.pop()?.strip_suffix(extension)?;
+    names.push(last);
+    names
+        .iter()
+        .all(|name| is_key_name(name))
+        .then(|| names.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bufferpool::{BufferPool, bottom_evictor};
+    use crate::framepool::FramePool;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    // Waits for want to be reported, passing over other changes.
+    fn wait_for<K: PartialEq>(changed: &mpsc::Receiver<K>, want: K) {
+        loop {
+            match changed.recv_timeout(Duration::from_secs(10)) {
+                Ok(key) if key == want => return,
+                Ok(_) => {}
+                Err(e) => panic!("change not reported: {}", e),
+            }
+        }
+    }
+
+    // Pages from MARKER up are written only to tell when the watch has caught up.
+    const MARKER: u64 = 100;
+
+    // Waits until everything written so far has been reported, by writing page marker and
+    // waiting for it, then drops what was reported: changes are reported in the order they
+    // were made.
+    fn settle(
+        other: &mut DiskPool,
+        changed: &mpsc::Receiver<u64>,
+        watch: &ChangeWatch<u64>,
+        marker: u64,
+    ) {
+        other.put_frame(marker, Arc::new(0u64)).unwrap();
+        wait_for(changed, marker);
+        while changed.try_recv().is_ok() {}
+        watch.take_changes();
+    }
+
+    // The changes reported, leaving out markers.
+    fn take_pages(watch: &ChangeWatch<u64>) -> Vec<u64> {
+        let mut pages = watch.take_changes();
+        pages.retain(|&idx| idx < MARKER);
+        pages
+    }
+
+    #[test]
+    fn test_diskpool_watch_invalidates_bufferpool() {
+        let test_dir = "/tmp/test_diskpool_watch";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let mut pool = DiskPool::new::<u64>(test_dir);
+        FramePool::<u64>::resize(&mut pool, 4).unwrap();
+        let (tx, changed) = mpsc::channel();
+        let watch = pool
+            .watch(move |idx| {
+                let _ = tx.send(*idx);
+            })
+            .unwrap();
+        let mut bp = BufferPool::<u64>::new(4, &mut pool, bottom_evictor);
+        for i in 0..4 {
+            bp.get_or_insert_with(i, || i).unwrap();
+        }
+        bp.flush_all().unwrap();
+        let mut other = DiskPool::new::<u64>(test_dir).unlocked();
+        settle(&mut other, &changed, &watch, MARKER);
+
+        // Another process rewrites page 1; the pool's cached copy is dropped and read again
+        other.put_frame(1, Arc::new(100u64)).unwrap();
+        wait_for(&changed, 1);
+        assert_eq!(take_pages(&watch), vec![1]);
+        assert_eq!(*bp.get_page_arc(1).unwrap(), 1);
+        assert!(bp.invalidate(&1));
+        assert!(!bp.invalidate(&1));
+        assert_eq!(*bp.get_page_arc(1).unwrap(), 100);
+
+        // but not a page with changes of its own
+        bp.put_page(2, 20).unwrap();
+        settle(&mut other, &changed, &watch, MARKER + 1);
+        other.put_frame(2, Arc::new(200u64)).unwrap();
+        wait_for(&changed, 2);
+        assert_eq!(take_pages(&watch), vec![2]);
+        assert!(!bp.invalidate(&2));
+        assert_eq!(*bp.get_page_arc(2).unwrap(), 20);
+        assert!(!watch.take_missed());
+        let _ = fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_filebackend_watch() {
+        let test_dir = "/tmp/test_filebackend_watch";
+        let _ = fs::remove_dir_all(test_dir);
+
+        let backend = FileBackend::new(test_dir);
+        let (tx, changed) = mpsc::channel();
+        let watch = backend
+            .watch(move |key| {
+                let _ = tx.send(key.clone());
+            })
+            .unwrap();
+        let mut other = FileBackend::new(test_dir);
+        other.write_data("users/42/profile", Arc::new(1)).unwrap();
+        wait_for(&changed, "users/42/profile".to_string());
+        other.delete_data::<u32>("users/42/profile").unwrap();
+        other.write_data("top", Arc::new(2)).unwrap();
+        wait_for(&changed, "top".to_string());
+        assert_eq!(watch.take_changes(), vec!["top", "users/42/profile"]);
+
+        assert_eq!(
+            key_of(Path::new("/b"), ".json", Path::new("/b/x/y.json")),
+            Some("x/y".to_string())
+        );
+        assert_eq!(key_of(Path::new("/b"), ".json", Path::new("/b/x")), None);
+        assert_eq!(
+            key_of(Path::new("/b"), ".json", Path::new("/b/.y.json.1.0.tmp")),
+            None
+        );
+        drop(watch);
+        let _ = fs::remove_dir_all(test_dir);
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::io;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+use super::{FramePoolError, StorageBackend, panic_message};
+
+// Called from the worker thread with the key and error of each queued write or delete that
+// failed. A panic in it is caught, leaving the failure to be reported by flush.
+pub type WriteErrorFn = Box<dyn Fn(&str, &FramePoolError) + Send + Sync>;
+
+// Wraps a StorageBackend so that writes and deletes return at once and are applied by a
+// background thread, in the order they were made. Reads see queued writes straight away.
+//
+// The queue is bounded: once `queue_len` operations are waiting, further writes block until
+// the worker catches up. A write that fails in the background is reported to the error
+// callback, if one is set, and by the next flush; reads then see whatever the backend holds. A
+// backend that panics fails the operation in the same way, so the worker never dies.
+// Dropping the wrapper, or taking the backend back with into_inner, applies everything queued
+// first.
+pub struct WriteBehindBackend<T, B> {
+    shared: Arc<Shared<T, B>>,
+    worker: Worker<T>,
+}
+
+struct Shared<T, B> {
+    backend: Mutex<B>,
+    queue: Mutex<Queue<T>>,
+    // signalled whenever the queue empties
+    drained: Condvar,
+    on_error: Mutex<Option<WriteErrorFn>>,
+}
+
+struct Queue<T> {
+    // the last queued operation on each key not yet applied, by sequence number; None is a
+    // delete
+    latest: HashMap<String, (u64, Option<Arc<T>>)>,
+    next_seq: u64,
+    // operations sent and not yet applied
+    queued: usize,
+    // the first background failure since the last flush
+    failed: Option<FramePoolError>,
+    failures: u64,
+}
+
+struct Job<T> {
+    key: String,
+    seq: u64,
+    data: Option<Arc<T>>,
+}
+
+// The worker thread, which drains the queue and stops once the sender is dropped.
+struct Worker<T> {
+    jobs: Option<mpsc::SyncSender<Job<T>>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<T> Drop for Worker<T> {
+    fn drop(&mut self) {
+        self.jobs.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<T, B> WriteBehindBackend<T, B>
+where
+    T: Clone + Send + Sync + 'static,
+    B: StorageBackend<T> + Send + 'static,
+{
+    // Starts the worker thread, queueing at most `queue_len` operations before writes block.
+    pub fn new(backend: B, queue_len: usize) -> Self {
+        let shared = Arc::new(Shared {
+            backend: Mutex::new(backend),
+            queue: Mutex::new(Queue {
+                latest: HashMap::new(),
+                next_seq: 0,
+                queued: 0,
+                failed: None,
+                failures: 0,
+            }),
+            drained: Condvar::new(),
+            on_error: Mutex::new(None),
+        });
+        let (jobs, receiver) = mpsc::sync_channel::<Job<T>>(queue_len);
+        let worker_shared = Arc::clone(&shared);
+        let handle = thread::spawn(move || {
+            for job in receiver {
+                worker_shared.apply(job);
+            }
+        });
+        WriteBehindBackend {
+            shared,
+            worker: Worker {
+                jobs: Some(jobs),
+                handle: Some(handle),
+            },
+        }
+    }
+}
+
+impl<T, B> WriteBehindBackend<T, B> {
+    // Sets the function called with each background failure, replacing any set before.
+    pub fn set_error_callback<F>(&self, callback: F)
+    where
+        F: Fn(&str, &FramePoolError) + Send + Sync + 'static,
+    {
+        *lock(&self.shared.on_error) = Some(Box::new(callback));
+    }
+
+    // Waits until every queued operation has been applied, returning the first one to fail
+    // since the last flush, if any did.
+    pub fn flush(&self) -> Result<(), FramePoolError> {
+        let mut queue = lock(&self.shared.queue);
+        while queue.queued > 0 {
+            queue = self
+                .shared
+                .drained
+                .wait(queue)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        queue.failed.take().map_or(Ok(()), Err)
+    }
+
+    // The number of operations waiting to be applied.
+    pub fn queued(&self) -> usize {
+        lock(&self.shared.queue).queued
+    }
+
+    // The number of queued operations that failed so far.
+    pub fn failures(&self) -> u64 {
+        lock(&self.shared.queue).failures
+    }
+
+    // Applies everything queued, stops the worker and returns the wrapped backend.
+    pub fn into_inner(self) -> B {
+        let WriteBehindBackend { shared, worker } = self;
+        drop(worker);
+        match Arc::try_unwrap(shared) {
+            Ok(shared) => shared
+                .backend
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner),
+            Err(_) => unreachable!("the worker has stopped"),
+        }
+    }
+
+    // Records the operation and hands it to the worker, blocking while the queue is full.
+    fn enqueue(&self, key: &str, data: Option<Arc<T>>) -> Result<(), FramePoolError> {
+        let seq = {
+            let mut queue = lock(&self.shared.queue);
+            queue.next_seq += 1;
+            let seq = queue.next_seq;
+            queue.latest.insert(key.to_string(), (seq, data.clone()));
+            queue.queued += 1;
+            seq
+        };
+        let job = Job {
+            key: key.to_string(),
+            seq,
+            data,
+        };
+        let sent = self
+            .worker
+            .jobs
+            .as_ref()
+            .is_some_and(|jobs| jobs.send(job).is_ok());
+        if !sent {
+            let mut queue = lock(&self.shared.queue);
+            queue.queued -= 1;
+            if queue
+                .latest
+                .get(key)
+                .is_some_and(|(latest, _)| *latest == seq)
+            {
+                queue.latest.remove(key);
+            }
+            return Err(io::Error::other("write-behind worker has stopped").into());
+        }
+        Ok(())
+    }
+
+    // The queued state of key: Some(None) if it is to be deleted.
+    fn pending(&self, key: &str) -> Option<Option<Arc<T>>> {
+        let queue = lock(&self.shared.queue);
+        queue.latest.get(key).map(|(_, data)| data.clone())
+    }
+}
+
+// The mutex's contents even if a thread panicked holding it: nothing under these locks is left
+// half updated.
+fn lock<M>(mutex: &Mutex<M>) -> MutexGuard<'_, M> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<T, B> Shared<T, B>
+where
+    T: Clone,
+    B: StorageBackend<T>,
+{
+    fn apply(&self, job: Job<T>) {
+        let result = {
+            let mut backend = lock(&self.backend);
+            catch_unwind(AssertUnwindSafe(|| match job.data {
+                Some(data) => backend.write(&job.key, data),
+                None => backend.delete(&job.key),
+            }))
+            .unwrap_or_else(|panic| Err(FramePoolError::Poisoned(panic_message(&*panic))))
+        };
+        if let Err(e) = &result
+            && let Some(callback) = lock(&self.on_error).as_ref()
+        {
+            let _ = catch_unwind(AssertUnwindSafe(|| callback(&job.key, e)));
+        }
+
+        let mut queue = lock(&self.queue);
+        queue.queued -= 1;
+        if queue
+            .latest
+            .get(&job.key)
+            .is_some_and(|(latest, _)| *latest == job.seq)
+        {
+            queue.latest.remove(&job.key);
+        }
+        if let Err(e) = result {
+            queue.failures += 1;
+            queue.failed.get_or_insert(e);
+        }
+        if queue.queued == 0 {
+            self.drained.notify_all();
+        }
+    }
+}
+
+impl<T, B> StorageBackend<T> for WriteBehindBackend<T, B>
+where
+    T: Clone,
+    B: StorageBackend<T>,
+{
+    fn read(&mut self, key: &str) -> Result<Arc<T>, FramePoolError> {
+        match self.pending(key) {
+            Some(Some(data)) => Ok(data),
+            Some(None) => Err(FramePoolError::NotFound(key.to_string())),
+            None => lock(&self.shared.backend).read(key),
+        }
+    }
+
+    fn write(&mut self, key: &str, data: Arc<T>) -> Result<(), FramePoolError> {
+        self.enqueue(key, Some(data))
+    }
+
+    fn exists(&self, key: &str) -> bool {
+        match self.pending(key) {
+            Some(data) => data.is_some(),
+            None => lock(&self.shared.backend).exists(key),
+        }
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), FramePoolError> {
+        self.enqueue(key, None)
+    }
+
+    fn list_keys(&self) -> Result<Vec<String>, FramePoolError> {
+        let queue = lock(&self.shared.queue);
+        let mut keys = lock(&self.shared.backend).list_keys()?;
+        keys.retain(|key| !matches!(queue.latest.get(key), Some((_, None))));
+        for (key, (_, data)) in queue.latest.iter() {
+            if data.is_some() && !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framepool::{FaultInjector, FaultyBackend, FileBackend};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[test]
+    fn test_write_behind_returns_before_writing() {
+        let test_dir = "/tmp/test_write_behind";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let mut pool = WriteBehindBackend::new(FileBackend::new(test_dir), 16);
+
+        // Holding the backend stalls the worker, so every write below is still queued
+        let shared = Arc::clone(&pool.shared);
+        let stalled = lock(&shared.backend);
+        for i in 0..5u32 {
+            pool.write(&format!("k{}", i), Arc::new(i)).unwrap();
+        }
+        pool.write("k0", Arc::new(100)).unwrap();
+        assert_eq!(pool.queued(), 6);
+        // Queued writes and deletes are visible at once
+        assert_eq!(*pool.read("k0").unwrap(), 100);
+        pool.delete("k4").unwrap();
+        assert!(!pool.exists("k4"));
+        assert!(pool.read("k4").is_err());
+        assert_eq!(pool.queued(), 7);
+        drop(stalled);
+        drop(shared);
+
+        pool.flush().unwrap();
+        assert_eq!(pool.queued(), 0);
+        assert!(!pool.exists("k4"));
+        assert_eq!(pool.list_keys().unwrap().len(), 4);
+        let mut backend = pool.into_inner();
+        assert_eq!(*backend.read_data::<u32>("k0").unwrap(), 100);
+        assert_eq!(*backend.read_data::<u32>("k3").unwrap(), 3);
+        assert!(!StorageBackend::<u32>::exists(&backend, "k4"));
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_write_behind_reports_failures() {
+        let test_dir = "/tmp/test_write_behind_failures";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let faults = FaultInjector::new(0);
+        let backend = FaultyBackend::new(FileBackend::new(test_dir), faults.clone());
+        let mut pool = WriteBehindBackend::new(backend, 1);
+        let reported = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&reported);
+        pool.set_error_callback(move |key, _| {
+            assert!(key.starts_with("bad"));
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        pool.write("good", Arc::new(1u8)).unwrap();
+        pool.flush().unwrap();
+        faults.set_write_failure_rate(1.0);
+        for i in 0..3 {
+            pool.write(&format!("bad{}", i), Arc::new(2)).unwrap();
+        }
+        assert!(matches!(pool.flush(), Err(FramePoolError::Io(_))));
+        assert_eq!(reported.load(Ordering::Relaxed), 3);
+        assert_eq!(pool.failures(), 3);
+        // The failed writes are gone; the earlier one stands
+        assert!(!pool.exists("bad0"));
+        assert_eq!(*pool.read("good").unwrap(), 1);
+        pool.flush().unwrap();
+
+        // Dropping the wrapper applies what is queued
+        faults.set_write_failure_rate(0.0);
+        pool.write("last", Arc::new(3)).unwrap();
+        drop(pool);
+        let backend = FileBackend::new(test_dir);
+        assert!(StorageBackend::<u8>::exists(&backend, "last"));
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+
+    #[test]
+    fn test_write_behind_survives_panicking_callback() {
+        let test_dir = "/tmp/test_write_behind_panics";
+        let _ = std::fs::remove_dir_all(test_dir);
+
+        let faults = FaultInjector::new(0);
+        let backend = FaultyBackend::new(FileBackend::new(test_dir), faults.clone());
+        let mut pool = WriteBehindBackend::new(backend, 4);
+        pool.set_error_callback(|key, _| panic!("callback failed on {}", key));
+
+        faults.set_write_failure_rate(1.0);
+        pool.write("bad", Arc::new(1u8)).unwrap();
+        // The failure still reaches flush, and the worker keeps going
+        assert!(matches!(pool.flush(), Err(FramePoolError::Io(_))));
+        assert_eq!(pool.failures(), 1);
+
+        faults.set_write_failure_rate(0.0);
+        pool.write("good", Arc::new(2)).unwrap();
+        pool.flush().unwrap();
+        assert_eq!(*pool.read("good").unwrap(), 2);
+        assert!(!pool.exists("bad"));
+        let _ = std::fs::remove_dir_all(test_dir);
+    }
+}
@@ -8,7 +8,7 @@
 //! - **Flexible Storage Backends**: Memory-based (`MemPool`) and disk-based (`DiskPool`) frame pools
 //! - **Pluggable Eviction Strategies**: Bottom eviction and random eviction algorithms
 //! - **Copy-on-Write Semantics**: Efficient data sharing with Arc-based memory management
-//! - **Thread Safety**: Safe concurrent access with proper synchronization
+//! - **Thread Safety**: `SharedBufferPool` gives cloneable handles to one pool for use across threads
 //! - **Comprehensive Testing**: Integration tests with forced cache evictions and benchmarking
 //!
 //! ## Basic Usage
@@ -88,8 +88,13 @@
 //! assert_eq!(all_data.len(), 10);
 //! ```
 //!
+//! Iterating only borrows the pool for the duration of the loop (`iter_mut()` is equivalent
+//! to `&mut buffer_pool`), so the pool can keep serving `get_page` calls afterwards.
+//!
 //! The iterator yields the actual data `T` from each frame, not the frames themselves.
 //! The BufferPool handles all caching, loading, and eviction transparently during iteration.
+//! For large values, call `.arcs()` on the iterator (or use `get_page_arc`) to receive
+//! `Arc<T>` handles instead of deep clones.
 //!
 //! ## Advanced Usage with Disk Storage
 //!
@@ -147,6 +152,40 @@
 //! }
 //! ```
 //!
+//! ## Custom Keys
+//!
+//! Frames are addressed by `u64` by default. `MemPool::new_keyed` and `BufferPool::new_keyed`
+//! accept any key implementing `FrameKey`, such as `(table_id, page_no)` pairs or strings:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use bufferpool::bufferpool::{BufferPool, bottom_evictor};
+//! use bufferpool::framepool::{FramePool, MemPool};
+//!
+//! let mut frame_pool = MemPool::<String, (u64, u64)>::new_keyed();
+//! frame_pool.put_frame((3, 0), Arc::new("table 3, page 0".to_string())).unwrap();
+//!
+//! let mut buffer_pool = BufferPool::new_keyed(8, &mut frame_pool, bottom_evictor);
+//! assert_eq!(buffer_pool.get_page((3, 0)).unwrap().data(), "table 3, page 0");
+//! ```
+//!
+//! Iteration, ranges, partitions and the other features that walk frame numbers are only
+//! available for `u64` keys.
+//!
+//! ## Optional Features
+//!
+//! - **`rayon`**: `ShardedBufferPool::par_iter`, which loads and processes frames across
+//!   threads, and `BufferPool::par_iter_chunks` and `BufferPool::par_for_each`, which load on
+//!   the calling thread and process across threads
+//! - **`async`**: `AsyncFramePool`, `AsyncDiskPool` (tokio::fs) and `AsyncBufferPool`, whose
+//!   flush writes dirty pages concurrently and which streams pages with read-ahead
+//! - **`uring`** (Linux): `UringPool`, a disk pool that submits batches of page reads, writes
+//!   and fsyncs through io_uring
+//! - **`watch`**: `DiskPool::watch` and `FileBackend::watch`, reporting pages and keys changed
+//!   on disk by other processes, for `BufferPool::invalidate` to drop
+//! - **`direct-io`** (Linux): `PagedFile::create_direct` and `open_direct`, reading and writing
+//!   pages with O_DIRECT so they are cached by the BufferPool alone, not by the OS as well
+//!
 //! ## Performance Analysis
 //!
 //! The crate includes comprehensive benchmarking tools:
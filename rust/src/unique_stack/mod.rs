@@ -1,9 +1,29 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::hash::Hash;
 
+// A stack without duplicates: pushing an item already on it moves it to the top. Every
+// operation but order is O(1): the items are a doubly linked list, bottom to top, through
+// slots found by an index from item to slot.
+//
+// A stack made with_capacity holds at most that many items, evicting the bottom one to make
+// room for another, and so doubles as a tracker of the most recently used keys, such as the
+// ghost lists of ARC or 2Q.
 pub struct UniqueStack<T> {
-    order: Vec<T>,
-    unique: HashSet<T>,
+    slots: Vec<Option<Node<T>>>,
+    // slots emptied by delete and pop, for reuse
+    free: Vec<usize>,
+    index: HashMap<T, usize>,
+    bottom: Option<usize>,
+    top: Option<usize>,
+    // the most items held, if bounded
+    capacity: Option<usize>,
+}
+
+struct Node<T> {
+    item: T,
+    // the slots of the items below and above
+    below: Option<usize>,
+    above: Option<usize>,
 }
 
 impl<T> UniqueStack<T>
@@ -12,62 +32,245 @@ where
 {
     pub fn new() -> UniqueStack<T> {
         UniqueStack {
-            order: Vec::new(),
-            unique: HashSet::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            index: HashMap::new(),
+            bottom: None,
+            top: None,
+            capacity: None,
+        }
+    }
+
+    // A stack holding at most max items: pushing a new one onto a full stack evicts the bottom
+    // item, and push returns it.
+    pub fn with_capacity(max: usize) -> UniqueStack<T> {
+        UniqueStack {
+            slots: Vec::with_capacity(max),
+            index: HashMap::with_capacity(max),
+            capacity: Some(max),
+            ..UniqueStack::new()
+        }
+    }
+
+    // The most items the stack holds, if it was made with_capacity.
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    // Pushes item onto the top, returning the item evicted to make room for it, if any.
+    pub fn push(&mut self, item: T) -> Option<T> {
+        if self.capacity == Some(0) {
+            return Some(item);
         }
+        let evicted = self.make_room(&item);
+        let slot = self.take_out(&item);
+        self.link_top(slot);
+        evicted
+    }
+
+    // Like push, but places the item at the bottom of the stack. On a full stack the item that
+    // was at the bottom is evicted.
+    pub fn push_bottom(&mut self, item: T) -> Option<T> {
+        if self.capacity == Some(0) {
+            return Some(item);
+        }
+        let evicted = self.make_room(&item);
+        let slot = self.take_out(&item);
+        self.link_bottom(slot);
+        evicted
+    }
+
+    // Moves item to the top if it is on the stack, as push would, returning whether it was.
+    // Unlike push, never adds it.
+    pub fn touch(&mut self, item: &T) -> bool {
+        let Some(&slot) = self.index.get(item) else {
+            return false;
+        };
+        self.unlink(slot);
+        self.link_top(slot);
+        true
+    }
+
+    // Moves item to the bottom if it is on the stack, returning whether it was: for demoting
+    // an item used once, such as a page read by a scan, so it goes first.
+    pub fn move_to_bottom(&mut self, item: &T) -> bool {
+        let Some(&slot) = self.index.get(item) else {
+            return false;
+        };
+        self.unlink(slot);
+        self.link_bottom(slot);
+        true
     }
 
-    pub fn push(&mut self, item: T) {
-        if self.unique.contains(&item) {
-            let idx = self.order.iter().position(|x| *x == item).unwrap();
-            self.order.remove(idx);
+    // Where item is on the stack, counting from the bottom as order does, or None if it is not
+    // on it. Takes time in proportion to the position, without copying the order.
+    pub fn position(&self, item: &T) -> Option<usize> {
+        let mut below = self.slots[*self.index.get(item)?].as_ref().unwrap().below;
+        let mut position = 0;
+        while let Some(slot) = below {
+            position += 1;
+            below = self.slots[slot].as_ref().unwrap().below;
         }
-        let i = item.clone();
-        self.unique.insert(i);
-        self.order.push(item);
+        Some(position)
     }
 
     pub fn delete(&mut self, item: T) {
-        if self.unique.contains(&item) {
-            let idx = self.order.iter().position(|x| *x == item).unwrap();
-            self.order.remove(idx);
-            self.unique.remove(&item);
+        if let Some(&slot) = self.index.get(&item) {
+            self.remove(slot);
         }
     }
 
     pub fn pop(&mut self) -> Option<T> {
-        let item = self.order.pop();
-        if let Some(x) = item.clone() {
-            self.unique.remove(&x);
+        let slot = self.top?;
+        Some(self.remove(slot))
+    }
+
+    // Returns the n items at the bottom of the stack, or all of them if there are fewer,
+    // oldest first, leaving them in place.
+    pub fn oldest_n(&self, n: usize) -> Vec<T> {
+        let mut oldest = Vec::with_capacity(n.min(self.index.len()));
+        let mut next = self.bottom;
+        while let Some(slot) = next.filter(|_| oldest.len() < n) {
+            oldest.push(self.item(slot).clone());
+            next = self.slots[slot].as_ref().unwrap().above;
         }
-        item
+        oldest
+    }
+
+    // Removes and returns the n items at the bottom of the stack, or all of them if there are
+    // fewer, oldest first.
+    pub fn drain_oldest(&mut self, n: usize) -> Vec<T> {
+        let mut oldest = Vec::with_capacity(n.min(self.index.len()));
+        while let Some(slot) = self.bottom.filter(|_| oldest.len() < n) {
+            oldest.push(self.remove(slot));
+        }
+        oldest
     }
 
     // Returns the most recently pushed item, or None if the stack is empty.
     pub fn top(&self) -> Option<T> {
-        self.order.last().map(|x| (*x).clone())
+        self.top.map(|slot| self.item(slot).clone())
     }
 
     // Returns the least recently pushed item, or None if the stack is empty.
     pub fn bottom(&self) -> Option<T> {
-        self.order.first().map(|x| (*x).clone())
+        self.bottom.map(|slot| self.item(slot).clone())
     }
 
     // Returns a copy of the items, in order.
     pub fn order(&self) -> Vec<T> {
-        self.order.iter().map(|x| (*x).clone()).collect()
+        let mut order = Vec::with_capacity(self.index.len());
+        let mut next = self.bottom;
+        while let Some(slot) = next {
+            order.push(self.item(slot).clone());
+            next = self.slots[slot].as_ref().unwrap().above;
+        }
+        order
     }
 
     pub fn contains(&self, item: &T) -> bool {
-        self.unique.contains(item)
+        self.index.contains_key(item)
     }
 
     pub fn len(&self) -> u64 {
-        self.order.len() as u64
+        self.index.len() as u64
     }
 
     pub fn is_empty(&self) -> bool {
-        self.order.is_empty()
+        self.index.is_empty()
+    }
+
+    // Evicts the bottom item if the stack is full and item is not on it.
+    fn make_room(&mut self, item: &T) -> Option<T> {
+        let full = self.capacity.is_some_and(|max| self.index.len() >= max);
+        if !full || self.index.contains_key(item) {
+            return None;
+        }
+        let item = self.bottom().unwrap();
+        self.delete(item.clone());
+        Some(item)
+    }
+
+    // The slot of item, unlinked from the stack if it was on it and newly filled if not.
+    fn take_out(&mut self, item: &T) -> usize {
+        if let Some(&slot) = self.index.get(item) {
+            self.unlink(slot);
+            return slot;
+        }
+        let node = Node {
+            item: item.clone(),
+            below: None,
+            above: None,
+        };
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot] = Some(node);
+                slot
+            }
+            None => {
+                self.slots.push(Some(node));
+                self.slots.len() - 1
+            }
+        };
+        self.index.insert(item.clone(), slot);
+        slot
+    }
+
+    // Takes the item in slot off the stack, freeing the slot.
+    fn remove(&mut self, slot: usize) -> T {
+        self.unlink(slot);
+        let node = self.slots[slot].take().unwrap();
+        self.free.push(slot);
+        self.index.remove(&node.item);
+        node.item
+    }
+
+    fn link_top(&mut self, slot: usize) {
+        let top = self.top;
+        let node = self.node(slot);
+        node.below = top;
+        node.above = None;
+        match top {
+            Some(top) => self.node(top).above = Some(slot),
+            None => self.bottom = Some(slot),
+        }
+        self.top = Some(slot);
+    }
+
+    fn link_bottom(&mut self, slot: usize) {
+        let bottom = self.bottom;
+        let node = self.node(slot);
+        node.below = None;
+        node.above = bottom;
+        match bottom {
+            Some(bottom) => self.node(bottom).below = Some(slot),
+            None => self.top = Some(slot),
+        }
+        self.bottom = Some(slot);
+    }
+
+    // Joins the items either side of slot's, leaving its own links as they were.
+    fn unlink(&mut self, slot: usize) {
+        let (below, above) = {
+            let node = self.node(slot);
+            (node.below, node.above)
+        };
+        match below {
+            Some(below) => self.node(below).above = above,
+            None => self.bottom = above,
+        }
+        match above {
+            Some(above) => self.node(above).below = below,
+            None => self.top = below,
+        }
+    }
+
+    fn node(&mut self, slot: usize) -> &mut Node<T> {
+        self.slots[slot].as_mut().unwrap()
+    }
+
+    fn item(&self, slot: usize) -> &T {
+        &self.slots[slot].as_ref().unwrap().item
     }
 }
 
@@ -205,4 +408,100 @@ mod tests {
         assert_eq!(stack.bottom(), None);
         assert!(stack.is_empty());
     }
+
+    #[test]
+    fn test_push_bottom() {
+        let mut stack = UniqueStack::new();
+        stack.push(1);
+        stack.push(2);
+        stack.push_bottom(3);
+        assert_eq!(stack.order(), vec![3, 1, 2]);
+        stack.push_bottom(2);
+        assert_eq!(stack.order(), vec![2, 3, 1]);
+        assert_eq!(stack.len(), 3);
+    }
+
+    #[test]
+    fn test_reuses_slots() {
+        let mut stack = UniqueStack::new();
+        for i in 0..4 {
+            stack.push(i);
+        }
+        stack.delete(1);
+        stack.push(0);
+        assert_eq!(stack.pop(), Some(0));
+        stack.push(4);
+        stack.push_bottom(5);
+        stack.push(2);
+        assert_eq!(stack.order(), vec![5, 3, 4, 2]);
+        assert_eq!(stack.slots.len(), 4);
+        while stack.pop().is_some() {}
+        assert_eq!((stack.top(), stack.bottom()), (None, None));
+        stack.push(6);
+        assert_eq!(stack.order(), vec![6]);
+        assert_eq!(stack.bottom(), Some(6));
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut stack = UniqueStack::with_capacity(3);
+        assert_eq!(stack.capacity(), Some(3));
+        for i in 0..3 {
+            assert_eq!(stack.push(i), None);
+        }
+        // pushing one already on it evicts nothing
+        assert_eq!(stack.push(0), None);
+        assert_eq!(stack.push(3), Some(1));
+        assert_eq!(stack.push_bottom(4), Some(2));
+        assert_eq!(stack.order(), vec![4, 0, 3]);
+        assert_eq!(stack.len(), 3);
+
+        let mut none = UniqueStack::with_capacity(0);
+        assert_eq!(none.push(1), Some(1));
+        assert!(none.is_empty());
+        assert_eq!(UniqueStack::<i32>::new().capacity(), None);
+    }
+
+    #[test]
+    fn test_recency() {
+        let mut stack = UniqueStack::new();
+        for i in 0..4 {
+            stack.push(i);
+        }
+        assert!(stack.touch(&1));
+        assert!(!stack.touch(&9));
+        assert_eq!(stack.order(), vec![0, 2, 3, 1]);
+        assert!(stack.move_to_bottom(&3));
+        assert!(stack.move_to_bottom(&3));
+        assert!(!stack.move_to_bottom(&9));
+        assert_eq!(stack.order(), vec![3, 0, 2, 1]);
+        assert!(stack.touch(&1));
+        assert_eq!(stack.order(), vec![3, 0, 2, 1]);
+        assert_eq!(stack.len(), 4);
+        for (position, item) in stack.order().iter().enumerate() {
+            assert_eq!(stack.position(item), Some(position));
+        }
+        assert_eq!(stack.position(&9), None);
+        assert_eq!((stack.top(), stack.bottom()), (Some(1), Some(3)));
+    }
+
+    #[test]
+    fn test_oldest_n() {
+        let mut stack = UniqueStack::new();
+        for i in 0..5 {
+            stack.push(i);
+        }
+        stack.push(0);
+        assert_eq!(stack.oldest_n(2), vec![1, 2]);
+        assert_eq!(stack.oldest_n(9), vec![1, 2, 3, 4, 0]);
+        assert!(stack.oldest_n(0).is_empty());
+        assert_eq!(stack.len(), 5);
+
+        assert_eq!(stack.drain_oldest(3), vec![1, 2, 3]);
+        assert_eq!(stack.order(), vec![4, 0]);
+        assert!(!stack.contains(&1));
+        assert_eq!(stack.drain_oldest(9), vec![4, 0]);
+        assert!(stack.is_empty());
+        assert!(stack.drain_oldest(1).is_empty());
+    }
 }